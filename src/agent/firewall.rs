@@ -0,0 +1,157 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use std::process::Command;
+use super::protocol::{CommandResult, FIREWALL_CHAIN_PREFIX, FirewallChain};
+use super::run_agent::{failed, run};
+
+// brings the TORC- chains in line with the ones the controller sent, chains it didn't send belong to
+// tasks that are gone and get removed together with their jumps
+pub fn sync_firewall(arguments: &Vec<String>) -> CommandResult {
+    let mut chains = vec![];
+    for argument in arguments {
+        match FirewallChain::parse(argument) {
+            Ok(chain) => chains.push(chain),
+            Err(error) => return failed(error),
+        }
+    }
+
+    for chain in &chains {
+        let result = apply_chain(chain);
+        if !result.success {
+            return result;
+        }
+    }
+
+    let mut removed = 0;
+    for existing in existing_chains() {
+        if chains.iter().any(|chain| chain.chain == existing) {
+            continue;
+        }
+        let result = remove_chain(&existing);
+        if !result.success {
+            return result;
+        }
+        removed += 1;
+    }
+
+    CommandResult {
+        success: true,
+        output: format!("applied {} chains, removed {}", chains.len(), removed),
+    }
+}
+
+// accepts the declared ports and replies to connections the task opened itself, drops the rest of the
+// dropped ports. the host chain guards all of INPUT so loopback and icmp stay open
+fn apply_chain(chain: &FirewallChain) -> CommandResult {
+    let name = chain.chain.as_str();
+    // fails if the chain is there already, the flush below tells whether it's usable
+    let _ = run_iptables(&["-N", name]);
+    let flushed = run_iptables(&["-F", name]);
+    if !flushed.success {
+        return flushed;
+    }
+
+    let mut rules: Vec<Vec<String>> = vec![];
+    if chain.target == "host" {
+        rules.push(arguments(&["-A", name, "-i", "lo", "-j", "ACCEPT"]));
+        rules.push(arguments(&["-A", name, "-p", "icmp", "-j", "ACCEPT"]));
+    }
+    rules.push(arguments(&["-A", name, "-m", "conntrack", "--ctstate", "ESTABLISHED,RELATED", "-j", "ACCEPT"]));
+    for &(ref protocol, port) in &chain.ports {
+        rules.push(arguments(&["-A", name, "-p", protocol.as_str(), "--dport", port.to_string().as_str(), "-j", "ACCEPT"]));
+    }
+    match chain.drop {
+        Some((1, 65535)) => rules.push(arguments(&["-A", name, "-j", "DROP"])),
+        Some((first, last)) => {
+            let range = format!("{}:{}", first, last);
+            for protocol in &["tcp", "udp"] {
+                rules.push(arguments(&["-A", name, "-p", *protocol, "--dport", range.as_str(), "-j", "DROP"]));
+            }
+        }
+        // handed back to the rest of the chain it was jumped to from
+        None => rules.push(arguments(&["-A", name, "-j", "RETURN"])),
+    }
+
+    for rule in rules {
+        let result = run(Command::new("iptables").args(&rule));
+        if !result.success {
+            return result;
+        }
+    }
+
+    let jump = match chain.target.as_ref() {
+        "host" => arguments(&["INPUT", "-j", name]),
+        ip => arguments(&["FORWARD", "-d", ip, "-j", name]),
+    };
+    let mut check = arguments(&["-C"]);
+    check.extend(jump.iter().cloned());
+    if run(Command::new("iptables").args(&check)).success {
+        return CommandResult {
+            success: true,
+            output: "".to_string(),
+        };
+    }
+
+    // first in line so rules docker or calico add later don't accept the traffic before it gets here
+    let mut insert = arguments(&["-I"]);
+    insert.push(jump[0].clone());
+    insert.push("1".to_string());
+    insert.extend(jump[1..].iter().cloned());
+    run(Command::new("iptables").args(&insert))
+}
+
+fn remove_chain(name: &String) -> CommandResult {
+    let jump = format!("-j {}", name);
+    for parent in &["INPUT", "FORWARD"] {
+        let rules = run_iptables(&["-S", *parent]);
+        for rule in rules.output.lines().filter(|rule| rule.ends_with(&jump)) {
+            let mut delete: Vec<&str> = rule.split_whitespace().collect();
+            delete[0] = "-D";
+            let _ = run_iptables(&delete);
+        }
+    }
+
+    let flushed = run_iptables(&["-F", name.as_str()]);
+    if !flushed.success {
+        return flushed;
+    }
+    run_iptables(&["-X", name.as_str()])
+}
+
+fn existing_chains() -> Vec<String> {
+    let prefix = format!("-N {}", FIREWALL_CHAIN_PREFIX);
+    run_iptables(&["-S"])
+        .output
+        .lines()
+        .filter(|line| line.starts_with(&prefix))
+        .map(|line| line[3..].trim().to_string())
+        .collect()
+}
+
+fn arguments(arguments: &[&str]) -> Vec<String> {
+    arguments.iter().map(|argument| argument.to_string()).collect()
+}
+
+fn run_iptables(arguments: &[&str]) -> CommandResult {
+    run(Command::new("iptables").args(arguments))
+}
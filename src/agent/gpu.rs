@@ -0,0 +1,94 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use state::Gpu;
+use std::io::ErrorKind;
+use std::process::Command;
+
+const QUERY: &'static str = "--query-gpu=index,name,driver_version,memory.total,memory.used,ecc.errors.uncorrected.volatile.total";
+
+// nvidia-smi prints these in place of a value once it can't talk to the card anymore
+const FAILURE_MARKERS: &'static [&'static str] = &["[GPU is lost]", "[Unknown Error]", "ERR!"];
+
+// nvidia-smi's answer on a host with the driver installed but no card in it
+const NO_DEVICES: &'static str = "No devices were found";
+
+// none on a node without nvidia-smi, a driver that doesn't answer shows up as a single failed entry
+pub fn read_gpus() -> Option<Vec<Gpu>> {
+    let output = match Command::new("nvidia-smi").arg(QUERY).arg("--format=csv,noheader,nounits").output() {
+        Ok(output) => output,
+        Err(ref error) if error.kind() == ErrorKind::NotFound => return None,
+        Err(error) => return Some(vec![failed_driver(error.to_string())]),
+    };
+
+    let text = String::from_utf8_lossy(&output.stdout).to_string();
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    if text.contains(NO_DEVICES) || stderr.contains(NO_DEVICES) {
+        return Some(vec![]);
+    }
+    if !output.status.success() {
+        let reason = match stderr.is_empty() {
+            true => text.trim().to_string(),
+            false => stderr,
+        };
+        return Some(vec![failed_driver(reason)]);
+    }
+
+    Some(text.lines().filter(|line| !line.trim().is_empty()).map(parse_gpu).collect())
+}
+
+fn parse_gpu(line: &str) -> Gpu {
+    let fields: Vec<&str> = line.split(',').map(|field| field.trim()).collect();
+    let field = |position: usize| fields.get(position).cloned().unwrap_or("");
+
+    let mut health_reason = match FAILURE_MARKERS.iter().find(|marker| line.contains(*marker)) {
+        Some(marker) => format!("nvidia-smi reports {}", marker),
+        None => "".to_string(),
+    };
+    // cards without ecc report n/a
+    let uncorrected = field(5).parse::<i64>().unwrap_or(0);
+    if health_reason.is_empty() && uncorrected > 0 {
+        health_reason = format!("{} uncorrected ecc errors", uncorrected);
+    }
+
+    Gpu {
+        index: field(0).parse::<i64>().unwrap_or(-1),
+        model: field(1).to_string(),
+        driver_version: field(2).to_string(),
+        healthy: health_reason.is_empty(),
+        health_reason: health_reason,
+        memory_total_in_mb: field(3).parse::<i64>().unwrap_or(0),
+        memory_used_in_mb: field(4).parse::<i64>().unwrap_or(0),
+    }
+}
+
+fn failed_driver(reason: String) -> Gpu {
+    Gpu {
+        index: -1,
+        model: "".to_string(),
+        driver_version: "".to_string(),
+        healthy: false,
+        health_reason: reason,
+        memory_total_in_mb: 0,
+        memory_used_in_mb: 0,
+    }
+}
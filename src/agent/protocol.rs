@@ -0,0 +1,154 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use state::Gpu;
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+
+// the contract between the controller and torc-node-agent, both sides encode and decode these
+
+pub const AGENT_PORT: i16 = 8085;
+pub const AGENT_TOKEN_HEADER: &'static str = "X-Torc-Agent-Token";
+
+// an agent put in place by upgrade_agent goes back to the previous binary by itself unless the controller
+// accepted one of its heartbeats within this time
+pub const UPGRADE_TRIAL_IN_SECONDS: i64 = 120;
+
+pub const FIREWALL_CHAIN_PREFIX: &'static str = "TORC-";
+pub const HOST_FIREWALL_CHAIN: &'static str = "TORC-HOST";
+
+// legacy shell command as sent by the old command proxy, answered with 202 on success
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct SyncCommand {
+    pub cmd: String,
+    pub env: String,
+}
+
+// kind is one of docker_gc, stop_container, restart_container, pull_image, upgrade_agent, rollback_agent,
+// sync_firewall
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct AgentCommand {
+    pub kind: String,
+    pub args: Vec<String>,
+}
+
+// one argument of sync_firewall, sent as chain,target,protocol:port,...,drop:first-last where target is the task ip
+// the chain guards in FORWARD or host for the node's own INPUT, e.g. TORC-1A2B3C4D,10.0.0.5,tcp:8080,drop:1-65535.
+// only new connections to the dropped ports that aren't accepted are dropped, a chain without drop lets everything pass
+#[derive(Clone, Debug, PartialEq)]
+pub struct FirewallChain {
+    pub chain: String,
+    pub target: String,
+    pub ports: Vec<(String, i64)>,
+    pub drop: Option<(i64, i64)>,
+}
+
+impl FirewallChain {
+    pub fn to_argument(&self) -> String {
+        let mut parts = vec![self.chain.clone(), self.target.clone()];
+        parts.extend(self.ports.iter().map(|&(ref protocol, port)| format!("{}:{}", protocol, port)));
+        if let Some((first, last)) = self.drop {
+            parts.push(format!("drop:{}-{}", first, last));
+        }
+        parts.join(",")
+    }
+
+    // strict, everything in here ends up on an iptables command line
+    pub fn parse(argument: &str) -> Result<FirewallChain, String> {
+        let parts: Vec<&str> = argument.split(',').collect();
+        if parts.len() < 2 {
+            return Err(format!("firewall chain needs a chain and a target: {}", argument));
+        }
+
+        let chain = parts[0];
+        if !chain.starts_with(FIREWALL_CHAIN_PREFIX) || chain.len() > 28 ||
+           !chain.chars().all(|c| (c >= 'A' && c <= 'Z') || c.is_digit(10) || c == '-') {
+            return Err(format!("invalid firewall chain: {}", chain));
+        }
+        let target = parts[1];
+        if target != "host" && Ipv4Addr::from_str(target).is_err() {
+            return Err(format!("invalid firewall target: {}", target));
+        }
+
+        let mut ports = vec![];
+        let mut drop = None;
+        for part in &parts[2..] {
+            if part.starts_with("drop:") {
+                drop = Some(try!(parse_port_range(&part[5..])));
+                continue;
+            }
+            let mut pieces = part.splitn(2, ':');
+            let protocol = pieces.next().unwrap_or("");
+            let port = pieces.next().and_then(|port| port.parse::<i64>().ok()).unwrap_or(0);
+            if (protocol != "tcp" && protocol != "udp") || port <= 0 || port > 65535 {
+                return Err(format!("invalid firewall port: {}", part));
+            }
+            ports.push((protocol.to_string(), port));
+        }
+
+        Ok(FirewallChain {
+            chain: chain.to_string(),
+            target: target.to_string(),
+            ports: ports,
+            drop: drop,
+        })
+    }
+}
+
+// first-last, both within 1 to 65535
+pub fn parse_port_range(range: &str) -> Result<(i64, i64), String> {
+    let mut pieces = range.splitn(2, '-');
+    let first = pieces.next().and_then(|port| port.trim().parse::<i64>().ok()).unwrap_or(0);
+    let last = pieces.next().and_then(|port| port.trim().parse::<i64>().ok()).unwrap_or(0);
+    if first <= 0 || last > 65535 || first > last {
+        return Err(format!("invalid port range: {}", range));
+    }
+    Ok((first, last))
+}
+
+// iptables chain names are limited to 28 characters, task names aren't. fnv-1a, the controller and
+// every agent version have to come up with the same name for a task
+pub fn task_firewall_chain(task_name: &str) -> String {
+    let mut hash: u32 = 0x811c9dc5;
+    for byte in task_name.bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    format!("{}{:08X}", FIREWALL_CHAIN_PREFIX, hash)
+}
+
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct CommandResult {
+    pub success: bool,
+    pub output: String,
+}
+
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct Heartbeat {
+    pub node_name: String,
+    pub ip: String,
+    pub version: String,
+    pub running_containers: i64,
+    pub timestamp: i64,
+    // none from nodes without nvidia-smi and from agents older than gpu reporting
+    pub gpus: Option<Vec<Gpu>>,
+}
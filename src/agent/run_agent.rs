@@ -0,0 +1,510 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use chrono::UTC;
+use collaborator::verifying_client;
+use hyper::Client;
+use hyper::header::{Authorization, Bearer, Headers};
+use iron::{Iron, IronResult, Request, Response};
+use iron::mime::{Mime, SubLevel, TopLevel};
+use iron::status;
+use router::Router;
+use rustc_serialize::json;
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Mutex;
+use std::sync::mpsc::{Sender, channel};
+use std::thread;
+use std::time::Duration;
+use super::firewall::sync_firewall;
+use super::gpu::read_gpus;
+use super::protocol::{AGENT_TOKEN_HEADER, AgentCommand, CommandResult, Heartbeat, SyncCommand, UPGRADE_TRIAL_IN_SECONDS};
+
+const DEFAULT_LOG_LINES: i64 = 100;
+// starts of a new binary before it's given up on, a crashing one never gets to its trial deadline
+const MAX_TRIAL_STARTS: u32 = 3;
+
+#[derive(Clone, Debug)]
+pub struct AgentConfig {
+    pub node_name: String,
+    pub ip: String,
+    pub port: i16,
+    pub version: String,
+    // controller api as host:port, heartbeats are posted there
+    pub controller: String,
+    pub controller_token: String,
+    // expected in the X-Torc-Agent-Token header for typed commands and logs, empty keeps them open,
+    // shell commands through /sync are only taken with a token set
+    pub token: String,
+    pub heartbeat_interval_in_seconds: u64,
+    pub gc_interval_in_seconds: u64,
+    // with both set the agent serves https, the controller has to list node-agent under http.tls.collaborators
+    pub tls_cert_file: String,
+    pub tls_key_file: String,
+    // checks the certificate of a controller reached over https, the system's trust store without it
+    pub ca_file: String,
+}
+
+// serves until an upgrade or a rollback put another binary in place, the reason for the restart is returned then.
+// the agent is expected to run under a supervisor that brings it back up.
+pub fn run_agent(config: &AgentConfig) -> Result<String, String> {
+    info!("node agent starting node={} ip={} version={}", config.node_name, config.ip, config.version);
+
+    let (restart, restart_requested) = channel::<String>();
+    if let Some(reason) = try!(start_trial(restart.clone())) {
+        return Ok(reason);
+    }
+
+    let heartbeat_config = config.clone();
+    let client = try!(verifying_client(&config.ca_file).map_err(|error| format!("can't set up the heartbeat client: {}", error)));
+    thread::Builder::new()
+        .name("heartbeat".to_string())
+        .spawn(move || {
+            loop {
+                if send_heartbeat(&client, &heartbeat_config) {
+                    confirm_upgrade();
+                }
+                thread::sleep(Duration::from_secs(heartbeat_config.heartbeat_interval_in_seconds));
+            }
+        })
+        .unwrap();
+
+    // 0 turns the periodic gc off, it can still be triggered by the controller
+    if config.gc_interval_in_seconds > 0 {
+        let gc_interval = config.gc_interval_in_seconds;
+        thread::Builder::new()
+            .name("docker-gc".to_string())
+            .spawn(move || {
+                loop {
+                    thread::sleep(Duration::from_secs(gc_interval));
+                    let result = docker_gc();
+                    info!("docker gc output={:?}", result.output);
+                }
+            })
+            .unwrap();
+    }
+
+    let mut router = Router::new();
+    let sync_token = config.token.clone();
+    router.post("/sync", move |request: &mut Request| handle_sync(&sync_token, request));
+
+    let command_token = config.token.clone();
+    let command_restart = Mutex::new(restart);
+    router.post("/command",
+                move |request: &mut Request| handle_command(&command_token, &command_restart, request));
+
+    let logs_token = config.token.clone();
+    router.get("/logs",
+               move |request: &mut Request| handle_logs(&logs_token, request));
+
+    let version = config.version.clone();
+    router.get("/version", move |_r: &mut Request| handle_version(&version));
+
+    if config.token.is_empty() {
+        warn!("no agent token set, sync commands are refused");
+    }
+    let address = format!("0.0.0.0:{}", config.port);
+    let listening = match (config.tls_cert_file.is_empty(), config.tls_key_file.is_empty()) {
+        (true, true) => {
+            info!("node agent listening port={} protocol=http", config.port);
+            Iron::new(router).http(&*address)
+        }
+        (false, false) => {
+            info!("node agent listening port={} protocol=https", config.port);
+            Iron::new(router).https(&*address, PathBuf::from(&config.tls_cert_file), PathBuf::from(&config.tls_key_file))
+        }
+        _ => return Err("tls needs both a certificate and a key".to_string()),
+    };
+    let mut listening = try!(listening.map_err(|error| format!("can't listen on {}: {}", address, error)));
+
+    let reason = restart_requested.recv().unwrap_or("restart channel closed".to_string());
+    // lets go of the server threads instead of waiting for them, they end with the process
+    let _ = listening.close();
+    Ok(reason)
+}
+
+#[derive(Clone, Debug, RustcEncodable)]
+struct VersionResponse {
+    version: String,
+}
+
+// same contract as the old command proxy plus the token, the command runs through a shell as the agent
+fn handle_sync(token: &String, request: &mut Request) -> IronResult<Response> {
+    if token.is_empty() {
+        return Ok(Response::with((status::Forbidden, "no agent token configured, sync commands are turned off")));
+    }
+    if !is_authorized(token, request) {
+        return Ok(Response::with((status::Unauthorized, "missing or invalid agent token")));
+    }
+
+    let mut body = String::new();
+    if let Err(error) = request.body.read_to_string(&mut body) {
+        return Ok(Response::with((status::BadRequest, format!("can't read command: {}", error))));
+    }
+    let command: SyncCommand = match json::decode(&body) {
+        Ok(command) => command,
+        Err(error) => return Ok(Response::with((status::BadRequest, format!("invalid command: {}", error)))),
+    };
+
+    let mut process = Command::new("sh");
+    process.arg("-c").arg(&command.cmd);
+    for pair in command.env.split_whitespace() {
+        let mut elements = pair.splitn(2, '=');
+        match (elements.next(), elements.next()) {
+            (Some(key), Some(value)) => {
+                process.env(key, value);
+            }
+            _ => {}
+        }
+    }
+
+    let result = run(&mut process);
+    info!("sync command cmd={} success={}", command.cmd, result.success);
+
+    let response_status = match result.success {
+        true => status::Accepted,
+        false => status::InternalServerError,
+    };
+    Ok(Response::with((response_status, result.output)))
+}
+
+fn handle_command(token: &String, restart: &Mutex<Sender<String>>, request: &mut Request) -> IronResult<Response> {
+    if !is_authorized(token, request) {
+        return Ok(Response::with((status::Unauthorized, "missing or invalid agent token")));
+    }
+
+    let mut body = String::new();
+    if let Err(error) = request.body.read_to_string(&mut body) {
+        return Ok(Response::with((status::BadRequest, format!("can't read command: {}", error))));
+    }
+    let command: AgentCommand = match json::decode(&body) {
+        Ok(command) => command,
+        Err(error) => return Ok(Response::with((status::BadRequest, format!("invalid command: {}", error)))),
+    };
+
+    // arguments are handed to docker and iptables as is, don't let them turn into flags
+    if command.args.iter().any(|argument| argument.is_empty() || argument.starts_with('-')) {
+        return Ok(Response::with((status::BadRequest, "invalid argument")));
+    }
+
+    let result = match (command.kind.as_ref(), command.args.len()) {
+        ("docker_gc", 0) => docker_gc(),
+        ("stop_container", 1) => run_docker(&["stop", &command.args[0]]),
+        ("restart_container", 1) => run_docker(&["restart", &command.args[0]]),
+        ("pull_image", 1) => run_docker(&["pull", &command.args[0]]),
+        ("upgrade_agent", 1) => upgrade_agent(&command.args[0]),
+        ("rollback_agent", 0) => rollback_agent(),
+        ("sync_firewall", _) => sync_firewall(&command.args),
+        _ => return Ok(Response::with((status::BadRequest, format!("unknown command: {}", command.kind)))),
+    };
+    info!("command kind={} args={:?} success={}", command.kind, command.args, result.success);
+    if result.success && (command.kind == "upgrade_agent" || command.kind == "rollback_agent") {
+        restart_soon(restart.lock().unwrap().clone(), result.output.clone());
+    }
+
+    let response_status = match result.success {
+        true => status::Ok,
+        false => status::InternalServerError,
+    };
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    Ok(Response::with((content_type, response_status, json::encode(&result).unwrap())))
+}
+
+fn handle_logs(token: &String, request: &mut Request) -> IronResult<Response> {
+    if !is_authorized(token, request) {
+        return Ok(Response::with((status::Unauthorized, "missing or invalid agent token")));
+    }
+
+    let container = read_query_parameter(request, "container");
+    if container.is_empty() || container.starts_with('-') {
+        return Ok(Response::with((status::BadRequest, "container missing")));
+    }
+    let lines = read_query_parameter(request, "lines").parse::<i64>().unwrap_or(DEFAULT_LOG_LINES);
+
+    let result = run_docker(&["logs", "--tail", &lines.to_string(), &container]);
+
+    let response_status = match result.success {
+        true => status::Ok,
+        false => status::NotFound,
+    };
+    let content_type = Mime(TopLevel::Text, SubLevel::Plain, Vec::new());
+    Ok(Response::with((content_type, response_status, result.output)))
+}
+
+fn handle_version(version: &String) -> IronResult<Response> {
+    let response = VersionResponse { version: version.clone() };
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    Ok(Response::with((content_type, status::Ok, json::encode(&response).unwrap())))
+}
+
+// true once the controller took it
+fn send_heartbeat(client: &Client, config: &AgentConfig) -> bool {
+    let running_containers = run_docker(&["ps", "-q"]).output.lines().count() as i64;
+    let heartbeat = Heartbeat {
+        node_name: config.node_name.clone(),
+        ip: config.ip.clone(),
+        version: config.version.clone(),
+        running_containers: running_containers,
+        timestamp: UTC::now().timestamp(),
+        gpus: read_gpus(),
+    };
+
+    let mut headers = Headers::new();
+    if !config.controller_token.is_empty() {
+        headers.set(Authorization(Bearer { token: config.controller_token.clone() }));
+    }
+
+    // a controller serving https is given with its scheme, e.g. https://10.0.0.1:3000
+    let address = match config.controller.contains("://") {
+        true => format!("{}/node/heartbeat", config.controller),
+        false => format!("http://{}/node/heartbeat", config.controller),
+    };
+    match client.post(&address).headers(headers).body(&json::encode(&heartbeat).unwrap()).send() {
+        Ok(response) => {
+            if !response.status.is_success() {
+                warn!("heartbeat rejected controller={} status={}", config.controller, response.status);
+            }
+            response.status.is_success()
+        }
+        Err(error) => {
+            error!("can't send heartbeat controller={} error={}", config.controller, error);
+            false
+        }
+    }
+}
+
+// a binary put in place by an upgrade is on trial while <executable>.pending exists, the file counts its starts.
+// the controller can't reach an agent that doesn't come up, so the way back to the previous binary is taken here.
+// a reason is returned when the previous binary was put back right away and has to be started instead
+fn start_trial(restart: Sender<String>) -> Result<Option<String>, String> {
+    let pending = try!(pending_path());
+    let mut content = String::new();
+    match File::open(&pending) {
+        Ok(mut file) => {
+            let _ = file.read_to_string(&mut content);
+        }
+        Err(_) => return Ok(None),
+    }
+
+    let starts = content.trim().parse::<u32>().unwrap_or(0) + 1;
+    if starts > MAX_TRIAL_STARTS {
+        let result = rollback_agent();
+        return match result.success {
+            true => Ok(Some(format!("new binary didn't stay up, {}", result.output))),
+            false => Err(result.output),
+        };
+    }
+    try!(write_pending(&pending, starts));
+    info!("new binary on trial starts={} trial_in_seconds={}", starts, UPGRADE_TRIAL_IN_SECONDS);
+
+    thread::Builder::new()
+        .name("upgrade-trial".to_string())
+        .spawn(move || {
+            thread::sleep(Duration::from_secs(UPGRADE_TRIAL_IN_SECONDS as u64));
+            if pending.exists() {
+                let result = rollback_agent();
+                warn!("upgrade not confirmed in time, rolling back success={} output={}", result.success, result.output);
+                if result.success {
+                    let _ = restart.send(result.output);
+                }
+            }
+        })
+        .unwrap();
+    Ok(None)
+}
+
+fn confirm_upgrade() {
+    if let Ok(pending) = pending_path() {
+        if pending.exists() && fs::remove_file(&pending).is_ok() {
+            info!("upgrade confirmed by the controller");
+        }
+    }
+}
+
+fn pending_path() -> Result<PathBuf, String> {
+    env::current_exe()
+        .map(|executable| executable.with_extension("pending"))
+        .map_err(|error| format!("can't find own executable: {}", error))
+}
+
+fn write_pending(pending: &PathBuf, starts: u32) -> Result<(), String> {
+    File::create(pending)
+        .and_then(|mut file| file.write_all(starts.to_string().as_bytes()))
+        .map_err(|error| format!("can't write {:?}: {}", pending, error))
+}
+
+// downloads the new binary next to the running one and swaps them, the running one is kept for a rollback.
+// the new one starts out on trial, see start_trial
+fn upgrade_agent(url: &String) -> CommandResult {
+    let executable = match env::current_exe() {
+        Ok(executable) => executable,
+        Err(error) => return failed(format!("can't find own executable: {}", error)),
+    };
+    let staged = executable.with_extension("new");
+    let previous = executable.with_extension("previous");
+
+    let download = run(Command::new("curl").arg("-sf").arg("-o").arg(&staged).arg(url));
+    if !download.success {
+        return download;
+    }
+
+    match fs::set_permissions(&staged, fs::Permissions::from_mode(0o755)) {
+        Ok(_) => {}
+        Err(error) => return failed(format!("can't make {:?} executable: {}", staged, error)),
+    }
+    match fs::rename(&executable, &previous) {
+        Ok(_) => {}
+        Err(error) => return failed(format!("can't keep the current binary: {}", error)),
+    }
+    match fs::rename(&staged, &executable) {
+        Ok(_) => {}
+        Err(error) => {
+            let _ = fs::rename(&previous, &executable);
+            return failed(format!("can't install the new binary: {}", error));
+        }
+    }
+    if let Err(error) = write_pending(&executable.with_extension("pending"), 0) {
+        let _ = fs::rename(&previous, &executable);
+        return failed(error);
+    }
+
+    CommandResult {
+        success: true,
+        output: format!("installed {}, restarting", url),
+    }
+}
+
+fn rollback_agent() -> CommandResult {
+    let executable = match env::current_exe() {
+        Ok(executable) => executable,
+        Err(error) => return failed(format!("can't find own executable: {}", error)),
+    };
+    let previous = executable.with_extension("previous");
+
+    match fs::rename(&previous, &executable) {
+        Ok(_) => {
+            let _ = fs::remove_file(executable.with_extension("pending"));
+            CommandResult {
+                success: true,
+                output: "restored the previous binary, restarting".to_string(),
+            }
+        }
+        Err(error) => failed(format!("no previous binary to go back to: {}", error)),
+    }
+}
+
+// the delay gives the response to the controller a chance to go out first
+fn restart_soon(restart: Sender<String>, reason: String) {
+    thread::spawn(move || {
+        thread::sleep(Duration::from_secs(1));
+        let _ = restart.send(reason);
+    });
+}
+
+pub fn failed(output: String) -> CommandResult {
+    CommandResult {
+        success: false,
+        output: output,
+    }
+}
+
+// removes exited containers and dangling images
+fn docker_gc() -> CommandResult {
+    let containers = run_docker(&["ps", "-a", "-q", "-f", "status=exited"]);
+    let images = run_docker(&["images", "-q", "-f", "dangling=true"]);
+    if !containers.success || !images.success {
+        return CommandResult {
+            success: false,
+            output: format!("{} {}", containers.output, images.output).trim().to_string(),
+        };
+    }
+
+    let container_ids: Vec<&str> = containers.output.split_whitespace().collect();
+    let image_ids: Vec<&str> = images.output.split_whitespace().collect();
+    let mut success = true;
+
+    if !container_ids.is_empty() {
+        let mut arguments = vec!["rm"];
+        arguments.extend(container_ids.iter().cloned());
+        success = run_docker(&arguments).success && success;
+    }
+    if !image_ids.is_empty() {
+        let mut arguments = vec!["rmi"];
+        arguments.extend(image_ids.iter().cloned());
+        success = run_docker(&arguments).success && success;
+    }
+
+    CommandResult {
+        success: success,
+        output: format!("removed {} containers and {} images", container_ids.len(), image_ids.len()),
+    }
+}
+
+fn run_docker(arguments: &[&str]) -> CommandResult {
+    run(Command::new("docker").args(arguments))
+}
+
+pub fn run(command: &mut Command) -> CommandResult {
+    match command.output() {
+        Ok(output) => {
+            let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+            text.push_str(&String::from_utf8_lossy(&output.stderr));
+            CommandResult {
+                success: output.status.success(),
+                output: text.trim().to_string(),
+            }
+        }
+        Err(error) => failed(format!("can't run command: {}", error)),
+    }
+}
+
+fn is_authorized(token: &String, request: &Request) -> bool {
+    if token.is_empty() {
+        return true;
+    }
+
+    match request.headers.get_raw(AGENT_TOKEN_HEADER) {
+        Some(values) => values.iter().any(|value| value.as_slice() == token.as_bytes()),
+        None => false,
+    }
+}
+
+fn read_query_parameter(request: &Request, key: &str) -> String {
+    let url = request.url.clone().into_generic_url();
+    let query: String = match url.query {
+        Some(q) => q.clone(),
+        None => "".to_string(),
+    };
+
+    for pair in query.split('&') {
+        let mut elements = pair.splitn(2, '=');
+        if elements.next() == Some(key) {
+            return elements.next().unwrap_or("").to_string();
+        }
+    }
+
+    "".to_string()
+}
@@ -0,0 +1,157 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use iron::{BeforeMiddleware, IronError, IronResult, Request};
+use iron::status;
+use std::error::Error;
+use std::fmt;
+use std::net::IpAddr;
+use super::auth::is_mutating;
+use utils::read_string_list;
+use yaml_rust::Yaml;
+
+#[derive(Clone, Debug)]
+pub struct Cidr {
+    network: Vec<u8>,
+    prefix: usize,
+}
+
+// source addresses allowed to reach the api, an empty read list leaves reading open while
+// an empty admin list lets whoever may read change things as well; admin sources may read as well
+pub struct Allowlist {
+    read: Vec<Cidr>,
+    admin: Vec<Cidr>,
+}
+
+#[derive(Debug)]
+struct AllowlistError {
+    message: String,
+}
+
+impl fmt::Display for AllowlistError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for AllowlistError {
+    fn description(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Cidr {
+    // 10.0.0.0/8, fd00::/8, a plain address is taken as a single host
+    pub fn parse(value: &str) -> Option<Cidr> {
+        let mut elements = value.trim().splitn(2, '/');
+        let address = match elements.next().unwrap_or("").parse::<IpAddr>() {
+            Ok(address) => address,
+            Err(_) => return None,
+        };
+        let network = octets(&address);
+        let max_prefix = network.len() * 8;
+
+        let prefix = match elements.next() {
+            Some(prefix) => {
+                match prefix.parse::<usize>() {
+                    Ok(prefix) if prefix <= max_prefix => prefix,
+                    _ => return None,
+                }
+            }
+            None => max_prefix,
+        };
+
+        Some(Cidr {
+            network: network,
+            prefix: prefix,
+        })
+    }
+
+    pub fn contains(&self, address: &IpAddr) -> bool {
+        let address = octets(address);
+        if address.len() != self.network.len() {
+            return false;
+        }
+
+        for bit in 0..self.prefix {
+            let mask = 0x80 >> (bit % 8);
+            if (address[bit / 8] & mask) != (self.network[bit / 8] & mask) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+impl Allowlist {
+    pub fn new(config: &Yaml) -> Allowlist {
+        Allowlist {
+            read: read_cidrs(&config["api"]["allowlist"], "read"),
+            admin: read_cidrs(&config["api"]["allowlist"], "admin"),
+        }
+    }
+
+    fn is_allowed(&self, address: &IpAddr, mutating: bool) -> bool {
+        let in_read = self.read.is_empty() || self.read.iter().any(|cidr| cidr.contains(address));
+        let in_admin = self.admin.iter().any(|cidr| cidr.contains(address));
+        match (mutating, self.admin.is_empty()) {
+            (true, true) => in_read,
+            (true, false) => in_admin,
+            (false, _) => in_read || in_admin,
+        }
+    }
+}
+
+impl BeforeMiddleware for Allowlist {
+    fn before(&self, request: &mut Request) -> IronResult<()> {
+        let address = request.remote_addr.ip();
+
+        if !self.is_allowed(&address, is_mutating(request)) {
+            warn!("api request rejected by allowlist peer={}", address);
+            let message = format!("{} is not allowed to call this api", address);
+            return Err(IronError::new(AllowlistError { message: message.clone() }, (status::Forbidden, message)));
+        }
+
+        Ok(())
+    }
+}
+
+fn read_cidrs(element: &Yaml, key: &str) -> Vec<Cidr> {
+    let mut result = vec![];
+
+    for value in read_string_list(element, key.to_string()) {
+        match Cidr::parse(&value) {
+            Some(cidr) => result.push(cidr),
+            None => warn!("ignoring invalid cidr in api allowlist cidr={}", value),
+        }
+    }
+
+    result
+}
+
+fn octets(address: &IpAddr) -> Vec<u8> {
+    match *address {
+        IpAddr::V4(ref address) => address.octets().to_vec(),
+        IpAddr::V6(ref address) => address.octets().to_vec(),
+    }
+}
@@ -0,0 +1,143 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use chrono::UTC;
+use iron::status;
+use state::{StateManager, Task, TaskState};
+use std::net::IpAddr;
+use utils::read_string;
+
+// checks a task announced by a peer controller before it goes into the state,
+// returns the normalized task or the status and reason to reject it with
+pub fn validate_announced_task(state_manager: &StateManager, task: &Task, peer: &IpAddr) -> Result<Task, (status::Status, String)> {
+    if task.name.is_empty() || task.id.is_empty() {
+        return Err((status::UnprocessableEntity, "name and id are required".to_string()));
+    }
+    if !is_sane_resource(task.cpu) || !is_sane_resource(task.memory) {
+        return Err((status::UnprocessableEntity, format!("invalid resources, cpu: {} memory: {}", task.cpu, task.memory)));
+    }
+    if !task.ip.is_empty() && task.ip.parse::<IpAddr>().is_err() {
+        return Err((status::UnprocessableEntity, format!("invalid ip: {}", task.ip)));
+    }
+    if state_manager.request_node(task.node_name.clone()).is_none() {
+        return Err((status::UnprocessableEntity, format!("unknown node: {}", task.node_name)));
+    }
+
+    try!(verify_controller(state_manager, &task.controller, peer));
+
+    // whatever the peer claims, an announced task is running and has to keep being announced
+    // to stay, the clean cycle drops it once the announcements stop
+    let mut normalized = task.clone();
+    normalized.state = TaskState::Running;
+    normalized.last_update = UTC::now().timestamp();
+    if normalized.running_since == 0 {
+        normalized.running_since = normalized.last_update;
+    }
+    normalized.failure_reason = "".to_string();
+    normalized.network_retries = 0;
+    normalized.restart_count = 0;
+    normalized.power_in_watts = 0.0;
+    normalized.energy_in_wh = 0.0;
+
+    Ok(normalized)
+}
+
+// a task name already held by another controller is a conflict, a new id from the same controller is just a restart;
+// task-announcements.conflict_policy decides: reject (default), namespace as name@controller, or newest_wins
+pub fn resolve_announce_conflict(state_manager: &StateManager, task: Task) -> Result<Task, (status::Status, String)> {
+    let existing = match state_manager.request_task(task.name.clone()) {
+        Some(existing) => existing,
+        None => return Ok(task),
+    };
+    if existing.controller == task.controller {
+        return Ok(task);
+    }
+
+    let policy = read_string(&state_manager.get_yaml()["task-announcements"], "conflict_policy".to_string());
+    let (result, outcome) = match policy.as_ref() {
+        "namespace" => {
+            let mut namespaced = task.clone();
+            namespaced.name = format!("{}@{}", task.name, task.controller);
+            let outcome = format!("kept as {}", namespaced.name);
+            (Ok(namespaced), outcome)
+        }
+        "newest_wins" => (Ok(task.clone()), "announcement replaced it".to_string()),
+        _ => {
+            let reason = format!("{} is already held by {}", task.name, existing.controller);
+            (Err((status::Conflict, reason)), "announcement rejected".to_string())
+        }
+    };
+
+    state_manager.send_report_task_conflict(task.name.clone(), task.controller.clone(), existing.controller, outcome);
+    result
+}
+
+// a peer may only announce for itself: nobody but this controller speaks for this controller,
+// a controller we run as a task has to announce from that task's address and any other one has to be
+// listed in task-announcements.controllers with the address it announces from, everybody else is turned away
+fn verify_controller(state_manager: &StateManager, controller: &String, peer: &IpAddr) -> Result<(), (status::Status, String)> {
+    let peer = peer.to_string();
+
+    if controller.is_empty() {
+        return Err((status::UnprocessableEntity, "controller is required".to_string()));
+    }
+    if *controller == state_manager.get_my_name() && peer != state_manager.get_my_ip() {
+        return Err((status::Forbidden, format!("{} can't announce for this controller", peer)));
+    }
+
+    match state_manager.request_task(controller.clone()) {
+        Some(controller_task) => {
+            let mut addresses = vec![controller_task.ip.clone()];
+            match state_manager.request_node(controller_task.node_name.clone()) {
+                Some(node) => {
+                    addresses.push(node.ip);
+                    addresses.push(node.external_ip);
+                }
+                None => {}
+            }
+
+            match addresses.contains(&peer) {
+                true => Ok(()),
+                false => Err((status::Forbidden, format!("{} can't announce for {}", peer, controller))),
+            }
+        }
+        None => {
+            let config = state_manager.get_yaml();
+            let listed = config["task-announcements"]["controllers"]
+                .as_vec()
+                .map(|controllers| {
+                    controllers.iter().any(|entry| {
+                        read_string(entry, "name".to_string()) == *controller && read_string(entry, "ip".to_string()) == peer
+                    })
+                })
+                .unwrap_or(false);
+            match listed {
+                true => Ok(()),
+                false => Err((status::Forbidden, format!("unknown controller {} announcing from {}", controller, peer))),
+            }
+        }
+    }
+}
+
+fn is_sane_resource(value: f64) -> bool {
+    value.is_finite() && value >= 0.0
+}
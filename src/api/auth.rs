@@ -0,0 +1,152 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use iron::{BeforeMiddleware, IronError, IronResult, Request};
+use iron::headers::{Authorization, Bearer};
+use iron::method::Method;
+use iron::status;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use utils::{read_string, read_string_map};
+use yaml_rust::Yaml;
+
+// GET routes that nevertheless change the cluster
+static MUTATING_GET_ROUTES: &'static [&'static str] = &["/node/startup",
+                                                        "/node/shutdown",
+                                                        "/start/group",
+                                                        "/calico/configure",
+                                                        "/calico/shutdown"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Role {
+    Admin,
+    Observer,
+}
+
+pub struct Authenticator {
+    tokens: HashMap<String, Role>,
+    route_roles: HashMap<String, Role>,
+}
+
+#[derive(Debug)]
+struct AuthError {
+    message: String,
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for AuthError {
+    fn description(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Authenticator {
+    pub fn new(config: &Yaml) -> Authenticator {
+        let mut tokens = HashMap::new();
+
+        match config["api"]["tokens"].as_vec() {
+            Some(entries) => {
+                for entry in entries {
+                    let role = read_role(&read_string(entry, "role".to_string()));
+                    tokens.insert(read_string(entry, "token".to_string()), role);
+                }
+            }
+            None => {}
+        }
+
+        // overrides what the method implies, e.g. heartbeats from node agents holding an observer token
+        // or logs that only admins get to read
+        let route_roles = read_string_map(&config["api"], "route_roles".to_string())
+            .iter()
+            .map(|(path, role)| (path.clone(), read_role(role)))
+            .collect();
+
+        Authenticator {
+            tokens: tokens,
+            route_roles: route_roles,
+        }
+    }
+
+    fn required_role(&self, request: &Request) -> Role {
+        let path = format!("/{}", request.url.path.join("/"));
+        match self.route_roles.get(&path) {
+            Some(role) => role.clone(),
+            None if is_mutating(request) => Role::Admin,
+            None => Role::Observer,
+        }
+    }
+
+    fn role_for_request(&self, request: &Request) -> Option<Role> {
+        match request.headers.get::<Authorization<Bearer>>() {
+            Some(header) => self.tokens.get(&header.token).cloned(),
+            None => None,
+        }
+    }
+}
+
+impl BeforeMiddleware for Authenticator {
+    fn before(&self, request: &mut Request) -> IronResult<()> {
+        // no tokens configured, keep the api open
+        if self.tokens.is_empty() {
+            return Ok(());
+        }
+
+        let role = match self.role_for_request(request) {
+            Some(role) => role,
+            None => return Err(auth_error(status::Unauthorized, "missing or invalid token")),
+        };
+
+        if role == Role::Observer && self.required_role(request) == Role::Admin {
+            return Err(auth_error(status::Forbidden, "observer is not allowed on this route"));
+        }
+
+        Ok(())
+    }
+}
+
+pub fn is_mutating(request: &Request) -> bool {
+    match request.method {
+        Method::Get | Method::Head | Method::Options => {
+            let path = format!("/{}", request.url.path.join("/"));
+            MUTATING_GET_ROUTES.contains(&path.as_ref())
+        }
+        _ => true,
+    }
+}
+
+fn read_role(role: &String) -> Role {
+    match role.as_ref() {
+        "admin" => Role::Admin,
+        _ => Role::Observer,
+    }
+}
+
+fn auth_error(status: status::Status, message: &str) -> IronError {
+    IronError::new(AuthError { message: message.to_string() },
+                   (status, message.to_string()))
+}
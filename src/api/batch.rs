@@ -0,0 +1,160 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use collaborator::kill_task;
+use rustc_serialize::json::Json;
+use state::StateManager;
+use super::service::parse_service;
+use utils::{Task, first_task_name, read_bool, read_service_definition, start_task};
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct BatchResult {
+    pub op: String,
+    pub name: String,
+    pub ok: bool,
+    pub result: String,
+}
+
+enum Planned {
+    Start(Task),
+    Restart,
+    Stop(bool),
+    Rejected(String),
+}
+
+// e.g. [{"op": "restart", "name": "redis"}, {"op": "start", "name": "dns"}], a start takes the service
+// from the configured service groups or from "service" given like for POST /service, a stop takes "soft"
+// like DELETE /service and otherwise follows deletes.soft,
+// every operation is checked before any of them runs and the restarts reach the state in one go
+pub fn run_batch(state_manager: &StateManager, body: &str) -> Result<Vec<BatchResult>, String> {
+    let items = match Json::from_str(body) {
+        Ok(Json::Array(items)) => items,
+        Ok(_) => return Err("expected an array of operations".to_string()),
+        Err(error) => return Err(format!("invalid json: {}", error)),
+    };
+
+    let mut planned: Vec<(String, String, Planned)> = vec![];
+    for item in &items {
+        let op = item.find("op").and_then(|op| op.as_string()).unwrap_or("").to_string();
+        let name = item.find("name").and_then(|name| name.as_string()).unwrap_or("").to_string();
+        let already_planned = planned.iter().any(|&(_, ref planned_name, _)| *planned_name == name);
+        let plan = if already_planned {
+            Planned::Rejected(format!("{} is already part of this batch", name))
+        } else {
+            plan(state_manager, &op, &name, item)
+        };
+        planned.push((op, name, plan));
+    }
+
+    let restarts: Vec<String> = planned.iter()
+        .filter(|&&(_, _, ref plan)| match *plan {
+            Planned::Restart => true,
+            _ => false,
+        })
+        .map(|&(_, ref name, _)| name.clone())
+        .collect();
+    if !restarts.is_empty() {
+        state_manager.send_restart_tasks(restarts.clone());
+        for name in &restarts {
+            kill_task(name);
+        }
+    }
+
+    let mut results = vec![];
+    for (op, name, plan) in planned {
+        let (ok, result) = match plan {
+            Planned::Start(task) => {
+                match start_task(state_manager, &task, false) {
+                    Ok(_) => (true, "started".to_string()),
+                    Err(reason) => (false, reason),
+                }
+            }
+            Planned::Restart => (true, "restarting".to_string()),
+            Planned::Stop(false) => {
+                state_manager.send_kill_task_by_name(name.clone());
+                (true, "stopping".to_string())
+            }
+            Planned::Stop(true) => {
+                match state_manager.send_delete_task(name.clone()) {
+                    Ok(_) => (true, "deleted".to_string()),
+                    Err(reason) => (false, reason),
+                }
+            }
+            Planned::Rejected(reason) => (false, reason),
+        };
+        results.push(BatchResult {
+            op: op,
+            name: name,
+            ok: ok,
+            result: result,
+        });
+    }
+
+    Ok(results)
+}
+
+fn plan(state_manager: &StateManager, op: &String, name: &String, item: &Json) -> Planned {
+    if name.is_empty() {
+        return Planned::Rejected("name is required".to_string());
+    }
+
+    match op.as_ref() {
+        "start" => {
+            let task = match item.find("service") {
+                Some(service) => {
+                    match parse_service(state_manager, &service.to_string()) {
+                        Ok(task) => task,
+                        Err((_, reason)) => return Planned::Rejected(reason),
+                    }
+                }
+                None => {
+                    match read_service_definition(state_manager, name) {
+                        Some(task) => task,
+                        None => return Planned::Rejected(format!("no service {} configured", name)),
+                    }
+                }
+            };
+            if task.name != *name {
+                return Planned::Rejected(format!("service is called {}", task.name));
+            }
+            if state_manager.request_task(first_task_name(&task)).is_some() {
+                return Planned::Rejected(format!("{} already exists", name));
+            }
+            Planned::Start(task)
+        }
+        "restart" | "stop" => {
+            if state_manager.request_task(name.clone()).is_none() {
+                return Planned::Rejected(format!("no task {}", name));
+            }
+            match op.as_ref() {
+                "restart" => Planned::Restart,
+                _ => {
+                    let soft = item.find("soft")
+                        .and_then(|soft| soft.as_boolean())
+                        .unwrap_or_else(|| read_bool(&state_manager.get_yaml()["deletes"], "soft".to_string()));
+                    Planned::Stop(soft)
+                }
+            }
+        }
+        _ => Planned::Rejected(format!("unknown op {}, expected start, stop or restart", op)),
+    }
+}
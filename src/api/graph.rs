@@ -0,0 +1,131 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use scheduler::Constraint;
+use state::{StateManager, Task};
+use std::collections::{BTreeMap, BTreeSet};
+
+// nodes and links the way d3's force layout takes them, graphviz gets the same from a few lines of jq
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct ServiceGraph {
+    pub nodes: Vec<ServiceGraphNode>,
+    pub links: Vec<ServiceGraphLink>,
+}
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct ServiceGraphNode {
+    pub id: String,
+    pub state: String,
+    pub placement: String,
+    pub ip: String,
+    pub group: String,
+}
+
+// kind is dependency, affinity or anti-affinity, pointing from the task that asks for it
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct ServiceGraphLink {
+    pub source: String,
+    pub target: String,
+    pub kind: String,
+}
+
+// names a task refers to without being known get a node of their own, in state Missing
+pub fn service_graph(state_manager: &StateManager) -> ServiceGraph {
+    let tasks = state_manager.request_list_tasks();
+    let node_by_slave_id: BTreeMap<String, String> = state_manager.request_list_nodes()
+        .into_iter()
+        .filter(|node| !node.slave_id.is_empty())
+        .map(|node| (node.slave_id, node.name))
+        .collect();
+
+    let mut graph = ServiceGraph {
+        nodes: vec![],
+        links: vec![],
+    };
+    let mut known: BTreeSet<String> = BTreeSet::new();
+
+    for task in &tasks {
+        let placement = match node_by_slave_id.get(&task.slave_id) {
+            Some(node_name) => node_name.clone(),
+            None => task.node_name.clone(),
+        };
+        graph.nodes.push(ServiceGraphNode {
+            id: task.name.clone(),
+            state: format!("{:?}", task.state),
+            placement: placement,
+            ip: task.ip.clone(),
+            group: group_of(task),
+        });
+        known.insert(task.name.clone());
+    }
+
+    for task in &tasks {
+        if !task.dependent_service.is_empty() {
+            add_link(&mut graph, task, &task.dependent_service, "dependency");
+        }
+        for constraint in task.constraints.iter().filter_map(|source| Constraint::parse(source).ok()) {
+            match constraint {
+                Constraint::Affinity(target) => add_link(&mut graph, task, &target, "affinity"),
+                Constraint::AntiAffinity(target) => add_link(&mut graph, task, &target, "anti-affinity"),
+                Constraint::Attribute { .. } => {}
+            }
+        }
+    }
+
+    // links to an array or replica group point at its name, which is a group rather than a task
+    let groups: BTreeSet<String> = tasks.iter().map(group_of).filter(|group| !group.is_empty()).collect();
+    let missing: BTreeSet<String> = graph.links
+        .iter()
+        .map(|link| link.target.clone())
+        .filter(|target| !known.contains(target))
+        .collect();
+    for target in missing {
+        let state = match groups.contains(&target) {
+            true => "Group",
+            false => "Missing",
+        };
+        graph.nodes.push(ServiceGraphNode {
+            id: target.clone(),
+            state: state.to_string(),
+            placement: "".to_string(),
+            ip: "".to_string(),
+            group: "".to_string(),
+        });
+    }
+
+    graph
+}
+
+fn group_of(task: &Task) -> String {
+    match task.array_name.is_empty() {
+        true => task.replica_group.clone(),
+        false => task.array_name.clone(),
+    }
+}
+
+fn add_link(graph: &mut ServiceGraph, task: &Task, target: &String, kind: &str) {
+    graph.links.push(ServiceGraphLink {
+        source: task.name.clone(),
+        target: target.clone(),
+        kind: kind.to_string(),
+    });
+}
@@ -0,0 +1,131 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use iron::{BeforeMiddleware, IronError, IronResult, Request, Timeouts};
+use iron::headers::ContentLength;
+use iron::status;
+use iron::typemap::Key;
+use std::error::Error;
+use std::fmt;
+use std::io::{ErrorKind, Read};
+use std::time::Duration;
+use utils::read_int;
+use yaml_rust::Yaml;
+
+const DEFAULT_MAX_BODY_IN_BYTES: i64 = 1048576;
+const DEFAULT_TIMEOUT_IN_SECONDS: i64 = 10;
+const DEFAULT_MAX_CONNECTIONS: i64 = 32;
+
+// reads the body up front so a handler never sees more than max_body_in_bytes,
+// handlers pick it up with request_body()
+pub struct BodyLimit {
+    max_body_in_bytes: u64,
+}
+
+struct RequestBody;
+
+impl Key for RequestBody {
+    type Value = String;
+}
+
+#[derive(Debug)]
+struct LimitError {
+    message: String,
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for LimitError {
+    fn description(&self) -> &str {
+        &self.message
+    }
+}
+
+impl BodyLimit {
+    pub fn new(config: &Yaml) -> BodyLimit {
+        let max_body_in_bytes = read_int(&config["api"]["limits"],
+                                         "max_body_in_bytes".to_string(),
+                                         DEFAULT_MAX_BODY_IN_BYTES);
+        BodyLimit { max_body_in_bytes: max_body_in_bytes as u64 }
+    }
+}
+
+impl BeforeMiddleware for BodyLimit {
+    fn before(&self, request: &mut Request) -> IronResult<()> {
+        match request.headers.get::<ContentLength>() {
+            Some(&ContentLength(length)) if length > self.max_body_in_bytes => {
+                return Err(limit_error(status::PayloadTooLarge, "request body too large"));
+            }
+            _ => {}
+        }
+
+        // one byte more than allowed tells a too large chunked body apart from one that just fits
+        let mut body = String::new();
+        let result = request.body.by_ref().take(self.max_body_in_bytes + 1).read_to_string(&mut body);
+        match result {
+            Ok(length) if length as u64 > self.max_body_in_bytes => {
+                return Err(limit_error(status::PayloadTooLarge, "request body too large"));
+            }
+            Ok(_) => {}
+            Err(ref error) if error.kind() == ErrorKind::WouldBlock || error.kind() == ErrorKind::TimedOut => {
+                return Err(limit_error(status::RequestTimeout, "timed out reading request body"));
+            }
+            Err(_) => return Err(limit_error(status::BadRequest, "can't read request body")),
+        }
+
+        request.extensions.insert::<RequestBody>(body);
+        Ok(())
+    }
+}
+
+pub fn request_body(request: &Request) -> String {
+    match request.extensions.get::<RequestBody>() {
+        Some(body) => body.clone(),
+        None => "".to_string(),
+    }
+}
+
+// the server works off one connection per thread, so the thread count caps concurrent connections
+pub fn max_connections(config: &Yaml) -> usize {
+    read_int(&config["api"]["limits"], "max_connections".to_string(), DEFAULT_MAX_CONNECTIONS) as usize
+}
+
+pub fn timeouts(config: &Yaml) -> Timeouts {
+    let limits = &config["api"]["limits"];
+    let read = read_int(limits, "read_timeout_in_seconds".to_string(), DEFAULT_TIMEOUT_IN_SECONDS);
+    let write = read_int(limits, "write_timeout_in_seconds".to_string(), DEFAULT_TIMEOUT_IN_SECONDS);
+
+    Timeouts {
+        keep_alive: Some(Duration::from_secs(read as u64)),
+        read: Some(Duration::from_secs(read as u64)),
+        write: Some(Duration::from_secs(write as u64)),
+    }
+}
+
+fn limit_error(status: status::Status, message: &str) -> IronError {
+    IronError::new(LimitError { message: message.to_string() },
+                   (status, message.to_string()))
+}
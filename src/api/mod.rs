@@ -21,5 +21,22 @@
 // THE SOFTWARE.
 
 pub use self::run_api::run_api;
+pub use self::service::read_scheduled_service;
+pub use self::task_version::decode_task;
 
+mod allowlist;
+mod announce;
+mod auth;
+mod batch;
+mod graph;
+mod limits;
+mod node_register;
+mod node_update;
+mod route_dump;
 mod run_api;
+mod schema;
+mod service;
+mod shutdown_gate;
+mod task_override;
+mod task_version;
+mod tls;
@@ -0,0 +1,75 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use rustc_serialize::json::Json;
+use state::Node;
+use std::net::IpAddr;
+
+// the body of POST /node/register, name and ip are required, the type defaults to slave,
+// with persist the node gets stored and comes back after a restart
+pub fn parse_node_registration(body: &str) -> Result<Node, String> {
+    let json = try!(Json::from_str(body).map_err(|error| format!("invalid json: {}", error)));
+    if json.as_object().is_none() {
+        return Err("expected an object".to_string());
+    }
+
+    let name = try!(read_string(&json, "name"));
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == '.') {
+        return Err(format!("invalid name: {}", name));
+    }
+    let ip = try!(read_ip(&json, "ip"));
+    if ip.is_empty() {
+        return Err("ip is required".to_string());
+    }
+    let node_type = match try!(read_string(&json, "type")) {
+        ref node_type if node_type.is_empty() => "slave".to_string(),
+        node_type => node_type,
+    };
+
+    let mut node = Node::new(name,
+                             ip,
+                             try!(read_ip(&json, "external_ip")),
+                             try!(read_ip(&json, "management_ip")),
+                             node_type);
+    node.registered = match json.find("persist") {
+        Some(&Json::Boolean(persist)) => persist,
+        Some(_) => return Err("persist has to be true or false".to_string()),
+        None => false,
+    };
+    Ok(node)
+}
+
+fn read_string(json: &Json, key: &str) -> Result<String, String> {
+    match json.find(key) {
+        Some(&Json::String(ref value)) => Ok(value.trim().to_string()),
+        Some(_) => Err(format!("{} has to be a string", key)),
+        None => Ok("".to_string()),
+    }
+}
+
+fn read_ip(json: &Json, key: &str) -> Result<String, String> {
+    let ip = try!(read_string(json, key));
+    if !ip.is_empty() && ip.parse::<IpAddr>().is_err() {
+        return Err(format!("{} has to be an ip address", key));
+    }
+    Ok(ip)
+}
@@ -0,0 +1,107 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use rustc_serialize::json::Json;
+use state::NodeUpdate;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+const UPDATABLE_FIELDS: [&'static str; 6] = ["external_ip", "management_ip", "port_id", "zone", "labels", "annotations"];
+
+// the body of PATCH /node, only the fields present get changed and a label or annotation set to null gets removed
+pub fn parse_node_update(body: &str) -> Result<NodeUpdate, String> {
+    let json = try!(Json::from_str(body).map_err(|error| format!("invalid json: {}", error)));
+    let fields = match json.as_object() {
+        Some(fields) => fields,
+        None => return Err("expected an object".to_string()),
+    };
+
+    for key in fields.keys() {
+        if !UPDATABLE_FIELDS.contains(&key.as_ref()) {
+            return Err(format!("{} can't be changed", key));
+        }
+    }
+
+    let update = NodeUpdate {
+        external_ip: try!(read_ip(&json, "external_ip")),
+        management_ip: try!(read_ip(&json, "management_ip")),
+        port_id: match json.find("port_id") {
+            Some(value) => {
+                match value.as_i64() {
+                    Some(port_id) if port_id >= 0 => Some(port_id),
+                    _ => return Err("port_id has to be a non-negative whole number".to_string()),
+                }
+            }
+            None => None,
+        },
+        zone: match json.find("zone") {
+            Some(&Json::String(ref zone)) => Some(zone.clone()),
+            Some(_) => return Err("zone has to be a string".to_string()),
+            None => None,
+        },
+        labels: try!(read_map_update(&json, "labels")),
+        annotations: try!(read_map_update(&json, "annotations")),
+    };
+
+    Ok(update)
+}
+
+// the body of PATCH /service/annotations, {"annotations": {...}} with the same rules as for a node
+pub fn parse_annotations_update(body: &str) -> Result<BTreeMap<String, Option<String>>, String> {
+    let json = try!(Json::from_str(body).map_err(|error| format!("invalid json: {}", error)));
+    match json.find("annotations") {
+        Some(_) => read_map_update(&json, "annotations"),
+        None => Err("expected an object with annotations".to_string()),
+    }
+}
+
+// an empty string clears an address
+fn read_ip(json: &Json, key: &str) -> Result<Option<String>, String> {
+    match json.find(key) {
+        Some(&Json::String(ref ip)) if ip.is_empty() || ip.parse::<IpAddr>().is_ok() => Ok(Some(ip.clone())),
+        Some(_) => Err(format!("{} has to be an ip address", key)),
+        None => Ok(None),
+    }
+}
+
+fn read_map_update(json: &Json, field: &str) -> Result<BTreeMap<String, Option<String>>, String> {
+    let mut result = BTreeMap::new();
+
+    match json.find(field) {
+        Some(&Json::Object(ref entries)) => {
+            for (key, value) in entries {
+                if key.is_empty() {
+                    return Err(format!("{} keys can't be empty", field));
+                }
+                match *value {
+                    Json::String(ref value) => result.insert(key.clone(), Some(value.clone())),
+                    Json::Null => result.insert(key.clone(), None),
+                    _ => return Err(format!("{}.{} has to be a string or null", field, key)),
+                };
+            }
+        }
+        Some(_) => return Err(format!("{} has to be an object", field)),
+        None => {}
+    }
+
+    Ok(result)
+}
@@ -0,0 +1,116 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use collaborator::{FibEntry, read_fib};
+use state::StateManager;
+use std::collections::{BTreeMap, BTreeSet};
+
+// the routes the controller wants next to what the network agent has programmed, fib is left empty
+// with fib_error set for backends that can't be read back
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct RouteDump {
+    pub agent_type: String,
+    pub connection: String,
+    pub desired: Vec<DesiredRoute>,
+    pub fib: Vec<FibEntry>,
+    pub fib_error: String,
+    pub missing: Vec<String>,
+    pub unexpected: Vec<String>,
+}
+
+// kind is infrastructure for system services and task for everything else,
+// pending while the route queue still holds an operation for it
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct DesiredRoute {
+    pub task_name: String,
+    pub kind: String,
+    pub route_to: String,
+    pub route_via: String,
+    pub pending: bool,
+}
+
+pub fn route_dump(state_manager: &StateManager) -> RouteDump {
+    let network_agent = state_manager.get_network_agent();
+    let system_services: BTreeSet<String> = state_manager.request_list_tasks()
+        .into_iter()
+        .filter(|task| task.is_system_service)
+        .map(|task| task.name)
+        .collect();
+    let pending: BTreeSet<String> = state_manager.request_list_route_operations()
+        .into_iter()
+        .map(|operation| operation.task_name)
+        .collect();
+
+    let mut desired: Vec<DesiredRoute> = state_manager.request_list_routes()
+        .into_iter()
+        .filter(|route| !route.ip.is_empty() && !route.next_hop.is_empty())
+        .map(|route| {
+            DesiredRoute {
+                kind: match system_services.contains(&route.task_name) {
+                    true => "infrastructure".to_string(),
+                    false => "task".to_string(),
+                },
+                pending: pending.contains(&route.task_name),
+                task_name: route.task_name,
+                route_to: format!("{}/32", route.ip),
+                route_via: route.next_hop,
+            }
+        })
+        .collect();
+    desired.sort_by(|a, b| a.route_to.cmp(&b.route_to));
+
+    let mut dump = RouteDump {
+        agent_type: network_agent.agent_type().to_string(),
+        connection: network_agent.connection().clone(),
+        desired: desired,
+        fib: vec![],
+        fib_error: "".to_string(),
+        missing: vec![],
+        unexpected: vec![],
+    };
+
+    match read_fib(&*network_agent) {
+        Ok(fib) => dump.fib = fib,
+        Err(error) => {
+            dump.fib_error = error.to_string();
+            return dump;
+        }
+    }
+
+    // a route with the wrong next hop counts as missing
+    let programmed: BTreeMap<&String, &String> = dump.fib.iter().map(|entry| (&entry.route_to, &entry.route_via)).collect();
+    let wanted: BTreeSet<&String> = dump.desired.iter().map(|route| &route.route_to).collect();
+    let missing: Vec<String> = dump.desired
+        .iter()
+        .filter(|route| programmed.get(&route.route_to) != Some(&&route.route_via))
+        .map(|route| route.route_to.clone())
+        .collect();
+    let unexpected: Vec<String> = dump.fib
+        .iter()
+        .filter(|entry| !wanted.contains(&entry.route_to))
+        .map(|entry| entry.route_to.clone())
+        .collect();
+    dump.missing = missing;
+    dump.unexpected = unexpected;
+
+    dump
+}
@@ -20,28 +20,109 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 // THE SOFTWARE.
 
-use collaborator::{configure_network, shutdown_network, shutdown_node, startup_node};
+use agent::{AgentCommand, CommandResult, Heartbeat};
+use chrono::UTC;
+use collaborator::{HttpError, agent_token, configure_network, find_mesos_agent, join_network, mesos_agents, run_agent_command,
+                   shutdown_network, shutdown_node, startup_node, tail_container_logs, tail_sandbox_file, task_sandbox};
+use health::{DrainReport, plan_agent_upgrade, run_agent_upgrade, run_bootstrap, run_consul_gc, run_drain, run_rolling_update,
+             run_selftest, run_teardown, start_service_group};
 use hyper::header::AccessControlAllowOrigin;
-use iron::{Iron, IronResult, Request, Response};
+use iron::{Chain, Iron, IronResult, Request, Response};
 use iron::mime::{Mime, SubLevel, TopLevel};
 use iron::status;
 use router::Router;
-use rustc_serialize::json;
-use state::{StateManager, Task};
-use std::io::Read;
+use rustc_serialize::json::{self, Json};
+use scheduler::{plan_capacity, scale_service};
+use state::{CollaboratorStatus, Node, PowerState, RestartPolicy, Revision, RevisionDiff, StateManager, StoredSchedule, Task,
+            TaskState, diff_revisions};
+use std::collections::BTreeMap;
+use std::str;
 use std::sync::Mutex;
-use utils::read_task;
+use std::thread;
+use std::time::Duration;
+use super::allowlist::Allowlist;
+use super::announce::{resolve_announce_conflict, validate_announced_task};
+use super::auth::Authenticator;
+use super::batch::run_batch;
+use super::graph::service_graph;
+use super::limits::{BodyLimit, max_connections, request_body, timeouts};
+use super::node_register::parse_node_registration;
+use super::node_update::{parse_annotations_update, parse_node_update};
+use super::route_dump::route_dump;
+use super::schema::{node_schema, task_schema};
+use super::service::parse_service;
+use super::shutdown_gate::ShutdownGate;
+use super::task_override::parse_task_override;
+use super::task_version::{accepted_version, content_version, decode_task, task_content_type, task_to_json, tasks_to_json};
+use super::tls::{protocol, protocol_name};
+use utils::{encode_argument_list, read_bool, read_int, read_service_group, render_metrics, start_task};
 
 pub fn run_api(state_manager: &StateManager) {
-    println!("api starting");
+    info!("api starting");
     state_manager.send_ping();
 
     let mut router = Router::new();
     router.get("/admin/ping", handle_ping);
 
+    router.get("/metrics", handle_metrics);
+    router.get("/v1/schema/task", |_r: &mut Request| handle_schema(task_schema()));
+    router.get("/v1/schema/node", |_r: &mut Request| handle_schema(node_schema()));
+
+    let health_state_manager = Mutex::new(state_manager.clone());
+    router.get("/admin/health",
+               move |_r: &mut Request| handle_health(&health_state_manager));
+
+    let admin_bootstrap_state_manager = Mutex::new(state_manager.clone());
+    router.post("/admin/bootstrap",
+                move |_r: &mut Request| handle_admin_bootstrap(&admin_bootstrap_state_manager));
+
+    let admin_teardown_state_manager = Mutex::new(state_manager.clone());
+    router.post("/admin/teardown",
+                move |request: &mut Request| handle_admin_teardown(&admin_teardown_state_manager, request));
+
+    let admin_selftest_state_manager = Mutex::new(state_manager.clone());
+    router.post("/admin/selftest",
+                move |_r: &mut Request| handle_admin_selftest(&admin_selftest_state_manager));
+
+    let admin_consul_gc_state_manager = Mutex::new(state_manager.clone());
+    router.post("/admin/consul/gc",
+                move |request: &mut Request| handle_admin_consul_gc(&admin_consul_gc_state_manager, request));
+
+    let debug_state_state_manager = Mutex::new(state_manager.clone());
+    router.get("/debug/state",
+               move |_r: &mut Request| handle_debug_state(&debug_state_state_manager));
+
+    let debug_memory_state_manager = Mutex::new(state_manager.clone());
+    router.get("/debug/memory",
+               move |_r: &mut Request| handle_debug_memory(&debug_memory_state_manager));
+
+    let admin_clean_state_manager = Mutex::new(state_manager.clone());
+    router.post("/admin/clean",
+                move |_r: &mut Request| handle_admin_clean(&admin_clean_state_manager));
+
+    let admin_clean_last_state_manager = Mutex::new(state_manager.clone());
+    router.get("/admin/clean/last",
+               move |_r: &mut Request| handle_admin_clean_last(&admin_clean_last_state_manager));
+
+    let admin_sync_state_manager = Mutex::new(state_manager.clone());
+    router.post("/admin/sync",
+                move |_r: &mut Request| handle_admin_sync(&admin_sync_state_manager));
+
+    let admin_sync_last_state_manager = Mutex::new(state_manager.clone());
+    router.get("/admin/sync/last",
+               move |_r: &mut Request| handle_admin_sync_last(&admin_sync_last_state_manager));
+
+    let agent_upgrade_state_manager = Mutex::new(state_manager.clone());
+    router.post("/maintenance/agent/upgrade",
+                move |request: &mut Request| handle_agent_upgrade(&agent_upgrade_state_manager, request));
+
+    let agent_upgrade_status_state_manager = Mutex::new(state_manager.clone());
+    router.get("/maintenance/agent/upgrade",
+               move |_r: &mut Request| handle_agent_upgrade_status(&agent_upgrade_status_state_manager));
+
     let nodes_state_manager = Mutex::new(state_manager.clone());
     router.get("/nodes",
-               move |_r: &mut Request| handle_nodes(&nodes_state_manager));
+               move |request: &mut Request| handle_nodes(&nodes_state_manager, request));
 
     let node_startup_state_manager = Mutex::new(state_manager.clone());
     router.get("/node/startup",
@@ -51,6 +132,42 @@ pub fn run_api(state_manager: &StateManager) {
     router.get("/node/shutdown",
                move |request: &mut Request| handle_node_shutdown(&node_shutdown_state_manager, request));
 
+    let node_groups_state_manager = Mutex::new(state_manager.clone());
+    router.get("/node/groups",
+               move |_r: &mut Request| handle_node_groups(&node_groups_state_manager));
+
+    let node_tasks_state_manager = Mutex::new(state_manager.clone());
+    router.get("/node/tasks",
+               move |request: &mut Request| handle_node_tasks(&node_tasks_state_manager, request));
+
+    let node_drain_state_manager = Mutex::new(state_manager.clone());
+    router.delete("/node",
+                  move |request: &mut Request| handle_node_drain(&node_drain_state_manager, request));
+
+    let node_update_state_manager = Mutex::new(state_manager.clone());
+    router.patch("/node",
+                 move |request: &mut Request| handle_node_update(&node_update_state_manager, request));
+
+    let node_register_state_manager = Mutex::new(state_manager.clone());
+    router.post("/node/register",
+                move |request: &mut Request| handle_node_register(&node_register_state_manager, request));
+
+    let node_unregister_state_manager = Mutex::new(state_manager.clone());
+    router.delete("/node/register/:name",
+                  move |request: &mut Request| handle_node_unregister(&node_unregister_state_manager, request));
+
+    let node_heartbeat_state_manager = Mutex::new(state_manager.clone());
+    router.post("/node/heartbeat",
+                move |request: &mut Request| handle_node_heartbeat(&node_heartbeat_state_manager, request));
+
+    let node_gc_state_manager = Mutex::new(state_manager.clone());
+    router.post("/node/gc",
+                move |request: &mut Request| handle_node_gc(&node_gc_state_manager, request));
+
+    let node_logs_state_manager = Mutex::new(state_manager.clone());
+    router.get("/node/logs",
+               move |request: &mut Request| handle_node_logs(&node_logs_state_manager, request));
+
     let services_metered_state_manager = Mutex::new(state_manager.clone());
     router.get("/services/metered",
                move |_r: &mut Request| handle_services_metered(&services_metered_state_manager));
@@ -59,14 +176,81 @@ pub fn run_api(state_manager: &StateManager) {
     router.get("/services/running",
                move |_r: &mut Request| handle_services_running(&services_running_state_manager));
 
+    let services_weights_state_manager = Mutex::new(state_manager.clone());
+    router.get("/services/weights",
+               move |_r: &mut Request| handle_services_weights(&services_weights_state_manager));
+
+    let jobs_state_manager = Mutex::new(state_manager.clone());
+    router.get("/jobs",
+               move |_r: &mut Request| handle_jobs(&jobs_state_manager));
+
+    let unschedule_job_state_manager = Mutex::new(state_manager.clone());
+    router.delete("/jobs",
+                  move |request: &mut Request| handle_unschedule_job(&unschedule_job_state_manager, request));
+
+    let job_arrays_state_manager = Mutex::new(state_manager.clone());
+    router.get("/jobs/arrays",
+               move |request: &mut Request| handle_job_arrays(&job_arrays_state_manager, request));
+
     let service_state_manager = Mutex::new(state_manager.clone());
     router.get("/service",
                move |request: &mut Request| handle_service(&service_state_manager, request));
 
+    let service_detail_state_manager = Mutex::new(state_manager.clone());
+    router.get("/service/detail",
+               move |request: &mut Request| handle_service_detail(&service_detail_state_manager, request));
+
+    let service_annotations_state_manager = Mutex::new(state_manager.clone());
+    router.patch("/service/annotations",
+                 move |request: &mut Request| handle_service_annotations(&service_annotations_state_manager, request));
+
+    let service_logs_state_manager = Mutex::new(state_manager.clone());
+    router.get("/service/logs",
+               move |request: &mut Request| handle_service_logs(&service_logs_state_manager, request));
+
+    let start_service_state_manager = Mutex::new(state_manager.clone());
+    router.post("/service",
+                move |request: &mut Request| handle_start_service(&start_service_state_manager, request));
+
+    let service_revisions_state_manager = Mutex::new(state_manager.clone());
+    router.get("/service/revisions",
+               move |request: &mut Request| handle_service_revisions(&service_revisions_state_manager, request));
+
     let service_delete_state_manager = Mutex::new(state_manager.clone());
     router.delete("/service",
                   move |request: &mut Request| handle_service_delete(&service_delete_state_manager, request));
 
+    let service_scale_state_manager = Mutex::new(state_manager.clone());
+    router.put("/service/scale",
+               move |request: &mut Request| handle_service_scale(&service_scale_state_manager, request));
+
+    let service_restore_state_manager = Mutex::new(state_manager.clone());
+    router.post("/service/restore",
+                move |request: &mut Request| handle_service_restore(&service_restore_state_manager, request));
+
+    let service_update_state_manager = Mutex::new(state_manager.clone());
+    router.put("/service/update",
+               move |request: &mut Request| handle_service_update(&service_update_state_manager, request));
+
+    let task_override_state_manager = Mutex::new(state_manager.clone());
+    router.put("/admin/task/state",
+               move |request: &mut Request| handle_task_override(&task_override_state_manager, request));
+
+    let history_metrics_state_manager = Mutex::new(state_manager.clone());
+    router.get("/history/metrics",
+               move |request: &mut Request| handle_history_metrics(&history_metrics_state_manager, request));
+
+    let volumes_state_manager = Mutex::new(state_manager.clone());
+    router.get("/volumes", move |_r: &mut Request| handle_volumes(&volumes_state_manager));
+
+    let graph_services_state_manager = Mutex::new(state_manager.clone());
+    router.get("/graph/services",
+               move |_r: &mut Request| handle_graph_services(&graph_services_state_manager));
+
+    let services_batch_state_manager = Mutex::new(state_manager.clone());
+    router.post("/services/batch",
+                move |request: &mut Request| handle_services_batch(&services_batch_state_manager, request));
+
     let start_service_group_state_manager = Mutex::new(state_manager.clone());
     router.get("/start/group",
                move |request: &mut Request| handle_start_service_group(&start_service_group_state_manager, request));
@@ -79,12 +263,64 @@ pub fn run_api(state_manager: &StateManager) {
     router.get("/calico/shutdown",
                move |_r: &mut Request| handle_calico_shutdown(&calico_shutdown_state_manager));
 
+    let routes_state_manager = Mutex::new(state_manager.clone());
+    router.get("/network/routes",
+               move |_r: &mut Request| handle_routes(&routes_state_manager));
+
+    let route_queue_state_manager = Mutex::new(state_manager.clone());
+    router.get("/network/routes/queue",
+               move |_r: &mut Request| handle_route_queue(&route_queue_state_manager));
+
+    let capacity_report_state_manager = Mutex::new(state_manager.clone());
+    router.get("/capacity/report",
+               move |_r: &mut Request| handle_capacity_report(&capacity_report_state_manager));
+
+    let cluster_cost_state_manager = Mutex::new(state_manager.clone());
+    router.get("/cluster/cost",
+               move |_r: &mut Request| handle_cluster_cost(&cluster_cost_state_manager));
+
+    let events_state_manager = Mutex::new(state_manager.clone());
+    router.get("/events",
+               move |r: &mut Request| handle_events(&events_state_manager, r));
+
     let handle_announce_state_manager = Mutex::new(state_manager.clone());
     router.post("/service/announce",
                 move |request: &mut Request| handle_task_announce(&handle_announce_state_manager, request));
 
-    println!("API Server listening at: 3000");
-    Iron::new(router).http("0.0.0.0:3000").unwrap();
+    let service_restarts_state_manager = Mutex::new(state_manager.clone());
+    router.get("/service/restarts",
+               move |_r: &mut Request| handle_service_restarts(&service_restarts_state_manager));
+
+    let service_conflicts_state_manager = Mutex::new(state_manager.clone());
+    router.get("/service/conflicts",
+               move |_r: &mut Request| handle_service_conflicts(&service_conflicts_state_manager));
+
+    let config = state_manager.get_yaml();
+    let delegated_task_state_manager = Mutex::new(state_manager.clone());
+    router.post("/service/delegated",
+                move |request: &mut Request| handle_delegated_task(&delegated_task_state_manager, request));
+
+    let delegated_task_state_state_manager = Mutex::new(state_manager.clone());
+    router.get("/service/delegated",
+               move |request: &mut Request| handle_delegated_task_state(&delegated_task_state_state_manager, request));
+
+    let delete_delegated_task_state_manager = Mutex::new(state_manager.clone());
+    router.delete("/service/delegated",
+                  move |request: &mut Request| handle_delete_delegated_task(&delete_delegated_task_state_manager, request));
+
+    let kill_delegated_task_state_manager = Mutex::new(state_manager.clone());
+    router.post("/service/delegated/kill",
+                move |request: &mut Request| handle_kill_delegated_task(&kill_delegated_task_state_manager, request));
+
+    let mut chain = Chain::new(router);
+    chain.link_before(ShutdownGate::new(state_manager));
+    chain.link_before(Allowlist::new(&config));
+    chain.link_before(Authenticator::new(&config));
+    chain.link_before(BodyLimit::new(&config));
+
+    let protocol = protocol(&config);
+    info!("api listening port=3000 protocol={}", protocol_name(&protocol));
+    Iron::new(chain).listen_with("0.0.0.0:3000", max_connections(&config), protocol, Some(timeouts(&config))).unwrap();
 }
 
 
@@ -93,209 +329,1424 @@ struct SimpleResponse {
     result: String,
 }
 
+#[derive(Clone, Debug, RustcEncodable)]
+struct HealthResponse {
+    ready: bool,
+    collaborators: Vec<CollaboratorStatus>,
+}
+
+#[derive(Clone, Debug, RustcEncodable)]
+struct ServiceRevisions {
+    name: String,
+    revisions: Vec<Revision>,
+    diffs: Vec<RevisionDiff>,
+}
+
+// next_restart_at is 0 unless the task is waiting to be restarted
+#[derive(Clone, Debug, RustcEncodable)]
+struct ServiceRestarts {
+    name: String,
+    state: TaskState,
+    restart_policy: String,
+    restart_count: i64,
+    max_restarts: i64,
+    next_restart_at: i64,
+    quarantined: bool,
+    failure_reason: String,
+}
+
+#[derive(Clone, Debug, RustcEncodable)]
+struct ServiceWeight {
+    name: String,
+    ip: String,
+    weight: i64,
+}
+
+#[derive(Clone, Debug, RustcEncodable)]
+struct JobArrayProgress {
+    name: String,
+    count: usize,
+    pending: usize,
+    running: usize,
+    finished: usize,
+    failed: usize,
+    members: Json,
+}
+
+// resources of the tasks placed on a node against what it has
+#[derive(Clone, Debug, RustcEncodable)]
+struct NodeUtilization {
+    total_cpu: f64,
+    total_memory: f64,
+    allocated_cpu: f64,
+    allocated_memory: f64,
+    cpu_utilization: f64,
+    memory_utilization: f64,
+    tasks: usize,
+}
+
+#[derive(Clone, Debug, RustcEncodable)]
+struct NodeCost {
+    name: String,
+    cost_weight: f64,
+    running_tasks: usize,
+    allocated_cpu: f64,
+    cost: f64,
+}
+
+#[derive(Clone, Debug, RustcEncodable)]
+struct ClusterCost {
+    total_cost: f64,
+    nodes: Vec<NodeCost>,
+}
+
 fn handle_ping(_request: &mut Request) -> IronResult<Response> {
     Ok(Response::with((status::Ok, "pong")))
 }
 
-fn handle_nodes(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
-    let nodes = state_manager.lock().unwrap().request_list_nodes();
-    let mut result = vec![];
+fn handle_metrics(_request: &mut Request) -> IronResult<Response> {
+    let content_type = Mime(TopLevel::Text, SubLevel::Plain, Vec::new());
+    Ok(Response::with((content_type, status::Ok, render_metrics())))
+}
 
-    for node in nodes {
-        result.push(node);
+fn handle_health(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
+    let state = state_manager.lock().unwrap();
+    let response = HealthResponse {
+        ready: state.request_is_ready(),
+        collaborators: state.request_list_collaborators(),
+    };
+
+    let status = match response.ready {
+        true => status::Ok,
+        false => status::ServiceUnavailable,
+    };
+
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    Ok(Response::with((content_type, status, json::encode(&response).unwrap())))
+}
+
+fn handle_admin_bootstrap(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
+    let state_manager = state_manager.lock().unwrap().clone();
+    let report = run_bootstrap(&state_manager);
+
+    let response_status = match report.ok {
+        true => status::Ok,
+        false => status::InternalServerError,
+    };
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    Ok(Response::with((content_type, response_status, json::encode(&report).unwrap())))
+}
+
+// the cluster name has to be passed in as confirmation, e.g. ?confirm=torc-scheduler&power_off=true
+fn handle_admin_teardown(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let state_manager = state_manager.lock().unwrap().clone();
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+
+    if read_query_parameter(request, "confirm") != state_manager.get_my_name() {
+        let response = SimpleResponse { result: "confirm with the cluster name".to_string() };
+        return Ok(Response::with((content_type, status::BadRequest, json::encode(&response).unwrap())));
     }
 
+    let power_off = read_query_parameter(request, "power_off") == "true";
+    let report = run_teardown(&state_manager, power_off);
+
+    Ok(Response::with((content_type, status::Ok, json::encode(&report).unwrap())))
+}
+
+// ?dry_run=true only lists the stale entries
+fn handle_admin_consul_gc(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let state_manager = state_manager.lock().unwrap().clone();
+    let dry_run = read_query_parameter(request, "dry_run") == "true";
+    let report = run_consul_gc(&state_manager, dry_run);
+
+    let response_status = match report.errors.is_empty() {
+        true => status::Ok,
+        false => status::BadGateway,
+    };
     let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
-    Ok(Response::with((content_type, status::Ok, json::encode(&result).unwrap())))
+    Ok(Response::with((content_type, response_status, json::encode(&report).unwrap())))
 }
 
-fn handle_node_startup(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
-    let url = request.url.clone().into_generic_url();
-    let query: String = match url.query {
-        Some(q) => q.clone(),
-        None => "".to_string(),
+fn handle_admin_selftest(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
+    let state_manager = state_manager.lock().unwrap().clone();
+    let report = run_selftest(&state_manager);
+
+    let response_status = match report.ok {
+        true => status::Ok,
+        false => status::InternalServerError,
     };
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    Ok(Response::with((content_type, response_status, json::encode(&report).unwrap())))
+}
 
-    if !query.is_empty() && query.starts_with("name=") {
-        let (_, name) = query.split_at(5);
-        if !name.is_empty() {
-            let state = state_manager.lock().unwrap();
-            match state.request_node(name.to_string()) {
-                Some(node) => startup_node(&state.get_ipmi_proxy(), &node.management_ip),
-                _ => {}
-            }
+fn handle_debug_state(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
+    let snapshot = state_manager.lock().unwrap().request_snapshot();
+
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    match snapshot {
+        Ok(snapshot) => Ok(Response::with((content_type, status::Ok, json::encode(&snapshot).unwrap()))),
+        Err(reason) => {
+            error!("can't read the state snapshot reason={}", reason);
+            let response = SimpleResponse { result: reason };
+            Ok(Response::with((content_type, status::InternalServerError, json::encode(&response).unwrap())))
         }
     }
+}
+
+fn handle_debug_memory(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
+    let report = state_manager.lock().unwrap().request_memory_usage();
 
-    let response = SimpleResponse { result: "done".to_string() };
     let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
-    Ok(Response::with((content_type, status::Ok, json::encode(&response).unwrap())))
+    Ok(Response::with((content_type, status::Ok, json::encode(&report).unwrap())))
 }
 
-fn handle_node_shutdown(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
-    let url = request.url.clone().into_generic_url();
-    let query: String = match url.query {
-        Some(q) => q.clone(),
-        None => "".to_string(),
-    };
+fn handle_admin_clean(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
+    let state_manager = state_manager.lock().unwrap().clone();
+    let report = state_manager.run_clean_cycle("api");
 
-    if !query.is_empty() && query.starts_with("name=") {
-        let (_, name) = query.split_at(5);
-        if !name.is_empty() {
-            let state = state_manager.lock().unwrap();
-            match state.request_node(name.to_string()) {
-                Some(node) => shutdown_node(&state.get_ipmi_proxy(), &node.management_ip),
-                _ => {}
-            }
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    Ok(Response::with((content_type, status::Ok, json::encode(&report).unwrap())))
+}
+
+fn handle_admin_clean_last(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
+    let report = state_manager.lock().unwrap().request_last_clean_report();
+
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    match report {
+        Some(report) => Ok(Response::with((content_type, status::Ok, json::encode(&report).unwrap()))),
+        None => {
+            let response = SimpleResponse { result: "no clean cycle yet".to_string() };
+            Ok(Response::with((content_type, status::NotFound, json::encode(&response).unwrap())))
         }
     }
+}
+
+fn handle_admin_sync(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
+    let state_manager = state_manager.lock().unwrap().clone();
+    let report = state_manager.run_sync_cycle("api");
 
-    let response = SimpleResponse { result: "done".to_string() };
     let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
-    Ok(Response::with((content_type, status::Ok, json::encode(&response).unwrap())))
+    Ok(Response::with((content_type, status::Ok, json::encode(&report).unwrap())))
 }
 
-fn handle_services_metered(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
-    let tasks = state_manager.lock().unwrap().request_list_running_tasks();
+fn handle_admin_sync_last(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
+    let report = state_manager.lock().unwrap().request_last_sync_report();
+
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    match report {
+        Some(report) => Ok(Response::with((content_type, status::Ok, json::encode(&report).unwrap()))),
+        None => {
+            let response = SimpleResponse { result: "no sync cycle yet".to_string() };
+            Ok(Response::with((content_type, status::NotFound, json::encode(&response).unwrap())))
+        }
+    }
+}
+
+// every node carries what the mesos master reports for it under mesos, null if the master doesn't know it
+// ?type=slave&function=storage&active=true&group=rack1&zone=a, a node has to match all of the given ones
+fn handle_nodes(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    let (node_type, function) = (read_query_parameter(request, "type"), read_query_parameter(request, "function"));
+    let (group, zone) = (read_query_parameter(request, "group"), read_query_parameter(request, "zone"));
+    let active = match read_query_parameter(request, "active").as_str() {
+        "" => None,
+        "true" => Some(true),
+        "false" => Some(false),
+        other => {
+            let response = SimpleResponse { result: format!("active has to be true or false, not {}", other) };
+            return Ok(Response::with((content_type, status::BadRequest, json::encode(&response).unwrap())));
+        }
+    };
+
+    let state_manager = state_manager.lock().unwrap().clone();
+    let nodes: Vec<Node> = state_manager.request_list_nodes()
+        .into_iter()
+        .filter(|node| node_type.is_empty() || node.node_type == node_type)
+        .filter(|node| function.is_empty() || node.node_function == function)
+        .filter(|node| group.is_empty() || node.groups.contains(&group))
+        .filter(|node| zone.is_empty() || node.zone == zone)
+        .filter(|node| active.map(|active| node.active == active).unwrap_or(true))
+        .collect();
+    let ttl = read_int(&state_manager.get_yaml()["mesos-info"], "cache_ttl_in_seconds".to_string(), 5);
+    let agents = mesos_agents(&state_manager.get_master_ip(), Duration::from_secs(ttl as u64));
     let mut result = vec![];
 
-    for task in tasks {
-        if task.is_metered {
-            result.push(task);
+    for node in nodes {
+        let agent = find_mesos_agent(&agents, &node);
+        let utilization = node_utilization(&node, &state_manager.request_list_tasks_on_node(&node));
+        let mut entry = Json::from_str(&json::encode(&node).unwrap()).unwrap();
+        match entry {
+            Json::Object(ref mut object) => {
+                let mesos = match agent {
+                    Some(agent) => Json::from_str(&json::encode(agent).unwrap()).unwrap(),
+                    None => Json::Null,
+                };
+                object.insert("mesos".to_string(), mesos);
+                object.insert("utilization".to_string(),
+                              Json::from_str(&json::encode(&utilization).unwrap()).unwrap());
+            }
+            _ => {}
         }
+        result.push(entry);
     }
 
     let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
     Ok(Response::with((content_type, status::Ok, json::encode(&result).unwrap())))
 }
 
-fn handle_services_running(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
-    let tasks = state_manager.lock().unwrap().request_list_running_tasks();
-    let mut result = vec![];
+// placed are the tasks holding resources on the node, launching or running
+fn node_utilization(node: &Node, tasks: &Vec<Task>) -> NodeUtilization {
+    let placed: Vec<&Task> = tasks.iter()
+        .filter(|task| task.state == TaskState::Accepted || task.state == TaskState::Running)
+        .collect();
+    let allocated_cpu: f64 = placed.iter().map(|task| task.cpu).sum();
+    let allocated_memory: f64 = placed.iter().map(|task| task.memory).sum();
+    let fraction = |allocated: f64, total: f64| if total > 0.0 { allocated / total } else { 0.0 };
 
-    for task in tasks {
-        if !task.is_job {
-            result.push(task);
+    NodeUtilization {
+        total_cpu: node.total_cpu,
+        total_memory: node.total_memory,
+        allocated_cpu: allocated_cpu,
+        allocated_memory: allocated_memory,
+        cpu_utilization: fraction(allocated_cpu, node.total_cpu),
+        memory_utilization: fraction(allocated_memory, node.total_memory),
+        tasks: placed.len(),
+    }
+}
+
+fn handle_node_startup(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let state = state_manager.lock().unwrap();
+    for node in select_nodes(&state, request) {
+        startup_node(&state.get_ipmi_config().for_node(&node), &node.management_ip);
+        state.send_set_node_expected_power_state(node.name.clone(), PowerState::On);
+        if node.draining {
+            state.send_set_node_draining(node.name.clone(), false);
         }
     }
 
+    let response = SimpleResponse { result: "done".to_string() };
     let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
-    Ok(Response::with((content_type, status::Ok, json::encode(&result).unwrap())))
+    Ok(Response::with((content_type, status::Ok, json::encode(&response).unwrap())))
 }
 
-fn handle_service(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
-    let url = request.url.clone().into_generic_url();
-    let mut result = "".to_string();
-    let query: String = match url.query {
-        Some(q) => q.clone(),
-        None => "".to_string(),
+fn handle_node_shutdown(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let state = state_manager.lock().unwrap();
+    for node in select_nodes(&state, request) {
+        shutdown_node(&state.get_ipmi_config().for_node(&node), &node.management_ip);
+        state.send_set_node_expected_power_state(node.name.clone(), PowerState::Off)
+    }
+
+    let response = SimpleResponse { result: "done".to_string() };
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    Ok(Response::with((content_type, status::Ok, json::encode(&response).unwrap())))
+}
+
+fn handle_node_tasks(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let state = state_manager.lock().unwrap();
+    let name = read_query_parameter(request, "name");
+    let version = match accepted_version(request) {
+        Ok(version) => version,
+        Err(reason) => return Ok(Response::with((status::NotAcceptable, reason))),
     };
 
-    if !query.is_empty() && query.starts_with("id=") {
-        let (_, id) = query.split_at(3);
-        if !id.is_empty() {
-            result = state_manager.lock().unwrap().request_task_name_by_id(id.to_string()).clone();
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    match state.request_node(name.clone()) {
+        Some(node) => {
+            let tasks = tasks_to_json(&state.request_list_tasks_on_node(&node), version);
+            Ok(Response::with((task_content_type(version), status::Ok, tasks.to_string())))
+        }
+        None => {
+            let response = SimpleResponse { result: format!("no such node: {}", name) };
+            Ok(Response::with((content_type, status::NotFound, json::encode(&response).unwrap())))
         }
     }
+}
 
-    let response = SimpleResponse { result: result };
+// ?name= or ?group=, with &shutdown=true the drained nodes get powered off as well
+fn handle_node_drain(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let state_manager = state_manager.lock().unwrap().clone();
+    let shutdown = read_query_parameter(request, "shutdown") == "true";
+
+    let nodes = select_nodes(&state_manager, request);
     let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    if nodes.is_empty() {
+        let response = SimpleResponse { result: "no such node".to_string() };
+        return Ok(Response::with((content_type, status::NotFound, json::encode(&response).unwrap())));
+    }
+    match state_manager.send_begin_drain(&nodes) {
+        Ok(_) => {}
+        Err(reason) => {
+            let response = SimpleResponse { result: reason };
+            return Ok(Response::with((content_type, status::Conflict, json::encode(&response).unwrap())));
+        }
+    }
 
-    let mut res = Response::with((content_type, status::Ok, json::encode(&response).unwrap()));
-    res.headers.set(AccessControlAllowOrigin::Any);
-    Ok(res)
+    let reports: Vec<DrainReport> = nodes.iter().map(|node| run_drain(&state_manager, node, shutdown)).collect();
+    Ok(Response::with((content_type, status::Ok, json::encode(&reports).unwrap())))
 }
 
-fn handle_calico_configure(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
+fn handle_node_groups(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
     let nodes = state_manager.lock().unwrap().request_list_nodes();
-    configure_network(&nodes);
+    let mut result: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for node in nodes {
+        for group in &node.groups {
+            result.entry(group.clone()).or_insert(vec![]).push(node.name.clone());
+        }
+    }
 
-    let response = SimpleResponse { result: "done".to_string() };
     let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
-    Ok(Response::with((content_type, status::Ok, json::encode(&response).unwrap())))
+    Ok(Response::with((content_type, status::Ok, json::encode(&result).unwrap())))
 }
 
-fn handle_calico_shutdown(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
-    let nodes = state_manager.lock().unwrap().request_list_nodes();
-    shutdown_network(&nodes);
+// e.g. ?version=0.2.0&url=http://repo/torc-node-agent-0.2.0, runs in the background,
+// progress is available via GET on the same path
+fn handle_agent_upgrade(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let state_manager = state_manager.lock().unwrap().clone();
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+
+    let version = read_query_parameter(request, "version");
+    let url = read_query_parameter(request, "url");
+    if version.is_empty() || url.is_empty() {
+        let response = SimpleResponse { result: "version and url are required".to_string() };
+        return Ok(Response::with((content_type, status::BadRequest, json::encode(&response).unwrap())));
+    }
+
+    let plan = plan_agent_upgrade(&state_manager, &version, &url);
+    if !state_manager.send_begin_agent_upgrade(plan.clone()) {
+        let response = SimpleResponse { result: "an agent upgrade is already in progress".to_string() };
+        return Ok(Response::with((content_type, status::Conflict, json::encode(&response).unwrap())));
+    }
+
+    let upgrade_state_manager = state_manager.clone();
+    let upgrade_plan = plan.clone();
+    let _ = thread::Builder::new()
+        .name("agent-upgrade".to_string())
+        .spawn(move || run_agent_upgrade(&upgrade_state_manager, upgrade_plan));
+
+    Ok(Response::with((content_type, status::Accepted, json::encode(&plan).unwrap())))
+}
+
+fn handle_agent_upgrade_status(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
+    let report = state_manager.lock().unwrap().request_agent_upgrade_report();
 
-    let response = SimpleResponse { result: "done".to_string() };
     let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
-    Ok(Response::with((content_type, status::Ok, json::encode(&response).unwrap())))
+    match report {
+        Some(report) => Ok(Response::with((content_type, status::Ok, json::encode(&report).unwrap()))),
+        None => {
+            let response = SimpleResponse { result: "no agent upgrade yet".to_string() };
+            Ok(Response::with((content_type, status::NotFound, json::encode(&response).unwrap())))
+        }
+    }
 }
 
-fn handle_service_delete(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
-    let url = request.url.clone().into_generic_url();
-    let query: String = match url.query {
-        Some(q) => q.clone(),
-        None => "".to_string(),
+fn handle_node_update(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    let name = read_query_parameter(request, "name");
+
+    let result = match parse_node_update(&request_body(request)) {
+        Ok(update) => {
+            match state_manager.lock().unwrap().send_update_node_metadata(name.clone(), update) {
+                Ok(node) => Ok(node),
+                Err(reason) => Err((status::NotFound, format!("{}: {}", reason, name))),
+            }
+        }
+        Err(reason) => Err((status::UnprocessableEntity, reason)),
     };
 
-    if !query.is_empty() && query.starts_with("name=") {
-        let (_, name) = query.split_at(5);
-        if !name.is_empty() {
-            state_manager.lock().unwrap().send_kill_task_by_name(name.to_string());
+    match result {
+        Ok(node) => Ok(Response::with((content_type, status::Ok, json::encode(&node).unwrap()))),
+        Err((response_status, reason)) => {
+            let response = SimpleResponse { result: reason };
+            Ok(Response::with((content_type, response_status, json::encode(&response).unwrap())))
         }
     }
+}
 
-    let response = SimpleResponse { result: "done".to_string() };
+// ?name=, annotations set to null get removed
+// the node stays registered when calico can't be set up on it, GET /calico/configure can be run again later
+fn handle_node_register(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
     let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
-    Ok(Response::with((content_type, status::Ok, json::encode(&response).unwrap())))
-}
 
-fn handle_start_service_group(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
-    let url = request.url.clone().into_generic_url();
-    let query: String = match url.query {
-        Some(q) => q.clone(),
-        None => "".to_string(),
+    let result = match parse_node_registration(&request_body(request)) {
+        Ok(node) => {
+            match state_manager.lock().unwrap().send_register_node(node) {
+                Ok(node) => Ok(node),
+                Err(reason) => Err((status::Conflict, reason)),
+            }
+        }
+        Err(reason) => Err((status::UnprocessableEntity, reason)),
+    };
+    let node = match result {
+        Ok(node) => node,
+        Err((response_status, reason)) => {
+            let response = SimpleResponse { result: reason };
+            return Ok(Response::with((content_type, response_status, json::encode(&response).unwrap())));
+        }
     };
 
-    if !query.is_empty() && query.starts_with("name=") {
-        let (_, name) = query.split_at(5);
-        if !name.is_empty() {
-            let config = state_manager.lock().unwrap().get_yaml();
-
-            let service_groups = config["api"]["service-groups"].as_vec().unwrap();
-            for service_group in service_groups {
-                if service_group["name"].as_str().unwrap().to_string() == name {
-                    let services = service_group["services"].as_vec().unwrap();
-                    for service in services {
-                        let task = read_task(service, &state_manager.lock().unwrap());
-                        state_manager.lock().unwrap().send_start_task(&task.name,
-                                                                      &task.image,
-                                                                      &task.node_name,
-                                                                      &task.node_type,
-                                                                      &task.node_function,
-                                                                      &task.dependent_service,
-                                                                      &task.arguments,
-                                                                      &task.parameters,
-                                                                      &task.memory,
-                                                                      &task.cpu,
-                                                                      &task.volumes,
-                                                                      &task.privileged,
-                                                                      &task.sla,
-                                                                      &task.is_metered,
-                                                                      &false,
-                                                                      &task.is_job,
-                                                                      &task.network_type)
-                    }
+    // a node calico doesn't know can't run tasks on its network, it's taken back out again
+    match join_network(&node) {
+        Ok(_) |
+        Err(HttpError::NotCompiledIn(_)) => Ok(Response::with((content_type, status::Created, json::encode(&node).unwrap()))),
+        Err(error) => {
+            let rolled_back = state_manager.lock().unwrap().send_unregister_node(node.name.clone());
+            let reason = match rolled_back {
+                Ok(_) => format!("can't configure calico, {} not registered: {}", node.name, error),
+                Err(reason) => {
+                    format!("can't configure calico for {}: {}, can't undo the registration: {}", node.name, error, reason)
                 }
-            }
+            };
+            let response = SimpleResponse { result: reason };
+            Ok(Response::with((content_type, status::BadGateway, json::encode(&response).unwrap())))
         }
     }
+}
 
-    let response = SimpleResponse { result: "done".to_string() };
+fn handle_node_unregister(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
     let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
-    Ok(Response::with((content_type, status::Ok, json::encode(&response).unwrap())))
+    let name = request.extensions.get::<Router>().and_then(|router| router.find("name")).unwrap_or("").to_string();
+
+    let node = match state_manager.lock().unwrap().send_unregister_node(name) {
+        Ok(node) => node,
+        Err(reason) => {
+            let response_status = match reason.starts_with("no node") {
+                true => status::NotFound,
+                false => status::Conflict,
+            };
+            let response = SimpleResponse { result: reason };
+            return Ok(Response::with((content_type, response_status, json::encode(&response).unwrap())));
+        }
+    };
+    Ok(Response::with((content_type, status::Ok, json::encode(&node).unwrap())))
 }
 
-fn handle_task_announce(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
-    let mut body = String::new();
-    request.body.read_to_string(&mut body).unwrap();
-    let decoded: Task = json::decode(&body).unwrap();
-    state_manager.lock().unwrap().send_announce_task(&decoded);
+fn handle_service_annotations(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    let name = read_query_parameter(request, "name");
+
+    let result = match parse_annotations_update(&request_body(request)) {
+        Ok(update) => {
+            match state_manager.lock().unwrap().send_update_task_annotations(name.clone(), update) {
+                Ok(task) => Ok(task),
+                Err(reason) => Err((status::NotFound, format!("{}: {}", reason, name))),
+            }
+        }
+        Err(reason) => Err((status::UnprocessableEntity, reason)),
+    };
+
+    match result {
+        Ok(task) => Ok(Response::with((content_type, status::Ok, json::encode(&task).unwrap()))),
+        Err((response_status, reason)) => {
+            let response = SimpleResponse { result: reason };
+            Ok(Response::with((content_type, response_status, json::encode(&response).unwrap())))
+        }
+    }
+}
+
+fn handle_task_override(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+
+    let result = match parse_task_override(&request_body(request)) {
+        Ok((name, task_override)) => {
+            match state_manager.lock().unwrap().send_override_task(name.clone(), task_override) {
+                Ok(task) => Ok(task),
+                Err(reason) => Err((status::NotFound, format!("{}: {}", reason, name))),
+            }
+        }
+        Err(reason) => Err((status::UnprocessableEntity, reason)),
+    };
+
+    match result {
+        Ok(task) => Ok(Response::with((content_type, status::Ok, json::encode(&task).unwrap()))),
+        Err((response_status, reason)) => {
+            let response = SimpleResponse { result: reason };
+            Ok(Response::with((content_type, response_status, json::encode(&response).unwrap())))
+        }
+    }
+}
+
+fn handle_node_heartbeat(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let body = request_body(request);
+    let heartbeat: Heartbeat = match json::decode(&body) {
+        Ok(heartbeat) => heartbeat,
+        Err(error) => return Ok(Response::with((status::BadRequest, format!("invalid heartbeat: {}", error)))),
+    };
+    state_manager.lock().unwrap().send_node_heartbeat(heartbeat.node_name, heartbeat.version, heartbeat.gpus);
+
+    let response = SimpleResponse { result: "done".to_string() };
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    Ok(Response::with((content_type, status::Ok, json::encode(&response).unwrap())))
+}
+
+fn handle_node_gc(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let state_manager = state_manager.lock().unwrap().clone();
+    let token = agent_token(&state_manager.get_yaml());
+    let command = AgentCommand {
+        kind: "docker_gc".to_string(),
+        args: vec![],
+    };
+
+    let mut result: BTreeMap<String, Option<CommandResult>> = BTreeMap::new();
+    for node in select_nodes(&state_manager, request) {
+        result.insert(node.name.clone(), run_agent_command(&node.ip, &token, &command));
+    }
+
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    Ok(Response::with((content_type, status::Ok, json::encode(&result).unwrap())))
+}
+
+fn handle_node_logs(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let state_manager = state_manager.lock().unwrap().clone();
+    let container = read_query_parameter(request, "container");
+    let lines = read_query_parameter(request, "lines").parse::<i64>().unwrap_or(100);
+
+    let node = match state_manager.request_node(read_query_parameter(request, "name")) {
+        Some(node) => node,
+        None => return Ok(Response::with((status::NotFound, "unknown node"))),
+    };
+    if container.is_empty() {
+        return Ok(Response::with((status::BadRequest, "container missing")));
+    }
+
+    let token = agent_token(&state_manager.get_yaml());
+    let content_type = Mime(TopLevel::Text, SubLevel::Plain, Vec::new());
+    match tail_container_logs(&node.ip, &token, &container, lines) {
+        Some(logs) => Ok(Response::with((content_type, status::Ok, logs))),
+        None => Ok(Response::with((content_type, status::BadGateway, "can't get logs from node agent"))),
+    }
+}
+
+// stdout and stderr from the task's mesos sandbox, ?stream=stdout or stderr for just one of them
+fn handle_service_logs(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let state_manager = state_manager.lock().unwrap().clone();
+    let lines = read_query_parameter(request, "lines").parse::<i64>().unwrap_or(200);
+    let files = match read_query_parameter(request, "stream").as_ref() {
+        "" => vec!["stdout", "stderr"],
+        "stdout" => vec!["stdout"],
+        "stderr" => vec!["stderr"],
+        other => return Ok(Response::with((status::BadRequest, format!("unknown stream: {}", other)))),
+    };
+    if lines <= 0 {
+        return Ok(Response::with((status::BadRequest, "lines has to be positive")));
+    }
+
+    let task = match state_manager.request_task(read_query_parameter(request, "name")) {
+        Some(task) => task,
+        None => return Ok(Response::with((status::NotFound, "unknown task"))),
+    };
+    // the node name is set once the task got placed, the slave id once mesos reported on it
+    let node = state_manager.request_node(task.node_name.clone()).or_else(|| {
+        state_manager.request_list_nodes()
+            .into_iter()
+            .find(|node| !task.slave_id.is_empty() && node.slave_id == task.slave_id)
+    });
+    let node = match node {
+        Some(node) => node,
+        None => return Ok(Response::with((status::Conflict, format!("{} isn't placed on a node", task.name)))),
+    };
+
+    let directory = match task_sandbox(&node.ip, &task.name) {
+        Ok(Some(directory)) => directory,
+        Ok(None) => return Ok(Response::with((status::NotFound, format!("no sandbox for {} on {}", task.name, node.name)))),
+        Err(error) => return Ok(Response::with((status::BadGateway, format!("can't reach mesos agent: {}", error)))),
+    };
+
+    let mut logs = vec![];
+    for file in files {
+        match tail_sandbox_file(&node.ip, &directory, file, lines) {
+            Ok(tail) => logs.push(format!("==> {} <==\n{}", file, tail)),
+            Err(error) => return Ok(Response::with((status::BadGateway, format!("can't read {}: {}", file, error)))),
+        }
+    }
+
+    let content_type = Mime(TopLevel::Text, SubLevel::Plain, Vec::new());
+    Ok(Response::with((content_type, status::Ok, logs.join("\n\n"))))
+}
+
+// a single node via ?name=, or all members of a node group via ?group=
+fn select_nodes(state: &StateManager, request: &Request) -> Vec<Node> {
+    let name = read_query_parameter(request, "name");
+    let group = read_query_parameter(request, "group");
+
+    if !name.is_empty() {
+        match state.request_node(name) {
+            Some(node) => vec![node],
+            None => vec![],
+        }
+    } else if !group.is_empty() {
+        state.request_list_nodes_in_group(group)
+    } else {
+        vec![]
+    }
+}
+
+fn read_query_parameter(request: &Request, key: &str) -> String {
+    let url = request.url.clone().into_generic_url();
+    let query: String = match url.query {
+        Some(q) => q.clone(),
+        None => "".to_string(),
+    };
+
+    for pair in query.split('&') {
+        let mut elements = pair.splitn(2, '=');
+        if elements.next() == Some(key) {
+            return percent_decode(elements.next().unwrap_or(""));
+        }
+    }
+
+    "".to_string()
+}
+
+// query values come as application/x-www-form-urlencoded, broken escapes are kept as they are
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'+' => decoded.push(b' '),
+            b'%' if index + 2 < bytes.len() => {
+                let hex = str::from_utf8(&bytes[index + 1..index + 3]).unwrap_or("");
+                match u8::from_str_radix(hex, 16) {
+                    Ok(byte) => {
+                        decoded.push(byte);
+                        index += 2;
+                    }
+                    Err(_) => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        index += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn handle_services_metered(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
+    let tasks = state_manager.lock().unwrap().request_list_running_tasks();
+    let mut result = vec![];
+
+    for task in tasks {
+        if task.is_metered {
+            result.push(task);
+        }
+    }
+
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    Ok(Response::with((content_type, status::Ok, json::encode(&result).unwrap())))
+}
+
+fn handle_services_running(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
+    let tasks = state_manager.lock().unwrap().request_list_running_tasks();
+    let mut result = vec![];
+
+    for task in tasks {
+        if !task.is_job {
+            result.push(task);
+        }
+    }
+
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    Ok(Response::with((content_type, status::Ok, json::encode(&result).unwrap())))
+}
+
+// load balancer view of the running services, new replicas ramp up to full weight during their slow start
+fn handle_services_weights(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
+    let tasks = state_manager.lock().unwrap().request_list_running_tasks();
+    let mut result = vec![];
+
+    for task in tasks {
+        if task.is_job || task.standby || task.ip.is_empty() {
+            continue;
+        }
+        let weight = task.traffic_weight();
+        for name in Some(task.name.clone()).into_iter().chain(task.aliases.clone().into_iter()) {
+            result.push(ServiceWeight {
+                name: name,
+                ip: task.ip.clone(),
+                weight: weight,
+            });
+        }
+    }
+
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    Ok(Response::with((content_type, status::Ok, json::encode(&result).unwrap())))
+}
+
+fn handle_jobs(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
+    let jobs = state_manager.lock().unwrap().get_scheduled_jobs().list();
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    Ok(Response::with((content_type, status::Ok, json::encode(&jobs).unwrap())))
+}
+
+// ?name=, runs already started are left alone
+fn handle_unschedule_job(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let state_manager = state_manager.lock().unwrap().clone();
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    let name = read_query_parameter(request, "name");
+
+    let scheduled_jobs = state_manager.get_scheduled_jobs();
+    if !scheduled_jobs.list().iter().any(|job| job.name == name) {
+        let response = SimpleResponse { result: format!("{} isn't scheduled", name) };
+        return Ok(Response::with((content_type, status::NotFound, json::encode(&response).unwrap())));
+    }
+    if let Err(reason) = scheduled_jobs.remove(&name) {
+        let response = SimpleResponse { result: reason };
+        return Ok(Response::with((content_type, status::Conflict, json::encode(&response).unwrap())));
+    }
+    state_manager.send_remove_schedule(name.clone());
+
+    let response = SimpleResponse { result: name };
+    Ok(Response::with((content_type, status::Ok, json::encode(&response).unwrap())))
+}
+
+fn handle_job_arrays(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let name = read_query_parameter(request, "name");
+    let version = match accepted_version(request) {
+        Ok(version) => version,
+        Err(reason) => return Ok(Response::with((status::NotAcceptable, reason))),
+    };
+    let tasks = state_manager.lock().unwrap().request_list_tasks();
+    let mut arrays: BTreeMap<String, Vec<Task>> = BTreeMap::new();
+
+    for task in tasks {
+        if task.array_name.is_empty() || (!name.is_empty() && task.array_name != name) {
+            continue;
+        }
+        arrays.entry(task.array_name.clone()).or_insert(vec![]).push(task);
+    }
+
+    let mut result = vec![];
+    for (array_name, mut members) in arrays {
+        members.sort_by(|a, b| a.array_index.cmp(&b.array_index));
+        let count_state = |task_state: TaskState| members.iter().filter(|task| task.state == task_state).count();
+        let running = count_state(TaskState::Running);
+        let finished = count_state(TaskState::Finished);
+        let failed = count_state(TaskState::Failed) + count_state(TaskState::NetworkFailed);
+
+        result.push(JobArrayProgress {
+            name: array_name,
+            count: members.len(),
+            pending: members.len() - running - finished - failed,
+            running: running,
+            finished: finished,
+            failed: failed,
+            members: tasks_to_json(&members, version),
+        });
+    }
+
+    Ok(Response::with((task_content_type(version), status::Ok, json::encode(&result).unwrap())))
+}
+
+fn handle_service(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let url = request.url.clone().into_generic_url();
+    let mut result = "".to_string();
+    let query: String = match url.query {
+        Some(q) => q.clone(),
+        None => "".to_string(),
+    };
+
+    if !query.is_empty() && query.starts_with("id=") {
+        let (_, id) = query.split_at(3);
+        if !id.is_empty() {
+            result = state_manager.lock().unwrap().request_task_name_by_id(id.to_string()).clone();
+        }
+    }
+
+    let response = SimpleResponse { result: result };
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+
+    let mut res = Response::with((content_type, status::Ok, json::encode(&response).unwrap()));
+    res.headers.set(AccessControlAllowOrigin::Any);
+    Ok(res)
+}
+
+// the whole task for ?name= or ?id=<prefix>, in the task version the client accepts
+fn handle_service_detail(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let version = match accepted_version(request) {
+        Ok(version) => version,
+        Err(reason) => return Ok(Response::with((status::NotAcceptable, reason))),
+    };
+    let (name, id) = (read_query_parameter(request, "name"), read_query_parameter(request, "id"));
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+
+    let state_manager = state_manager.lock().unwrap();
+    let (task, wanted) = match (name.is_empty(), id.is_empty()) {
+        (false, _) => (state_manager.request_task(name.clone()), name),
+        (true, false) => (state_manager.request_task_by_id(id.clone()), id),
+        (true, true) => {
+            let response = SimpleResponse { result: "name or id required".to_string() };
+            return Ok(Response::with((content_type, status::BadRequest, json::encode(&response).unwrap())));
+        }
+    };
+
+    let mut res = match task {
+        Some(task) => Response::with((task_content_type(version), status::Ok, task_to_json(&task, version).to_string())),
+        None => {
+            let response = SimpleResponse { result: format!("no such service: {}", wanted) };
+            Response::with((content_type, status::NotFound, json::encode(&response).unwrap()))
+        }
+    };
+    res.headers.set(AccessControlAllowOrigin::Any);
+    Ok(res)
+}
+
+fn handle_start_service(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let state_manager = state_manager.lock().unwrap().clone();
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+
+    let body = request_body(request);
+    // a job with a schedule gets launched by the job scheduler instead of right away
+    let schedule = Json::from_str(&body)
+        .ok()
+        .and_then(|service| service.find("schedule").and_then(|schedule| schedule.as_string()).map(|s| s.to_string()));
+    let result = parse_service(&state_manager, &body).and_then(|task| match schedule {
+        Some(ref schedule) => {
+            state_manager.get_scheduled_jobs()
+                .add(task.clone(), schedule, "api")
+                .map(|_| {
+                    state_manager.send_put_schedule(StoredSchedule {
+                        name: task.name.clone(),
+                        schedule: schedule.clone(),
+                        service: body.clone(),
+                    });
+                    task
+                })
+                .map_err(|reason| (status::UnprocessableEntity, reason))
+        }
+        None => start_task(&state_manager, &task, false).map(|_| task).map_err(|reason| (status::Conflict, reason)),
+    });
+
+    match result {
+        Ok(task) => {
+            let response = SimpleResponse { result: task.name.clone() };
+            Ok(Response::with((content_type, status::Created, json::encode(&response).unwrap())))
+        }
+        Err((response_status, reason)) => {
+            warn!("service rejected peer={} reason={}", request.remote_addr, reason);
+            let response = SimpleResponse { result: reason };
+            Ok(Response::with((content_type, response_status, json::encode(&response).unwrap())))
+        }
+    }
+}
+
+fn handle_service_revisions(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let url = request.url.clone().into_generic_url();
+    let query: String = match url.query {
+        Some(q) => q.clone(),
+        None => "".to_string(),
+    };
+
+    let mut response = ServiceRevisions {
+        name: "".to_string(),
+        revisions: vec![],
+        diffs: vec![],
+    };
+
+    if !query.is_empty() && query.starts_with("name=") {
+        let (_, name) = query.split_at(5);
+        if !name.is_empty() {
+            let revisions = state_manager.lock().unwrap().request_task_revisions(name.to_string());
+            response.name = name.to_string();
+            response.diffs = diff_revisions(&revisions);
+            response.revisions = revisions;
+        }
+    }
+
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    Ok(Response::with((content_type, status::Ok, json::encode(&response).unwrap())))
+}
+
+fn handle_calico_configure(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
+    let nodes = state_manager.lock().unwrap().request_list_nodes();
+    if let Err(error) = configure_network(&nodes) {
+        return Ok(Response::with((status::BadGateway, format!("can't configure calico: {}", error))));
+    }
 
     let response = SimpleResponse { result: "done".to_string() };
     let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
     Ok(Response::with((content_type, status::Ok, json::encode(&response).unwrap())))
 }
+
+fn handle_calico_shutdown(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
+    let nodes = state_manager.lock().unwrap().request_list_nodes();
+    if let Err(error) = shutdown_network(&nodes) {
+        return Ok(Response::with((status::BadGateway, format!("can't shut down calico: {}", error))));
+    }
+
+    let response = SimpleResponse { result: "done".to_string() };
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    Ok(Response::with((content_type, status::Ok, json::encode(&response).unwrap())))
+}
+
+// soft=true keeps the definition around as deleted for deletes.restore_window_in_seconds, deletes.soft sets the default
+fn handle_service_delete(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    let name = read_query_parameter(request, "name");
+    if name.is_empty() {
+        let response = SimpleResponse { result: "done".to_string() };
+        return Ok(Response::with((content_type, status::Ok, json::encode(&response).unwrap())));
+    }
+
+    let state_manager = state_manager.lock().unwrap().clone();
+    let soft = match read_query_parameter(request, "soft").as_str() {
+        "true" => true,
+        "false" => false,
+        _ => read_bool(&state_manager.get_yaml()["deletes"], "soft".to_string()),
+    };
+    if !soft {
+        state_manager.send_kill_task_by_name(name);
+        let response = SimpleResponse { result: "done".to_string() };
+        return Ok(Response::with((content_type, status::Ok, json::encode(&response).unwrap())));
+    }
+
+    match state_manager.send_delete_task(name.clone()) {
+        Ok(_) => {
+            info!("service soft-deleted name={} peer={}", name, request.remote_addr);
+            let response = SimpleResponse { result: "deleted".to_string() };
+            Ok(Response::with((content_type, status::Ok, json::encode(&response).unwrap())))
+        }
+        Err(reason) => {
+            let response = SimpleResponse { result: reason };
+            Ok(Response::with((content_type, status::NotFound, json::encode(&response).unwrap())))
+        }
+    }
+}
+
+fn handle_service_restore(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    let name = read_query_parameter(request, "name");
+    if name.is_empty() {
+        let response = SimpleResponse { result: "name is required".to_string() };
+        return Ok(Response::with((content_type, status::BadRequest, json::encode(&response).unwrap())));
+    }
+
+    match state_manager.lock().unwrap().send_restore_task(name.clone()) {
+        Ok(_) => {
+            info!("service restored name={} peer={}", name, request.remote_addr);
+            let response = SimpleResponse { result: "restored".to_string() };
+            Ok(Response::with((content_type, status::Ok, json::encode(&response).unwrap())))
+        }
+        Err(reason) => {
+            let response = SimpleResponse { result: reason };
+            Ok(Response::with((content_type, status::Conflict, json::encode(&response).unwrap())))
+        }
+    }
+}
+
+fn handle_service_scale(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    let name = read_query_parameter(request, "name");
+    let count = match read_query_parameter(request, "count").parse::<usize>() {
+        Ok(count) if count > 0 && !name.is_empty() => count,
+        _ => {
+            let response = SimpleResponse { result: "name and a count of at least 1 are required".to_string() };
+            return Ok(Response::with((content_type, status::BadRequest, json::encode(&response).unwrap())));
+        }
+    };
+
+    let state_manager = state_manager.lock().unwrap().clone();
+    match scale_service(&state_manager, &name, count) {
+        Ok(report) => Ok(Response::with((content_type, status::Ok, json::encode(&report).unwrap()))),
+        Err(reason) => {
+            let response = SimpleResponse { result: reason };
+            Ok(Response::with((content_type, status::NotFound, json::encode(&response).unwrap())))
+        }
+    }
+}
+
+// body is {"image": ..., "arguments": ...}, either one can be left out, arguments can be a string or a list
+fn handle_service_update(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    let name = read_query_parameter(request, "name");
+    let update = Json::from_str(&request_body(request)).ok();
+    let read_field = |key: &str| update.as_ref().and_then(|update| update.find(key)).map(|value| value.clone());
+    let image = read_field("image").and_then(|image| image.as_string().map(|image| image.to_string()));
+    let arguments = match read_field("arguments") {
+        Some(Json::Array(ref arguments)) if arguments.iter().all(|argument| argument.is_string()) => {
+            let arguments = arguments.iter().map(|argument| argument.as_string().unwrap().to_string()).collect();
+            Some(encode_argument_list(&arguments))
+        }
+        Some(Json::String(arguments)) => Some(arguments),
+        _ => None,
+    };
+    if name.is_empty() || (image.is_none() && arguments.is_none()) {
+        let response = SimpleResponse { result: "name and an image or arguments are required".to_string() };
+        return Ok(Response::with((content_type, status::BadRequest, json::encode(&response).unwrap())));
+    }
+
+    let state_manager = state_manager.lock().unwrap().clone();
+    match run_rolling_update(&state_manager, &name, image, arguments) {
+        Ok(report) => Ok(Response::with((content_type, status::Ok, json::encode(&report).unwrap()))),
+        Err(reason) => {
+            warn!("rolling update failed name={} reason={}", name, reason);
+            let response = SimpleResponse { result: reason };
+            Ok(Response::with((content_type, status::Conflict, json::encode(&response).unwrap())))
+        }
+    }
+}
+
+fn handle_history_metrics(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let window = match read_window_in_seconds(&read_query_parameter(request, "window")) {
+        Ok(window) => window,
+        Err(reason) => return Ok(Response::with((status::BadRequest, reason))),
+    };
+    let samples = state_manager.lock().unwrap().request_metric_history(UTC::now().timestamp() - window);
+
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    Ok(Response::with((content_type, status::Ok, json::encode(&samples).unwrap())))
+}
+
+// 90s, 30m, 24h, 7d or plain seconds, 24h when not given
+fn read_window_in_seconds(window: &str) -> Result<i64, String> {
+    if window.is_empty() {
+        return Ok(24 * 3600);
+    }
+
+    let (value, unit) = match window.chars().last() {
+        Some('s') => (&window[..window.len() - 1], 1),
+        Some('m') => (&window[..window.len() - 1], 60),
+        Some('h') => (&window[..window.len() - 1], 3600),
+        Some('d') => (&window[..window.len() - 1], 24 * 3600),
+        _ => (window, 1),
+    };
+    match value.parse::<i64>() {
+        Ok(value) if value > 0 => value.checked_mul(unit).ok_or(format!("window {} is too large", window)),
+        _ => Err(format!("invalid window {}", window)),
+    }
+}
+
+fn handle_volumes(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
+    let volumes = state_manager.lock().unwrap().request_list_volumes();
+
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    Ok(Response::with((content_type, status::Ok, json::encode(&volumes).unwrap())))
+}
+
+fn handle_schema(schema: Json) -> IronResult<Response> {
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    Ok(Response::with((content_type, status::Ok, schema.to_string())))
+}
+
+fn handle_graph_services(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
+    let state_manager = state_manager.lock().unwrap().clone();
+    let graph = service_graph(&state_manager);
+
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    Ok(Response::with((content_type, status::Ok, json::encode(&graph).unwrap())))
+}
+
+fn handle_services_batch(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let state_manager = state_manager.lock().unwrap().clone();
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+
+    match run_batch(&state_manager, &request_body(request)) {
+        Ok(results) => Ok(Response::with((content_type, status::Ok, json::encode(&results).unwrap()))),
+        Err(reason) => {
+            let response = SimpleResponse { result: reason };
+            Ok(Response::with((content_type, status::BadRequest, json::encode(&response).unwrap())))
+        }
+    }
+}
+
+fn handle_start_service_group(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let url = request.url.clone().into_generic_url();
+    let query: String = match url.query {
+        Some(q) => q.clone(),
+        None => "".to_string(),
+    };
+
+    let mut rejected = vec![];
+    if !query.is_empty() && query.starts_with("name=") {
+        let (_, name) = query.split_at(5);
+        if !name.is_empty() {
+            let state_manager = state_manager.lock().unwrap().clone();
+            rejected = start_service_group(&state_manager, name, read_service_group(&state_manager, name));
+        }
+    }
+
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    if !rejected.is_empty() {
+        let response = SimpleResponse { result: rejected.join(", ") };
+        return Ok(Response::with((content_type, status::Conflict, json::encode(&response).unwrap())));
+    }
+    let response = SimpleResponse { result: "done".to_string() };
+    Ok(Response::with((content_type, status::Ok, json::encode(&response).unwrap())))
+}
+
+fn handle_cluster_cost(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
+    let nodes = state_manager.lock().unwrap().request_list_nodes();
+    let tasks = state_manager.lock().unwrap().request_list_running_tasks();
+
+    let mut result = ClusterCost {
+        total_cost: 0.0,
+        nodes: vec![],
+    };
+
+    for node in nodes {
+        let mut node_cost = NodeCost {
+            name: node.name.clone(),
+            cost_weight: node.cost,
+            running_tasks: 0,
+            allocated_cpu: 0.0,
+            cost: 0.0,
+        };
+
+        for task in tasks.iter().filter(|task| !task.slave_id.is_empty() && task.slave_id == node.slave_id) {
+            node_cost.running_tasks += 1;
+            node_cost.allocated_cpu += task.cpu;
+        }
+
+        node_cost.cost = node_cost.allocated_cpu * node.cost;
+        result.total_cost += node_cost.cost;
+        result.nodes.push(node_cost);
+    }
+
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    Ok(Response::with((content_type, status::Ok, json::encode(&result).unwrap())))
+}
+
+fn handle_routes(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
+    let state_manager = state_manager.lock().unwrap().clone();
+    let dump = route_dump(&state_manager);
+
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    Ok(Response::with((content_type, status::Ok, json::encode(&dump).unwrap())))
+}
+
+fn handle_route_queue(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
+    let operations = state_manager.lock().unwrap().request_list_route_operations();
+
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    Ok(Response::with((content_type, status::Ok, json::encode(&operations).unwrap())))
+}
+
+fn handle_capacity_report(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
+    let state_manager = state_manager.lock().unwrap().clone();
+    let ttl = read_int(&state_manager.get_yaml()["mesos-info"], "cache_ttl_in_seconds".to_string(), 5);
+    let agents = mesos_agents(&state_manager.get_master_ip(), Duration::from_secs(ttl as u64));
+
+    let report = plan_capacity(&state_manager.request_list_nodes(),
+                               &agents,
+                               &state_manager.request_list_running_tasks(),
+                               &state_manager.request_list_requested_tasks());
+
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    Ok(Response::with((content_type, status::Ok, json::encode(&report).unwrap())))
+}
+
+// ?since=<sequence>&epoch=<epoch> for just the events after it, 410 if some of those are gone or the controller
+// restarted since and the consumer has to resync from /debug/state, whose event_sequence is where to pick up again
+fn handle_events(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    let since = read_query_parameter(request, "since");
+    if since.is_empty() {
+        let events = state_manager.lock().unwrap().request_list_events();
+        return Ok(Response::with((content_type, status::Ok, json::encode(&events).unwrap())));
+    }
+
+    let since = match since.parse::<u64>() {
+        Ok(since) => since,
+        Err(_) => {
+            let response = SimpleResponse { result: format!("invalid since: {}", since) };
+            return Ok(Response::with((content_type, status::BadRequest, json::encode(&response).unwrap())));
+        }
+    };
+    let epoch = read_query_parameter(request, "epoch");
+    let epoch = match epoch.is_empty() {
+        true => None,
+        false => {
+            match epoch.parse::<i64>() {
+                Ok(epoch) => Some(epoch),
+                Err(_) => {
+                    let response = SimpleResponse { result: format!("invalid epoch: {}", epoch) };
+                    return Ok(Response::with((content_type, status::BadRequest, json::encode(&response).unwrap())));
+                }
+            }
+        }
+    };
+    match state_manager.lock().unwrap().request_list_events_since(since, epoch) {
+        Ok(events) => Ok(Response::with((content_type, status::Ok, json::encode(&events).unwrap()))),
+        Err(reason) => {
+            let response = SimpleResponse { result: reason };
+            Ok(Response::with((content_type, status::Gone, json::encode(&response).unwrap())))
+        }
+    }
+}
+
+fn handle_task_announce(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let state_manager = state_manager.lock().unwrap().clone();
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+
+    let version = match content_version(request) {
+        Ok(version) => version,
+        Err(reason) => {
+            let response = SimpleResponse { result: reason };
+            return Ok(Response::with((content_type, status::UnsupportedMediaType, json::encode(&response).unwrap())));
+        }
+    };
+
+    let body = request_body(request);
+    let decoded: Task = match decode_task(&body, version) {
+        Ok(task) => task,
+        Err(error) => {
+            let response = SimpleResponse { result: format!("invalid task: {}", error) };
+            return Ok(Response::with((content_type, status::BadRequest, json::encode(&response).unwrap())));
+        }
+    };
+
+    let result = validate_announced_task(&state_manager, &decoded, &request.remote_addr.ip())
+        .and_then(|task| resolve_announce_conflict(&state_manager, task));
+    match result {
+        Ok(task) => {
+            state_manager.send_announce_task(&task);
+            let response = SimpleResponse { result: "done".to_string() };
+            Ok(Response::with((content_type, status::Ok, json::encode(&response).unwrap())))
+        }
+        Err((response_status, reason)) => {
+            warn!("announce rejected task={} peer={} reason={}", decoded.name, request.remote_addr, reason);
+            let response = SimpleResponse { result: reason };
+            Ok(Response::with((content_type, response_status, json::encode(&response).unwrap())))
+        }
+    }
+}
+
+// the tasks that went through restarts in a row, crash loops show up here before they get quarantined
+fn handle_service_restarts(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
+    let state_manager = state_manager.lock().unwrap().clone();
+    let restart_policy = RestartPolicy::new(&state_manager.get_yaml());
+
+    let restarts: Vec<ServiceRestarts> = state_manager.request_list_tasks()
+        .into_iter()
+        .filter(|task| task.restart_count > 0)
+        .map(|task| {
+            ServiceRestarts {
+                next_restart_at: match task.state {
+                    TaskState::Restart => task.last_update + restart_policy.delay_in_seconds(task.restart_count),
+                    _ => 0,
+                },
+                quarantined: task.state == TaskState::Failed,
+                name: task.name,
+                state: task.state,
+                restart_policy: task.restart_policy,
+                restart_count: task.restart_count,
+                max_restarts: task.max_restarts,
+                failure_reason: task.failure_reason,
+            }
+        })
+        .collect();
+
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    Ok(Response::with((content_type, status::Ok, json::encode(&restarts).unwrap())))
+}
+
+fn handle_service_conflicts(state_manager: &Mutex<StateManager>) -> IronResult<Response> {
+    let conflicts = state_manager.lock().unwrap().request_task_conflicts();
+
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    Ok(Response::with((content_type, status::Ok, json::encode(&conflicts).unwrap())))
+}
+
+// a peer controller handing over a task it found no place for
+fn handle_delegated_task(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let state_manager = state_manager.lock().unwrap().clone();
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+
+    let result = content_version(request)
+        .map_err(|reason| (status::UnsupportedMediaType, reason))
+        .and_then(|version| {
+            decode_task(&request_body(request), version).map_err(|error| (status::BadRequest, format!("invalid task: {}", error)))
+        })
+        .and_then(|task| {
+            if task.name.is_empty() || task.controller.is_empty() {
+                return Err((status::UnprocessableEntity, "name and controller are required".to_string()));
+            }
+            if state_manager.request_task(task.name.clone()).is_some() {
+                return Err((status::Conflict, format!("task already exists: {}", task.name)));
+            }
+            Ok(task)
+        });
+
+    let result = result.and_then(|task| {
+        state_manager.send_start_delegated_task(&task).map(|_| task).map_err(|reason| (status::Conflict, reason))
+    });
+
+    match result {
+        Ok(task) => {
+            let response = SimpleResponse { result: task.name.clone() };
+            Ok(Response::with((content_type, status::Created, json::encode(&response).unwrap())))
+        }
+        Err((response_status, reason)) => {
+            let response = SimpleResponse { result: reason };
+            Ok(Response::with((content_type, response_status, json::encode(&response).unwrap())))
+        }
+    }
+}
+
+// how a task delegated to us is doing, asked for by the controller it came from
+fn handle_delegated_task_state(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let name = read_query_parameter(request, "name");
+    let task = state_manager.lock().unwrap().request_task(name.clone());
+
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    match task {
+        Some(ref task) if !task.delegated_from.is_empty() => {
+            let response = SimpleResponse { result: format!("{:?}", task.state) };
+            Ok(Response::with((content_type, status::Ok, json::encode(&response).unwrap())))
+        }
+        _ => {
+            let response = SimpleResponse { result: format!("no delegated task {}", name) };
+            Ok(Response::with((content_type, status::NotFound, json::encode(&response).unwrap())))
+        }
+    }
+}
+
+// the controller a task came from deleted it, or took over how it ended
+fn handle_delete_delegated_task(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let name = read_query_parameter(request, "name");
+    let state_manager = state_manager.lock().unwrap().clone();
+
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    match state_manager.request_task(name.clone()) {
+        Some(ref task) if !task.delegated_from.is_empty() => {
+            let result = match task.state {
+                TaskState::Deleted => Ok(()),
+                _ => state_manager.send_delete_task(name.clone()),
+            };
+            match result {
+                Ok(_) => {
+                    let response = SimpleResponse { result: name };
+                    Ok(Response::with((content_type, status::Ok, json::encode(&response).unwrap())))
+                }
+                Err(reason) => {
+                    let response = SimpleResponse { result: reason };
+                    Ok(Response::with((content_type, status::Conflict, json::encode(&response).unwrap())))
+                }
+            }
+        }
+        _ => {
+            let response = SimpleResponse { result: format!("no delegated task {}", name) };
+            Ok(Response::with((content_type, status::NotFound, json::encode(&response).unwrap())))
+        }
+    }
+}
+
+fn handle_kill_delegated_task(state_manager: &Mutex<StateManager>, request: &mut Request) -> IronResult<Response> {
+    let name = read_query_parameter(request, "name");
+    let state_manager = state_manager.lock().unwrap().clone();
+
+    let content_type = Mime(TopLevel::Application, SubLevel::Json, Vec::new());
+    match state_manager.request_task(name.clone()) {
+        Some(ref task) if !task.delegated_from.is_empty() => {
+            state_manager.send_kill_task_by_name(name.clone());
+            let response = SimpleResponse { result: name };
+            Ok(Response::with((content_type, status::Ok, json::encode(&response).unwrap())))
+        }
+        _ => {
+            let response = SimpleResponse { result: format!("no delegated task {}", name) };
+            Ok(Response::with((content_type, status::NotFound, json::encode(&response).unwrap())))
+        }
+    }
+}
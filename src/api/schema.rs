@@ -0,0 +1,253 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use rustc_serialize::json::{self, Json};
+use state::{Gpu, HealthCheck, NetworkPolicy, Node, PortRule, PowerState, RunWindow, SLA, Task, TaskState, Volume};
+use std::collections::BTreeMap;
+use super::task_version::TASK_VERSION;
+
+// the schemas are inferred from a fully populated sample of each type as it gets encoded. the samples
+// are struct literals, so a field added to Task or Node doesn't compile until it shows up here as well.
+// what the encoding can't tell gets listed by hand: the values of enums and the fields that may be null.
+pub fn task_schema() -> Json {
+    let enums = vec![("state", vec!["NotRunning", "Restart", "Requested", "Accepted", "Running", "NetworkFailed", "Failed",
+                                    "Finished", "Delegated", "Deleted"]),
+                     ("sla", vec!["None", "SingletonEachNode", "SingletonEachSlave"])];
+    let nullable = vec!["run_window", "health_check", "network_policy"];
+
+    document(&format!("Task, version {}", TASK_VERSION), &sample_task(), &enums, &nullable)
+}
+
+pub fn node_schema() -> Json {
+    let power_states = vec!["Unknown", "On", "Off"];
+    let enums = vec![("power_state", power_states.clone()), ("expected_power_state", power_states)];
+
+    document("Node", &sample_node(), &enums, &vec![])
+}
+
+fn document(title: &str, sample: &Json, enums: &Vec<(&str, Vec<&str>)>, nullable: &Vec<&str>) -> Json {
+    let mut schema = match infer(sample) {
+        Json::Object(schema) => schema,
+        _ => BTreeMap::new(),
+    };
+
+    let mut required = vec![];
+    if let Some(&mut Json::Object(ref mut properties)) = schema.get_mut("properties") {
+        for (name, property) in properties.iter_mut() {
+            if let Some(&(_, ref values)) = enums.iter().find(|&&(field, _)| *name == field) {
+                let mut restricted = BTreeMap::new();
+                restricted.insert("type".to_string(), Json::String("string".to_string()));
+                restricted.insert("enum".to_string(),
+                                  Json::Array(values.iter().map(|value| Json::String(value.to_string())).collect()));
+                *property = Json::Object(restricted);
+            }
+            if nullable.contains(&name.as_str()) {
+                let mut either = BTreeMap::new();
+                either.insert("oneOf".to_string(), Json::Array(vec![property.clone(), type_only("null")]));
+                *property = Json::Object(either);
+            } else {
+                required.push(Json::String(name.clone()));
+            }
+        }
+    }
+
+    schema.insert("$schema".to_string(), Json::String("http://json-schema.org/draft-04/schema#".to_string()));
+    schema.insert("title".to_string(), Json::String(title.to_string()));
+    schema.insert("required".to_string(), Json::Array(required));
+    schema.insert("additionalProperties".to_string(), Json::Boolean(false));
+    Json::Object(schema)
+}
+
+// an empty object in a sample is a map, every map in there has strings for keys and values
+fn infer(value: &Json) -> Json {
+    match *value {
+        Json::Object(ref fields) if fields.is_empty() => {
+            let mut schema = type_only_map("object");
+            schema.insert("additionalProperties".to_string(), type_only("string"));
+            Json::Object(schema)
+        }
+        Json::Object(ref fields) => {
+            let mut schema = type_only_map("object");
+            let properties = fields.iter().map(|(name, field)| (name.clone(), infer(field))).collect();
+            schema.insert("properties".to_string(), Json::Object(properties));
+            Json::Object(schema)
+        }
+        Json::Array(ref items) => {
+            let mut schema = type_only_map("array");
+            if let Some(item) = items.first() {
+                schema.insert("items".to_string(), infer(item));
+            }
+            Json::Object(schema)
+        }
+        Json::String(_) => type_only("string"),
+        Json::Boolean(_) => type_only("boolean"),
+        Json::I64(_) | Json::U64(_) => type_only("integer"),
+        Json::F64(_) => type_only("number"),
+        Json::Null => type_only("null"),
+    }
+}
+
+fn type_only(name: &str) -> Json {
+    Json::Object(type_only_map(name))
+}
+
+fn type_only_map(name: &str) -> BTreeMap<String, Json> {
+    let mut schema = BTreeMap::new();
+    schema.insert("type".to_string(), Json::String(name.to_string()));
+    schema
+}
+
+fn encoded<T: ::rustc_serialize::Encodable>(value: &T) -> Json {
+    Json::from_str(&json::encode(value).unwrap()).unwrap_or(Json::Null)
+}
+
+// floats get a fraction so they don't encode like integers
+fn sample_task() -> Json {
+    let text = "text".to_string();
+    encoded(&Task {
+        name: text.clone(),
+        controller: text.clone(),
+        id: text.clone(),
+        image: text.clone(),
+        image_digest: text.clone(),
+        node_name: text.clone(),
+        node_type: text.clone(),
+        node_function: text.clone(),
+        constraints: vec![text.clone()],
+        dependent_service: text.clone(),
+        restart_dependents: false,
+        arguments: text.clone(),
+        parameters: text.clone(),
+        env: vec![(text.clone(), text.clone())].into_iter().collect(),
+        env_from_consul_kv: text.clone(),
+        memory: 0.5,
+        cpu: 0.5,
+        cpu_policy: text.clone(),
+        volumes: vec![Volume {
+                          host_path: text.clone(),
+                          container_path: text.clone(),
+                          read_only_mode: false,
+                      }],
+        privileged: false,
+        sla: SLA::None,
+        priority: 0,
+        is_metered: false,
+        is_system_service: false,
+        is_job: false,
+        network_type: text.clone(),
+        run_window: Some(RunWindow {
+            days: vec![text.clone()],
+            start: text.clone(),
+            end: text.clone(),
+            timezone: text.clone(),
+        }),
+        health_check: Some(HealthCheck {
+            check_type: text.clone(),
+            path: text.clone(),
+            port: 0,
+            interval_in_seconds: 0,
+            failures: 0,
+        }),
+        network_policy: Some(NetworkPolicy {
+            ingress: vec![PortRule {
+                              port: 0,
+                              protocol: text.clone(),
+                          }],
+        }),
+        aliases: vec![text.clone()],
+        slow_start_in_seconds: 0,
+        max_runtime_seconds: 0,
+        restart_policy: text.clone(),
+        max_restarts: 0,
+        failure_reason: text.clone(),
+        array_name: text.clone(),
+        array_index: 0,
+        replica_group: text.clone(),
+        replica_index: 0,
+        standby_group: text.clone(),
+        standby: false,
+        delegated_to: text.clone(),
+        delegated_from: text.clone(),
+        remote_state: text.clone(),
+        artifacts: vec![text.clone()],
+        annotations: BTreeMap::new(),
+        artifact_urls: vec![text.clone()],
+        accepted_since: 0,
+        running_since: 0,
+        ip: text.clone(),
+        slave_id: text.clone(),
+        state: TaskState::NotRunning,
+        last_update: 0,
+        deleted_at: 0,
+        network_retries: 0,
+        restart_count: 0,
+        power_in_watts: 0.5,
+        energy_in_wh: 0.5,
+    })
+}
+
+fn sample_node() -> Json {
+    let text = "text".to_string();
+    encoded(&Node {
+        name: text.clone(),
+        ip: text.clone(),
+        external_ip: text.clone(),
+        management_ip: text.clone(),
+        power_backend: text.clone(),
+        power_credentials: text.clone(),
+        node_type: text.clone(),
+        node_function: text.clone(),
+        active: false,
+        slave_id: text.clone(),
+        port_id: 0,
+        cost: 0.5,
+        job_slots: 0,
+        total_cpu: 0.5,
+        total_memory: 0.5,
+        allocated_cpu: 0.5,
+        allocated_memory: 0.5,
+        groups: vec![text.clone()],
+        zone: text.clone(),
+        labels: BTreeMap::new(),
+        annotations: BTreeMap::new(),
+        metadata_version: 0,
+        draining: false,
+        last_seen: 0,
+        power_state: PowerState::Unknown,
+        expected_power_state: PowerState::Unknown,
+        expected_power_state_since: 0,
+        power_mismatch: false,
+        power_in_watts: 0.5,
+        agent_version: text.clone(),
+        last_heartbeat: 0,
+        gpus: vec![Gpu {
+                       index: 0,
+                       model: text.clone(),
+                       driver_version: text.clone(),
+                       healthy: false,
+                       health_reason: text.clone(),
+                       memory_total_in_mb: 0,
+                       memory_used_in_mb: 0,
+                   }],
+        registered: false,
+    })
+}
@@ -0,0 +1,363 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use iron::status;
+use rustc_serialize::json::Json;
+use scheduler::Constraint;
+use state::{HealthCheck, NetworkPolicy, PortRule, RunWindow, SLA, StateManager, Volume};
+use std::collections::BTreeMap;
+use utils::{DEFAULT_CPU, DEFAULT_MEMORY, Task, encode_argument_list, encode_parameter_map, first_task_name};
+
+// turns the body of POST /service into a task the same way read_task does for the config,
+// returns the status and reason to reject it with
+pub fn parse_service(state_manager: &StateManager, body: &str) -> Result<Task, (status::Status, String)> {
+    let service = try!(Json::from_str(body).map_err(|error| (status::BadRequest, format!("invalid json: {}", error))));
+    if !service.is_object() {
+        return Err((status::BadRequest, "expected a task object".to_string()));
+    }
+
+    let task = try!(read_service(&service).map_err(|reason| (status::UnprocessableEntity, reason)));
+    try!(validate_service(&task).map_err(|reason| (status::UnprocessableEntity, reason)));
+
+    if state_manager.request_task(first_task_name(&task)).is_some() {
+        return Err((status::Conflict, format!("{} already exists", task.name)));
+    }
+
+    Ok(task)
+}
+
+// a scheduled job as it was posted, read again when its schedule is restored after a restart
+pub fn read_scheduled_service(body: &str) -> Result<Task, String> {
+    let service = try!(Json::from_str(body).map_err(|error| format!("invalid json: {}", error)));
+    let task = try!(read_service(&service));
+    try!(validate_service(&task));
+    Ok(task)
+}
+
+fn read_service(service: &Json) -> Result<Task, String> {
+    let array = service.find("array");
+    let mut task = Task {
+        name: try!(read_string(service, "name")),
+        image: try!(read_string(service, "image")),
+        node_name: try!(read_string(service, "node_name")),
+        node_type: try!(read_string(service, "node_type")),
+        node_function: try!(read_string(service, "node_function")),
+        constraints: try!(read_string_list(service, "constraints")),
+        dependent_service: try!(read_string(service, "dependent_service")),
+        restart_dependents: try!(read_bool(service, "restart_dependents")),
+        arguments: try!(read_arguments(service)),
+        parameters: try!(read_parameters(service)),
+        env: try!(read_env(service)),
+        env_from_consul_kv: try!(read_string(service, "env_from_consul_kv")),
+        memory: try!(read_float(service, "memory", DEFAULT_MEMORY)),
+        cpu: try!(read_float(service, "cpu", DEFAULT_CPU)),
+        cpu_policy: match try!(read_string(service, "cpu_policy")).as_ref() {
+            "quota" => "quota".to_string(),
+            _ => "shares".to_string(),
+        },
+        volumes: try!(read_volumes(service)),
+        privileged: try!(read_bool(service, "privileged")),
+        sla: match try!(read_string(service, "sla")).as_ref() {
+            "" | "none" => SLA::None,
+            "singleton_each_node" => SLA::SingletonEachNode,
+            "singleton_each_slave" => SLA::SingletonEachSlave,
+            other => return Err(format!("unknown sla: {}", other)),
+        },
+        priority: match try!(read_int(service, "priority", 0)) {
+            priority if priority >= 0 && priority <= 255 => priority as u8,
+            priority => return Err(format!("priority has to be between 0 and 255: {}", priority)),
+        },
+        is_metered: try!(read_bool(service, "is_metered")),
+        is_job: try!(read_bool(service, "is_job")),
+        network_type: try!(read_string(service, "network_type")),
+        run_window: try!(read_run_window(service)),
+        health_check: try!(read_health_check(service)),
+        network_policy: try!(read_network_policy(service)),
+        aliases: try!(read_string_list(service, "aliases")),
+        slow_start_in_seconds: try!(read_int(service, "slow_start_in_seconds", 0)),
+        max_runtime_seconds: try!(read_int(service, "max_runtime_seconds", 0)),
+        restart_policy: try!(read_string(service, "restart_policy")),
+        max_restarts: try!(read_int(service, "max_restarts", 0)),
+        array_count: match array {
+            Some(array) => try!(read_int(array, "count", 0)),
+            None => 0,
+        },
+        array_var: match array {
+            Some(array) => try!(read_string(array, "var")),
+            None => "".to_string(),
+        },
+        instances: try!(read_int(service, "instances", 0)),
+        standby_replicas: try!(read_int(service, "standby_replicas", 0)),
+        artifacts: try!(read_string_list(service, "artifacts")),
+        annotations: try!(read_annotations(service)),
+    };
+    if task.array_var.is_empty() {
+        task.array_var = "INDEX".to_string();
+    }
+
+    Ok(task)
+}
+
+fn validate_service(task: &Task) -> Result<(), String> {
+    if task.name.is_empty() || task.image.is_empty() || task.network_type.is_empty() {
+        return Err("name, image and network_type are required".to_string());
+    }
+    if !is_plain_word(&task.name) {
+        return Err(format!("invalid name: {}", task.name));
+    }
+    // anything but host and none ends up as --net=<network_type> in the docker parameters
+    if !is_plain_word(&task.network_type) {
+        return Err(format!("invalid network_type: {}", task.network_type));
+    }
+    if !task.cpu.is_finite() || task.cpu <= 0.0 || !task.memory.is_finite() || task.memory <= 0.0 {
+        return Err(format!("invalid resources, cpu: {} memory: {}", task.cpu, task.memory));
+    }
+    if task.array_count < 0 || task.instances < 0 || task.slow_start_in_seconds < 0 || task.max_runtime_seconds < 0 {
+        return Err("array count, instances, slow_start_in_seconds and max_runtime_seconds can't be negative".to_string());
+    }
+    if task.standby_replicas < 0 {
+        return Err(format!("standby_replicas can't be negative: {}", task.standby_replicas));
+    }
+    if task.is_job && task.standby_replicas > 0 {
+        return Err("jobs can't have standby_replicas".to_string());
+    }
+    for volume in &task.volumes {
+        if !volume.host_path.starts_with("/") || !volume.container_path.starts_with("/") {
+            return Err(format!("volume paths have to be absolute: {} {}", volume.host_path, volume.container_path));
+        }
+    }
+    match task.health_check {
+        Some(ref health_check) => {
+            if health_check.check_type != "http" && health_check.check_type != "tcp" {
+                return Err(format!("unknown healthcheck type: {}", health_check.check_type));
+            }
+            if health_check.port <= 0 || health_check.port > 65535 || health_check.interval_in_seconds <= 0 ||
+               health_check.failures <= 0 {
+                return Err("healthcheck needs a port, a positive interval and failures".to_string());
+            }
+        }
+        None => {}
+    }
+    if let Some(ref network_policy) = task.network_policy {
+        for rule in &network_policy.ingress {
+            if rule.port <= 0 || rule.port > 65535 || (rule.protocol != "tcp" && rule.protocol != "udp") {
+                return Err(format!("invalid ingress rule: {}/{}", rule.port, rule.protocol));
+            }
+        }
+    }
+    for constraint in &task.constraints {
+        try!(Constraint::parse(constraint));
+    }
+
+    Ok(())
+}
+
+fn is_plain_word(value: &String) -> bool {
+    value.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+fn read_string(element: &Json, key: &str) -> Result<String, String> {
+    match element.find(key) {
+        None | Some(&Json::Null) => Ok("".to_string()),
+        Some(&Json::String(ref value)) => Ok(value.clone()),
+        Some(_) => Err(format!("{} has to be a string", key)),
+    }
+}
+
+// a string as before or a list of arguments passed on as is
+fn read_arguments(element: &Json) -> Result<String, String> {
+    match element.find("arguments") {
+        Some(&Json::Array(_)) => read_string_list(element, "arguments").map(|arguments| encode_argument_list(&arguments)),
+        _ => read_string(element, "arguments"),
+    }
+}
+
+// a string as before or an object of docker parameters, a list as value gives the parameter once for every entry
+fn read_parameters(element: &Json) -> Result<String, String> {
+    let parameters = match element.find("parameters") {
+        Some(&Json::Object(ref parameters)) => parameters,
+        _ => return read_string(element, "parameters"),
+    };
+
+    let mut result = BTreeMap::new();
+    for (key, value) in parameters {
+        let values: Vec<String> = match *value {
+            Json::String(ref value) => vec![value.clone()],
+            Json::Array(ref values) if values.iter().all(|value| value.is_string()) => {
+                values.iter().map(|value| value.as_string().unwrap().to_string()).collect()
+            }
+            _ => return Err(format!("parameter {} has to be a string or a list of strings", key)),
+        };
+        result.insert(key.clone(), values);
+    }
+    Ok(encode_parameter_map(&result))
+}
+
+// an object of variable names to string values
+fn read_env(element: &Json) -> Result<BTreeMap<String, String>, String> {
+    let env = match element.find("env") {
+        None | Some(&Json::Null) => return Ok(BTreeMap::new()),
+        Some(&Json::Object(ref env)) => env,
+        Some(_) => return Err("env has to be an object".to_string()),
+    };
+
+    let mut result = BTreeMap::new();
+    for (name, value) in env {
+        match value.as_string() {
+            Some(value) => result.insert(name.clone(), value.to_string()),
+            None => return Err(format!("env {} has to be a string", name)),
+        };
+    }
+    Ok(result)
+}
+
+fn read_annotations(element: &Json) -> Result<BTreeMap<String, String>, String> {
+    let annotations = match element.find("annotations") {
+        None | Some(&Json::Null) => return Ok(BTreeMap::new()),
+        Some(&Json::Object(ref annotations)) => annotations,
+        Some(_) => return Err("annotations has to be an object".to_string()),
+    };
+
+    let mut result = BTreeMap::new();
+    for (key, value) in annotations {
+        match value.as_string() {
+            Some(value) if !key.is_empty() => result.insert(key.clone(), value.to_string()),
+            _ => return Err(format!("annotation {} has to be a string under a non-empty key", key)),
+        };
+    }
+    Ok(result)
+}
+
+fn read_string_list(element: &Json, key: &str) -> Result<Vec<String>, String> {
+    match element.find(key) {
+        None | Some(&Json::Null) => Ok(vec![]),
+        Some(&Json::Array(ref values)) => {
+            let mut result = vec![];
+            for value in values {
+                match value.as_string() {
+                    Some(value) => result.push(value.to_string()),
+                    None => return Err(format!("{} has to be a list of strings", key)),
+                }
+            }
+            Ok(result)
+        }
+        Some(_) => Err(format!("{} has to be a list of strings", key)),
+    }
+}
+
+fn read_bool(element: &Json, key: &str) -> Result<bool, String> {
+    match element.find(key) {
+        None | Some(&Json::Null) => Ok(false),
+        Some(&Json::Boolean(value)) => Ok(value),
+        Some(_) => Err(format!("{} has to be true or false", key)),
+    }
+}
+
+fn read_float(element: &Json, key: &str, default: f64) -> Result<f64, String> {
+    match element.find(key) {
+        None | Some(&Json::Null) => Ok(default),
+        Some(value) => value.as_f64().ok_or(format!("{} has to be a number", key)),
+    }
+}
+
+fn read_int(element: &Json, key: &str, default: i64) -> Result<i64, String> {
+    match element.find(key) {
+        None | Some(&Json::Null) => Ok(default),
+        Some(value) => value.as_i64().ok_or(format!("{} has to be a whole number", key)),
+    }
+}
+
+fn read_volumes(service: &Json) -> Result<Vec<Volume>, String> {
+    let mut result = vec![];
+
+    match service.find("volumes") {
+        None | Some(&Json::Null) => {}
+        Some(&Json::Array(ref volumes)) => {
+            for volume in volumes {
+                result.push(Volume {
+                    host_path: try!(read_string(volume, "host_path")),
+                    container_path: try!(read_string(volume, "container_path")),
+                    read_only_mode: try!(read_bool(volume, "read_only_mode")),
+                });
+            }
+        }
+        Some(_) => return Err("volumes has to be a list".to_string()),
+    }
+
+    Ok(result)
+}
+
+fn read_run_window(service: &Json) -> Result<Option<RunWindow>, String> {
+    match service.find("run_window") {
+        None | Some(&Json::Null) => Ok(None),
+        Some(run_window) => {
+            Ok(Some(RunWindow {
+                days: try!(read_string_list(run_window, "days")),
+                start: try!(read_string(run_window, "start")),
+                end: try!(read_string(run_window, "end")),
+                timezone: try!(read_string(run_window, "timezone")),
+            }))
+        }
+    }
+}
+
+fn read_network_policy(service: &Json) -> Result<Option<NetworkPolicy>, String> {
+    let network_policy = match service.find("network_policy") {
+        None | Some(&Json::Null) => return Ok(None),
+        Some(network_policy) => network_policy,
+    };
+
+    let mut ingress = vec![];
+    match network_policy.find("ingress") {
+        None | Some(&Json::Null) => {}
+        Some(&Json::Array(ref rules)) => {
+            for rule in rules {
+                let protocol = try!(read_string(rule, "protocol"));
+                ingress.push(PortRule {
+                    port: try!(read_int(rule, "port", 0)),
+                    protocol: match protocol.is_empty() {
+                        true => "tcp".to_string(),
+                        false => protocol,
+                    },
+                });
+            }
+        }
+        Some(_) => return Err("network_policy ingress has to be a list".to_string()),
+    }
+
+    Ok(Some(NetworkPolicy { ingress: ingress }))
+}
+
+fn read_health_check(service: &Json) -> Result<Option<HealthCheck>, String> {
+    match service.find("healthcheck") {
+        None | Some(&Json::Null) => Ok(None),
+        Some(health_check) => {
+            Ok(Some(HealthCheck {
+                check_type: try!(read_string(health_check, "type")),
+                path: try!(read_string(health_check, "path")),
+                port: try!(read_int(health_check, "port", 0)),
+                interval_in_seconds: try!(read_int(health_check, "interval", 10)),
+                failures: try!(read_int(health_check, "failures", 3)),
+            }))
+        }
+    }
+}
@@ -0,0 +1,67 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use iron::{BeforeMiddleware, IronError, IronResult, Request};
+use iron::status;
+use state::StateManager;
+use std::error::Error;
+use std::fmt;
+use std::sync::Mutex;
+
+// once the controller is shutting down every request is turned away, the listener itself stays up until exit
+pub struct ShutdownGate {
+    state_manager: Mutex<StateManager>,
+}
+
+#[derive(Debug)]
+struct ShutdownError {
+    message: String,
+}
+
+impl fmt::Display for ShutdownError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for ShutdownError {
+    fn description(&self) -> &str {
+        &self.message
+    }
+}
+
+impl ShutdownGate {
+    pub fn new(state_manager: &StateManager) -> ShutdownGate {
+        ShutdownGate { state_manager: Mutex::new(state_manager.clone()) }
+    }
+}
+
+impl BeforeMiddleware for ShutdownGate {
+    fn before(&self, _request: &mut Request) -> IronResult<()> {
+        if self.state_manager.lock().unwrap().request_is_shutting_down() {
+            let message = "controller is shutting down".to_string();
+            return Err(IronError::new(ShutdownError { message: message.clone() }, (status::ServiceUnavailable, message)));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,62 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use rustc_serialize::json::{self, Json};
+use state::{TaskOverride, TaskState};
+use std::net::IpAddr;
+
+// the body of PUT /admin/task/state, state and reason are required, node_name and ip only change when present
+pub fn parse_task_override(body: &str) -> Result<(String, TaskOverride), String> {
+    let json = try!(Json::from_str(body).map_err(|error| format!("invalid json: {}", error)));
+
+    let name = try!(read_required(&json, "name"));
+    let reason = try!(read_required(&json, "reason"));
+    let state = try!(read_required(&json, "state"));
+    let state = try!(json::decode::<TaskState>(&json::encode(&state).unwrap())
+        .map_err(|_| format!("unknown state {}", state)));
+
+    let ip = match json.find("ip") {
+        Some(&Json::String(ref ip)) if ip.is_empty() || ip.parse::<IpAddr>().is_ok() => Some(ip.clone()),
+        Some(_) => return Err("ip has to be an ip address".to_string()),
+        None => None,
+    };
+    let node_name = match json.find("node_name") {
+        Some(&Json::String(ref node_name)) => Some(node_name.clone()),
+        Some(_) => return Err("node_name has to be a string".to_string()),
+        None => None,
+    };
+
+    let task_override = TaskOverride {
+        state: state,
+        node_name: node_name,
+        ip: ip,
+        reason: reason,
+    };
+    Ok((name, task_override))
+}
+
+fn read_required(json: &Json, key: &str) -> Result<String, String> {
+    match json.find(key) {
+        Some(&Json::String(ref value)) if !value.trim().is_empty() => Ok(value.clone()),
+        _ => Err(format!("{} is required", key)),
+    }
+}
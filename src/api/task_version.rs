@@ -0,0 +1,297 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use iron::Request;
+use iron::headers::{Accept, ContentType};
+use iron::mime::{Attr, Mime, SubLevel, TopLevel, Value};
+use rustc_serialize::json::{self, Json};
+use state::Task;
+use std::collections::BTreeMap;
+
+// version 1 is the task as the first controllers sent it, version 2 added everything from
+// image digests to energy metering, version 3 delegation between controllers, version 4 network policies,
+// version 5 priorities, version 6 soft deletes, version 7 environment variables, version 8 restart policies,
+// version 9 standbys, version 10 annotations, version 11 env as an object instead of pairs,
+// version 12 the time of a soft delete, bump it and add a converter whenever a field or state is added
+pub const TASK_VERSION: u32 = 12;
+
+fn fields_added_in_v2() -> Vec<(&'static str, Json)> {
+    vec![("image_digest", Json::String("".to_string())),
+         ("constraints", Json::Array(vec![])),
+         ("restart_dependents", Json::Boolean(false)),
+         ("cpu_policy", Json::String("".to_string())),
+         ("run_window", Json::Null),
+         ("health_check", Json::Null),
+         ("aliases", Json::Array(vec![])),
+         ("slow_start_in_seconds", Json::I64(0)),
+         ("max_runtime_seconds", Json::I64(0)),
+         ("failure_reason", Json::String("".to_string())),
+         ("array_name", Json::String("".to_string())),
+         ("array_index", Json::I64(0)),
+         ("replica_group", Json::String("".to_string())),
+         ("replica_index", Json::I64(0)),
+         ("artifacts", Json::Array(vec![])),
+         ("artifact_urls", Json::Array(vec![])),
+         ("accepted_since", Json::I64(0)),
+         ("running_since", Json::I64(0)),
+         ("network_retries", Json::I64(0)),
+         ("power_in_watts", Json::F64(0.0)),
+         ("energy_in_wh", Json::F64(0.0))]
+}
+
+fn fields_added_in_v3() -> Vec<(&'static str, Json)> {
+    vec![("delegated_to", Json::String("".to_string())),
+         ("delegated_from", Json::String("".to_string())),
+         ("remote_state", Json::String("".to_string()))]
+}
+
+fn fields_added_in_v4() -> Vec<(&'static str, Json)> {
+    vec![("network_policy", Json::Null)]
+}
+
+fn fields_added_in_v5() -> Vec<(&'static str, Json)> {
+    vec![("priority", Json::U64(0))]
+}
+
+fn fields_added_in_v7() -> Vec<(&'static str, Json)> {
+    vec![("env", Json::Object(BTreeMap::new())), ("env_from_consul_kv", Json::String("".to_string()))]
+}
+
+fn fields_added_in_v8() -> Vec<(&'static str, Json)> {
+    vec![("restart_policy", Json::String("".to_string())),
+         ("max_restarts", Json::I64(0)),
+         ("restart_count", Json::I64(0))]
+}
+
+fn fields_added_in_v9() -> Vec<(&'static str, Json)> {
+    vec![("standby_group", Json::String("".to_string())), ("standby", Json::Boolean(false))]
+}
+
+fn fields_added_in_v10() -> Vec<(&'static str, Json)> {
+    vec![("annotations", Json::Object(BTreeMap::new()))]
+}
+
+fn fields_added_in_v12() -> Vec<(&'static str, Json)> {
+    vec![("deleted_at", Json::I64(0))]
+}
+
+// the version a task payload was sent in, from e.g. Content-Type: application/json; version=1,
+// without one it is decoded leniently so controllers from before versioning keep working
+pub fn content_version(request: &Request) -> Result<Option<u32>, String> {
+    let header = request.headers.get::<ContentType>().map(|header| header.to_string());
+    parse_version(header)
+}
+
+// the version the client wants tasks in, from e.g. Accept: application/json; version=1
+pub fn accepted_version(request: &Request) -> Result<u32, String> {
+    let header = request.headers.get::<Accept>().map(|header| header.to_string());
+    parse_version(header).map(|version| version.unwrap_or(TASK_VERSION))
+}
+
+pub fn task_content_type(version: u32) -> Mime {
+    Mime(TopLevel::Application,
+         SubLevel::Json,
+         vec![(Attr::Ext("version".to_string()), Value::Ext(version.to_string()))])
+}
+
+// older payloads get the fields added since filled with their defaults
+pub fn decode_task(body: &str, version: Option<u32>) -> Result<Task, String> {
+    let mut task = match Json::from_str(body) {
+        Ok(Json::Object(task)) => task,
+        Ok(_) => return Err("task has to be an object".to_string()),
+        Err(error) => return Err(error.to_string()),
+    };
+
+    let version = version.unwrap_or(1);
+    if version < 2 {
+        for (field, default) in fields_added_in_v2() {
+            task.entry(field.to_string()).or_insert(default);
+        }
+    }
+    if version < 3 {
+        for (field, default) in fields_added_in_v3() {
+            task.entry(field.to_string()).or_insert(default);
+        }
+    }
+    if version < 4 {
+        for (field, default) in fields_added_in_v4() {
+            task.entry(field.to_string()).or_insert(default);
+        }
+    }
+    if version < 5 {
+        for (field, default) in fields_added_in_v5() {
+            task.entry(field.to_string()).or_insert(default);
+        }
+    }
+    if version < 7 {
+        for (field, default) in fields_added_in_v7() {
+            task.entry(field.to_string()).or_insert(default);
+        }
+    }
+    if version < 8 {
+        for (field, default) in fields_added_in_v8() {
+            task.entry(field.to_string()).or_insert(default);
+        }
+    }
+    if version < 9 {
+        for (field, default) in fields_added_in_v9() {
+            task.entry(field.to_string()).or_insert(default);
+        }
+    }
+    if version < 10 {
+        for (field, default) in fields_added_in_v10() {
+            task.entry(field.to_string()).or_insert(default);
+        }
+    }
+    if version < 12 {
+        for (field, default) in fields_added_in_v12() {
+            task.entry(field.to_string()).or_insert(default);
+        }
+    }
+    // stored tasks come without a version, env pairs are turned into an object whatever version is given
+    let env = match task.get("env") {
+        Some(&Json::Array(ref pairs)) => Some(try!(env_from_pairs(pairs))),
+        _ => None,
+    };
+    if let Some(env) = env {
+        task.insert("env".to_string(), env);
+    }
+
+    json::decode(&Json::Object(task).to_string()).map_err(|error| error.to_string())
+}
+
+pub fn task_to_json(task: &Task, version: u32) -> Json {
+    let mut encoded = match Json::from_str(&json::encode(task).unwrap()) {
+        Ok(Json::Object(encoded)) => encoded,
+        _ => return Json::Null,
+    };
+
+    if version < 12 {
+        for (field, _) in fields_added_in_v12() {
+            encoded.remove(field);
+        }
+    }
+    if version < 11 {
+        if let Some(env) = encoded.get_mut("env") {
+            *env = env_to_pairs(env);
+        }
+    }
+    if version < 10 {
+        for (field, _) in fields_added_in_v10() {
+            encoded.remove(field);
+        }
+    }
+    if version < 9 {
+        for (field, _) in fields_added_in_v9() {
+            encoded.remove(field);
+        }
+    }
+    if version < 8 {
+        for (field, _) in fields_added_in_v8() {
+            encoded.remove(field);
+        }
+    }
+    if version < 7 {
+        for (field, _) in fields_added_in_v7() {
+            encoded.remove(field);
+        }
+    }
+    // older peers don't know the state, to them the task is done and won't be launched again
+    if version < 6 && encoded.get("state") == Some(&Json::String("Deleted".to_string())) {
+        encoded.insert("state".to_string(), Json::String("Finished".to_string()));
+    }
+    if version < 5 {
+        for (field, _) in fields_added_in_v5() {
+            encoded.remove(field);
+        }
+    }
+    if version < 4 {
+        for (field, _) in fields_added_in_v4() {
+            encoded.remove(field);
+        }
+    }
+    if version < 3 {
+        for (field, _) in fields_added_in_v3() {
+            encoded.remove(field);
+        }
+        // older peers don't know the state, to them the task is still waiting to be placed
+        if encoded.get("state") == Some(&Json::String("Delegated".to_string())) {
+            encoded.insert("state".to_string(), Json::String("Requested".to_string()));
+        }
+    }
+    if version < 2 {
+        for (field, _) in fields_added_in_v2() {
+            encoded.remove(field);
+        }
+    }
+
+    Json::Object(encoded)
+}
+
+pub fn tasks_to_json(tasks: &Vec<Task>, version: u32) -> Json {
+    Json::Array(tasks.iter().map(|task| task_to_json(task, version)).collect())
+}
+
+// up to version 10 env went out as [["NAME", "value"], ..]
+fn env_from_pairs(pairs: &Vec<Json>) -> Result<Json, String> {
+    let mut env = BTreeMap::new();
+    for pair in pairs {
+        let entry = match pair.as_array() {
+            Some(entry) if entry.len() == 2 => (entry[0].as_string(), entry[1].as_string()),
+            _ => (None, None),
+        };
+        match entry {
+            (Some(name), Some(value)) => env.insert(name.to_string(), Json::String(value.to_string())),
+            _ => return Err(format!("env entry has to be a name and a value: {}", pair)),
+        };
+    }
+    Ok(Json::Object(env))
+}
+
+fn env_to_pairs(env: &Json) -> Json {
+    match *env {
+        Json::Object(ref env) => {
+            Json::Array(env.iter()
+                .map(|(name, value)| Json::Array(vec![Json::String(name.clone()), value.clone()]))
+                .collect())
+        }
+        _ => env.clone(),
+    }
+}
+
+fn parse_version(header: Option<String>) -> Result<Option<u32>, String> {
+    let header = match header {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+
+    for parameter in header.split(|c| c == ';' || c == ',').map(|parameter| parameter.trim()) {
+        if parameter.starts_with("version=") {
+            let version = &parameter["version=".len()..];
+            return match version.trim_matches('"').parse::<u32>() {
+                Ok(version) if version >= 1 && version <= TASK_VERSION => Ok(Some(version)),
+                _ => Err(format!("unsupported task version {}, up to {} is supported", version, TASK_VERSION)),
+            };
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,50 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use iron::Protocol;
+use std::path::PathBuf;
+use utils::read_string;
+use yaml_rust::Yaml;
+
+// https once both a certificate and a key are configured, half a setup is a mistake rather than a wish for http
+pub fn protocol(config: &Yaml) -> Protocol {
+    let certificate = read_string(&config["api"]["tls"], "cert_file".to_string());
+    let key = read_string(&config["api"]["tls"], "key_file".to_string());
+
+    match (certificate.is_empty(), key.is_empty()) {
+        (true, true) => Protocol::Http,
+        (false, false) => {
+            Protocol::Https {
+                certificate: PathBuf::from(certificate),
+                key: PathBuf::from(key),
+            }
+        }
+        _ => panic!("api.tls needs both cert_file and key_file"),
+    }
+}
+
+pub fn protocol_name(protocol: &Protocol) -> &'static str {
+    match *protocol {
+        Protocol::Http => "http",
+        Protocol::Https { .. } => "https",
+    }
+}
@@ -0,0 +1,120 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+extern crate torc_scheduler;
+
+#[macro_use]
+extern crate clap;
+#[macro_use]
+extern crate log;
+
+use clap::{App, Arg};
+use std::env;
+use std::process;
+use torc_scheduler::agent::{AGENT_PORT, AgentConfig, run_agent};
+use torc_scheduler::utils::init_logging;
+
+fn main() {
+    let matches = App::new("ToRC Node Agent")
+        .about("Runs on the slaves and executes commands on behalf of the ToRC controller")
+        .version(&crate_version!()[..])
+        .arg(Arg::with_name("NAME")
+            .short("n")
+            .long("name")
+            .required(true)
+            .help("Name of this node as configured on the controller")
+            .takes_value(true))
+        .arg(Arg::with_name("MY_IP")
+            .short("i")
+            .long("myip")
+            .required(true)
+            .help("IP of this node")
+            .takes_value(true))
+        .arg(Arg::with_name("CONTROLLER")
+            .short("c")
+            .long("controller")
+            .required(true)
+            .help("Controller api as host:port")
+            .takes_value(true))
+        .arg(Arg::with_name("PORT")
+            .short("p")
+            .long("port")
+            .required(false)
+            .help("Port to listen on")
+            .takes_value(true))
+        .arg(Arg::with_name("HEARTBEAT_INTERVAL")
+            .long("heartbeat-interval")
+            .required(false)
+            .help("Seconds between heartbeats")
+            .takes_value(true))
+        .arg(Arg::with_name("GC_INTERVAL")
+            .long("gc-interval")
+            .required(false)
+            .help("Seconds between docker garbage collections, 0 disables it")
+            .takes_value(true))
+        .arg(Arg::with_name("TLS_CERT")
+            .long("tls-cert")
+            .required(false)
+            .help("Certificate to serve https with, needs --tls-key")
+            .takes_value(true))
+        .arg(Arg::with_name("TLS_KEY")
+            .long("tls-key")
+            .required(false)
+            .help("Key of the certificate given with --tls-cert")
+            .takes_value(true))
+        .arg(Arg::with_name("CA_FILE")
+            .long("ca-file")
+            .required(false)
+            .help("CA to check the controller's certificate against instead of the system's")
+            .takes_value(true))
+        .get_matches();
+
+    init_logging("info");
+
+    // tokens are taken from the environment so they don't show up in the process list
+    let config = AgentConfig {
+        node_name: matches.value_of("NAME").unwrap().to_string(),
+        ip: matches.value_of("MY_IP").unwrap().to_string(),
+        port: value_t!(matches, "PORT", i16).unwrap_or(AGENT_PORT),
+        version: crate_version!().to_string(),
+        controller: matches.value_of("CONTROLLER").unwrap().to_string(),
+        controller_token: env::var("TORC_CONTROLLER_TOKEN").unwrap_or("".to_string()),
+        token: env::var("TORC_AGENT_TOKEN").unwrap_or("".to_string()),
+        heartbeat_interval_in_seconds: value_t!(matches, "HEARTBEAT_INTERVAL", u64).unwrap_or(10),
+        gc_interval_in_seconds: value_t!(matches, "GC_INTERVAL", u64).unwrap_or(3600),
+        tls_cert_file: matches.value_of("TLS_CERT").unwrap_or("").to_string(),
+        tls_key_file: matches.value_of("TLS_KEY").unwrap_or("").to_string(),
+        ca_file: matches.value_of("CA_FILE").unwrap_or("").to_string(),
+    };
+
+    // the supervisor starts whatever binary is in place now
+    match run_agent(&config) {
+        Ok(reason) => {
+            info!("node agent exiting for restart reason={}", reason);
+            process::exit(0);
+        }
+        Err(error) => {
+            error!("node agent can't run error={}", error);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,133 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+extern crate chrono;
+extern crate rustc_serialize;
+extern crate torc_scheduler;
+
+#[macro_use]
+extern crate clap;
+
+use chrono::UTC;
+use clap::{App, Arg};
+use rustc_serialize::json;
+use std::process;
+use torc_scheduler::state::{StoreEntry, replay_store};
+
+fn main() {
+    let matches = App::new("ToRC Replay")
+        .about("Rebuilds what the ToRC controller knew about its tasks at a point in the past from its state store")
+        .version(&crate_version!()[..])
+        .arg(Arg::with_name("STORE")
+            .short("s")
+            .long("store")
+            .required(true)
+            .help("Directory of the file state store, state-store.path in the controller config")
+            .takes_value(true))
+        .arg(Arg::with_name("AT")
+            .short("a")
+            .long("at")
+            .required(false)
+            .help("Unix timestamp to rebuild the state at, defaults to now")
+            .takes_value(true))
+        .arg(Arg::with_name("STEPS")
+            .long("steps")
+            .required(false)
+            .help("Print every change from the snapshot up to the timestamp"))
+        .arg(Arg::with_name("TASK")
+            .short("t")
+            .long("task")
+            .required(false)
+            .help("Only print the changes and state of this task")
+            .takes_value(true))
+        .get_matches();
+
+    let store = matches.value_of("STORE").unwrap();
+    let at = value_t!(matches, "AT", i64).unwrap_or(UTC::now().timestamp());
+    let task_filter = matches.value_of("TASK").unwrap_or("").to_string();
+
+    let replay = match replay_store(store, at) {
+        Ok(replay) => replay,
+        Err(error) => {
+            println!("{}", error);
+            process::exit(1);
+        }
+    };
+
+    if matches.is_present("STEPS") {
+        println!("snapshot at {} with {} tasks", replay.base.timestamp, replay.base.tasks.len());
+        for (step, logged) in replay.entries.iter().enumerate() {
+            match logged.entry {
+                StoreEntry::PutTask(ref task) if task_filter.is_empty() || task.name == task_filter => {
+                    println!("{:>5} {} put {} {:?} node [{}] slave [{}] ip [{}]",
+                             step + 1,
+                             logged.timestamp,
+                             task.name,
+                             task.state,
+                             task.node_name,
+                             task.slave_id,
+                             task.ip)
+                }
+                StoreEntry::RemoveTask(ref task_name) if task_filter.is_empty() || *task_name == task_filter => {
+                    println!("{:>5} {} remove {}", step + 1, logged.timestamp, task_name)
+                }
+                StoreEntry::PutNode(ref node) if task_filter.is_empty() => {
+                    println!("{:>5} {} put node {} version {} ip [{}] annotations {:?}",
+                             step + 1,
+                             logged.timestamp,
+                             node.name,
+                             node.metadata_version,
+                             node.ip,
+                             node.annotations)
+                }
+                StoreEntry::RemoveNode(ref node_name) if task_filter.is_empty() => {
+                    println!("{:>5} {} remove node {}", step + 1, logged.timestamp, node_name)
+                }
+                StoreEntry::PutSchedule(ref schedule) if task_filter.is_empty() => {
+                    println!("{:>5} {} put schedule {} [{}]", step + 1, logged.timestamp, schedule.name, schedule.schedule)
+                }
+                StoreEntry::RemoveSchedule(ref job_name) if task_filter.is_empty() => {
+                    println!("{:>5} {} remove schedule {}", step + 1, logged.timestamp, job_name)
+                }
+                StoreEntry::PutVolume(ref binding) if task_filter.is_empty() || binding.task_name == task_filter => {
+                    println!("{:>5} {} put volume {} {} node [{}]",
+                             step + 1,
+                             logged.timestamp,
+                             binding.task_name,
+                             binding.state,
+                             binding.node_name)
+                }
+                StoreEntry::RemoveVolume(ref task_name) if task_filter.is_empty() || *task_name == task_filter => {
+                    println!("{:>5} {} remove volume {}", step + 1, logged.timestamp, task_name)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut state = replay.state_after(replay.entries.len());
+    state.timestamp = at;
+    if !task_filter.is_empty() {
+        state.tasks.retain(|task| task.name == task_filter);
+        state.volumes.retain(|binding| binding.task_name == task_filter);
+    }
+    println!("{}", json::as_pretty_json(&state));
+}
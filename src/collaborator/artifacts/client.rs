@@ -0,0 +1,61 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use super::super::node_agent::run_sync_command;
+
+// has the agent on the node copy the given paths out of the task's container and upload them in one go,
+// returns the url the archive can be fetched from afterwards
+pub fn collect_artifacts(node_ip: &String,
+                         task_name: &String,
+                         container: &String,
+                         paths: &Vec<String>,
+                         upload_url: &String)
+                         -> Option<String> {
+    if paths.is_empty() || upload_url.is_empty() || container.is_empty() {
+        return None;
+    }
+    if let Some(path) = paths.iter().find(|path| !path.starts_with('/')) {
+        warn!("not collecting artifacts, path has to be absolute task={} path={}", task_name, path);
+        return None;
+    }
+
+    // the job has exited by now, docker cp still reads a stopped container where exec can't
+    let artifact_url = format!("{}/{}.tar.gz", upload_url.trim_right_matches('/'), task_name);
+    let copies: Vec<String> = paths.iter()
+        .enumerate()
+        .map(|(index, path)| format!("docker cp {} \"$dir\"/{}", quote(&format!("{}:{}", container, path)), index))
+        .collect();
+    let command = format!("dir=$(mktemp -d) && {} && tar czf - -C \"$dir\" . | curl -sf -T - {}; status=$?; rm -rf \"$dir\"; \
+                           exit $status",
+                          copies.join(" && "),
+                          quote(&artifact_url));
+
+    match run_sync_command("artifacts", node_ip, &command, &"".to_string()) {
+        Ok(_) => Some(artifact_url),
+        Err(_) => None,
+    }
+}
+
+// single quoted for sh, a quote inside ends the quoting, gets escaped and starts it again
+fn quote(argument: &str) -> String {
+    format!("'{}'", argument.replace("'", "'\\''"))
+}
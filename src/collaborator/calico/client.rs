@@ -20,107 +20,84 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 // THE SOFTWARE.
 
-use hyper::Client;
-use hyper::status::StatusCode;
-use rustc_serialize::json;
 use state::Node;
-use std::io::Read;
-
-const DEFAULT_PORT: i16 = 8085;
+use super::super::http::HttpError;
+use super::CALICO_NETWORK;
+use super::super::node_agent::run_sync_command;
 
 static CALICO_CTL: &'static str = "/home/bladerunner/calicoctl";
 static ETCD_ENV: &'static str = "ETCD_AUTHORITY=etcd.service.torc:2379";
 static IP_POOL: &'static str = "192.168.0.0/16";
 static DOCKER_NETWORK: &'static str = "docker network";
-static NETWORK_NAME: &'static str = "torc";
-
-lazy_static! {
-    static ref CLIENT: Client = Client::new();
-}
 
-pub fn configure_network(nodes: &Vec<Node>) {
+// stops at the first command a node doesn't take, later steps depend on the earlier ones
+pub fn configure_network(nodes: &Vec<Node>) -> Result<(), HttpError> {
     for node in nodes {
         if node.node_type == "slave" {
-            send_command_to_node(node.ip.clone(),
-                                 DEFAULT_PORT,
-                                 format!("{} {}", CALICO_CTL, "node --libnetwork".to_string()),
-                                 ETCD_ENV.to_string());
+            try!(send_command_to_node(node.ip.clone(),
+                                      format!("{} {}", CALICO_CTL, "node --libnetwork".to_string()),
+                                      ETCD_ENV.to_string()));
         }
     }
     for node in nodes {
         if node.node_type == "slave" {
-            send_command_to_node(node.ip.clone(),
-                                 DEFAULT_PORT,
-                                 format!("{} {} {} --nat-outgoing",
-                                         CALICO_CTL,
-                                         "pool add".to_string(),
-                                         IP_POOL),
-                                 ETCD_ENV.to_string());
-            send_command_to_node(node.ip.clone(),
-                                 DEFAULT_PORT,
-                                 format!("{} {} {}",
-                                         DOCKER_NETWORK,
-                                         "create --driver calico --ipam-driver calico".to_string(),
-                                         NETWORK_NAME),
-                                 "".to_string());
+            try!(send_command_to_node(node.ip.clone(),
+                                      format!("{} {} {} --nat-outgoing",
+                                              CALICO_CTL,
+                                              "pool add".to_string(),
+                                              IP_POOL),
+                                      ETCD_ENV.to_string()));
+            try!(send_command_to_node(node.ip.clone(),
+                                      format!("{} {} {}",
+                                              DOCKER_NETWORK,
+                                              "create --driver calico --ipam-driver calico".to_string(),
+                                              CALICO_NETWORK),
+                                      "".to_string()));
             // only have to do this on one node
             break;
         }
     }
+    Ok(())
+}
+
+// a node joining a network configure_network already set up, the pool and the docker network are there
+pub fn join_network(node: &Node) -> Result<(), HttpError> {
+    if node.node_type != "slave" {
+        return Ok(());
+    }
+    send_command_to_node(node.ip.clone(),
+                         format!("{} {}", CALICO_CTL, "node --libnetwork".to_string()),
+                         ETCD_ENV.to_string())
 }
 
-pub fn shutdown_network(nodes: &Vec<Node>) {
+pub fn shutdown_network(nodes: &Vec<Node>) -> Result<(), HttpError> {
     for node in nodes {
         if node.node_type == "slave" {
-            send_command_to_node(node.ip.clone(),
-                                 DEFAULT_PORT,
-                                 format!("{} {}", CALICO_CTL, "node stop".to_string()),
-                                 ETCD_ENV.to_string());
-            send_command_to_node(node.ip.clone(),
-                                 DEFAULT_PORT,
-                                 format!("{} {}",
-                                         CALICO_CTL,
-                                         "node remove --remove-endpoints".to_string()),
-                                 ETCD_ENV.to_string());
+            try!(send_command_to_node(node.ip.clone(),
+                                      format!("{} {}", CALICO_CTL, "node stop".to_string()),
+                                      ETCD_ENV.to_string()));
+            try!(send_command_to_node(node.ip.clone(),
+                                      format!("{} {}",
+                                              CALICO_CTL,
+                                              "node remove --remove-endpoints".to_string()),
+                                      ETCD_ENV.to_string()));
         }
     }
     for node in nodes {
         if node.node_type == "slave" {
-            send_command_to_node(node.ip.clone(),
-                                 DEFAULT_PORT,
-                                 format!("{} {} {}", CALICO_CTL, "pool remove".to_string(), IP_POOL),
-                                 ETCD_ENV.to_string());
-            send_command_to_node(node.ip.clone(),
-                                 DEFAULT_PORT,
-                                 format!("{} {} {}", DOCKER_NETWORK, "rm".to_string(), NETWORK_NAME),
-                                 "".to_string());
+            try!(send_command_to_node(node.ip.clone(),
+                                      format!("{} {} {}", CALICO_CTL, "pool remove".to_string(), IP_POOL),
+                                      ETCD_ENV.to_string()));
+            try!(send_command_to_node(node.ip.clone(),
+                                      format!("{} {} {}", DOCKER_NETWORK, "rm".to_string(), CALICO_NETWORK),
+                                      "".to_string()));
             // only have to do this on one node
             break;
         }
     }
+    Ok(())
 }
 
-#[derive(Clone, Debug, RustcEncodable)]
-struct Command {
-    cmd: String,
-    env: String,
-}
-
-fn send_command_to_node(ip: String, port: i16, command: String, env: String) {
-    let address = format!("http://{}:{}/sync", ip, port);
-    let command = Command {
-        cmd: command.clone(),
-        env: env.clone(),
-    };
-
-    let mut response = CLIENT.post(&address).body(&json::encode(&command).unwrap()).send().unwrap();
-
-    let mut body = String::new();
-    response.read_to_string(&mut body).unwrap();
-    println!("response from {}: {:?}", ip, body);
-
-    match response.status {
-        StatusCode::Accepted => {}
-        _ => println!("error posting"),
-    }
+fn send_command_to_node(ip: String, command: String, env: String) -> Result<(), HttpError> {
+    run_sync_command("calico", &ip, &command, &env).map(|_| ())
 }
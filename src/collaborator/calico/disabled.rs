@@ -0,0 +1,40 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use state::Node;
+use super::super::http::HttpError;
+
+// built without the calico feature, the network calls fail instead of doing nothing
+pub fn configure_network(_nodes: &Vec<Node>) -> Result<(), HttpError> {
+    error!("calico not compiled in, can't configure the network");
+    Err(HttpError::NotCompiledIn("calico"))
+}
+
+pub fn join_network(_node: &Node) -> Result<(), HttpError> {
+    error!("calico not compiled in, can't add a node to the network");
+    Err(HttpError::NotCompiledIn("calico"))
+}
+
+pub fn shutdown_network(_nodes: &Vec<Node>) -> Result<(), HttpError> {
+    error!("calico not compiled in, can't shut down the network");
+    Err(HttpError::NotCompiledIn("calico"))
+}
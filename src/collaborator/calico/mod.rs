@@ -20,6 +20,15 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 // THE SOFTWARE.
 
-pub use self::client::{configure_network, shutdown_network};
+// the docker network tasks on calico ask for with network_type
+pub const CALICO_NETWORK: &'static str = "torc";
 
+#[cfg(feature = "calico")]
+pub use self::client::{configure_network, join_network, shutdown_network};
+#[cfg(not(feature = "calico"))]
+pub use self::disabled::{configure_network, join_network, shutdown_network};
+
+#[cfg(feature = "calico")]
 pub mod client;
+#[cfg(not(feature = "calico"))]
+mod disabled;
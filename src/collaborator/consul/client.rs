@@ -21,37 +21,165 @@
 // THE SOFTWARE.
 
 
-use hyper::Client;
+use rustc_serialize::json::Json;
 use state::Task;
+use super::alias_id;
+use super::super::http::{HttpError, collaborator_url, http_get, http_put};
+use super::ttl_check::TtlCheck;
 
+const CONSUL_PORT: i64 = 8500;
 
-lazy_static! {
-    static ref CLIENT: Client = Client::new();
+// tells the task registrations apart from the controllers and the dns addons
+const TASK_TAG: &'static str = "torc-task";
+const CONTROLLER_TAG: &'static str = "torc-controller";
+const UNMANAGED_TAG: &'static str = "torc-unmanaged";
+
+// the local agent's api, over https once http.tls.collaborators has consul
+pub fn consul_url(master_ip: &String) -> String {
+    collaborator_url("consul", master_ip, CONSUL_PORT)
 }
 
-pub fn register_running_task(master_ip: &String, task: &Task) {
-    register_service(master_ip, task);
+// Ok(false) for a task that has no ip to register yet
+pub fn register_running_task(master_ip: &String, task: &Task, check: &TtlCheck) -> Result<bool, HttpError> {
+    register_service(master_ip, task, check)
 }
 
-pub fn register_torc_controller(master_ip: &String, controller_name: &String, controller_ip: &String) {
-    register_controller(master_ip, controller_name, controller_ip);
+// keeps going through the aliases, the first error is the one returned
+pub fn deregister_task(master_ip: &String, task: &Task) -> Result<(), HttpError> {
+    let mut result = deregister(master_ip, &task.name);
+    for alias in &task.aliases {
+        result = result.and(deregister(master_ip, &alias_id(alias, task)));
+    }
+    result
 }
 
-pub fn register_unmanaged_service(master_ip: &String, service_name: &String, service_ip: &String) {
-    register_controller(master_ip, service_name, service_ip);
+// marks the ttl checks of the task and its aliases as passing
+pub fn refresh_task_ttl(master_ip: &String, task: &Task) -> Result<(), HttpError> {
+    let mut result = pass_check(master_ip, &task.name);
+    for alias in &task.aliases {
+        result = result.and(pass_check(master_ip, &alias_id(alias, task)));
+    }
+    result
 }
 
-fn register_controller(master_ip: &String, controller_name: &String, controller_ip: &String) {
-    register(master_ip, controller_name, controller_ip);
+// just the one name, a task's aliases stay registered
+pub fn deregister_service(master_ip: &String, name: &String) -> Result<(), HttpError> {
+    deregister(master_ip, name)
 }
 
-fn register_service(master_ip: &String, task: &Task) {
-    register(master_ip, &task.name, &task.ip);
+// ids of the services on the agent that were registered for a task, by tag or for older
+// registrations without one by a name starting with prefix, an empty prefix matches nothing.
+// controllers and dns addons are never listed, whatever their name
+pub fn list_task_services(master_ip: &String, prefix: &String) -> Result<Vec<String>, HttpError> {
+    let address = format!("{}/v1/agent/services", consul_url(master_ip));
+    let response = try!(http_get("consul", master_ip, &address));
+
+    let services = try!(Json::from_str(&response.body).map_err(|error| HttpError::Invalid(error.to_string())));
+    let services = match services.as_object() {
+        Some(services) => services.clone(),
+        None => return Err(HttpError::Invalid(format!("no services in {}", response.body))),
+    };
+    let mut result = vec![];
+    for (id, service) in services {
+        let tags: Vec<&str> = service.find("Tags")
+            .and_then(|tags| tags.as_array())
+            .map(|tags| tags.iter().filter_map(|tag| tag.as_string()).collect())
+            .unwrap_or(vec![]);
+        if tags.contains(&CONTROLLER_TAG) || tags.contains(&UNMANAGED_TAG) {
+            continue;
+        }
+        if tags.contains(&TASK_TAG) || (!prefix.is_empty() && id.starts_with(prefix.as_str())) {
+            result.push(id);
+        }
+    }
+    Ok(result)
 }
 
-fn register(master_ip: &String, name: &String, ip: &String) {
-    let address = format!("http://{}:8500/v1/agent/service/register", master_ip);
+pub fn is_service_registered(master_ip: &String, name: &String) -> Result<bool, HttpError> {
+    let address = format!("{}/v1/catalog/service/{}", consul_url(master_ip), name);
+    let response = try!(http_get("consul", master_ip, &address));
+    Ok(response.body.contains(&format!("\"ServiceName\":\"{}\"", name)))
+}
+
+// peers and monitoring find the live controllers by their passing check, the health checker refreshes it
+pub fn register_torc_controller(master_ip: &String,
+                                controller_name: &String,
+                                controller_ip: &String,
+                                check: &TtlCheck)
+                                -> Result<(), HttpError> {
+    let address = format!("{}/v1/agent/service/register", consul_url(master_ip));
+    let service_description = format!{"{{\"Name\": \"{}\",\"Address\": \"{}\",\"Tags\": [\"{}\"],\
+                                       \"Check\": {{\"TTL\": \"{}s\",\"Status\": \"passing\",\
+                                       \"DeregisterCriticalServiceAfter\": \"{}s\"}}}}",
+                                      controller_name,
+                                      controller_ip,
+                                      CONTROLLER_TAG,
+                                      check.ttl_in_seconds,
+                                      check.deregister_after_in_seconds};
+    http_put("consul", master_ip, &address, &service_description).map(|_| ())
+}
+
+pub fn refresh_controller_ttl(master_ip: &String, controller_name: &String) -> Result<(), HttpError> {
+    pass_check(master_ip, controller_name)
+}
+
+pub fn deregister_torc_controller(master_ip: &String, controller_name: &String) -> Result<(), HttpError> {
+    deregister(master_ip, controller_name)
+}
+
+pub fn register_unmanaged_service(master_ip: &String, service_name: &String, service_ip: &String) -> Result<(), HttpError> {
+    let address = format!("{}/v1/agent/service/register", consul_url(master_ip));
+    let service_description = format!{"{{\"Name\": \"{}\",\"Address\": \"{}\",\"Tags\": [\"{}\"]}}",
+                                      service_name,
+                                      service_ip,
+                                      UNMANAGED_TAG};
+    http_put("consul", master_ip, &address, &service_description).map(|_| ())
+}
+
+fn register_service(master_ip: &String, task: &Task, check: &TtlCheck) -> Result<bool, HttpError> {
+    if task.ip.is_empty() {
+        warn!("not registering task without ip task={}", task.name);
+        return Ok(false);
+    }
+    let weight = task.traffic_weight();
+    let mut result = register_weighted(master_ip, &task.name, task.service_name(), &task.ip, weight, check);
+    for alias in &task.aliases {
+        result = result.and(register_weighted(master_ip, &alias_id(alias, task), alias, &task.ip, weight, check));
+    }
+    result.map(|_| true)
+}
+
+// weights are picked up by consul aware load balancers, re-registering updates them,
+// only running tasks get registered so the check starts out passing
+fn register_weighted(master_ip: &String,
+                     id: &String,
+                     name: &String,
+                     ip: &String,
+                     weight: i64,
+                     check: &TtlCheck)
+                     -> Result<(), HttpError> {
+    let address = format!("{}/v1/agent/service/register", consul_url(master_ip));
+    let service_description = format!{"{{\"ID\": \"{}\",\"Name\": \"{}\",\"Address\": \"{}\",\"Tags\": [\"{}\"],\
+                                       \"Weights\": {{\"Passing\": {},\"Warning\": 1}},\
+                                       \"Check\": {{\"TTL\": \"{}s\",\"Status\": \"passing\",\
+                                       \"DeregisterCriticalServiceAfter\": \"{}s\"}}}}",
+                                      id,
+                                      name,
+                                      ip,
+                                      TASK_TAG,
+                                      weight,
+                                      check.ttl_in_seconds,
+                                      check.deregister_after_in_seconds};
+    http_put("consul", master_ip, &address, &service_description).map(|_| ())
+}
+
+// a check registered along with a service gets the id service:<service id>
+fn pass_check(master_ip: &String, name: &String) -> Result<(), HttpError> {
+    let address = format!("{}/v1/agent/check/pass/service:{}", consul_url(master_ip), name);
+    http_put("consul", master_ip, &address, "").map(|_| ())
+}
 
-    let service_description = format!{"{{\"Name\": \"{}\",\"Address\": \"{}\"}}", name, ip};
-    let _ = CLIENT.post(&address).body(&service_description).send();
+fn deregister(master_ip: &String, name: &String) -> Result<(), HttpError> {
+    let address = format!("{}/v1/agent/service/deregister/{}", consul_url(master_ip), name);
+    http_put("consul", master_ip, &address, "").map(|_| ())
 }
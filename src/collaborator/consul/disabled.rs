@@ -0,0 +1,111 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use state::Task;
+use super::super::http::HttpError;
+use super::ttl_check::TtlCheck;
+
+// built without the consul feature, every call fails so registrations and leader election
+// report what is missing instead of pretending to work
+pub fn register_running_task(_master_ip: &String, _task: &Task, _check: &TtlCheck) -> Result<bool, HttpError> {
+    Err(HttpError::NotCompiledIn("consul"))
+}
+
+pub fn deregister_task(_master_ip: &String, _task: &Task) -> Result<(), HttpError> {
+    Err(HttpError::NotCompiledIn("consul"))
+}
+
+pub fn refresh_task_ttl(_master_ip: &String, _task: &Task) -> Result<(), HttpError> {
+    Err(HttpError::NotCompiledIn("consul"))
+}
+
+pub fn deregister_service(_master_ip: &String, _name: &String) -> Result<(), HttpError> {
+    Err(HttpError::NotCompiledIn("consul"))
+}
+
+pub fn list_task_services(_master_ip: &String, _prefix: &String) -> Result<Vec<String>, HttpError> {
+    Err(HttpError::NotCompiledIn("consul"))
+}
+
+pub fn is_service_registered(_master_ip: &String, _name: &String) -> Result<bool, HttpError> {
+    Err(HttpError::NotCompiledIn("consul"))
+}
+
+pub fn register_torc_controller(_master_ip: &String,
+                                _controller_name: &String,
+                                _controller_ip: &String,
+                                _check: &TtlCheck)
+                                -> Result<(), HttpError> {
+    Err(HttpError::NotCompiledIn("consul"))
+}
+
+pub fn refresh_controller_ttl(_master_ip: &String, _controller_name: &String) -> Result<(), HttpError> {
+    Err(HttpError::NotCompiledIn("consul"))
+}
+
+pub fn deregister_torc_controller(_master_ip: &String, _controller_name: &String) -> Result<(), HttpError> {
+    Err(HttpError::NotCompiledIn("consul"))
+}
+
+pub fn register_unmanaged_service(_master_ip: &String, _service_name: &String, _service_ip: &String) -> Result<(), HttpError> {
+    Err(HttpError::NotCompiledIn("consul"))
+}
+
+pub fn create_session(_master_ip: &String, _name: &String, _ttl_in_seconds: i64) -> Result<String, HttpError> {
+    Err(HttpError::NotCompiledIn("consul"))
+}
+
+pub fn renew_session(_master_ip: &String, _session: &String) -> Result<(), HttpError> {
+    Err(HttpError::NotCompiledIn("consul"))
+}
+
+pub fn destroy_session(_master_ip: &String, _session: &String) -> Result<(), HttpError> {
+    Err(HttpError::NotCompiledIn("consul"))
+}
+
+pub fn acquire_lock(_master_ip: &String, _key: &String, _session: &String, _value: &String) -> Result<bool, HttpError> {
+    Err(HttpError::NotCompiledIn("consul"))
+}
+
+pub fn release_lock(_master_ip: &String, _key: &String, _session: &String) -> Result<(), HttpError> {
+    Err(HttpError::NotCompiledIn("consul"))
+}
+
+pub fn lock_holder(_master_ip: &String, _key: &String) -> Result<Option<String>, HttpError> {
+    Err(HttpError::NotCompiledIn("consul"))
+}
+
+pub fn read_key(_master_ip: &String, _key: &String) -> Result<Option<String>, HttpError> {
+    Err(HttpError::NotCompiledIn("consul"))
+}
+
+pub fn create_key(_master_ip: &String, _key: &String, _value: &String) -> Result<bool, HttpError> {
+    Err(HttpError::NotCompiledIn("consul"))
+}
+
+pub fn delete_key(_master_ip: &String, _key: &String) -> Result<(), HttpError> {
+    Err(HttpError::NotCompiledIn("consul"))
+}
+
+pub fn read_keys(_master_ip: &String, _prefix: &String) -> Result<Vec<(String, String)>, HttpError> {
+    Err(HttpError::NotCompiledIn("consul"))
+}
@@ -20,6 +20,31 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 // THE SOFTWARE.
 
-pub use self::client::{register_running_task, register_torc_controller, register_unmanaged_service};
+#[cfg(feature = "consul")]
+pub use self::client::{deregister_service, deregister_task, deregister_torc_controller, is_service_registered,
+                       list_task_services, refresh_controller_ttl, refresh_task_ttl, register_running_task,
+                       register_torc_controller, register_unmanaged_service};
+#[cfg(feature = "consul")]
+pub use self::session::{acquire_lock, create_key, create_session, delete_key, destroy_session, lock_holder, read_key,
+                        read_keys, release_lock, renew_session};
+#[cfg(not(feature = "consul"))]
+pub use self::disabled::{acquire_lock, create_key, create_session, delete_key, deregister_service, deregister_task,
+                         deregister_torc_controller, destroy_session, is_service_registered, list_task_services,
+                         lock_holder, read_key, read_keys, refresh_controller_ttl, refresh_task_ttl, register_running_task,
+                         register_torc_controller, register_unmanaged_service, release_lock, renew_session};
+pub use self::ttl_check::TtlCheck;
 
+use state::Task;
+
+#[cfg(feature = "consul")]
 pub mod client;
+#[cfg(feature = "consul")]
+pub mod session;
+#[cfg(not(feature = "consul"))]
+mod disabled;
+mod ttl_check;
+
+// every replica registers an alias under an id of its own, the name is what they share
+pub fn alias_id(alias: &String, task: &Task) -> String {
+    format!("{}-{}", alias, task.name)
+}
@@ -0,0 +1,128 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use hyper::header::Headers;
+use hyper::method::Method;
+use rustc_serialize::base64::FromBase64;
+use rustc_serialize::json::Json;
+use super::client::consul_url;
+use super::super::http::{HttpError, http_get, http_put, http_send};
+
+// consul sessions and the kv locks they hold, a session that isn't renewed within its ttl
+// gets invalidated and releases its locks
+pub fn create_session(master_ip: &String, name: &String, ttl_in_seconds: i64) -> Result<String, HttpError> {
+    let address = format!("{}/v1/session/create", consul_url(master_ip));
+    let body = format!("{{\"Name\": \"{}\",\"TTL\": \"{}s\",\"Behavior\": \"release\"}}", name, ttl_in_seconds);
+    let response = try!(http_put("consul", master_ip, &address, &body));
+
+    let id = Json::from_str(&response.body)
+        .ok()
+        .and_then(|json| json.find("ID").and_then(|id| id.as_string()).map(|id| id.to_string()));
+    match id {
+        Some(id) => Ok(id),
+        None => Err(HttpError::Invalid(format!("no session id in {}", response.body))),
+    }
+}
+
+pub fn renew_session(master_ip: &String, session: &String) -> Result<(), HttpError> {
+    let address = format!("{}/v1/session/renew/{}", consul_url(master_ip), session);
+    http_put("consul", master_ip, &address, "").map(|_| ())
+}
+
+pub fn destroy_session(master_ip: &String, session: &String) -> Result<(), HttpError> {
+    let address = format!("{}/v1/session/destroy/{}", consul_url(master_ip), session);
+    http_put("consul", master_ip, &address, "").map(|_| ())
+}
+
+// false while another session holds the lock
+pub fn acquire_lock(master_ip: &String, key: &String, session: &String, value: &String) -> Result<bool, HttpError> {
+    let address = format!("{}/v1/kv/{}?acquire={}", consul_url(master_ip), key, session);
+    http_put("consul", master_ip, &address, value).map(|response| response.body.trim() == "true")
+}
+
+pub fn release_lock(master_ip: &String, key: &String, session: &String) -> Result<(), HttpError> {
+    let address = format!("{}/v1/kv/{}?release={}", consul_url(master_ip), key, session);
+    http_put("consul", master_ip, &address, "").map(|_| ())
+}
+
+// the session holding the lock on key, None for a key that is missing or not locked
+pub fn lock_holder(master_ip: &String, key: &String) -> Result<Option<String>, HttpError> {
+    let address = format!("{}/v1/kv/{}", consul_url(master_ip), key);
+    let response = match http_get("consul", master_ip, &address) {
+        Ok(response) => response,
+        Err(HttpError::Status(404, _)) => return Ok(None),
+        Err(error) => return Err(error),
+    };
+
+    let entries = try!(Json::from_str(&response.body).map_err(|error| HttpError::Invalid(error.to_string())));
+    Ok(entries.as_array()
+        .and_then(|entries| entries.first())
+        .and_then(|entry| entry.find("Session"))
+        .and_then(|session| session.as_string())
+        .map(|session| session.to_string()))
+}
+
+pub fn read_key(master_ip: &String, key: &String) -> Result<Option<String>, HttpError> {
+    let address = format!("{}/v1/kv/{}?raw", consul_url(master_ip), key);
+    match http_get("consul", master_ip, &address) {
+        Ok(response) => Ok(Some(response.body)),
+        Err(HttpError::Status(404, _)) => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+// every key below prefix with its value, the names relative to the prefix, an unknown prefix has no keys
+pub fn read_keys(master_ip: &String, prefix: &String) -> Result<Vec<(String, String)>, HttpError> {
+    let prefix = format!("{}/", prefix.trim_matches('/'));
+    let address = format!("{}/v1/kv/{}?recurse", consul_url(master_ip), prefix);
+    let response = match http_get("consul", master_ip, &address) {
+        Ok(response) => response,
+        Err(HttpError::Status(404, _)) => return Ok(vec![]),
+        Err(error) => return Err(error),
+    };
+
+    let entries = try!(Json::from_str(&response.body).map_err(|error| HttpError::Invalid(error.to_string())));
+    let mut result = vec![];
+    for entry in entries.as_array().unwrap_or(&vec![]) {
+        let key = entry.find("Key").and_then(|key| key.as_string()).unwrap_or("");
+        // folders come back as keys of their own without a value
+        let value = match entry.find("Value").and_then(|value| value.as_string()) {
+            Some(value) => try!(value.from_base64().map_err(|error| HttpError::Invalid(error.to_string()))),
+            None => continue,
+        };
+        if key.len() > prefix.len() {
+            result.push((key[prefix.len()..].to_string(), String::from_utf8_lossy(&value).to_string()));
+        }
+    }
+    Ok(result)
+}
+
+// only writes a key that doesn't exist yet, false if it does
+pub fn create_key(master_ip: &String, key: &String, value: &String) -> Result<bool, HttpError> {
+    let address = format!("{}/v1/kv/{}?cas=0", consul_url(master_ip), key);
+    http_put("consul", master_ip, &address, value).map(|response| response.body.trim() == "true")
+}
+
+pub fn delete_key(master_ip: &String, key: &String) -> Result<(), HttpError> {
+    let address = format!("{}/v1/kv/{}", consul_url(master_ip), key);
+    http_send(Method::Delete, "consul", master_ip, &address, &Headers::new(), "").map(|_| ())
+}
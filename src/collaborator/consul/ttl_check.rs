@@ -0,0 +1,41 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use utils::read_int;
+use yaml_rust::Yaml;
+
+// task services come with a ttl check, a service whose task stops getting refreshed turns critical
+// and consul drops it after deregister_after_in_seconds, in case the controller misses the deregistration
+#[derive(Clone, Debug)]
+pub struct TtlCheck {
+    pub ttl_in_seconds: i64,
+    pub deregister_after_in_seconds: i64,
+}
+
+impl TtlCheck {
+    pub fn from_config(config: &Yaml) -> TtlCheck {
+        TtlCheck {
+            ttl_in_seconds: read_int(&config["consul"], "ttl_in_seconds".to_string(), 60),
+            deregister_after_in_seconds: read_int(&config["consul"], "deregister_after_in_seconds".to_string(), 300),
+        }
+    }
+}
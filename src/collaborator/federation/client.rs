@@ -0,0 +1,77 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use hyper::header::{Authorization, Bearer, Headers};
+use hyper::method::Method;
+use rustc_serialize::json::{self, Json};
+use state::Task;
+use super::super::http::{HttpError, http_send};
+
+// sent without a version so peers from before versioning decode it as well,
+// fields they don't know yet are ignored
+pub fn delegate_task(peer_url: &String, token: &String, task: &Task) -> Result<(), String> {
+    let url = format!("{}/service/delegated", peer_url);
+    http_send(Method::Post, "federation", peer_url, &url, &auth_headers(token), &json::encode(task).unwrap())
+        .map(|_| ())
+        .map_err(|error| format!("error calling {}: {}", url, error))
+}
+
+// none when the peer doesn't know the task (anymore)
+pub fn delegated_task_state(peer_url: &String, token: &String, task_name: &String) -> Result<Option<String>, String> {
+    let url = format!("{}/service/delegated?name={}", peer_url, task_name);
+    let body = match http_send(Method::Get, "federation", peer_url, &url, &auth_headers(token), "") {
+        Ok(response) => response.body,
+        Err(HttpError::Status(404, _)) => return Ok(None),
+        Err(error) => return Err(format!("error calling {}: {}", url, error)),
+    };
+
+    match Json::from_str(&body) {
+        Ok(result) => Ok(result.find("result").and_then(|state| state.as_string()).map(|state| state.to_string())),
+        Err(error) => Err(format!("invalid answer from {}: {}", url, error)),
+    }
+}
+
+// delete and kill on a delegated task have to happen where it runs, a task the peer doesn't know is gone already
+pub fn delete_delegated_task(peer_url: &String, token: &String, task_name: &String) -> Result<(), String> {
+    let url = format!("{}/service/delegated?name={}", peer_url, task_name);
+    forward(Method::Delete, peer_url, token, &url)
+}
+
+pub fn kill_delegated_task(peer_url: &String, token: &String, task_name: &String) -> Result<(), String> {
+    let url = format!("{}/service/delegated/kill?name={}", peer_url, task_name);
+    forward(Method::Post, peer_url, token, &url)
+}
+
+fn forward(method: Method, peer_url: &String, token: &String, url: &String) -> Result<(), String> {
+    match http_send(method, "federation", peer_url, url, &auth_headers(token), "") {
+        Ok(_) | Err(HttpError::Status(404, _)) => Ok(()),
+        Err(error) => Err(format!("error calling {}: {}", url, error)),
+    }
+}
+
+fn auth_headers(token: &String) -> Headers {
+    let mut headers = Headers::new();
+    if !token.is_empty() {
+        headers.set(Authorization(Bearer { token: token.clone() }));
+    }
+    headers
+}
@@ -0,0 +1,49 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use rustc_serialize::json;
+use super::super::http::http_post;
+
+#[derive(Clone, Debug, RustcEncodable)]
+struct RestartHookCall {
+    dependency: String,
+    ip: String,
+    dependents: Vec<String>,
+}
+
+// lets an outside system refresh the dependents of a restarted service instead of the scheduler restarting them,
+// any 2xx counts as handled
+pub fn call_restart_hook(url: &String, dependency: &String, ip: &String, dependents: &Vec<String>) -> bool {
+    let call = RestartHookCall {
+        dependency: dependency.clone(),
+        ip: ip.clone(),
+        dependents: dependents.clone(),
+    };
+
+    match http_post("restart-hook", url, url, &json::encode(&call).unwrap()) {
+        Ok(_) => true,
+        Err(error) => {
+            error!("can't call restart hook url={} error={}", url, error);
+            false
+        }
+    }
+}
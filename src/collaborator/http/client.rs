@@ -0,0 +1,263 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use hyper::Client;
+use hyper::header::Headers;
+use hyper::method::Method;
+use hyper::net::{HttpsConnector, Openssl};
+use openssl::ssl::{SSL_VERIFY_PEER, SslContext, SslMethod};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Read;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+use utils::{CallTimer, read_int, read_string};
+use yaml_rust::Yaml;
+
+// shared by the collaborator clients, connection errors and 5xx of idempotent requests are retried
+// with exponential backoff, anything else is handed back to the caller right away
+#[derive(Clone, Debug)]
+pub struct HttpPolicy {
+    pub retries: i64,
+    pub initial_backoff_in_ms: u64,
+    pub max_backoff_in_ms: u64,
+    pub timeout_in_seconds: u64,
+    // the collaborators called over https and the port their tls listener is on, 0 for the usual one
+    pub tls_collaborators: BTreeMap<String, i64>,
+    // pins the peers to this ca instead of the system's trust store
+    pub ca_file: String,
+}
+
+impl HttpPolicy {
+    pub fn from_config(config: &Yaml) -> HttpPolicy {
+        let mut tls_collaborators = BTreeMap::new();
+        if let Some(collaborators) = config["http"]["tls"]["collaborators"].as_hash() {
+            for (collaborator, port) in collaborators {
+                if let Some(collaborator) = collaborator.as_str() {
+                    tls_collaborators.insert(collaborator.to_string(), port.as_i64().unwrap_or(0));
+                }
+            }
+        }
+
+        HttpPolicy {
+            retries: read_int(&config["http"], "retries".to_string(), 2),
+            initial_backoff_in_ms: read_int(&config["http"], "initial_backoff_in_ms".to_string(), 200) as u64,
+            max_backoff_in_ms: read_int(&config["http"], "max_backoff_in_ms".to_string(), 2000) as u64,
+            timeout_in_seconds: read_int(&config["http"], "timeout_in_seconds".to_string(), 5) as u64,
+            tls_collaborators: tls_collaborators,
+            ca_file: read_string(&config["http"]["tls"], "ca_file".to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum HttpError {
+    // no connection or no answer within the timeout
+    Unreachable(String),
+    // status and body of the last attempt
+    Status(u16, String),
+    // an answer the caller can't make sense of
+    Invalid(String),
+    // the build left out the collaborator, named by its cargo feature
+    NotCompiledIn(&'static str),
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            HttpError::Unreachable(ref reason) => write!(f, "unreachable: {}", reason),
+            HttpError::Status(status, ref body) => write!(f, "status {}: {}", status, body),
+            HttpError::Invalid(ref reason) => write!(f, "invalid answer: {}", reason),
+            HttpError::NotCompiledIn(feature) => write!(f, "not compiled in, build with --features {}", feature),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Headers,
+    pub body: String,
+}
+
+struct HttpClient {
+    client: Client,
+    policy: HttpPolicy,
+}
+
+lazy_static! {
+    static ref CLIENT: RwLock<Arc<HttpClient>> = {
+        RwLock::new(Arc::new(new_client(HttpPolicy {
+            retries: 2,
+            initial_backoff_in_ms: 200,
+            max_backoff_in_ms: 2000,
+            timeout_in_seconds: 5,
+            tls_collaborators: BTreeMap::new(),
+            ca_file: "".to_string(),
+        }).unwrap()))
+    };
+}
+
+// calls already in flight finish with the policy they started with, a tls setup that fails
+// leaves the previous client in place
+pub fn set_http_policy(policy: HttpPolicy) -> Result<(), String> {
+    let client = try!(new_client(policy));
+    *CLIENT.write().unwrap() = Arc::new(client);
+    Ok(())
+}
+
+// scheme, host and port for the collaborators to build their urls on, the policy decides between
+// http on the usual port and https on the one configured for the collaborator
+pub fn collaborator_url(collaborator: &str, host: &String, port: i64) -> String {
+    match CLIENT.read().unwrap().policy.tls_collaborators.get(collaborator) {
+        Some(&tls_port) if tls_port > 0 => format!("https://{}:{}", host, tls_port),
+        Some(_) => format!("https://{}:{}", host, port),
+        None => format!("http://{}:{}", host, port),
+    }
+}
+
+pub fn http_get(collaborator: &str, target: &String, url: &String) -> Result<HttpResponse, HttpError> {
+    http_send(Method::Get, collaborator, target, url, &Headers::new(), "")
+}
+
+pub fn http_post(collaborator: &str, target: &String, url: &String, body: &str) -> Result<HttpResponse, HttpError> {
+    http_send(Method::Post, collaborator, target, url, &Headers::new(), body)
+}
+
+pub fn http_put(collaborator: &str, target: &String, url: &String, body: &str) -> Result<HttpResponse, HttpError> {
+    http_send(Method::Put, collaborator, target, url, &Headers::new(), body)
+}
+
+// the https connector talks plain http to the collaborators left on it
+fn new_client(policy: HttpPolicy) -> Result<HttpClient, String> {
+    let mut client = match policy.tls_collaborators.is_empty() {
+        true => Client::new(),
+        false => try!(verifying_client(&policy.ca_file)),
+    };
+    client.set_read_timeout(Some(Duration::from_secs(policy.timeout_in_seconds)));
+    client.set_write_timeout(Some(Duration::from_secs(policy.timeout_in_seconds)));
+    Ok(HttpClient {
+        client: client,
+        policy: policy,
+    })
+}
+
+pub fn verifying_client(ca_file: &String) -> Result<Client, String> {
+    Ok(Client::with_connector(HttpsConnector::new(try!(verifying_ssl(ca_file)))))
+}
+
+// the default context doesn't check the peer at all
+fn verifying_ssl(ca_file: &String) -> Result<Openssl, String> {
+    let mut context = try!(SslContext::new(SslMethod::Sslv23).map_err(|error| format!("can't create ssl context: {}", error)));
+    match ca_file.is_empty() {
+        true => try!(context.set_default_verify_paths().map_err(|error| format!("can't load system ca: {}", error))),
+        false => try!(context.set_CA_file(ca_file).map_err(|error| format!("can't load ca_file {}: {}", ca_file, error))),
+    }
+    context.set_verify(SSL_VERIFY_PEER, None);
+    Ok(Openssl { context: Arc::new(context) })
+}
+
+// for the calls that need more than the body, e.g. an auth header
+pub fn http_send(method: Method,
+                 collaborator: &str,
+                 target: &String,
+                 url: &String,
+                 headers: &Headers,
+                 body: &str)
+                 -> Result<HttpResponse, HttpError> {
+    let http = CLIENT.read().unwrap().clone();
+    let mut backoff = http.policy.initial_backoff_in_ms;
+    let mut attempt = 0;
+    // a post that timed out may well have been carried out, e.g. a shell command on a node
+    let idempotent = method != Method::Post && method != Method::Patch;
+
+    loop {
+        let result = send_once(&http.client, method.clone(), collaborator, target, url, headers, body).and_then(|response| {
+            match response.status >= 200 && response.status < 300 {
+                true => Ok(response),
+                false => Err(HttpError::Status(response.status, response.body.trim().to_string())),
+            }
+        });
+        let retryable = match result {
+            Err(HttpError::Unreachable(_)) => idempotent,
+            Err(HttpError::Status(status, _)) => idempotent && status >= 500,
+            Err(HttpError::Invalid(_)) | Err(HttpError::NotCompiledIn(_)) | Ok(_) => false,
+        };
+        if !retryable || attempt >= http.policy.retries {
+            return result;
+        }
+
+        attempt += 1;
+        debug!("retrying collaborator={} target={} attempt={} backoff_in_ms={}",
+               collaborator,
+               target,
+               attempt,
+               backoff);
+        thread::sleep(Duration::from_millis(backoff));
+        backoff = (backoff * 2).min(http.policy.max_backoff_in_ms);
+    }
+}
+
+// every status the collaborator answered with comes back as is, for the callers that act on e.g. a 401,
+// only a collaborator that can't be reached is an error and that one isn't retried
+pub fn http_answer(method: Method,
+                   collaborator: &str,
+                   target: &String,
+                   url: &String,
+                   headers: &Headers,
+                   body: &str)
+                   -> Result<HttpResponse, HttpError> {
+    let http = CLIENT.read().unwrap().clone();
+    send_once(&http.client, method, collaborator, target, url, headers, body)
+}
+
+fn send_once(client: &Client,
+             method: Method,
+             collaborator: &str,
+             target: &String,
+             url: &String,
+             headers: &Headers,
+             body: &str)
+             -> Result<HttpResponse, HttpError> {
+    let timer = CallTimer::start(collaborator, target);
+    let mut response = match client.request(method, url).headers(headers.clone()).body(body).send() {
+        Ok(response) => response,
+        Err(error) => {
+            timer.finish(false);
+            return Err(HttpError::Unreachable(error.to_string()));
+        }
+    };
+
+    let mut response_body = String::new();
+    if let Err(error) = response.read_to_string(&mut response_body) {
+        timer.finish(false);
+        return Err(HttpError::Unreachable(error.to_string()));
+    }
+
+    timer.finish(response.status.is_success());
+    Ok(HttpResponse {
+        status: response.status.to_u16(),
+        headers: response.headers.clone(),
+        body: response_body,
+    })
+}
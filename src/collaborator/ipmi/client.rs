@@ -20,53 +20,190 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 // THE SOFTWARE.
 
-use hyper::Client;
-use hyper::status::StatusCode;
-use rustc_serialize::json;
-use std::io::Read;
+use state::{Node, PowerState};
+use std::collections::HashMap;
+use std::env;
+#[cfg(feature = "ipmi")]
+use super::{ipmitool, proxy};
+use utils::read_string;
+use yaml_rust::Yaml;
 
-const DEFAULT_PORT: i16 = 8085;
+#[derive(Clone, Debug)]
+pub struct IpmiConfig {
+    pub backend: String,
+    pub proxy: String,
+    pub username: String,
+    pub password: String,
+    pub credentials: HashMap<String, IpmiCredentials>,
+}
+
+#[derive(Clone, Debug)]
+pub struct IpmiCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl IpmiConfig {
+    pub fn new(config: &Yaml) -> IpmiConfig {
+        let proxy = config["ipmiproxy"].as_str().unwrap_or("undefined").to_string();
+        let default_backend = match proxy.as_ref() {
+            "undefined" => "undefined",
+            _ => "proxy",
+        };
+
+        IpmiConfig {
+            backend: config["ipmi"]["backend"].as_str().unwrap_or(default_backend).to_string(),
+            proxy: proxy,
+            username: config["ipmi"]["username"].as_str().unwrap_or("root").to_string(),
+            password: read_string(&config["ipmi"], "password".to_string()),
+            credentials: read_credentials(&config["ipmi"]),
+        }
+    }
+
+    pub fn is_defined(&self) -> bool {
+        self.backend != "undefined"
+    }
+
+    // a node can bring its own backend and reference a named credential,
+    // everything it doesn't define falls back to the global settings
+    pub fn for_node(&self, node: &Node) -> IpmiConfig {
+        let mut result = self.clone();
+
+        if !node.power_backend.is_empty() {
+            result.backend = node.power_backend.clone();
+        }
+
+        if !node.power_credentials.is_empty() {
+            match self.credentials.get(&node.power_credentials) {
+                Some(credentials) => {
+                    result.username = credentials.username.clone();
+                    result.password = credentials.password.clone();
+                }
+                None => warn!("unknown ipmi credentials credentials={} node={}", node.power_credentials, node.name),
+            }
+        }
+
+        result
+    }
+}
+
+// passwords can be handed in through the environment instead of being kept in the config file
+fn read_credentials(ipmi: &Yaml) -> HashMap<String, IpmiCredentials> {
+    let mut result = HashMap::new();
 
-lazy_static! {
-    static ref CLIENT: Client = Client::new();
+    match ipmi["credentials"].as_vec() {
+        Some(credentials) => {
+            for credential in credentials {
+                let password_env = read_string(credential, "password_env".to_string());
+                let password = match password_env.is_empty() {
+                    true => read_string(credential, "password".to_string()),
+                    false => env::var(&password_env).unwrap_or("".to_string()),
+                };
+
+                result.insert(read_string(credential, "name".to_string()),
+                              IpmiCredentials {
+                                  username: credential["username"].as_str().unwrap_or("root").to_string(),
+                                  password: password,
+                              });
+            }
+        }
+        None => {}
+    }
+
+    result
+}
+
+pub fn shutdown_node(ipmi: &IpmiConfig, node_ip: &String) {
+    run_power_command(ipmi, node_ip, "off");
 }
 
-pub fn shutdown_node(proxy_ip: &String, node_ip: &String) {
-    send_command_to_node(proxy_ip.clone(),
-                         DEFAULT_PORT,
-                         format!("ipmitool -H {} -I lanplus -U root -P root power off",
-                                 &node_ip));
+pub fn startup_node(ipmi: &IpmiConfig, node_ip: &String) {
+    run_power_command(ipmi, node_ip, "on");
 }
 
-pub fn startup_node(proxy_ip: &String, node_ip: &String) {
-    send_command_to_node(proxy_ip.clone(),
-                         DEFAULT_PORT,
-                         format!("ipmitool -H {} -I lanplus -U root -P root power on",
-                                 &node_ip));
+pub fn power_status(ipmi: &IpmiConfig, node_ip: &String) -> PowerState {
+    let output = run_power_command(ipmi, node_ip, "status");
+    parse_power_status(&output)
 }
 
+// current draw in watts as reported by the bmc via dcmi
+pub fn power_reading(ipmi: &IpmiConfig, node_ip: &String) -> Option<f64> {
+    let output = run_ipmi_command(ipmi, node_ip, &["dcmi", "power", "reading"]);
+    parse_power_reading(&output)
+}
 
-#[derive(Clone, Debug, RustcEncodable)]
-struct Command {
-    cmd: String,
-    env: String,
+fn run_power_command(ipmi: &IpmiConfig, node_ip: &String, power_command: &str) -> String {
+    run_ipmi_command(ipmi, node_ip, &["power", power_command])
 }
 
-fn send_command_to_node(ip: String, port: i16, command: String) {
-    let address = format!("http://{}:{}/sync", ip, port);
-    let command = Command {
-        cmd: command.clone(),
-        env: "".to_string(),
+#[cfg(feature = "ipmi")]
+fn run_ipmi_command(ipmi: &IpmiConfig, node_ip: &String, arguments: &[&str]) -> String {
+    let password = match ipmi.password.is_empty() {
+        true => "root".to_string(),
+        false => ipmi.password.clone(),
     };
 
-    let mut response = CLIENT.post(&address).body(&json::encode(&command).unwrap()).send().unwrap();
+    match ipmi.backend.as_str() {
+        "proxy" => {
+            proxy::send_command_to_node(ipmi.proxy.clone(),
+                                        format!("ipmitool -H {} -I lanplus -U {} -P {} {}",
+                                                &node_ip,
+                                                &ipmi.username,
+                                                &password,
+                                                arguments.join(" ")))
+        }
+        "ipmitool" => {
+            match ipmitool::run(node_ip, &ipmi.username, &password, arguments) {
+                Ok(output) => output.stdout,
+                Err(error_msg) => {
+                    error!("ipmitool failed arguments={} ip={} error={}", arguments.join(" "), node_ip, error_msg);
+                    "".to_string()
+                }
+            }
+        }
+        "undefined" => {
+            warn!("ipmi backend undefined");
+            "".to_string()
+        }
+        _ => {
+            error!("unknown ipmi backend backend={}", ipmi.backend);
+            "".to_string()
+        }
+    }
+}
 
-    let mut body = String::new();
-    response.read_to_string(&mut body).unwrap();
-    println!("response from {}: {:?}", ip, body);
+// built without the ipmi feature there is no way to reach a bmc, the power state stays unknown
+#[cfg(not(feature = "ipmi"))]
+fn run_ipmi_command(ipmi: &IpmiConfig, node_ip: &String, _arguments: &[&str]) -> String {
+    if ipmi.is_defined() {
+        error!("ipmi not compiled in, build with --features ipmi backend={} ip={}", ipmi.backend, node_ip);
+    }
+    "".to_string()
+}
 
-    match response.status {
-        StatusCode::Accepted => {}
-        _ => println!("error posting"),
+fn parse_power_status(output: &String) -> PowerState {
+    let output = output.to_lowercase();
+    if output.contains("power is on") {
+        PowerState::On
+    } else if output.contains("power is off") {
+        PowerState::Off
+    } else {
+        PowerState::Unknown
     }
 }
+
+fn parse_power_reading(output: &String) -> Option<f64> {
+    for line in output.lines() {
+        let line = line.trim();
+        if !line.to_lowercase().starts_with("instantaneous power reading") {
+            continue;
+        }
+
+        return line.split(':')
+            .nth(1)
+            .and_then(|value| value.split_whitespace().next())
+            .and_then(|value| value.parse::<f64>().ok());
+    }
+
+    None
+}
@@ -0,0 +1,71 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use std::process::Command;
+use utils::CallTimer;
+
+#[derive(Clone, Debug)]
+pub struct IpmitoolOutput {
+    pub exit_code: i32,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+// runs ipmitool locally instead of going through the proxy,
+// the password is handed over via IPMI_PASSWORD so it doesn't show up in the process list
+pub fn run(host: &String, username: &String, password: &String, arguments: &[&str]) -> Result<IpmitoolOutput, String> {
+    let timer = CallTimer::start("ipmi", host);
+
+    let result = Command::new("ipmitool")
+        .arg("-H")
+        .arg(host)
+        .arg("-I")
+        .arg("lanplus")
+        .arg("-U")
+        .arg(username)
+        .arg("-E")
+        .args(arguments)
+        .env("IPMI_PASSWORD", password)
+        .output();
+
+    let output = match result {
+        Ok(output) => output,
+        Err(error) => {
+            timer.finish(false);
+            return Err(format!("can't run ipmitool: {}", error));
+        }
+    };
+
+    let result = IpmitoolOutput {
+        exit_code: output.status.code().unwrap_or(-1),
+        stdout: String::from_utf8_lossy(&output.stdout).trim().to_string(),
+        stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+    };
+
+    timer.finish(output.status.success());
+    debug!("ipmitool arguments={:?} host={} result={:?}", arguments, host, result);
+
+    match output.status.success() {
+        true => Ok(result),
+        false => Err(format!("exit code {}: {}", result.exit_code, result.stderr)),
+    }
+}
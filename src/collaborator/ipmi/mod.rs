@@ -20,6 +20,10 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 // THE SOFTWARE.
 
-pub use self::client::{shutdown_node, startup_node};
+pub use self::client::{IpmiConfig, power_reading, power_status, shutdown_node, startup_node};
 
 pub mod client;
+#[cfg(feature = "ipmi")]
+mod ipmitool;
+#[cfg(feature = "ipmi")]
+mod proxy;
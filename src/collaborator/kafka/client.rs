@@ -0,0 +1,99 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use kafka::producer::{Producer, Record, RequiredAcks};
+use rustc_serialize::json;
+use state::Event;
+use std::sync::mpsc::{Sender, channel};
+use std::thread;
+use utils::{CallTimer, read_string, read_string_list};
+use yaml_rust::Yaml;
+
+// bump whenever the layout of the exported event changes, version 2 added the sequence, version 3 the epoch
+const SCHEMA_VERSION: i64 = 3;
+
+#[derive(Clone, Debug, RustcEncodable)]
+struct ExportedEvent {
+    schema_version: i64,
+    source: String,
+    event: Event,
+}
+
+// returns the sender events get pushed into, or none if no kafka sink is configured.
+// the actual producing happens on its own thread so the state thread never waits on kafka.
+pub fn start_event_export(config: &Yaml, source: &String) -> Option<Sender<Event>> {
+    let kafka = &config["events"]["kafka"];
+    let brokers = read_string_list(kafka, "brokers".to_string());
+    let topic = read_string(kafka, "topic".to_string());
+    if brokers.is_empty() || topic.is_empty() {
+        return None;
+    }
+
+    let (sender, receiver) = channel::<Event>();
+    let source = source.clone();
+
+    thread::Builder::new()
+        .name("event-export".to_string())
+        .spawn(move || {
+            let mut producer: Option<Producer> = None;
+
+            for event in receiver.iter() {
+                if producer.is_none() {
+                    producer = match Producer::from_hosts(brokers.clone())
+                        .with_required_acks(RequiredAcks::One)
+                        .create() {
+                        Ok(producer) => Some(producer),
+                        Err(error) => {
+                            error!("can't connect to kafka brokers={:?} error={}", brokers, error);
+                            None
+                        }
+                    }
+                }
+
+                let exported = ExportedEvent {
+                    schema_version: SCHEMA_VERSION,
+                    source: source.clone(),
+                    event: event,
+                };
+                let payload = json::encode(&exported).unwrap();
+
+                // on errors the event is dropped and the connection recreated for the next one
+                let success = match producer {
+                    Some(ref mut producer) => {
+                        let timer = CallTimer::start("kafka", &topic);
+                        let success = producer.send(&Record::from_value(&topic, payload.as_bytes())).is_ok();
+                        timer.finish(success);
+                        success
+                    }
+                    None => false,
+                };
+
+                if !success {
+                    warn!("dropping event, kafka not available");
+                    producer = None;
+                }
+            }
+        })
+        .unwrap();
+
+    Some(sender)
+}
@@ -23,6 +23,7 @@
 use mesos::SchedulerClient;
 use mesos::proto::TaskID;
 use std::sync::{Arc, Mutex};
+use utils::CallTimer;
 
 
 lazy_static! {
@@ -42,11 +43,18 @@ pub fn kill_task(task_name: &String) {
     if let Some(ref client) = *mesos {
         let mut task_id = TaskID::new();
         task_id.set_value(task_name.clone());
+        let timer = CallTimer::start("mesos", "master");
         match client.kill(task_id, None) {
-            Ok(response) => println!("Task Deleted {:?}", response),
-            Err(error_msg) => println!("Kill Task Mesos Problem: {}", error_msg),
+            Ok(response) => {
+                timer.finish(true);
+                info!("task killed response={:?}", response)
+            }
+            Err(error_msg) => {
+                timer.finish(false);
+                error!("can't kill task error={}", error_msg)
+            }
         }
     } else {
-        println!("Error killing task: Mesos-Client not set")
+        error!("can't kill task, mesos client not set")
     }
 }
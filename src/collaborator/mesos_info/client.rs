@@ -0,0 +1,225 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use rustc_serialize::json::Json;
+use state::Node;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use super::super::http::{HttpError, collaborator_url, http_get};
+
+const MESOS_PORT: i16 = 5050;
+const MESOS_AGENT_PORT: i16 = 5051;
+// tails are cut from the end of the file, a line is assumed to be no longer than this on average
+const BYTES_PER_LINE: i64 = 512;
+// more than that is a download, not a tail
+const MAX_TAIL_LINES: i64 = 10000;
+
+lazy_static! {
+    static ref CACHE: Mutex<AgentCache> = Mutex::new(AgentCache {
+        fetched: None,
+        fetching: false,
+        agents: vec![],
+    });
+}
+
+struct AgentCache {
+    fetched: Option<Instant>,
+    fetching: bool,
+    agents: Vec<MesosAgentInfo>,
+}
+
+// an agent as the mesos master sees it
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct MesosAgentInfo {
+    pub id: String,
+    pub hostname: String,
+    pub version: String,
+    pub active: bool,
+    pub cpus: f64,
+    pub mem: f64,
+    pub disk: f64,
+    pub ports: String,
+}
+
+// agents registered with the master, answered from cache for ttl so busy dashboards don't hammer /state,
+// on an error the last known agents are returned
+pub fn mesos_agents(master_ip: &String, ttl: Duration) -> Vec<MesosAgentInfo> {
+    {
+        let mut cache = CACHE.lock().unwrap();
+        match cache.fetched {
+            Some(fetched) if fetched.elapsed() < ttl => return cache.agents.clone(),
+            // someone else is already asking the master, the stale agents do until then
+            Some(_) if cache.fetching => return cache.agents.clone(),
+            _ => {}
+        }
+        cache.fetching = true;
+    }
+
+    // the lock isn't held while the master answers so a slow one doesn't block every lookup
+    let fetched = fetch_agents(master_ip);
+
+    let mut cache = CACHE.lock().unwrap();
+    cache.fetching = false;
+    match fetched {
+        Some(agents) => {
+            cache.fetched = Some(Instant::now());
+            cache.agents = agents.clone();
+            agents
+        }
+        None => cache.agents.clone(),
+    }
+}
+
+// by agent id once the node registered, by hostname before that
+pub fn find_mesos_agent<'a>(agents: &'a Vec<MesosAgentInfo>, node: &Node) -> Option<&'a MesosAgentInfo> {
+    agents.iter().find(|agent| {
+        (!node.slave_id.is_empty() && agent.id == node.slave_id) || agent.hostname == node.ip || agent.hostname == node.name
+    })
+}
+
+fn fetch_agents(master_ip: &String) -> Option<Vec<MesosAgentInfo>> {
+    let address = format!("{}/state", collaborator_url("mesos-info", master_ip, MESOS_PORT as i64));
+
+    let response = match http_get("mesos-info", master_ip, &address) {
+        Ok(response) => response,
+        Err(error) => {
+            error!("can't get mesos state master={} error={}", master_ip, error);
+            return None;
+        }
+    };
+
+    let state = match Json::from_str(&response.body) {
+        Ok(state) => state,
+        Err(error) => {
+            error!("can't parse mesos state master={} error={}", master_ip, error);
+            return None;
+        }
+    };
+
+    let mut result = vec![];
+    match state.find("slaves").and_then(|slaves| slaves.as_array()) {
+        Some(slaves) => {
+            for slave in slaves {
+                result.push(MesosAgentInfo {
+                    id: read_json_string(slave, "id"),
+                    hostname: read_json_string(slave, "hostname"),
+                    version: read_json_string(slave, "version"),
+                    active: slave.find("active").and_then(|value| value.as_boolean()).unwrap_or(false),
+                    cpus: read_resource(slave, "cpus"),
+                    mem: read_resource(slave, "mem"),
+                    disk: read_resource(slave, "disk"),
+                    ports: slave.find_path(&["resources", "ports"])
+                        .and_then(|value| value.as_string())
+                        .unwrap_or("")
+                        .to_string(),
+                });
+            }
+        }
+        None => {}
+    }
+
+    Some(result)
+}
+
+// the sandbox directory of the executor that runs or ran the task, running ones win over completed ones
+pub fn task_sandbox(agent_ip: &String, task_id: &String) -> Result<Option<String>, HttpError> {
+    let address = format!("{}/state", collaborator_url("mesos-agent", agent_ip, MESOS_AGENT_PORT as i64));
+    let response = try!(http_get("mesos-agent", agent_ip, &address));
+    let state = match Json::from_str(&response.body) {
+        Ok(state) => state,
+        Err(error) => return Err(HttpError::Invalid(format!("can't parse agent state: {}", error))),
+    };
+
+    for frameworks in &["frameworks", "completed_frameworks"] {
+        for framework in state.find(frameworks).and_then(|value| value.as_array()).unwrap_or(&vec![]) {
+            for executors in &["executors", "completed_executors"] {
+                for executor in framework.find(executors).and_then(|value| value.as_array()).unwrap_or(&vec![]) {
+                    if runs_task(executor, task_id) {
+                        return Ok(Some(read_json_string(executor, "directory")));
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+// the last lines of stdout or stderr in a sandbox, read through the agent's /files api
+pub fn tail_sandbox_file(agent_ip: &String, directory: &String, file: &str, lines: i64) -> Result<String, HttpError> {
+    let path = encode_path(&format!("{}/{}", directory, file));
+
+    // offset -1 answers with the size of the file and no data
+    let address = format!("{}/files/read?path={}&offset=-1",
+                          collaborator_url("mesos-agent", agent_ip, MESOS_AGENT_PORT as i64),
+                          path);
+    let size = match Json::from_str(&try!(http_get("mesos-agent", agent_ip, &address)).body) {
+        Ok(json) => json.find("offset").and_then(|value| value.as_i64()).unwrap_or(0),
+        Err(error) => return Err(HttpError::Invalid(format!("can't parse file info: {}", error))),
+    };
+
+    let lines = lines.max(0).min(MAX_TAIL_LINES);
+    let length = lines.saturating_mul(BYTES_PER_LINE).min(size);
+    let address = format!("{}/files/read?path={}&offset={}&length={}",
+                          collaborator_url("mesos-agent", agent_ip, MESOS_AGENT_PORT as i64),
+                          path,
+                          size - length,
+                          length);
+    let data = match Json::from_str(&try!(http_get("mesos-agent", agent_ip, &address)).body) {
+        Ok(json) => read_json_string(&json, "data"),
+        Err(error) => return Err(HttpError::Invalid(format!("can't parse file data: {}", error))),
+    };
+
+    let all_lines: Vec<&str> = data.lines().collect();
+    let first = all_lines.len().saturating_sub(lines as usize);
+    Ok(all_lines[first..].join("\n"))
+}
+
+// the default executor is named after its task, a custom one lists it
+fn runs_task(executor: &Json, task_id: &String) -> bool {
+    if read_json_string(executor, "id") == *task_id {
+        return true;
+    }
+    ["tasks", "queued_tasks", "completed_tasks"].iter().any(|tasks| {
+        executor.find(tasks)
+            .and_then(|value| value.as_array())
+            .map(|tasks| tasks.iter().any(|task| read_json_string(task, "id") == *task_id))
+            .unwrap_or(false)
+    })
+}
+
+fn encode_path(path: &String) -> String {
+    path.bytes()
+        .map(|byte| match byte as char {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '/' | '-' | '_' | '.' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn read_json_string(element: &Json, key: &str) -> String {
+    element.find(key).and_then(|value| value.as_string()).unwrap_or("").to_string()
+}
+
+fn read_resource(element: &Json, key: &str) -> f64 {
+    element.find_path(&["resources", key]).and_then(|value| value.as_f64()).unwrap_or(0.0)
+}
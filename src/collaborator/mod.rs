@@ -20,14 +20,35 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 // THE SOFTWARE.
 
-pub use self::calico::{configure_network, shutdown_network};
-pub use self::consul::{register_running_task, register_torc_controller, register_unmanaged_service};
-pub use self::ipmi::{shutdown_node, startup_node};
+pub use self::artifacts::collect_artifacts;
+pub use self::calico::{CALICO_NETWORK, configure_network, join_network, shutdown_network};
+pub use self::consul::{TtlCheck, alias_id, deregister_service, deregister_task, deregister_torc_controller, is_service_registered,
+                       list_task_services, refresh_controller_ttl, refresh_task_ttl, register_running_task,
+                       register_torc_controller, register_unmanaged_service};
+pub use self::consul::{acquire_lock, create_key, create_session, delete_key, destroy_session, lock_holder, read_key,
+                       read_keys, release_lock, renew_session};
+pub use self::federation::{delegate_task, delegated_task_state, delete_delegated_task, kill_delegated_task};
+pub use self::hooks::call_restart_hook;
+pub use self::http::{HttpError, HttpPolicy, http_answer, set_http_policy, verifying_client};
+pub use self::kafka::start_event_export;
+pub use self::ipmi::{IpmiConfig, power_reading, power_status, shutdown_node, startup_node};
 pub use self::mesos::{kill_task, set_mesos_client};
-pub use self::network_agent::{add_route, delete_route, reset_fib};
+pub use self::mesos_info::{MesosAgentInfo, find_mesos_agent, mesos_agents, tail_sandbox_file, task_sandbox};
+pub use self::network_agent::{FibEntry, NetworkAgent, NetworkAgentError, add_route, delete_route, new_network_agent, read_fib,
+                              reset_fib};
+pub use self::node_agent::{agent_token, run_agent_command, set_agent_token, tail_container_logs};
+pub use self::registry::{pinned_image, resolve_image_digest};
 
+mod artifacts;
 mod consul;
+mod federation;
+mod hooks;
+mod http;
 mod calico;
 mod mesos;
+mod mesos_info;
 mod network_agent;
+mod node_agent;
 mod ipmi;
+mod kafka;
+mod registry;
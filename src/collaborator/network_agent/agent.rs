@@ -0,0 +1,186 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use std::fmt;
+use std::net::TcpStream;
+use std::panic;
+
+// whatever programs the routes towards the tasks, route_to arrives as a /32 prefix
+pub trait NetworkAgent: Send + Sync {
+    fn agent_type(&self) -> &str;
+    fn connection(&self) -> &String;
+    fn reset_fib(&self) -> Result<(), NetworkAgentError>;
+    fn add_route(&self, route_to: &String, route_via: &String) -> Result<(), NetworkAgentError>;
+    fn delete_route(&self, route_to: &String) -> Result<(), NetworkAgentError>;
+    fn read_fib(&self) -> Result<Vec<FibEntry>, NetworkAgentError>;
+}
+
+// a route as the backend has it programmed, route_to with its prefix length
+#[derive(Clone, Debug, PartialEq, RustcEncodable)]
+pub struct FibEntry {
+    pub route_to: String,
+    pub route_via: String,
+}
+
+// what every backend maps its failures into: unreachable and failed calls are worth another try,
+// an existing or missing route means there is nothing left to do, a rejected one won't ever go through,
+// unsupported is a backend without a way to do what was asked
+#[derive(Clone, Debug, PartialEq)]
+pub enum NetworkAgentError {
+    Unreachable(String),
+    AlreadyExists,
+    NotFound,
+    Rejected(String),
+    Failed(String),
+    Unsupported,
+}
+
+impl NetworkAgentError {
+    pub fn is_retryable(&self) -> bool {
+        match *self {
+            NetworkAgentError::Unreachable(_) |
+            NetworkAgentError::Failed(_) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for NetworkAgentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NetworkAgentError::Unreachable(ref reason) => write!(f, "unreachable: {}", reason),
+            NetworkAgentError::AlreadyExists => write!(f, "route already exists"),
+            NetworkAgentError::NotFound => write!(f, "route not found"),
+            NetworkAgentError::Rejected(ref reason) => write!(f, "rejected: {}", reason),
+            NetworkAgentError::Failed(ref reason) => write!(f, "failed: {}", reason),
+            NetworkAgentError::Unsupported => write!(f, "not supported by the network agent"),
+        }
+    }
+}
+
+// for the client libraries that don't report failures: an agent that can't be reached is told apart from
+// a call that panics, the panic message isn't anything to rely on so every panic is just a failed call
+#[cfg_attr(not(any(feature = "fboss", feature = "snaproute")), allow(dead_code))]
+pub fn call_client<F: FnOnce() + panic::UnwindSafe>(agent_type: &str,
+                                                   connection: &String,
+                                                   operation: F)
+                                                   -> Result<(), NetworkAgentError> {
+    if let Err(error) = TcpStream::connect(connection.as_str()) {
+        error!("{} not reachable connection={} error={}", agent_type, connection, error);
+        return Err(NetworkAgentError::Unreachable(error.to_string()));
+    }
+    panic::catch_unwind(operation).map_err(|_| NetworkAgentError::Failed(format!("{} client panicked", agent_type)))
+}
+
+// for clusters without a network agent, nothing to do counts as done
+pub struct NoopAgent {
+    agent_type: String,
+    connection: String,
+}
+
+impl NoopAgent {
+    pub fn new(agent_type: &String, connection: &String) -> NoopAgent {
+        NoopAgent {
+            agent_type: agent_type.clone(),
+            connection: connection.clone(),
+        }
+    }
+}
+
+impl NetworkAgent for NoopAgent {
+    fn agent_type(&self) -> &str {
+        &self.agent_type
+    }
+
+    fn connection(&self) -> &String {
+        &self.connection
+    }
+
+    fn reset_fib(&self) -> Result<(), NetworkAgentError> {
+        info!("no fib to reset agent_type={}", self.agent_type);
+        Ok(())
+    }
+
+    fn add_route(&self, _route_to: &String, _route_via: &String) -> Result<(), NetworkAgentError> {
+        Ok(())
+    }
+
+    fn delete_route(&self, _route_to: &String) -> Result<(), NetworkAgentError> {
+        Ok(())
+    }
+
+    // nothing gets programmed, an empty fib would have every route show up as missing
+    fn read_fib(&self) -> Result<Vec<FibEntry>, NetworkAgentError> {
+        Err(NetworkAgentError::Unsupported)
+    }
+}
+
+// for an agent type the build left out, every call gets rejected so the routes show up as failed
+#[cfg_attr(all(feature = "fboss", feature = "snaproute"), allow(dead_code))]
+pub struct MissingAgent {
+    agent_type: String,
+    connection: String,
+    reason: String,
+}
+
+impl MissingAgent {
+    #[cfg_attr(all(feature = "fboss", feature = "snaproute"), allow(dead_code))]
+    pub fn new(agent_type: &String, connection: &String) -> MissingAgent {
+        let reason = format!("{} not compiled in, build with --features {}", agent_type, agent_type);
+        error!("network-agent unavailable agent_type={} reason={}", agent_type, reason);
+        MissingAgent {
+            agent_type: agent_type.clone(),
+            connection: connection.clone(),
+            reason: reason,
+        }
+    }
+
+    fn rejected(&self) -> NetworkAgentError {
+        NetworkAgentError::Rejected(self.reason.clone())
+    }
+}
+
+impl NetworkAgent for MissingAgent {
+    fn agent_type(&self) -> &str {
+        &self.agent_type
+    }
+
+    fn connection(&self) -> &String {
+        &self.connection
+    }
+
+    fn reset_fib(&self) -> Result<(), NetworkAgentError> {
+        Err(self.rejected())
+    }
+
+    fn add_route(&self, _route_to: &String, _route_via: &String) -> Result<(), NetworkAgentError> {
+        Err(self.rejected())
+    }
+
+    fn delete_route(&self, _route_to: &String) -> Result<(), NetworkAgentError> {
+        Err(self.rejected())
+    }
+
+    fn read_fib(&self) -> Result<Vec<FibEntry>, NetworkAgentError> {
+        Err(self.rejected())
+    }
+}
@@ -20,53 +20,69 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 // THE SOFTWARE.
 
-use super::super::fboss;
-use super::super::snaproute;
+use std::sync::Arc;
+use super::super::agent::{FibEntry, MissingAgent, NetworkAgent, NetworkAgentError, NoopAgent};
+#[cfg(feature = "fboss")]
+use super::super::fboss::FbossAgent;
+use super::super::linux_netlink::NetlinkAgent;
+#[cfg(feature = "snaproute")]
+use super::super::snaproute::SnaprouteAgent;
 
-pub fn reset_fib(agent_type: &String, connection: &String) {
-    println!("reset_fib [{}] [{}]", agent_type, connection);
+// the only place that knows the agent types, picked once when the state manager comes up.
+// a type the build left out gets an agent that rejects every call
+pub fn new_network_agent(agent_type: &String, connection: &String) -> Arc<NetworkAgent> {
     match agent_type.as_str() {
-        "fboss" => fboss::reset_fib(&connection),
-        "snaproute" => snaproute::reset_fib(&connection),
-        "undefined" => println!("network-agent undefined"),
-        _ => println!("!! network-agent type {} unknown!!", agent_type),
+        #[cfg(feature = "fboss")]
+        "fboss" => Arc::new(FbossAgent::new(connection)),
+        #[cfg(not(feature = "fboss"))]
+        "fboss" => Arc::new(MissingAgent::new(agent_type, connection)),
+        #[cfg(feature = "snaproute")]
+        "snaproute" => Arc::new(SnaprouteAgent::new(connection)),
+        #[cfg(not(feature = "snaproute"))]
+        "snaproute" => Arc::new(MissingAgent::new(agent_type, connection)),
+        "linux-netlink" => Arc::new(NetlinkAgent::new(connection)),
+        "undefined" => Arc::new(NoopAgent::new(agent_type, connection)),
+        _ => {
+            error!("unknown network-agent type agent_type={}", agent_type);
+            Arc::new(NoopAgent::new(agent_type, connection))
+        }
     }
 }
 
-pub fn add_route(agent_type: &String, connection: &String, route_to: &String, route_via: &String) {
-    println!("add route {}, {}, {}, {}",
-             agent_type,
-             connection,
-             route_to,
-             route_via);
+pub fn read_fib(agent: &NetworkAgent) -> Result<Vec<FibEntry>, NetworkAgentError> {
+    debug!("read fib agent_type={} connection={}", agent.agent_type(), agent.connection());
+    agent.read_fib()
+}
+
+pub fn reset_fib(agent: &NetworkAgent) -> Result<(), NetworkAgentError> {
+    info!("reset fib agent_type={} connection={}", agent.agent_type(), agent.connection());
+    agent.reset_fib()
+}
+
+// nothing to do counts as done
+pub fn add_route(agent: &NetworkAgent, route_to: &String, route_via: &String) -> Result<(), NetworkAgentError> {
+    info!("add route agent_type={} connection={} route_to={} route_via={}",
+          agent.agent_type(),
+          agent.connection(),
+          route_to,
+          route_via);
 
     if route_via.is_empty() {
-        return;
+        return Ok(());
     }
-    if connection.starts_with(route_to) {
-        return;
+    if agent.connection().starts_with(route_to) {
+        return Ok(());
     }
 
-    let route_to = format!("{}/32", route_to.clone());
-
-    match agent_type.as_str() {
-        "fboss" => fboss::add_route(&connection, &route_to, &route_via),
-        "snaproute" => snaproute::add_route(&connection, &route_to, &route_via),
-        _ => println!("!! network-agent type {} unknown!!", agent_type),
-    }
+    agent.add_route(&format!("{}/32", route_to), route_via)
 }
 
-pub fn delete_route(agent_type: &String, connection: &String, route_to: &String) {
-    println!("delete route {}, {}, {}", agent_type, connection, route_to);
+pub fn delete_route(agent: &NetworkAgent, route_to: &String) -> Result<(), NetworkAgentError> {
+    info!("delete route agent_type={} connection={} route_to={}", agent.agent_type(), agent.connection(), route_to);
 
     if route_to.is_empty() {
-        return;
+        return Ok(());
     }
-    let route_to = format!("{}/32", route_to.clone());
 
-    match agent_type.as_str() {
-        "fboss" => fboss::delete_route(&connection, &route_to),
-        "snaproute" => snaproute::delete_route(&connection, &route_to),
-        _ => println!("!! network-agent type {} unknown!!", agent_type),
-    }
+    agent.delete_route(&format!("{}/32", route_to))
 }
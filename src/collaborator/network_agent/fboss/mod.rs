@@ -20,6 +20,6 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 // THE SOFTWARE.
 
-pub use self::client::{add_route, delete_route, reset_fib};
+pub use self::client::FbossAgent;
 
 mod client;
@@ -0,0 +1,147 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use std::process::Command;
+use super::super::{FibEntry, NetworkAgent, NetworkAgentError};
+use utils::CallTimer;
+
+// routes get their own protocol number so a reset only flushes what the controller put there
+const ROUTE_PROTOCOL: &'static str = "186";
+
+// programs the kernel routing table of the host the controller runs on through iproute2,
+// for clusters where that host is the router, the connection names the table, e.g. main
+pub struct NetlinkAgent {
+    table: String,
+}
+
+impl NetlinkAgent {
+    pub fn new(connection: &String) -> NetlinkAgent {
+        let table = match connection.as_str() {
+            "" | "undefined" => "main".to_string(),
+            _ => connection.clone(),
+        };
+        NetlinkAgent { table: table }
+    }
+
+    fn ip_route(&self, arguments: &[&str]) -> Result<(), NetworkAgentError> {
+        let timer = CallTimer::start("linux-netlink", &self.table);
+        let result = Command::new("ip")
+            .arg("route")
+            .args(arguments)
+            .arg("table")
+            .arg(&self.table)
+            .arg("proto")
+            .arg(ROUTE_PROTOCOL)
+            .output();
+
+        let result = match result {
+            Ok(output) => {
+                match output.status.success() {
+                    true => Ok(()),
+                    false => Err(read_error(String::from_utf8_lossy(&output.stderr).trim())),
+                }
+            }
+            Err(error) => Err(NetworkAgentError::Failed(format!("can't run ip: {}", error))),
+        };
+        timer.finish(result.is_ok());
+        if let Err(ref error) = result {
+            error!("ip route failed arguments={:?} error={}", arguments, error);
+        }
+        result
+    }
+}
+
+impl NetworkAgent for NetlinkAgent {
+    fn agent_type(&self) -> &str {
+        "linux-netlink"
+    }
+
+    fn connection(&self) -> &String {
+        &self.table
+    }
+
+    fn reset_fib(&self) -> Result<(), NetworkAgentError> {
+        self.ip_route(&["flush"])
+    }
+
+    // replace instead of add so a task that moved to another node just gets its next hop updated
+    fn add_route(&self, route_to: &String, route_via: &String) -> Result<(), NetworkAgentError> {
+        self.ip_route(&["replace", route_to.as_str(), "via", route_via.as_str()])
+    }
+
+    fn delete_route(&self, route_to: &String) -> Result<(), NetworkAgentError> {
+        self.ip_route(&["del", route_to.as_str()])
+    }
+
+    // only the routes with our protocol number, e.g. 192.168.1.5 via 10.250.3.21 dev eth0
+    fn read_fib(&self) -> Result<Vec<FibEntry>, NetworkAgentError> {
+        let timer = CallTimer::start("linux-netlink", &self.table);
+        let result = Command::new("ip")
+            .args(&["route", "show", "table", self.table.as_str(), "proto", ROUTE_PROTOCOL])
+            .output();
+
+        let result = match result {
+            Ok(output) => {
+                match output.status.success() {
+                    true => Ok(String::from_utf8_lossy(&output.stdout).lines().filter_map(read_fib_entry).collect()),
+                    false => Err(read_error(String::from_utf8_lossy(&output.stderr).trim())),
+                }
+            }
+            Err(error) => Err(NetworkAgentError::Failed(format!("can't run ip: {}", error))),
+        };
+        timer.finish(result.is_ok());
+        result
+    }
+}
+
+// iproute2 leaves off the prefix length of host routes
+fn read_fib_entry(line: &str) -> Option<FibEntry> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let route_via = match words.iter().position(|word| *word == "via") {
+        Some(index) if index + 1 < words.len() => words[index + 1].to_string(),
+        _ => return None,
+    };
+    let route_to = match words.first() {
+        Some(route_to) if route_to.contains('/') => route_to.to_string(),
+        Some(route_to) => format!("{}/32", route_to),
+        None => return None,
+    };
+
+    Some(FibEntry {
+        route_to: route_to,
+        route_via: route_via,
+    })
+}
+
+// the RTNETLINK answers iproute2 passes on, anything unknown is worth another try
+fn read_error(stderr: &str) -> NetworkAgentError {
+    if stderr.contains("File exists") {
+        NetworkAgentError::AlreadyExists
+    } else if stderr.contains("No such process") {
+        NetworkAgentError::NotFound
+    } else if stderr.contains("Operation not permitted") || stderr.contains("Invalid argument") ||
+              stderr.contains("Nexthop has invalid gateway") || stderr.contains("Network is unreachable") {
+        NetworkAgentError::Rejected(stderr.to_string())
+    } else {
+        NetworkAgentError::Failed(stderr.to_string())
+    }
+}
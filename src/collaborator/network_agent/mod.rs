@@ -20,8 +20,13 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 // THE SOFTWARE.
 
-pub use self::dispatch::client::{add_route, delete_route, reset_fib};
+pub use self::agent::{FibEntry, NetworkAgent, NetworkAgentError};
+pub use self::dispatch::client::{add_route, delete_route, new_network_agent, read_fib, reset_fib};
 
+mod agent;
 mod dispatch;
+#[cfg(feature = "fboss")]
 mod fboss;
+mod linux_netlink;
+#[cfg(feature = "snaproute")]
 mod snaproute;
@@ -20,16 +20,56 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 // THE SOFTWARE.
 
+use super::super::{FibEntry, NetworkAgent, NetworkAgentError};
+use super::super::agent::call_client;
 use torc_snaproute_client::api;
+use utils::CallTimer;
 
-pub fn reset_fib(connection: &String) {
-    api::reset_routes(&connection);
+pub struct SnaprouteAgent {
+    connection: String,
 }
 
-pub fn add_route(connection: &String, route_to: &String, route_via: &String) {
-    api::add_route(&connection, &route_to, &route_via);
+impl SnaprouteAgent {
+    pub fn new(connection: &String) -> SnaprouteAgent {
+        SnaprouteAgent { connection: connection.clone() }
+    }
 }
 
-pub fn delete_route(connection: &String, route_to: &String) {
-    api::delete_route(&connection, &route_to);
+impl NetworkAgent for SnaprouteAgent {
+    fn agent_type(&self) -> &str {
+        "snaproute"
+    }
+
+    fn connection(&self) -> &String {
+        &self.connection
+    }
+
+    fn reset_fib(&self) -> Result<(), NetworkAgentError> {
+        let connection = &self.connection;
+        let timer = CallTimer::start("snaproute", connection);
+        let result = call_client("snaproute", connection, || api::reset_routes(&connection));
+        timer.finish(result.is_ok());
+        result
+    }
+
+    fn add_route(&self, route_to: &String, route_via: &String) -> Result<(), NetworkAgentError> {
+        let connection = &self.connection;
+        let timer = CallTimer::start("snaproute", connection);
+        let result = call_client("snaproute", connection, || api::add_route(&connection, &route_to, &route_via));
+        timer.finish(result.is_ok());
+        result
+    }
+
+    fn delete_route(&self, route_to: &String) -> Result<(), NetworkAgentError> {
+        let connection = &self.connection;
+        let timer = CallTimer::start("snaproute", connection);
+        let result = call_client("snaproute", connection, || api::delete_route(&connection, &route_to));
+        timer.finish(result.is_ok());
+        result
+    }
+
+    // the client library only programs routes, it has no way to read them back
+    fn read_fib(&self) -> Result<Vec<FibEntry>, NetworkAgentError> {
+        Err(NetworkAgentError::Unsupported)
+    }
 }
@@ -0,0 +1,124 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use agent::{AGENT_PORT, AGENT_TOKEN_HEADER, AgentCommand, CommandResult, SyncCommand};
+use hyper::header::Headers;
+use hyper::method::Method;
+use rustc_serialize::json;
+use std::env;
+use std::sync::RwLock;
+use super::super::http::{HttpError, collaborator_url, http_answer, http_send};
+use utils::read_string;
+use yaml_rust::Yaml;
+
+lazy_static! {
+    static ref SYNC_TOKEN: RwLock<String> = RwLock::new("".to_string());
+}
+
+// agent.token, or the variable named by agent.token_env
+pub fn agent_token(config: &Yaml) -> String {
+    let token_env = read_string(&config["agent"], "token_env".to_string());
+    match token_env.is_empty() {
+        true => read_string(&config["agent"], "token".to_string()),
+        false => env::var(&token_env).unwrap_or("".to_string()),
+    }
+}
+
+// the collaborators running shell commands through /sync don't get the config handed in,
+// the token is set once when the state manager starts
+pub fn set_agent_token(token: String) {
+    *SYNC_TOKEN.write().unwrap() = token;
+}
+
+// runs cmd through a shell on the node, the answer is the command's output
+pub fn run_sync_command(collaborator: &str, node_ip: &String, cmd: &String, env: &String) -> Result<String, HttpError> {
+    let address = format!("{}/sync", collaborator_url("node-agent", node_ip, AGENT_PORT as i64));
+    let command = SyncCommand {
+        cmd: cmd.clone(),
+        env: env.clone(),
+    };
+    let headers = token_headers(&SYNC_TOKEN.read().unwrap());
+
+    match http_send(Method::Post, collaborator, node_ip, &address, &headers, &json::encode(&command).unwrap()) {
+        Ok(response) => {
+            debug!("sync command done ip={} body={:?}", node_ip, response.body);
+            Ok(response.body)
+        }
+        Err(error) => {
+            error!("can't run sync command ip={} cmd={} error={}", node_ip, cmd, error);
+            Err(error)
+        }
+    }
+}
+
+pub fn run_agent_command(node_ip: &String, token: &String, command: &AgentCommand) -> Option<CommandResult> {
+    let address = format!("{}/command", collaborator_url("node-agent", node_ip, AGENT_PORT as i64));
+
+    // a command that failed on the node still answers with its result
+    let response = match http_answer(Method::Post,
+                                     "node-agent",
+                                     node_ip,
+                                     &address,
+                                     &token_headers(token),
+                                     &json::encode(command).unwrap()) {
+        Ok(response) => response,
+        Err(error) => {
+            error!("can't reach node agent ip={} error={}", node_ip, error);
+            return None;
+        }
+    };
+
+    match json::decode::<CommandResult>(&response.body) {
+        Ok(result) => Some(result),
+        Err(_) => {
+            warn!("node agent rejected command ip={} kind={} status={} body={}",
+                  node_ip,
+                  command.kind,
+                  response.status,
+                  response.body);
+            None
+        }
+    }
+}
+
+pub fn tail_container_logs(node_ip: &String, token: &String, container: &String, lines: i64) -> Option<String> {
+    let address = format!("{}/logs?container={}&lines={}",
+                          collaborator_url("node-agent", node_ip, AGENT_PORT as i64),
+                          container,
+                          lines);
+
+    match http_send(Method::Get, "node-agent", node_ip, &address, &token_headers(token), "") {
+        Ok(response) => Some(response.body),
+        Err(error) => {
+            warn!("can't get logs container={} ip={} error={}", container, node_ip, error);
+            None
+        }
+    }
+}
+
+fn token_headers(token: &String) -> Headers {
+    let mut headers = Headers::new();
+    if !token.is_empty() {
+        headers.set_raw(AGENT_TOKEN_HEADER, vec![token.as_bytes().to_vec()]);
+    }
+    headers
+}
@@ -0,0 +1,220 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use hyper::header::Headers;
+use hyper::method::Method;
+use rustc_serialize::json::Json;
+use std::collections::BTreeMap;
+use super::super::http::{HttpResponse, http_answer, http_get};
+
+const DOCKER_HUB: &'static str = "registry-1.docker.io";
+const MANIFEST_V2: &'static str = "application/vnd.docker.distribution.manifest.v2+json";
+
+struct ImageReference {
+    registry: String,
+    repository: String,
+    tag: String,
+    digest: String,
+}
+
+// resolves the tag of an image to its manifest digest, images already given by digest are returned as is
+pub fn resolve_image_digest(image: &String, scheme: &String) -> Option<String> {
+    let reference = parse_image(image);
+    if !reference.digest.is_empty() {
+        return Some(reference.digest);
+    }
+
+    let address = format!("{}://{}/v2/{}/manifests/{}",
+                          scheme,
+                          reference.registry,
+                          reference.repository,
+                          reference.tag);
+
+    let mut headers = Headers::new();
+    headers.set_raw("Accept", vec![MANIFEST_V2.as_bytes().to_vec()]);
+
+    let mut response = match head_manifest(image, &reference, &address, &headers) {
+        Some(response) => response,
+        None => return None,
+    };
+
+    // docker hub and most private registries want a bearer token even for public images
+    if response.status == 401 {
+        let token = match bearer_token(image, &reference, &response) {
+            Some(token) => token,
+            None => return None,
+        };
+        headers.set_raw("Authorization", vec![format!("Bearer {}", token).into_bytes()]);
+        response = match head_manifest(image, &reference, &address, &headers) {
+            Some(response) => response,
+            None => return None,
+        };
+    }
+
+    if response.status < 200 || response.status >= 300 {
+        warn!("can't resolve digest image={} status={}", image, response.status);
+        return None;
+    }
+
+    match response.headers.get_raw("Docker-Content-Digest") {
+        Some(values) if values.len() > 0 => Some(String::from_utf8_lossy(&values[0]).trim().to_string()),
+        _ => {
+            warn!("registry returned no digest image={}", image);
+            None
+        }
+    }
+}
+
+fn head_manifest(image: &String, reference: &ImageReference, address: &String, headers: &Headers) -> Option<HttpResponse> {
+    match http_answer(Method::Head, "registry", &reference.registry, address, headers, "") {
+        Ok(response) => Some(response),
+        Err(error) => {
+            warn!("can't resolve digest image={} error={}", image, error);
+            None
+        }
+    }
+}
+
+// the challenge of the 401 names the token service, e.g.
+// Bearer realm="https://auth.docker.io/token",service="registry.docker.io",scope="repository:library/ubuntu:pull"
+fn bearer_token(image: &String, reference: &ImageReference, response: &HttpResponse) -> Option<String> {
+    let challenge = match response.headers.get_raw("Www-Authenticate") {
+        Some(values) if values.len() > 0 => String::from_utf8_lossy(&values[0]).trim().to_string(),
+        _ => "".to_string(),
+    };
+    if !challenge.starts_with("Bearer ") {
+        warn!("registry wants credentials image={} challenge={}", image, challenge);
+        return None;
+    }
+
+    let parameters = parse_challenge(&challenge["Bearer ".len()..]);
+    let realm = match parameters.get("realm") {
+        Some(realm) => realm,
+        None => {
+            warn!("registry challenge without realm image={} challenge={}", image, challenge);
+            return None;
+        }
+    };
+    let scope = match parameters.get("scope") {
+        Some(scope) => scope.clone(),
+        None => format!("repository:{}:pull", reference.repository),
+    };
+    let mut address = format!("{}?scope={}", realm, encode_query(&scope));
+    if let Some(service) = parameters.get("service") {
+        address.push_str(&format!("&service={}", encode_query(service)));
+    }
+
+    let response = match http_get("registry", &reference.registry, &address) {
+        Ok(response) => response,
+        Err(error) => {
+            warn!("can't get registry token image={} error={}", image, error);
+            return None;
+        }
+    };
+    let token = Json::from_str(&response.body).ok().and_then(|json| {
+        json.find("token")
+            .or_else(|| json.find("access_token"))
+            .and_then(|token| token.as_string())
+            .map(|token| token.to_string())
+    });
+    if token.is_none() {
+        warn!("registry token service returned no token image={}", image);
+    }
+    token
+}
+
+// key="value" pairs separated by commas, a quoted value may hold commas itself
+fn parse_challenge(parameters: &str) -> BTreeMap<String, String> {
+    let mut result = BTreeMap::new();
+    let mut chars = parameters.chars().peekable();
+
+    loop {
+        let key: String = chars.by_ref().take_while(|c| *c != '=').collect();
+        if key.trim().is_empty() {
+            return result;
+        }
+
+        let mut value = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            value.extend(chars.by_ref().take_while(|c| *c != '"'));
+            chars.by_ref().take_while(|c| *c != ',').count();
+        } else {
+            value.extend(chars.by_ref().take_while(|c| *c != ','));
+        }
+        result.insert(key.trim().to_string(), value.trim().to_string());
+    }
+}
+
+fn encode_query(value: &String) -> String {
+    value.bytes()
+        .map(|byte| match byte as char {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' | '~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+// the image name to hand to docker so that every start runs the identical image
+pub fn pinned_image(image: &String, digest: &String) -> String {
+    if digest.is_empty() || image.contains('@') {
+        return image.clone();
+    }
+
+    let name = match image.rfind(':') {
+        Some(position) if !image[position..].contains('/') => &image[..position],
+        _ => &image[..],
+    };
+
+    format!("{}@{}", name, digest)
+}
+
+fn parse_image(image: &String) -> ImageReference {
+    let (name, digest) = match image.find('@') {
+        Some(position) => (&image[..position], image[position + 1..].to_string()),
+        None => (&image[..], "".to_string()),
+    };
+
+    let (name, tag) = match name.rfind(':') {
+        Some(position) if !name[position..].contains('/') => (&name[..position], name[position + 1..].to_string()),
+        _ => (name, "latest".to_string()),
+    };
+
+    let (registry, repository) = match name.find('/') {
+        Some(position) => {
+            let host = &name[..position];
+            if host.contains('.') || host.contains(':') || host == "localhost" {
+                (host.to_string(), name[position + 1..].to_string())
+            } else {
+                (DOCKER_HUB.to_string(), name.to_string())
+            }
+        }
+        None => (DOCKER_HUB.to_string(), format!("library/{}", name)),
+    };
+
+    ImageReference {
+        registry: registry,
+        repository: repository,
+        tag: tag,
+        digest: digest,
+    }
+}
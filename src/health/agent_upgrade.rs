@@ -0,0 +1,198 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use agent::{AgentCommand, UPGRADE_TRIAL_IN_SECONDS};
+use chrono::UTC;
+use collaborator::{agent_token, run_agent_command};
+use state::{AgentUpgradeReport, AgentUpgradeStep, StateManager};
+use std::cmp;
+use std::thread;
+use std::time::Duration;
+use utils::read_int;
+
+// nodes that never sent a heartbeat don't run an agent and are left out,
+// the ones already on the requested version are skipped
+pub fn plan_agent_upgrade(state_manager: &StateManager, version: &str, url: &str) -> AgentUpgradeReport {
+    let config = state_manager.get_yaml();
+    let batch_size = cmp::max(1, read_int(&config["agent"]["upgrade"], "batch_size".to_string(), 2)) as usize;
+
+    let mut nodes = state_manager.request_list_nodes();
+    nodes.retain(|node| !node.agent_version.is_empty());
+    nodes.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut report = AgentUpgradeReport::new(version, url, batch_size);
+    for (index, node) in nodes.iter().enumerate() {
+        let status = match node.agent_version == version {
+            true => "skipped",
+            false => "pending",
+        };
+        report.steps.push(AgentUpgradeStep {
+            node: node.name.clone(),
+            batch: index / batch_size,
+            previous_version: node.agent_version.clone(),
+            status: status.to_string(),
+            message: "".to_string(),
+        });
+    }
+
+    report
+}
+
+// a batch is done once all of its agents heartbeat with the new version,
+// if one of them doesn't the whole batch is rolled back and the rollout stops
+pub fn run_agent_upgrade(state_manager: &StateManager, report: AgentUpgradeReport) -> AgentUpgradeReport {
+    let config = state_manager.get_yaml();
+    let timeout = read_int(&config["agent"]["upgrade"], "timeout_in_seconds".to_string(), 60);
+    let token = agent_token(&config);
+    let batch_count = report.steps.iter().map(|step| step.batch + 1).max().unwrap_or(0);
+
+    let mut report = report;
+    info!("upgrading node agents version={} batches={}", report.version, batch_count);
+
+    for batch in 0..batch_count {
+        if !report.ok {
+            break;
+        }
+
+        let indexes: Vec<usize> = (0..report.steps.len())
+            .filter(|&index| report.steps[index].batch == batch && report.steps[index].status == "pending")
+            .collect();
+        let started = UTC::now().timestamp();
+
+        let upgrade = AgentCommand {
+            kind: "upgrade_agent".to_string(),
+            args: vec![report.url.clone()],
+        };
+        for &index in &indexes {
+            let (status, message) = send_agent_command(state_manager, &report.steps[index].node, &token, &upgrade);
+            report.steps[index].status = status.to_string();
+            report.steps[index].message = message;
+        }
+        state_manager.send_set_agent_upgrade_report(report.clone());
+
+        // only agents that took the upgrade have a previous binary to go back to
+        let accepted: Vec<usize> = indexes.iter()
+            .cloned()
+            .filter(|&index| report.steps[index].status == "upgrading")
+            .collect();
+        for &index in &accepted {
+            match wait_for_agent_version(state_manager, &report.steps[index].node, &report.version, started, timeout) {
+                true => report.steps[index].status = "upgraded".to_string(),
+                false => {
+                    report.steps[index].status = "failed".to_string();
+                    report.steps[index].message = "no heartbeat with the new version".to_string();
+                }
+            }
+        }
+
+        if indexes.iter().any(|&index| report.steps[index].status == "failed") {
+            report.ok = false;
+            state_manager.send_set_agent_upgrade_report(report.clone());
+            rollback_batch(state_manager, &mut report, &accepted, &token, timeout);
+        }
+        state_manager.send_set_agent_upgrade_report(report.clone());
+    }
+
+    for step in report.steps.iter_mut().filter(|step| step.status == "pending") {
+        step.status = "skipped".to_string();
+        step.message = "rollout stopped".to_string();
+    }
+    report.finished = true;
+    state_manager.send_set_agent_upgrade_report(report.clone());
+    info!("node agent upgrade finished version={} ok={}", report.version, report.ok);
+
+    report
+}
+
+// agents that confirmed the new version with a heartbeat are told to go back. the ones that never did
+// may not be listening at all, they go back to the previous binary on their own once their trial runs out
+fn rollback_batch(state_manager: &StateManager,
+                  report: &mut AgentUpgradeReport,
+                  indexes: &Vec<usize>,
+                  token: &String,
+                  timeout: i64) {
+    let rollback = AgentCommand {
+        kind: "rollback_agent".to_string(),
+        args: vec![],
+    };
+    let started = UTC::now().timestamp();
+
+    for &index in indexes {
+        let node = report.steps[index].node.clone();
+        let previous_version = report.steps[index].previous_version.clone();
+        let (status, message) = match report.steps[index].status == "upgraded" {
+            true => send_agent_command(state_manager, &node, token, &rollback),
+            false => ("upgrading", "rolled back by the agent after its trial".to_string()),
+        };
+        let wait = match report.steps[index].status == "upgraded" {
+            true => timeout,
+            false => timeout + UPGRADE_TRIAL_IN_SECONDS,
+        };
+
+        let rolled_back = status == "upgrading" && wait_for_agent_version(state_manager, &node, &previous_version, started, wait);
+        match rolled_back {
+            true => report.steps[index].status = "rolled_back".to_string(),
+            false => {
+                report.steps[index].status = "failed".to_string();
+                report.steps[index].message = format!("rollback failed: {}", message);
+            }
+        }
+        warn!("node agent rollback node={} ok={}", node, rolled_back);
+    }
+}
+
+// returns upgrading when the agent accepted the command, failed otherwise
+fn send_agent_command(state_manager: &StateManager,
+                      node_name: &String,
+                      token: &String,
+                      command: &AgentCommand)
+                      -> (&'static str, String) {
+    let node = match state_manager.request_node(node_name.clone()) {
+        Some(node) => node,
+        None => return ("failed", "unknown node".to_string()),
+    };
+
+    match run_agent_command(&node.ip, token, command) {
+        Some(ref result) if result.success => ("upgrading", result.output.clone()),
+        Some(result) => ("failed", result.output),
+        None => ("failed", "node agent not reachable".to_string()),
+    }
+}
+
+fn wait_for_agent_version(state_manager: &StateManager, node_name: &String, version: &String, since: i64, timeout: i64) -> bool {
+    let deadline = UTC::now().timestamp() + timeout;
+
+    loop {
+        match state_manager.request_node(node_name.clone()) {
+            Some(node) => {
+                if node.agent_version == *version && node.last_heartbeat >= since {
+                    return true;
+                }
+            }
+            None => return false,
+        }
+        if UTC::now().timestamp() >= deadline {
+            return false;
+        }
+        thread::sleep(Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,134 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use chrono::UTC;
+use state::{StateManager, TaskState};
+use std::thread;
+use std::time::Duration;
+use super::run_health_checker::read_system_services;
+use utils::{Task, read_int, start_task};
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct BootstrapStep {
+    pub name: String,
+    pub state: TaskState,
+    pub ok: bool,
+    pub duration_in_seconds: i64,
+}
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct BootstrapReport {
+    pub timestamp: i64,
+    pub ok: bool,
+    pub cluster_open: bool,
+    pub steps: Vec<BootstrapStep>,
+}
+
+// starts the system services one after the other in dependency order and only opens
+// the cluster for regular workloads once every one of them made it to running
+pub fn run_bootstrap(state_manager: &StateManager) -> BootstrapReport {
+    let config = state_manager.get_yaml();
+    let timeout = read_int(&config["bootstrap"], "timeout_in_seconds".to_string(), 120);
+
+    let mut report = BootstrapReport {
+        timestamp: UTC::now().timestamp(),
+        ok: true,
+        cluster_open: false,
+        steps: vec![],
+    };
+
+    info!("bootstrapping");
+    state_manager.send_set_teardown(false);
+    for task in order_by_dependency(read_system_services(state_manager)) {
+        let started = UTC::now().timestamp();
+        if state_manager.request_task_state(task.name.clone()) == TaskState::NotRunning {
+            match start_task(state_manager, &task, true) {
+                Ok(_) => {}
+                Err(reason) => warn!("bootstrap can't start task={} reason={}", task.name, reason),
+            }
+        }
+
+        let state = wait_for_running(state_manager, &task.name, timeout);
+        let ok = state == TaskState::Running;
+        info!("bootstrap step task={} state={:?}", task.name, state);
+
+        report.steps.push(BootstrapStep {
+            name: task.name.clone(),
+            state: state,
+            ok: ok,
+            duration_in_seconds: UTC::now().timestamp() - started,
+        });
+
+        if !ok {
+            report.ok = false;
+            break;
+        }
+    }
+
+    if report.ok {
+        state_manager.send_set_cluster_open(true);
+    }
+    report.cluster_open = state_manager.request_is_cluster_open();
+
+    report
+}
+
+pub fn wait_for_running(state_manager: &StateManager, task_name: &String, timeout: i64) -> TaskState {
+    let deadline = UTC::now().timestamp() + timeout;
+
+    loop {
+        let state = state_manager.request_task_state(task_name.clone());
+        match state {
+            TaskState::Running | TaskState::NetworkFailed | TaskState::Failed => return state,
+            _ => {}
+        }
+        if UTC::now().timestamp() >= deadline {
+            return state;
+        }
+        thread::sleep(Duration::from_secs(1));
+    }
+}
+
+// a service comes after the one it depends on, dependencies outside of the given services are ignored
+pub fn order_by_dependency(tasks: Vec<Task>) -> Vec<Task> {
+    let names: Vec<String> = tasks.iter().map(|task| task.name.clone()).collect();
+    let mut ordered: Vec<Task> = vec![];
+    let mut remaining = tasks;
+
+    while !remaining.is_empty() {
+        let (ready, waiting): (Vec<Task>, Vec<Task>) = remaining.into_iter().partition(|task| {
+            task.dependent_service.is_empty() || !names.contains(&task.dependent_service) ||
+            ordered.iter().any(|done| done.name == task.dependent_service)
+        });
+
+        if ready.is_empty() {
+            warn!("circular dependency, starting the rest as is");
+            ordered.extend(waiting);
+            break;
+        }
+
+        ordered.extend(ready);
+        remaining = waiting;
+    }
+
+    ordered
+}
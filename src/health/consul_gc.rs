@@ -0,0 +1,101 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use chrono::UTC;
+use collaborator::{alias_id, deregister_service, list_task_services};
+use state::StateManager;
+use std::collections::HashSet;
+use utils::read_string;
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct ConsulGcReport {
+    pub timestamp: i64,
+    pub dry_run: bool,
+    pub checked: usize,
+    pub stale: Vec<String>,
+    pub deregistered: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+// consul keeps entries of tasks the controller lost track of, e.g. removed while consul was unreachable,
+// until their check turns critical for long enough. a dry run only reports what would get deregistered.
+pub fn run_consul_gc(state_manager: &StateManager, dry_run: bool) -> ConsulGcReport {
+    let config = state_manager.get_yaml();
+    let prefix = read_string(&config["consul"]["gc"], "prefix".to_string());
+    let master_ip = state_manager.get_master_ip();
+
+    let mut report = ConsulGcReport {
+        timestamp: UTC::now().timestamp(),
+        dry_run: dry_run,
+        checked: 0,
+        stale: vec![],
+        deregistered: vec![],
+        errors: vec![],
+    };
+
+    let services = match list_task_services(&master_ip, &prefix) {
+        Ok(services) => services,
+        Err(error) => {
+            warn!("can't list consul services error={}", error);
+            report.errors.push(error.to_string());
+            return report;
+        }
+    };
+
+    // registrations from before controllers and dns addons got their tags still come up by name,
+    // a standby task keeps its name after a fail over, it only counts while it isn't one
+    let mut expected = HashSet::new();
+    expected.insert(state_manager.get_my_name());
+    for entry in config["dns-addons"].as_vec().unwrap_or(&vec![]) {
+        expected.insert(read_string(entry, "name".to_string()));
+    }
+    for peer in config["federation"]["peers"].as_vec().unwrap_or(&vec![]) {
+        expected.insert(read_string(peer, "name".to_string()));
+    }
+    for task in state_manager.request_list_tasks().into_iter().filter(|task| !task.standby) {
+        for alias in &task.aliases {
+            expected.insert(alias_id(alias, &task));
+        }
+        expected.insert(task.name);
+    }
+
+    report.checked = services.len();
+    for service in services.into_iter().filter(|service| !expected.contains(service)) {
+        report.stale.push(service.clone());
+        if dry_run {
+            continue;
+        }
+        match deregister_service(&master_ip, &service) {
+            Ok(_) => {
+                info!("deregistered stale consul service service={}", service);
+                report.deregistered.push(service);
+            }
+            Err(error) => {
+                warn!("can't deregister stale consul service service={} error={}", service, error);
+                report.errors.push(format!("{}: {}", service, error));
+            }
+        }
+    }
+
+    info!("consul gc done dry_run={} checked={} stale={}", dry_run, report.checked, report.stale.len());
+    report
+}
@@ -0,0 +1,82 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use chrono::UTC;
+use collaborator::{kill_task, shutdown_node};
+use state::{Node, PowerState, StateManager, TaskState};
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct DrainReport {
+    pub timestamp: i64,
+    pub node: String,
+    pub restarted: Vec<String>,
+    pub stopped: Vec<String>,
+    pub shutdown: bool,
+}
+
+// takes a node out of scheduling for good: its tasks go through restart and land elsewhere,
+// tasks pinned to it by name have nowhere else to go, they are stopped and wait for the node to start up again.
+// send_begin_drain has marked the node draining already
+pub fn run_drain(state_manager: &StateManager, node: &Node, shutdown: bool) -> DrainReport {
+    let mut report = DrainReport {
+        timestamp: UTC::now().timestamp(),
+        node: node.name.clone(),
+        restarted: vec![],
+        stopped: vec![],
+        shutdown: shutdown,
+    };
+
+    info!("draining node={}", node.name);
+
+    let my_name = state_manager.get_my_name();
+    for task in state_manager.request_list_tasks_on_node(node) {
+        if task.controller != my_name {
+            continue;
+        }
+        match task.state {
+            TaskState::Running | TaskState::Accepted => {}
+            _ => continue,
+        }
+
+        let pinned = task.node_name == node.name && task.node_type.is_empty() && task.node_function.is_empty();
+        // the node picked for node_type or node_function when the task got accepted
+        if task.node_name == node.name && !pinned {
+            state_manager.send_update_task_node_name(task.name.clone(), "".to_string());
+        }
+        state_manager.send_restart_task(task.name.clone(), false);
+        kill_task(&task.name);
+        match pinned {
+            true => report.stopped.push(task.name.clone()),
+            false => report.restarted.push(task.name.clone()),
+        }
+    }
+
+    state_manager.send_set_node_inactive(node.name.clone());
+
+    if shutdown {
+        shutdown_node(&state_manager.get_ipmi_config().for_node(node), &node.management_ip);
+        state_manager.send_set_node_expected_power_state(node.name.clone(), PowerState::Off);
+    }
+
+    info!("drained node={} report={:?}", node.name, report);
+    report
+}
@@ -0,0 +1,151 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use agent::{AgentCommand, FirewallChain, HOST_FIREWALL_CHAIN, parse_port_range, task_firewall_chain};
+use collaborator::{CALICO_NETWORK, agent_token, run_agent_command};
+use state::{Node, StateManager, Task};
+use std::thread;
+use std::time::Duration;
+use utils::{read_bool, read_int, read_string};
+
+// what mesos hands out to tasks by default, whatever else listens on the node is outside of it
+const TASK_PORT_RANGE: &'static str = "31000-32000";
+
+// keeps the node agents' iptables in line with the network policies of the tasks running on their nodes,
+// sent every cycle since the rules don't survive a reboot and the agent drops chains of tasks that are gone
+pub fn run_firewall_sync(state_manager: &StateManager) {
+    let config = state_manager.get_yaml();
+    let token = agent_token(&config);
+    if !read_bool(&config["firewall"], "enabled".to_string()) {
+        remove_chains(state_manager, &token);
+        return;
+    }
+    let interval = read_int(&config["firewall"], "sync_interval_in_seconds".to_string(), 30) as u64;
+    let configured_ports: Vec<i64> = config["firewall"]["host_ports"]
+        .as_vec()
+        .map(|ports| ports.iter().filter_map(|port| port.as_i64()).collect())
+        .unwrap_or(vec![]);
+    let task_port_range = match read_string(&config["firewall"], "task_port_range".to_string()).as_ref() {
+        "" => TASK_PORT_RANGE.to_string(),
+        range => range.to_string(),
+    };
+    let task_ports = match parse_port_range(&task_port_range) {
+        Ok(task_ports) => task_ports,
+        Err(error) => {
+            error!("firewall sync not started error={}", error);
+            return;
+        }
+    };
+
+    loop {
+        thread::sleep(Duration::from_secs(interval));
+
+        let running_tasks = state_manager.request_list_running_tasks();
+        // nodes without an agent never heartbeat
+        for node in state_manager.request_list_nodes().iter().filter(|node| node.active && node.last_heartbeat > 0) {
+            let chains = firewall_chains(node, &running_tasks, &configured_ports, task_ports);
+            let command = AgentCommand {
+                kind: "sync_firewall".to_string(),
+                args: chains.iter().map(|chain| chain.to_argument()).collect(),
+            };
+            match run_agent_command(&node.ip, &token, &command) {
+                Some(ref result) if !result.success => {
+                    warn!("can't sync firewall node={} output={}", node.name, result.output)
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+// chains left from when the feature was on would keep dropping traffic, an empty sync removes them all
+fn remove_chains(state_manager: &StateManager, token: &String) {
+    let command = AgentCommand {
+        kind: "sync_firewall".to_string(),
+        args: vec![],
+    };
+    for node in state_manager.request_list_nodes().iter().filter(|node| node.active && node.last_heartbeat > 0) {
+        match run_agent_command(&node.ip, token, &command) {
+            Some(ref result) if !result.success => {
+                warn!("can't remove firewall chains node={} output={}", node.name, result.output)
+            }
+            _ => {}
+        }
+    }
+}
+
+// a chain in FORWARD per task with its own ip, everything sent to that ip belongs to the task. host network tasks
+// share the node's INPUT chain, it only drops within the task port range so the control plane and whatever else
+// runs on the node stays reachable. a host network task without a policy has unknown ports, its node's chain
+// lets everything pass. tasks on calico are left to felix
+fn firewall_chains(node: &Node,
+                   running_tasks: &Vec<Task>,
+                   configured_ports: &Vec<i64>,
+                   task_ports: (i64, i64))
+                   -> Vec<FirewallChain> {
+    let mut chains = vec![];
+    let mut host_chain: Option<FirewallChain> = None;
+    let mut host_unrestricted = false;
+
+    for task in running_tasks.iter().filter(|task| task.node_name == node.name && task.network_type != CALICO_NETWORK) {
+        let network_policy = match task.network_policy {
+            Some(ref network_policy) => network_policy,
+            None => {
+                host_unrestricted = host_unrestricted || task.network_type == "host";
+                continue;
+            }
+        };
+        let ports: Vec<(String, i64)> = network_policy.ingress
+            .iter()
+            .map(|rule| (rule.protocol.clone(), rule.port))
+            .collect();
+
+        if task.network_type == "host" {
+            let chain = host_chain.get_or_insert(FirewallChain {
+                chain: HOST_FIREWALL_CHAIN.to_string(),
+                target: "host".to_string(),
+                ports: configured_ports.iter().map(|&port| ("tcp".to_string(), port)).collect(),
+                drop: Some(task_ports),
+            });
+            for port in ports {
+                if !chain.ports.contains(&port) {
+                    chain.ports.push(port);
+                }
+            }
+        } else if !task.ip.is_empty() {
+            chains.push(FirewallChain {
+                chain: task_firewall_chain(&task.name),
+                target: task.ip.clone(),
+                ports: ports,
+                drop: Some((1, 65535)),
+            });
+        }
+    }
+
+    if let Some(ref mut chain) = host_chain {
+        if host_unrestricted {
+            chain.drop = None;
+        }
+    }
+    chains.extend(host_chain);
+    chains
+}
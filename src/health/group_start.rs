@@ -0,0 +1,108 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use chrono::UTC;
+use state::{EventType, StateManager, TaskState};
+use state::Task as StateTask;
+use std::thread;
+use std::time::Duration;
+use super::bootstrap::order_by_dependency;
+use super::task_probes::{probe_task, probe_timeout};
+use utils::{Task, read_int, start_task};
+
+// starts a service group in dependency order, services depending on another one of the group
+// are started from a thread each once that one is running and passed its health check,
+// returns the services rejected right away
+pub fn start_service_group(state_manager: &StateManager, name: &str, tasks: Vec<Task>) -> Vec<String> {
+    let names: Vec<String> = tasks.iter().map(|task| task.name.clone()).collect();
+    let (waiting, ready): (Vec<Task>, Vec<Task>) = order_by_dependency(tasks)
+        .into_iter()
+        .partition(|task| names.contains(&task.dependent_service));
+
+    let mut rejected = vec![];
+    for task in ready {
+        match start_task(state_manager, &task, false) {
+            Ok(_) => {}
+            Err(reason) => rejected.push(format!("{}: {}", task.name, reason)),
+        }
+    }
+
+    if !waiting.is_empty() {
+        let config = state_manager.get_yaml();
+        let timeout = read_int(&config["api"], "service_group_timeout_in_seconds".to_string(), 300);
+        for task in waiting {
+            let state_manager = state_manager.clone();
+            thread::Builder::new()
+                .name(format!("service-group-{}", task.name))
+                .spawn(move || start_dependent(&state_manager, task, timeout))
+                .unwrap();
+        }
+    }
+
+    rejected
+}
+
+// a dependent of a dependent waits just the same, its dependency shows up once that got started.
+// one that doesn't get ready fails the start of everything that depends on it
+fn start_dependent(state_manager: &StateManager, task: Task, timeout: i64) {
+    if !wait_for_ready(state_manager, &task.dependent_service, timeout) {
+        error!("not started, dependency did not get ready task={} dependency={}", task.name, task.dependent_service);
+        state_manager.send_add_event(EventType::DependencyTimeout,
+                                     task.name.clone(),
+                                     format!("not started, {} did not get ready within {}s", task.dependent_service, timeout));
+        return;
+    }
+    match start_task(state_manager, &task, false) {
+        Ok(_) => info!("started after dependency task={} dependency={}", task.name, task.dependent_service),
+        Err(reason) => warn!("not started task={} reason={}", task.name, reason),
+    }
+}
+
+// ready once every member of a group is, a failed member fails the wait
+pub fn wait_for_ready(state_manager: &StateManager, name: &String, timeout: i64) -> bool {
+    let deadline = UTC::now().timestamp() + timeout;
+    let probe_timeout = probe_timeout(state_manager);
+
+    loop {
+        let tasks = state_manager.request_dependency_tasks(name);
+        if tasks.iter().any(|task| task.state == TaskState::Failed || task.state == TaskState::NetworkFailed) {
+            return false;
+        }
+        if !tasks.is_empty() && tasks.iter().all(|task| is_ready(task, probe_timeout)) {
+            return true;
+        }
+        if UTC::now().timestamp() >= deadline {
+            return false;
+        }
+        thread::sleep(Duration::from_secs(1));
+    }
+}
+
+fn is_ready(task: &StateTask, probe_timeout: Duration) -> bool {
+    if task.state != TaskState::Running {
+        return false;
+    }
+    match task.health_check {
+        Some(_) => !task.ip.is_empty() && probe_task(task, probe_timeout),
+        None => true,
+    }
+}
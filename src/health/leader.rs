@@ -0,0 +1,191 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use collaborator::{HttpError, acquire_lock, create_key, create_session, delete_key, destroy_session, kill_task, lock_holder,
+                   read_key, release_lock, renew_session};
+use state::StateManager;
+use std::thread;
+use std::time::{Duration, Instant};
+use super::run_health_checker::read_system_services;
+use utils::{adopt_task, read_int, read_service_definition, read_string};
+use uuid::Uuid;
+use yaml_rust::Yaml;
+
+// controllers sharing a leader-election prefix elect one of them through a consul session lock,
+// the others wait in acquire_leadership before starting anything. the leader's framework id is kept
+// next to the lock so a standby taking over re-registers as the same framework and the tasks survive.
+// the state store gets picked up where the leader left it as long as its path is on shared storage,
+// otherwise the tasks reconcile reports get adopted.
+#[derive(Clone, Debug)]
+pub struct Leadership {
+    pub master_ip: String,
+    pub key: String,
+    pub session: String,
+    pub framework_id: String,
+    pub ttl_in_seconds: i64,
+}
+
+// blocks until this controller holds the lock
+pub fn acquire_leadership(config: &Yaml, master_ip: &String, my_name: &String, my_ip: &String) -> Leadership {
+    let election = &config["leader-election"];
+    let prefix = election_prefix(config, my_name);
+    let ttl = read_int(election, "session_ttl_in_seconds".to_string(), 15);
+    let retry_interval = read_int(election, "retry_interval_in_seconds".to_string(), 5) as u64;
+    let key = format!("{}/leader", prefix);
+    let framework_id_key = format!("{}/framework_id", prefix);
+
+    info!("waiting for leadership key={}", key);
+    let mut session: Option<String> = None;
+    loop {
+        // a session that ran out while waiting gets replaced by a new one
+        if let Some(ref current) = session.clone() {
+            if let Err(error) = renew_session(master_ip, current) {
+                warn!("can't renew session session={} error={}", current, error);
+                session = None;
+            }
+        }
+        if session.is_none() {
+            match create_session(master_ip, &format!("{} {}", my_name, my_ip), ttl) {
+                Ok(created) => session = Some(created),
+                Err(error) => warn!("can't create session error={}", error),
+            }
+        }
+
+        if let Some(ref current) = session {
+            match acquire_lock(master_ip, &key, current, my_ip) {
+                Ok(true) => {
+                    match shared_framework_id(master_ip, &framework_id_key, my_name) {
+                        Ok(framework_id) => {
+                            info!("leading key={} session={} framework_id={}", key, current, framework_id);
+                            return Leadership {
+                                master_ip: master_ip.clone(),
+                                key: key,
+                                session: current.clone(),
+                                framework_id: framework_id,
+                                ttl_in_seconds: ttl,
+                            };
+                        }
+                        // nothing to lead with, let somebody else try
+                        Err(error) => {
+                            warn!("can't resolve framework id error={}", error);
+                            let _ = release_lock(master_ip, &key, current);
+                        }
+                    }
+                }
+                Ok(false) => debug!("standing by key={}", key),
+                Err(error) => warn!("can't acquire leadership key={} error={}", key, error),
+            }
+        }
+
+        thread::sleep(Duration::from_secs(retry_interval));
+    }
+}
+
+// renews the session until the lock is lost, returns then. a leader that can't reach consul for
+// a whole ttl gives up as well, by then consul may have handed the lock to a standby.
+pub fn hold_leadership(leadership: &Leadership) {
+    let interval = Duration::from_secs((leadership.ttl_in_seconds / 3).max(1) as u64);
+    let ttl = Duration::from_secs(leadership.ttl_in_seconds as u64);
+    let mut last_renewed = Instant::now();
+
+    loop {
+        thread::sleep(interval);
+
+        match renew_session(&leadership.master_ip, &leadership.session) {
+            Ok(_) => last_renewed = Instant::now(),
+            Err(HttpError::Status(404, _)) => {
+                error!("session is gone session={}", leadership.session);
+                return;
+            }
+            Err(error) => warn!("can't renew session session={} error={}", leadership.session, error),
+        }
+        if last_renewed.elapsed() >= ttl {
+            error!("session not renewed within its ttl session={}", leadership.session);
+            return;
+        }
+
+        match lock_holder(&leadership.master_ip, &leadership.key) {
+            Ok(Some(ref holder)) if *holder == leadership.session => {}
+            Ok(holder) => {
+                error!("lost leadership key={} holder={:?}", leadership.key, holder);
+                return;
+            }
+            Err(error) => warn!("can't read leader lock key={} error={}", leadership.key, error),
+        }
+    }
+}
+
+// hands the lock over right away instead of letting the standbys wait for the session ttl
+pub fn release_leadership(leadership: &Leadership) {
+    if let Err(error) = release_lock(&leadership.master_ip, &leadership.key, &leadership.session) {
+        warn!("can't release leadership key={} error={}", leadership.key, error);
+    }
+    if let Err(error) = destroy_session(&leadership.master_ip, &leadership.session) {
+        warn!("can't destroy session session={} error={}", leadership.session, error);
+    }
+}
+
+// a framework the master removed can't register again, the next leader has to start a new one
+pub fn forget_framework_id(config: &Yaml, master_ip: &String, my_name: &String) {
+    let key = format!("{}/framework_id", election_prefix(config, my_name));
+    match delete_key(master_ip, &key) {
+        Ok(_) => info!("forgot framework id key={}", key),
+        Err(error) => error!("can't delete framework id key={} error={}", key, error),
+    }
+}
+
+fn election_prefix(config: &Yaml, my_name: &String) -> String {
+    match read_string(&config["leader-election"], "prefix".to_string()).as_ref() {
+        "" => format!("torc/{}", my_name),
+        prefix => prefix.to_string(),
+    }
+}
+
+// the first leader's framework id, created by whoever leads first
+fn shared_framework_id(master_ip: &String, key: &String, my_name: &String) -> Result<String, HttpError> {
+    let candidate = format!("{}-{}", my_name, Uuid::new_v4().to_simple_string());
+    if try!(create_key(master_ip, key, &candidate)) {
+        return Ok(candidate);
+    }
+
+    match try!(read_key(master_ip, key)) {
+        Some(ref framework_id) if !framework_id.trim().is_empty() => Ok(framework_id.trim().to_string()),
+        _ => Err(HttpError::Invalid(format!("{} exists but is empty", key))),
+    }
+}
+
+// a running task the previous leader started, known from reconcile only. the config has the definition
+// for system services and service group members, anything else can't be restarted or checked and gets killed
+pub fn adopt_reconciled_task(state_manager: &StateManager, task_name: &String, slave_id: &String) {
+    match read_system_services(state_manager).into_iter().find(|task| task.name == *task_name) {
+        Some(task) => adopt_task(state_manager, slave_id, &task, true),
+        None => {
+            match read_service_definition(state_manager, task_name) {
+                Some(task) => adopt_task(state_manager, slave_id, &task, false),
+                None => {
+                    warn!("killing reconciled task without a definition task={} slave_id={}", task_name, slave_id);
+                    kill_task(task_name);
+                }
+            }
+        }
+    }
+}
@@ -20,6 +20,33 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 // THE SOFTWARE.
 
+pub use self::agent_upgrade::{plan_agent_upgrade, run_agent_upgrade};
+pub use self::bootstrap::{BootstrapReport, BootstrapStep, run_bootstrap};
+pub use self::consul_gc::{ConsulGcReport, run_consul_gc};
+pub use self::drain::{DrainReport, run_drain};
+pub use self::firewall::run_firewall_sync;
+pub use self::group_start::start_service_group;
+pub use self::leader::{Leadership, acquire_leadership, adopt_reconciled_task, forget_framework_id, hold_leadership,
+                       release_leadership};
+pub use self::rolling_update::{RollingUpdateReport, run_rolling_update};
 pub use self::run_health_checker::run_health_checker;
+pub use self::selftest::{SelftestCheck, SelftestReport, run_selftest};
+pub use self::shutdown::{ShutdownReport, run_shutdown};
+pub use self::startup_gate::run_startup_gate;
+pub use self::task_probes::run_task_probes;
+pub use self::teardown::{TeardownReport, TeardownStep, run_teardown};
 
+mod agent_upgrade;
+mod bootstrap;
+mod consul_gc;
+mod drain;
+mod firewall;
+mod group_start;
+mod leader;
+mod rolling_update;
 mod run_health_checker;
+mod selftest;
+mod shutdown;
+mod startup_gate;
+mod task_probes;
+mod teardown;
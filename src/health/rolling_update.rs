@@ -0,0 +1,167 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use chrono::UTC;
+use collaborator::kill_task;
+use state::{EventType, Route, StateManager, Task, TaskState};
+use super::group_start::wait_for_ready;
+use utils::read_int;
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct RollingUpdateReport {
+    pub name: String,
+    pub previous_image: String,
+    pub image: String,
+    pub arguments: String,
+    pub route_swapped: bool,
+}
+
+// mesos knows a task by its name and so do consul, dns and the api, the update replaces the task in place.
+// a surge instance with the new definition takes the traffic first, only once it is running and through its
+// health check the task itself is killed and comes back with the new image or arguments, then the surge
+// instance goes again. a surge instance that doesn't get ready in time leaves the task as it is.
+pub fn run_rolling_update(state_manager: &StateManager,
+                          name: &String,
+                          image: Option<String>,
+                          arguments: Option<String>)
+                          -> Result<RollingUpdateReport, String> {
+    let current = match state_manager.request_task(name.clone()) {
+        Some(ref task) if task.state == TaskState::Running => task.clone(),
+        _ => return Err(format!("{} is not running", name)),
+    };
+    if current.is_job || !current.replica_group.is_empty() || current.controller != state_manager.get_my_name() {
+        return Err(format!("{} is not a single service of this controller", current.name));
+    }
+
+    let mut updated = current.clone();
+    if let Some(image) = image {
+        updated.image = image;
+        updated.image_digest = "".to_string();
+    }
+    if let Some(arguments) = arguments {
+        updated.arguments = arguments;
+    }
+    let route = state_manager.request_list_routes().into_iter().find(|route| route.task_name == *name);
+    let timeout = read_int(&state_manager.get_yaml()["rolling-update"], "ready_timeout_in_seconds".to_string(), 120);
+
+    // answers for the same consul service as the task, kept out of it until it is ready
+    let mut surge = updated.clone();
+    surge.name = format!("{}-surge-{}", name, UTC::now().timestamp());
+    surge.standby_group = current.service_name().clone();
+    surge.standby = true;
+
+    info!("rolling update task={} surge={} image={} previous_image={}", name, surge.name, updated.image, current.image);
+    try!(state_manager.send_replace_task(&surge));
+    if !wait_for_ready(state_manager, &surge.name, timeout) {
+        warn!("giving up rolling update task={} image={}", name, updated.image);
+        discard(state_manager, &surge.name);
+        return Err(format!("{} didn't get ready within {}s running {}, {} keeps running {}",
+                           surge.name,
+                           timeout,
+                           updated.image,
+                           name,
+                           current.image));
+    }
+    let surge = try!(running_task(state_manager, &surge.name));
+    hand_over(state_manager, &current, &surge, &route);
+
+    try!(replace(state_manager, &updated));
+    let replaced = match wait_for_ready(state_manager, name, timeout) {
+        true => try!(running_task(state_manager, name)),
+        false => {
+            warn!("rolling back task={} image={}", name, current.image);
+            try!(replace(state_manager, &current));
+            if !wait_for_ready(state_manager, name, timeout) {
+                return Err(format!("{} didn't get ready running {} nor {}, {} keeps answering for it",
+                                   name,
+                                   updated.image,
+                                   current.image,
+                                   surge.name));
+            }
+            let restored = try!(running_task(state_manager, name));
+            hand_over(state_manager, &surge, &restored, &route);
+            discard(state_manager, &surge.name);
+            return Err(format!("{} didn't get ready within {}s running {}, rolled back to {}",
+                               name,
+                               timeout,
+                               updated.image,
+                               current.image));
+        }
+    };
+    hand_over(state_manager, &surge, &replaced, &route);
+    discard(state_manager, &surge.name);
+
+    state_manager.send_add_event(EventType::ServiceUpdated,
+                                 name.clone(),
+                                 format!("{} replaced by {}", current.image, replaced.image));
+    Ok(RollingUpdateReport {
+        name: name.clone(),
+        previous_image: current.image.clone(),
+        image: replaced.image.clone(),
+        arguments: replaced.arguments.clone(),
+        route_swapped: route.is_some() && replaced.ip != current.ip,
+    })
+}
+
+// the next one is registered and routed before the previous one goes out of consul, a task keeping
+// its ip keeps its route as well
+fn hand_over(state_manager: &StateManager, from: &Task, to: &Task, route: &Option<Route>) {
+    state_manager.send_set_task_standby(to.name.clone(), false);
+    state_manager.send_set_task_standby(from.name.clone(), true);
+    if let Some(ref route) = *route {
+        if to.ip != from.ip {
+            let next_hop = next_hop(state_manager, to).unwrap_or(route.next_hop.clone());
+            state_manager.send_announce_route(to.name.clone(), to.ip.clone(), next_hop);
+            state_manager.send_withdraw_route(from.name.clone());
+        }
+    }
+}
+
+// the definition takes over the name first, so the kill's terminal status doesn't count as an exit.
+// it stays out of consul until it gets handed the traffic back
+fn replace(state_manager: &StateManager, definition: &Task) -> Result<(), String> {
+    let mut definition = definition.clone();
+    definition.standby = true;
+    try!(state_manager.send_replace_task(&definition));
+    kill_task(&definition.name);
+    Ok(())
+}
+
+fn discard(state_manager: &StateManager, surge_name: &String) {
+    state_manager.send_retire_task(surge_name.clone());
+    kill_task(surge_name);
+}
+
+fn running_task(state_manager: &StateManager, name: &String) -> Result<Task, String> {
+    match state_manager.request_task(name.clone()) {
+        Some(task) => Ok(task),
+        None => Err(format!("{} is gone", name)),
+    }
+}
+
+// the node the task runs on, it may have landed somewhere else than the one it replaces
+fn next_hop(state_manager: &StateManager, task: &Task) -> Option<String> {
+    state_manager.request_list_nodes()
+        .into_iter()
+        .find(|node| !node.slave_id.is_empty() && node.slave_id == task.slave_id && !node.external_ip.is_empty())
+        .map(|node| node.external_ip)
+}
@@ -20,11 +20,11 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 // THE SOFTWARE.
 
-use collaborator::{register_torc_controller, register_unmanaged_service};
+use collaborator::{TtlCheck, refresh_controller_ttl, register_torc_controller, register_unmanaged_service};
 use state::{SLA, StateManager, TaskState};
 use std::thread;
 use std::time::Duration;
-use utils::{read_string, read_string_replace_variable, read_task};
+use utils::{Task, read_string, read_string_replace_variable, read_task, start_task};
 
 struct DNSEntry {
     name: String,
@@ -32,14 +32,83 @@ struct DNSEntry {
 }
 
 pub fn run_health_checker(state_manager: &StateManager) {
-    println!("health check starting");
+    info!("health check starting");
     state_manager.send_ping();
 
     let is_system_service = true;
 
     let config = state_manager.get_yaml();
     let wait_time = config["healthcheck"]["poll_interval_in_seconds"].as_i64().unwrap() as u64;
+    let check = TtlCheck::from_config(&config);
 
+    let tasks = read_system_services(state_manager);
+
+    let mut dns_entries = Vec::new();
+    let dns_addons = config["dns-addons"].as_vec().unwrap();
+    for dns_addon in dns_addons {
+        dns_entries.push(DNSEntry {
+            name: read_string(dns_addon, "name".to_string()),
+            ip: read_string_replace_variable(dns_addon, "ip".to_string(), &state_manager),
+        });
+    }
+
+    // once up front so a registration left from before gets the controller tag
+    register_controller(state_manager, &check);
+
+    loop {
+        thread::sleep(Duration::from_secs(wait_time));
+        debug!("checking health");
+
+        // during a teardown system services are stopped on purpose
+        if !state_manager.request_is_teardown() {
+            for task in &tasks {
+                match state_manager.request_task_state(task.name.to_string()) {
+                    TaskState::Running | TaskState::Requested | TaskState::Accepted | TaskState::Restart |
+                    TaskState::NetworkFailed | TaskState::Failed | TaskState::Finished | TaskState::Delegated |
+                    TaskState::Deleted => {}
+                    TaskState::NotRunning => {
+                        match start_task(state_manager, task, is_system_service) {
+                            Ok(_) => {}
+                            Err(reason) => warn!("can't start task={} reason={}", task.name, reason),
+                        }
+                    }
+                };
+            }
+            state_manager.release_unavailable_volumes();
+        }
+
+        // the shutdown deregisters the controller, it must not show up again
+        if !state_manager.request_is_shutting_down() {
+            refresh_controller_registration(state_manager, &check);
+        }
+
+        for dns_entry in &dns_entries {
+            if let Err(error) = register_unmanaged_service(&state_manager.get_master_ip(), &dns_entry.name, &dns_entry.ip) {
+                warn!("can't register service with consul service={} error={}", dns_entry.name, error);
+            }
+        }
+    }
+}
+
+// registers the controller again whenever its check can't be refreshed, e.g. after consul lost it
+fn refresh_controller_registration(state_manager: &StateManager, check: &TtlCheck) {
+    if refresh_controller_ttl(&state_manager.get_master_ip(), &state_manager.get_my_name()).is_ok() {
+        return;
+    }
+    register_controller(state_manager, check);
+}
+
+fn register_controller(state_manager: &StateManager, check: &TtlCheck) {
+    let my_name = state_manager.get_my_name();
+    match register_torc_controller(&state_manager.get_master_ip(), &my_name, &state_manager.get_my_ip(), check) {
+        Ok(_) => info!("registered controller with consul controller={}", my_name),
+        Err(error) => warn!("can't register controller with consul error={}", error),
+    }
+}
+
+// system services from the config, expanded per node for the singleton slas
+pub fn read_system_services(state_manager: &StateManager) -> Vec<Task> {
+    let config = state_manager.get_yaml();
     let mut tasks = Vec::new();
 
     let system_services = config["healthcheck"]["system_services"].as_vec().unwrap();
@@ -71,54 +140,5 @@ pub fn run_health_checker(state_manager: &StateManager) {
         }
     }
 
-    let mut dns_entries = Vec::new();
-    let dns_addons = config["dns-addons"].as_vec().unwrap();
-    for dns_addon in dns_addons {
-        dns_entries.push(DNSEntry {
-            name: read_string(dns_addon, "name".to_string()),
-            ip: read_string_replace_variable(dns_addon, "ip".to_string(), &state_manager),
-        });
-    }
-
-
-    loop {
-        thread::sleep(Duration::from_secs(wait_time));
-        println!("checking health");
-
-        for task in &tasks {
-            match state_manager.request_task_state(task.name.to_string()) {
-                TaskState::Running | TaskState::Requested | TaskState::Accepted | TaskState::Restart => {}
-                TaskState::NotRunning => {
-                    state_manager.send_start_task(&task.name,
-                                                  &task.image,
-                                                  &task.node_name,
-                                                  &task.node_type,
-                                                  &task.node_function,
-                                                  &task.dependent_service,
-                                                  &task.arguments,
-                                                  &task.parameters,
-                                                  &task.memory,
-                                                  &task.cpu,
-                                                  &task.volumes,
-                                                  &task.privileged,
-                                                  &task.sla,
-                                                  &task.is_metered,
-                                                  &is_system_service,
-                                                  &task.is_job,
-                                                  &task.network_type)
-                }
-            };
-        }
-
-        register_torc_controller(&state_manager.get_master_ip(),
-                                 &state_manager.get_my_name(),
-                                 &state_manager.get_my_ip());
-
-        for dns_entry in &dns_entries {
-            register_unmanaged_service(&state_manager.get_master_ip(),
-                                       &dns_entry.name,
-                                       &dns_entry.ip);
-
-        }
-    }
+    tasks
 }
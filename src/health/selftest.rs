@@ -0,0 +1,156 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use chrono::UTC;
+use collaborator::{is_service_registered, kill_task};
+use state::{StateManager, TaskState};
+use std::net::TcpStream;
+use std::sync::mpsc::channel;
+use std::thread;
+use std::time::Duration;
+use super::bootstrap::wait_for_running;
+use utils::{read_int, read_task, start_task};
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct SelftestCheck {
+    pub name: String,
+    pub ok: bool,
+    pub detail: String,
+}
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct SelftestReport {
+    pub timestamp: i64,
+    pub task_name: String,
+    pub ok: bool,
+    pub checks: Vec<SelftestCheck>,
+}
+
+// runs the configured canary task through the whole pipeline: mesos, network, discovery, and removes it again
+pub fn run_selftest(state_manager: &StateManager) -> SelftestReport {
+    let config = state_manager.get_yaml();
+    let selftest = &config["selftest"];
+
+    let mut report = SelftestReport {
+        timestamp: UTC::now().timestamp(),
+        task_name: "".to_string(),
+        ok: false,
+        checks: vec![],
+    };
+
+    if selftest["task"].is_badvalue() {
+        add_check(&mut report, "config", false, "no selftest task configured".to_string());
+        return report;
+    }
+
+    let timeout = read_int(selftest, "timeout_in_seconds".to_string(), 60);
+    let probe_port = read_int(selftest, "probe_port".to_string(), 0);
+    let probe_timeout = read_int(selftest, "probe_timeout_in_seconds".to_string(), 5);
+
+    let mut task = read_task(&selftest["task"], state_manager);
+    task.name = format!("{}-{}", task.name, report.timestamp);
+    report.task_name = task.name.clone();
+
+    info!("selftest task={}", task.name);
+    let task_state = match start_task(state_manager, &task, false) {
+        Ok(_) => wait_for_running(state_manager, &task.name, timeout),
+        Err(reason) => {
+            add_check(&mut report, "start", false, reason);
+            TaskState::NotRunning
+        }
+    };
+    add_check(&mut report,
+              "running",
+              task_state == TaskState::Running,
+              format!("{:?}", task_state));
+
+    if task_state == TaskState::Running {
+        let ip = state_manager.request_task_ip(task.name.clone());
+        add_check(&mut report, "ip", !ip.is_empty(), ip.clone());
+
+        match is_service_registered(&state_manager.get_master_ip(), &task.name) {
+            Ok(registered) => add_check(&mut report, "consul", registered, "".to_string()),
+            Err(error) => add_check(&mut report, "consul", false, error.to_string()),
+        }
+
+        match state_manager.get_network_agent_type().as_ref() {
+            "undefined" => add_check(&mut report, "route", true, "no network agent configured".to_string()),
+            _ => {
+                let route = state_manager.request_list_routes()
+                    .into_iter()
+                    .find(|route| route.task_name == task.name);
+                match route {
+                    Some(route) => add_check(&mut report, "route", route.ip == ip, format!("{} via {}", route.ip, route.next_hop)),
+                    None => add_check(&mut report, "route", false, "no route announced".to_string()),
+                }
+            }
+        }
+
+        if probe_port > 0 {
+            let address = format!("{}:{}", ip, probe_port);
+            match probe(address.clone(), probe_timeout) {
+                Ok(_) => add_check(&mut report, "probe", true, address),
+                Err(error) => add_check(&mut report, "probe", false, format!("{}: {}", address, error)),
+            }
+        }
+    }
+
+    state_manager.send_remove_task_by_name(task.name.clone());
+    kill_task(&task.name);
+    let mut leftovers = vec![];
+    if state_manager.request_task(task.name.clone()).is_some() {
+        leftovers.push("task still in state".to_string());
+    }
+    match is_service_registered(&state_manager.get_master_ip(), &task.name) {
+        Ok(true) => leftovers.push("still registered in consul".to_string()),
+        Ok(false) => {}
+        Err(error) => leftovers.push(format!("consul: {}", error)),
+    }
+    add_check(&mut report, "teardown", leftovers.is_empty(), leftovers.join(", "));
+
+    report.ok = report.checks.iter().all(|check| check.ok);
+    report
+}
+
+// connect has no timeout of its own, an address that drops the syn would hang the selftest
+fn probe(address: String, timeout: i64) -> Result<(), String> {
+    let (sender, receiver) = channel();
+    let target = address.clone();
+    thread::spawn(move || {
+        let result = TcpStream::connect(target.as_str()).map(|_| ()).map_err(|error| error.to_string());
+        let _ = sender.send(result);
+    });
+
+    match receiver.recv_timeout(Duration::from_secs(timeout as u64)) {
+        Ok(result) => result,
+        Err(_) => Err(format!("no connection within {}s", timeout)),
+    }
+}
+
+fn add_check(report: &mut SelftestReport, name: &str, ok: bool, detail: String) {
+    info!("selftest check={} ok={} detail={}", name, ok, detail);
+    report.checks.push(SelftestCheck {
+        name: name.to_string(),
+        ok: ok,
+        detail: detail,
+    });
+}
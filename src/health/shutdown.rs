@@ -0,0 +1,96 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use chrono::UTC;
+use collaborator::{NetworkAgentError, delete_route, deregister_task, deregister_torc_controller};
+use state::StateManager;
+use utils::read_bool;
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct ShutdownReport {
+    pub timestamp: i64,
+    pub signal: String,
+    pub controller_deregistered: bool,
+    pub deregistered_tasks: Vec<String>,
+    pub removed_routes: Vec<String>,
+    pub failed_routes: Vec<String>,
+}
+
+// leaves the cluster the way a restarted controller expects to find it: the api turns requests away,
+// consul entries and routes of this controller go, and the state store gets a last snapshot.
+// the tasks themselves keep running, a controller coming back picks them up from the store.
+pub fn run_shutdown(state_manager: &StateManager, signal: &str) -> ShutdownReport {
+    let config = state_manager.get_yaml();
+    let deregister_services = read_bool(&config["shutdown"], "deregister_services".to_string());
+    let keep_routes = read_bool(&config["shutdown"], "keep_routes".to_string());
+
+    let mut report = ShutdownReport {
+        timestamp: UTC::now().timestamp(),
+        signal: signal.to_string(),
+        controller_deregistered: false,
+        deregistered_tasks: vec![],
+        removed_routes: vec![],
+        failed_routes: vec![],
+    };
+
+    info!("shutting down signal={}", signal);
+    state_manager.send_set_shutting_down(true);
+
+    // peers stop counting on this controller right away instead of waiting for its ttl to run out
+    match deregister_torc_controller(&state_manager.get_master_ip(), &state_manager.get_my_name()) {
+        Ok(_) => report.controller_deregistered = true,
+        Err(error) => warn!("can't deregister controller from consul error={}", error),
+    }
+
+    // registrations and routes still in the coalescing window, deregistering and route removal below come after them
+    state_manager.flush_pending_updates();
+
+    if deregister_services {
+        let master_ip = state_manager.get_master_ip();
+        let my_name = state_manager.get_my_name();
+        for task in state_manager.request_list_running_tasks().iter().filter(|task| task.controller == my_name) {
+            match deregister_task(&master_ip, task) {
+                Ok(_) => report.deregistered_tasks.push(task.name.clone()),
+                Err(error) => warn!("can't deregister task from consul task={} error={}", task.name, error),
+            }
+        }
+    }
+
+    // straight to the network agent, the route queue would only get to them after we're gone
+    if !keep_routes {
+        let network_agent = state_manager.get_network_agent();
+        for route in state_manager.request_list_routes() {
+            match delete_route(&*network_agent, &route.ip) {
+                Ok(_) | Err(NetworkAgentError::NotFound) => report.removed_routes.push(route.task_name),
+                Err(_) => report.failed_routes.push(route.task_name),
+            }
+        }
+    }
+
+    state_manager.send_flush_store();
+    info!("shutdown done deregistered={} routes_removed={} routes_failed={}",
+          report.deregistered_tasks.len(),
+          report.removed_routes.len(),
+          report.failed_routes.len());
+
+    report
+}
@@ -0,0 +1,160 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use collaborator::reset_fib;
+use state::{CollaboratorStatus, StateManager};
+use std::cmp;
+use std::net::TcpStream;
+use std::sync::mpsc::channel;
+use std::thread;
+use std::time::Duration;
+use utils::{first_task_name, read_int, read_service_group, read_string_list, start_task};
+
+const MESOS_PORT: i64 = 5050;
+const CONSUL_PORT: i64 = 8500;
+const IPMI_PROXY_PORT: i64 = 8085;
+
+pub fn run_startup_gate(state_manager: &StateManager) {
+    info!("startup gate starting");
+    state_manager.send_ping();
+
+    let config = state_manager.get_yaml();
+    let retries = read_int(&config["startup"], "retries".to_string(), 10);
+    let initial_backoff = read_int(&config["startup"], "initial_backoff_in_seconds".to_string(), 1) as u64;
+    let max_backoff = read_int(&config["startup"], "max_backoff_in_seconds".to_string(), 30) as u64;
+    let mut required = read_string_list(&config["startup"], "required".to_string());
+    if config["startup"]["required"].is_badvalue() {
+        required = vec!["mesos".to_string(), "consul".to_string()];
+    }
+
+    let mut collaborators = vec![];
+    collaborators.push(new_status("mesos",
+                                  format!("{}:{}", state_manager.get_master_ip(), MESOS_PORT),
+                                  &required));
+    collaborators.push(new_status("consul",
+                                  format!("{}:{}", state_manager.get_master_ip(), CONSUL_PORT),
+                                  &required));
+    if state_manager.get_network_agent_type() != "undefined" {
+        collaborators.push(new_status("network-agent", state_manager.get_network_agent_connection(), &required));
+    }
+    let ipmi_config = state_manager.get_ipmi_config();
+    if ipmi_config.backend == "proxy" {
+        collaborators.push(new_status("ipmi-proxy",
+                                      format!("{}:{}", ipmi_config.proxy, IPMI_PROXY_PORT),
+                                      &required));
+    }
+
+    state_manager.send_set_collaborators(collaborators.clone());
+
+    let (done_sender, done_receiver) = channel();
+    for collaborator in collaborators {
+        let probe_state_manager = state_manager.clone();
+        let done = done_sender.clone();
+        thread::Builder::new()
+            .name(format!("probe-{}", collaborator.name))
+            .spawn(move || {
+                let reachable = probe(&probe_state_manager, &collaborator, retries, initial_backoff, max_backoff);
+                done.send((collaborator.name.clone(), reachable)).unwrap();
+            })
+            .unwrap();
+    }
+    drop(done_sender);
+
+    for (name, reachable) in done_receiver.iter() {
+        info!("startup gate collaborator={} reachable={}", name, reachable);
+        if name == "network-agent" && reachable {
+            match reset_fib(&*state_manager.get_network_agent()) {
+                Ok(_) => {}
+                Err(error) => error!("can't reset fib error={}", error),
+            }
+        }
+    }
+
+    info!("startup gate done ready={}", state_manager.request_is_ready());
+
+    if state_manager.request_is_ready() {
+        autostart(state_manager);
+    }
+}
+
+// submits the configured service groups, members that are already known are left alone
+fn autostart(state_manager: &StateManager) {
+    let config = state_manager.get_yaml();
+    for group in read_string_list(&config, "autostart".to_string()) {
+        info!("autostart group={}", group);
+        for task in read_service_group(state_manager, &group) {
+            if state_manager.request_task(first_task_name(&task)).is_some() {
+                continue;
+            }
+            match start_task(state_manager, &task, false) {
+                Ok(_) => {}
+                Err(reason) => warn!("startup gate can't start task={} reason={}", task.name, reason),
+            }
+        }
+    }
+}
+
+fn new_status(name: &str, address: String, required: &Vec<String>) -> CollaboratorStatus {
+    CollaboratorStatus {
+        name: name.to_string(),
+        address: address,
+        required: required.contains(&name.to_string()),
+        reachable: false,
+        attempts: 0,
+        last_error: "".to_string(),
+    }
+}
+
+// required collaborators are probed until they show up, optional ones give up after the configured retries
+fn probe(state_manager: &StateManager,
+         collaborator: &CollaboratorStatus,
+         retries: i64,
+         initial_backoff: u64,
+         max_backoff: u64)
+         -> bool {
+    let mut backoff = initial_backoff;
+    let mut attempt = 0;
+
+    loop {
+        attempt += 1;
+        match TcpStream::connect(collaborator.address.as_str()) {
+            Ok(_) => {
+                state_manager.send_update_collaborator(collaborator.name.clone(), true, "".to_string());
+                return true;
+            }
+            Err(error) => {
+                warn!("collaborator not reachable collaborator={} address={} error={}",
+                      collaborator.name,
+                      collaborator.address,
+                      error);
+                state_manager.send_update_collaborator(collaborator.name.clone(), false, error.to_string());
+            }
+        }
+
+        if !collaborator.required && attempt >= retries {
+            return false;
+        }
+
+        thread::sleep(Duration::from_secs(backoff));
+        backoff = cmp::min(backoff * 2, max_backoff);
+    }
+}
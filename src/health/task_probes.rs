@@ -0,0 +1,194 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use chrono::UTC;
+use collaborator::{http_answer, kill_task};
+use hyper::header::Headers;
+use hyper::method::Method;
+use state::{EventType, HealthCheck, StateManager, Task};
+use std::collections::BTreeMap;
+use std::net::{SocketAddr, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::thread;
+use std::time::Duration;
+use utils::read_int;
+
+struct Probe {
+    task_name: String,
+    ip: String,
+    health_check: HealthCheck,
+    timeout: Duration,
+}
+
+struct ProbeResult {
+    task_name: String,
+    healthy: bool,
+}
+
+// probes the running tasks that come with a healthcheck from a pool of workers,
+// a task failing its probe failures times in a row gets restarted
+pub fn run_task_probes(state_manager: &StateManager) {
+    let config = state_manager.get_yaml();
+    let workers = read_int(&config["healthcheck"], "probe_workers".to_string(), 4);
+    let timeout = probe_timeout(state_manager);
+    let my_name = state_manager.get_my_name();
+
+    let (probe_sender, probe_receiver) = channel::<Probe>();
+    let (result_sender, result_receiver) = channel::<ProbeResult>();
+    let probe_receiver = Arc::new(Mutex::new(probe_receiver));
+    for index in 0..workers {
+        start_worker(index, probe_receiver.clone(), result_sender.clone());
+    }
+
+    let mut last_probes: BTreeMap<String, i64> = BTreeMap::new();
+    let mut in_flight: BTreeMap<String, HealthCheck> = BTreeMap::new();
+    let mut failures: BTreeMap<String, i64> = BTreeMap::new();
+
+    loop {
+        thread::sleep(Duration::from_secs(1));
+
+        while let Ok(result) = result_receiver.try_recv() {
+            let health_check = match in_flight.remove(&result.task_name) {
+                Some(health_check) => health_check,
+                None => continue,
+            };
+            if result.healthy {
+                failures.remove(&result.task_name);
+                continue;
+            }
+
+            let failed = failures.get(&result.task_name).cloned().unwrap_or(0) + 1;
+            failures.insert(result.task_name.clone(), failed);
+            if failed < health_check.failures {
+                continue;
+            }
+            // the failure count stays, the next failed probe tries again
+            if !state_manager.take_automated_kill(&result.task_name) {
+                continue;
+            }
+
+            failures.remove(&result.task_name);
+            state_manager.send_restart_task(result.task_name.clone(), false);
+            kill_task(&result.task_name);
+            state_manager.send_add_event(EventType::HealthCheckFailed,
+                                         result.task_name.clone(),
+                                         format!("{} {} probes failed on port {}, restarting",
+                                                 failed,
+                                                 health_check.check_type,
+                                                 health_check.port));
+        }
+
+        let now = UTC::now().timestamp();
+        let running_tasks = state_manager.request_list_running_tasks();
+        let running_names: Vec<String> = running_tasks.iter().map(|task| task.name.clone()).collect();
+        last_probes = last_probes.into_iter().filter(|&(ref name, _)| running_names.contains(name)).collect();
+        failures = failures.into_iter().filter(|&(ref name, _)| running_names.contains(name)).collect();
+
+        for task in running_tasks {
+            let health_check = match task.health_check {
+                Some(ref health_check) if task.controller == my_name && !task.ip.is_empty() => health_check.clone(),
+                _ => continue,
+            };
+            // no probes while the task is still warming up
+            if in_flight.contains_key(&task.name) || task.running_since + task.slow_start_in_seconds > now {
+                continue;
+            }
+            let last_probe = last_probes.get(&task.name).cloned().unwrap_or(task.running_since);
+            if last_probe + health_check.interval_in_seconds > now {
+                continue;
+            }
+
+            last_probes.insert(task.name.clone(), now);
+            in_flight.insert(task.name.clone(), health_check.clone());
+            probe_sender.send(Probe {
+                    task_name: task.name.clone(),
+                    ip: task.ip.clone(),
+                    health_check: health_check,
+                    timeout: timeout,
+                })
+                .unwrap();
+        }
+    }
+}
+
+fn start_worker(index: i64, probes: Arc<Mutex<Receiver<Probe>>>, results: Sender<ProbeResult>) {
+    thread::Builder::new()
+        .name(format!("task-probe-{}", index))
+        .spawn(move || {
+            loop {
+                let probe = match probes.lock().unwrap().recv() {
+                    Ok(probe) => probe,
+                    Err(_) => return,
+                };
+                let healthy = run_probe(&probe);
+                if !healthy {
+                    warn!("probe failed check_type={} task={}", probe.health_check.check_type, probe.task_name);
+                }
+                let _ = results.send(ProbeResult {
+                    task_name: probe.task_name,
+                    healthy: healthy,
+                });
+            }
+        })
+        .unwrap();
+}
+
+pub fn probe_timeout(state_manager: &StateManager) -> Duration {
+    let config = state_manager.get_yaml();
+    Duration::from_secs(read_int(&config["healthcheck"], "probe_timeout_in_seconds".to_string(), 5).max(1) as u64)
+}
+
+// a single probe, for tasks with a healthcheck that are running
+pub fn probe_task(task: &Task, timeout: Duration) -> bool {
+    let health_check = match task.health_check {
+        Some(ref health_check) => health_check.clone(),
+        None => return true,
+    };
+
+    run_probe(&Probe {
+        task_name: task.name.clone(),
+        ip: task.ip.clone(),
+        health_check: health_check,
+        timeout: timeout,
+    })
+}
+
+// http probes go through the collaborator client and its timeout, a failed probe isn't retried
+fn run_probe(probe: &Probe) -> bool {
+    let address = format!("{}:{}", probe.ip, probe.health_check.port);
+    match probe.health_check.check_type.as_ref() {
+        "tcp" => {
+            match address.parse::<SocketAddr>() {
+                Ok(socket_address) => TcpStream::connect_timeout(&socket_address, probe.timeout).is_ok(),
+                Err(_) => false,
+            }
+        }
+        _ => {
+            let url = format!("http://{}{}", address, probe.health_check.path);
+            match http_answer(Method::Get, "task-probe", &probe.ip, &url, &Headers::new(), "") {
+                Ok(response) => response.status >= 200 && response.status < 300,
+                Err(_) => false,
+            }
+        }
+    }
+}
@@ -0,0 +1,106 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use chrono::UTC;
+use collaborator::{kill_task, shutdown_network, shutdown_node};
+use state::{PowerState, StateManager, Task};
+use std::thread;
+use std::time::Duration;
+use utils::read_int;
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct TeardownStep {
+    pub step: String,
+    pub subject: String,
+    pub timestamp: i64,
+}
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct TeardownReport {
+    pub timestamp: i64,
+    pub power_off: bool,
+    pub steps: Vec<TeardownStep>,
+}
+
+// the reverse of the bootstrap: regular tasks first, then system services, then the overlay network
+// and optionally the slaves themselves. removing a task also withdraws its route and consul entries.
+pub fn run_teardown(state_manager: &StateManager, power_off: bool) -> TeardownReport {
+    let config = state_manager.get_yaml();
+    let pause = read_int(&config["teardown"], "pause_in_seconds".to_string(), 5) as u64;
+
+    let mut report = TeardownReport {
+        timestamp: UTC::now().timestamp(),
+        power_off: power_off,
+        steps: vec![],
+    };
+
+    info!("tearing down");
+    state_manager.send_set_teardown(true);
+    state_manager.send_set_cluster_open(false);
+    add_step(&mut report, "close-cluster", &state_manager.get_my_name());
+
+    let tasks = state_manager.request_list_tasks();
+    let (system_tasks, regular_tasks): (Vec<Task>, Vec<Task>) = tasks.into_iter().partition(|task| task.is_system_service);
+
+    for task in &regular_tasks {
+        stop_task(state_manager, task);
+        add_step(&mut report, "stop-task", &task.name);
+    }
+    thread::sleep(Duration::from_secs(pause));
+
+    for task in &system_tasks {
+        stop_task(state_manager, task);
+        add_step(&mut report, "stop-system-task", &task.name);
+    }
+    thread::sleep(Duration::from_secs(pause));
+
+    let nodes = state_manager.request_list_nodes();
+    match shutdown_network(&nodes) {
+        Ok(_) => add_step(&mut report, "shutdown-network", &"calico".to_string()),
+        Err(error) => add_step(&mut report, "shutdown-network-failed", &error.to_string()),
+    }
+
+    if power_off {
+        let ipmi_config = state_manager.get_ipmi_config();
+        for node in nodes.iter().filter(|node| node.node_type == "slave") {
+            shutdown_node(&ipmi_config.for_node(node), &node.management_ip);
+            state_manager.send_set_node_expected_power_state(node.name.clone(), PowerState::Off);
+            add_step(&mut report, "power-off", &node.name);
+        }
+    }
+
+    report
+}
+
+fn stop_task(state_manager: &StateManager, task: &Task) {
+    state_manager.send_remove_task_by_name(task.name.clone());
+    kill_task(&task.name);
+}
+
+fn add_step(report: &mut TeardownReport, step: &str, subject: &String) {
+    info!("teardown step={} subject={}", step, subject);
+    report.steps.push(TeardownStep {
+        step: step.to_string(),
+        subject: subject.clone(),
+        timestamp: UTC::now().timestamp(),
+    });
+}
@@ -21,17 +21,25 @@
 // THE SOFTWARE.
 
 extern crate mesos;
+#[cfg(feature = "fboss")]
 extern crate torc_fboss_client;
+#[cfg(feature = "snaproute")]
 extern crate torc_snaproute_client;
 extern crate protobuf;
 extern crate iron;
 extern crate hyper;
+extern crate openssl;
 extern crate router;
 extern crate clap;
 extern crate yaml_rust;
 extern crate rustc_serialize;
 extern crate uuid;
 extern crate chrono;
+extern crate kafka;
+extern crate env_logger;
+
+#[macro_use]
+extern crate log;
 
 #[macro_use]
 extern crate lazy_static;
@@ -42,3 +50,4 @@ pub mod api;
 pub mod utils;
 pub mod health;
 pub mod collaborator;
+pub mod agent;
@@ -21,18 +21,29 @@
 // THE SOFTWARE.
 
 extern crate torc_scheduler;
+extern crate chan_signal;
 
 #[macro_use]
 extern crate clap;
+#[macro_use]
+extern crate log;
 
+use chan_signal::Signal;
 use clap::{App, Arg};
+use std::process;
+use std::sync::{Arc, Mutex};
 use std::thread;
 use torc_scheduler::api::run_api;
-use torc_scheduler::health::run_health_checker;
-use torc_scheduler::scheduler::run_scheduler;
+use torc_scheduler::health::{Leadership, acquire_leadership, hold_leadership, release_leadership, run_bootstrap,
+                             run_firewall_sync, run_health_checker, run_shutdown, run_startup_gate, run_task_probes};
+use torc_scheduler::scheduler::{run_delegation, run_job_scheduler, run_preemption, run_scheduler};
 use torc_scheduler::state::StateManager;
+use torc_scheduler::utils::{init_logging, read_bool, read_string};
 
 fn main() {
+    // has to happen before any thread gets spawned, they inherit the blocked signals
+    let signal = chan_signal::notify(&[Signal::INT, Signal::TERM]);
+
     let matches = App::new("ToRC Scheduler")
         .about("Orchestrates core services on Facebook Wedge")
         .version(&crate_version!()[..])
@@ -48,6 +59,11 @@ fn main() {
             .required(false)
             .help("IP of controller")
             .takes_value(true))
+        .arg(Arg::with_name("BOOTSTRAP")
+            .short("b")
+            .long("bootstrap")
+            .required(false)
+            .help("Start the system services in order and open the cluster once they are running"))
         .arg(Arg::with_name("CONFIG")
             .short("c")
             .long("config")
@@ -56,23 +72,95 @@ fn main() {
             .takes_value(true))
         .get_matches();
 
+    let config_file = matches.value_of("CONFIG").unwrap_or("./config/config.yml");
+    let config = match StateManager::read_config_file(config_file.to_string()) {
+        Ok(config) => config,
+        Err(error) => {
+            // logging isn't set up before the config is read
+            eprintln!("invalid config error={}", error);
+            process::exit(1);
+        }
+    };
+    init_logging(&read_string(&config["logging"], "level".to_string()));
+    info!("config file path={}", config_file);
+
     let master_ip = matches.value_of("MASTER_IP").unwrap();
-    println!("Connecting to Master at: {}", master_ip);
+    info!("connecting to master ip={}", master_ip);
 
     let mut my_ip = matches.value_of("MY_IP").unwrap_or("");
     match my_ip.len() {
         0 => my_ip = master_ip.clone(),
         _ => {}
     }
-    println!("My IP set to : {}", my_ip);
+    info!("controller ip={}", my_ip);
 
-    let config_file = matches.value_of("CONFIG").unwrap_or("./config/config.yml");
-    println!("Config file: {}", config_file);
+    let bootstrap = matches.is_present("BOOTSTRAP");
+    let controller: Arc<Mutex<Option<StateManager>>> = Arc::new(Mutex::new(None));
+    let leading: Arc<Mutex<Option<Leadership>>> = Arc::new(Mutex::new(None));
+
+    if read_bool(&config["leader-election"], "enabled".to_string()) {
+        let my_name = config["name"].as_str().unwrap_or("torc-controller").to_string();
+        let (master_ip, my_ip, config_file) = (master_ip.to_string(), my_ip.to_string(), config_file.to_string());
+        let (controller, leading) = (controller.clone(), leading.clone());
+        let _ = thread::Builder::new()
+            .name("leader-election".to_string())
+            .spawn(move || {
+                let leadership = acquire_leadership(&config, &master_ip, &my_name, &my_ip);
+                *leading.lock().unwrap() = Some(leadership.clone());
+                let state_manager = start_controller(&master_ip,
+                                                     &my_ip,
+                                                     &config_file,
+                                                     Some(leadership.framework_id.clone()),
+                                                     bootstrap);
+                *controller.lock().unwrap() = Some(state_manager);
 
+                hold_leadership(&leadership);
+                // a standby may be leading already, keep out of its way
+                error!("lost leadership, exiting");
+                process::exit(1);
+            });
+    } else {
+        *controller.lock().unwrap() = Some(start_controller(master_ip, my_ip, config_file, None, bootstrap));
+    }
 
-    let state_manager = StateManager::new(master_ip.to_string(),
-                                          my_ip.to_string(),
-                                          config_file.to_string());
+    // wait for SIGINT or SIGTERM
+    let received = match signal.recv() {
+        Some(received) => format!("{:?}", received),
+        None => "closed".to_string(),
+    };
+    let state_manager = controller.lock().unwrap().clone();
+    match state_manager {
+        Some(state_manager) => {
+            let report = run_shutdown(&state_manager, &received);
+            info!("exiting report={:?}", report);
+        }
+        None => info!("exiting while standing by signal={}", received),
+    }
+    if let Some(ref leadership) = *leading.lock().unwrap() {
+        release_leadership(leadership);
+    }
+    process::exit(0);
+}
+
+// everything acting on the cluster, only started on the leader when leader election is enabled
+fn start_controller(master_ip: &str, my_ip: &str, config_file: &str, framework_id: Option<String>, bootstrap: bool)
+                    -> StateManager {
+    let state_manager = StateManager::new(master_ip.to_string(), my_ip.to_string(), config_file.to_string(), framework_id);
+
+    let gate_state_manager = state_manager.clone();
+    let _ = thread::Builder::new()
+        .name("startup-gate".to_string())
+        .spawn(move || run_startup_gate(&gate_state_manager));
+
+    if bootstrap {
+        let bootstrap_state_manager = state_manager.clone();
+        let _ = thread::Builder::new()
+            .name("bootstrap".to_string())
+            .spawn(move || {
+                let report = run_bootstrap(&bootstrap_state_manager);
+                info!("bootstrap finished report={:?}", report);
+            });
+    }
 
     let api_state_manager = state_manager.clone();
     let _ = thread::Builder::new()
@@ -84,12 +172,35 @@ fn main() {
         .name("scheduler".to_string())
         .spawn(move || run_scheduler(&scheduler_state_manager));
 
+    let probes_state_manager = state_manager.clone();
+    let _ = thread::Builder::new()
+        .name("task-probes".to_string())
+        .spawn(move || run_task_probes(&probes_state_manager));
+
+    let firewall_state_manager = state_manager.clone();
+    let _ = thread::Builder::new()
+        .name("firewall-sync".to_string())
+        .spawn(move || run_firewall_sync(&firewall_state_manager));
+
+    let delegation_state_manager = state_manager.clone();
+    let _ = thread::Builder::new()
+        .name("delegation".to_string())
+        .spawn(move || run_delegation(&delegation_state_manager));
+
+    let preemption_state_manager = state_manager.clone();
+    let _ = thread::Builder::new()
+        .name("preemption".to_string())
+        .spawn(move || run_preemption(&preemption_state_manager));
+
+    let jobs_state_manager = state_manager.clone();
+    let _ = thread::Builder::new()
+        .name("jobs".to_string())
+        .spawn(move || run_job_scheduler(&jobs_state_manager));
+
     let health_state_manager = state_manager.clone();
-    let health_check_runner = thread::Builder::new()
+    let _ = thread::Builder::new()
         .name("health".to_string())
-        .spawn(move || run_health_checker(&health_state_manager))
-        .unwrap();
+        .spawn(move || run_health_checker(&health_state_manager));
 
-    // wait forever
-    let _ = health_check_runner.join();
+    state_manager
 }
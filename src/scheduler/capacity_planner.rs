@@ -0,0 +1,243 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use chrono::UTC;
+use collaborator::{MesosAgentInfo, find_mesos_agent};
+use state::{Node, SLA, Task};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct NodeCapacity {
+    pub name: String,
+    pub node_type: String,
+    pub node_function: String,
+    pub active: bool,
+    pub cpus: f64,
+    pub mem: f64,
+    pub reserved_cpus: f64,
+    pub reserved_mem: f64,
+    pub free_cpus: f64,
+    pub free_mem: f64,
+    pub tasks: Vec<String>,
+}
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct NodeLoss {
+    pub node: String,
+    pub displaced_tasks: Vec<String>,
+    pub unplaceable_tasks: Vec<String>,
+    pub survivable: bool,
+}
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct ServiceHeadroom {
+    pub name: String,
+    pub cpu: f64,
+    pub memory: f64,
+    pub additional_instances: i64,
+}
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct CapacityReport {
+    pub timestamp: i64,
+    pub total_cpus: f64,
+    pub total_mem: f64,
+    pub reserved_cpus: f64,
+    pub reserved_mem: f64,
+    pub pending_cpus: f64,
+    pub pending_mem: f64,
+    pub unplaceable_pending: Vec<String>,
+    pub survives_any_node_loss: bool,
+    pub node_loss: Vec<NodeLoss>,
+    pub headroom: Vec<ServiceHeadroom>,
+    pub nodes: Vec<NodeCapacity>,
+}
+
+// capacities come from what the mesos agents advertise, reservations from the running tasks;
+// pending tasks are assumed to get placed before anything else
+pub fn plan_capacity(nodes: &Vec<Node>, agents: &Vec<MesosAgentInfo>, running: &Vec<Task>, pending: &Vec<Task>) -> CapacityReport {
+    let capacities = node_capacities(nodes, agents, running);
+
+    let mut report = CapacityReport {
+        timestamp: UTC::now().timestamp(),
+        total_cpus: capacities.iter().map(|node| node.cpus).sum(),
+        total_mem: capacities.iter().map(|node| node.mem).sum(),
+        reserved_cpus: capacities.iter().map(|node| node.reserved_cpus).sum(),
+        reserved_mem: capacities.iter().map(|node| node.reserved_mem).sum(),
+        pending_cpus: pending.iter().map(|task| task.cpu).sum(),
+        pending_mem: pending.iter().map(|task| task.memory).sum(),
+        unplaceable_pending: vec![],
+        survives_any_node_loss: true,
+        node_loss: vec![],
+        headroom: vec![],
+        nodes: capacities.clone(),
+    };
+
+    let mut after_pending = capacities.clone();
+    report.unplaceable_pending = place(pending, &mut after_pending);
+
+    for lost in capacities.iter().filter(|node| node.active) {
+        // singletons run once per node anyway, losing the node doesn't move them
+        let displaced: Vec<Task> = running.iter()
+            .filter(|task| lost.tasks.contains(&task.name) && task.sla == SLA::None)
+            .cloned()
+            .collect();
+
+        let mut remaining: Vec<NodeCapacity> = after_pending.iter().filter(|node| node.name != lost.name).cloned().collect();
+        let unplaceable = place(&displaced, &mut remaining);
+
+        report.survives_any_node_loss = report.survives_any_node_loss && unplaceable.is_empty();
+        report.node_loss.push(NodeLoss {
+            node: lost.name.clone(),
+            displaced_tasks: displaced.iter().map(|task| task.name.clone()).collect(),
+            survivable: unplaceable.is_empty(),
+            unplaceable_tasks: unplaceable,
+        });
+    }
+
+    report.headroom = headroom(running, pending, &after_pending);
+
+    report
+}
+
+fn node_capacities(nodes: &Vec<Node>, agents: &Vec<MesosAgentInfo>, running: &Vec<Task>) -> Vec<NodeCapacity> {
+    let mut result = vec![];
+
+    for node in nodes {
+        let (cpus, mem) = match find_mesos_agent(agents, node) {
+            Some(agent) if node.active => (agent.cpus, agent.mem),
+            _ => (0.0, 0.0),
+        };
+
+        let mut capacity = NodeCapacity {
+            name: node.name.clone(),
+            node_type: node.node_type.clone(),
+            node_function: node.node_function.clone(),
+            active: node.active,
+            cpus: cpus,
+            mem: mem,
+            reserved_cpus: 0.0,
+            reserved_mem: 0.0,
+            free_cpus: 0.0,
+            free_mem: 0.0,
+            tasks: vec![],
+        };
+
+        for task in running.iter().filter(|task| !task.slave_id.is_empty() && task.slave_id == node.slave_id) {
+            capacity.reserved_cpus += task.cpu;
+            capacity.reserved_mem += task.memory;
+            capacity.tasks.push(task.name.clone());
+        }
+
+        capacity.free_cpus = (capacity.cpus - capacity.reserved_cpus).max(0.0);
+        capacity.free_mem = (capacity.mem - capacity.reserved_mem).max(0.0);
+        result.push(capacity);
+    }
+
+    result
+}
+
+// first fit decreasing onto the node with the most free cpu, returns the tasks that didn't fit
+fn place(tasks: &Vec<Task>, nodes: &mut Vec<NodeCapacity>) -> Vec<String> {
+    let mut ordered = tasks.clone();
+    ordered.sort_by(|a, b| b.cpu.partial_cmp(&a.cpu).unwrap_or(Ordering::Equal));
+
+    let mut unplaceable = vec![];
+    for task in ordered {
+        let mut target: Option<usize> = None;
+        for (index, node) in nodes.iter().enumerate() {
+            if !node.active || !fits(task.cpu, task.memory, node) || !matches_constraints(&task, node) {
+                continue;
+            }
+            target = match target {
+                Some(best) if nodes[best].free_cpus >= node.free_cpus => Some(best),
+                _ => Some(index),
+            };
+        }
+
+        match target {
+            Some(index) => {
+                let node = &mut nodes[index];
+                node.free_cpus -= task.cpu;
+                node.free_mem -= task.memory;
+                node.tasks.push(task.name.clone());
+            }
+            None => unplaceable.push(task.name.clone()),
+        }
+    }
+
+    unplaceable
+}
+
+// how many more instances of each service would fit on top of what's running and pending
+fn headroom(running: &Vec<Task>, pending: &Vec<Task>, nodes: &Vec<NodeCapacity>) -> Vec<ServiceHeadroom> {
+    let mut services: BTreeMap<String, Task> = BTreeMap::new();
+    for task in running.iter().chain(pending.iter()).filter(|task| task.sla == SLA::None) {
+        let name = match task.array_name.is_empty() {
+            true => task.name.clone(),
+            false => task.array_name.clone(),
+        };
+        services.entry(name).or_insert(task.clone());
+    }
+
+    let mut result = vec![];
+    for (name, task) in services {
+        if task.cpu <= 0.0 && task.memory <= 0.0 {
+            continue;
+        }
+
+        let mut additional_instances = 0;
+        for node in nodes.iter().filter(|node| node.active && matches_constraints(&task, node)) {
+            let by_cpu = match task.cpu > 0.0 {
+                true => (node.free_cpus / task.cpu).floor() as i64,
+                false => i64::max_value(),
+            };
+            let by_mem = match task.memory > 0.0 {
+                true => (node.free_mem / task.memory).floor() as i64,
+                false => i64::max_value(),
+            };
+            additional_instances += by_cpu.min(by_mem);
+        }
+
+        result.push(ServiceHeadroom {
+            name: name,
+            cpu: task.cpu,
+            memory: task.memory,
+            additional_instances: additional_instances,
+        });
+    }
+
+    result
+}
+
+fn fits(cpu: f64, memory: f64, node: &NodeCapacity) -> bool {
+    cpu <= node.free_cpus && memory <= node.free_mem
+}
+
+// the scheduler rewrites node_name for tasks placed by type or function, so only a bare node_name pins a task
+fn matches_constraints(task: &Task, node: &NodeCapacity) -> bool {
+    let pinned = task.node_type.is_empty() && task.node_function.is_empty() && !task.node_name.is_empty();
+
+    (!pinned || task.node_name == node.name) && (task.node_type.is_empty() || task.node_type == node.node_type) &&
+    (task.node_function.is_empty() || task.node_function == node.node_function)
+}
@@ -0,0 +1,119 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+// node_function==storage, node_type!=wedge, group==rack-a, affinity:task=redis, anti-affinity:task=redis,
+// gpu_health==healthy (or failed, none), gpu_driver==535.104.05 (or a prefix like 535)
+#[derive(Clone, Debug, PartialEq)]
+pub enum Constraint {
+    Attribute {
+        key: String,
+        value: String,
+        equal: bool,
+    },
+    Affinity(String),
+    AntiAffinity(String),
+}
+
+static ATTRIBUTES: &'static [&'static str] = &["node_name", "node_type", "node_function", "group", "gpu_health", "gpu_driver"];
+
+// what an offer tells about the node, tasks holds the names and array names of the tasks on it
+pub struct NodeFacts<'a> {
+    pub name: &'a str,
+    pub node_type: &'a str,
+    pub node_function: &'a str,
+    pub groups: &'a Vec<String>,
+    pub tasks: &'a Vec<String>,
+    pub gpu_health: &'a str,
+    pub gpu_driver: &'a str,
+}
+
+impl Constraint {
+    pub fn parse(source: &str) -> Result<Constraint, String> {
+        let source = source.trim();
+
+        if source.starts_with("affinity:task=") {
+            return task_reference(&source["affinity:task=".len()..]).map(Constraint::Affinity);
+        }
+        if source.starts_with("anti-affinity:task=") {
+            return task_reference(&source["anti-affinity:task=".len()..]).map(Constraint::AntiAffinity);
+        }
+
+        let (key, value, equal) = match (source.find("=="), source.find("!=")) {
+            (Some(position), _) => (&source[..position], &source[position + 2..], true),
+            (None, Some(position)) => (&source[..position], &source[position + 2..], false),
+            (None, None) => return Err(format!("invalid constraint: {}", source)),
+        };
+
+        let key = key.trim();
+        let value = value.trim();
+        if !ATTRIBUTES.contains(&key) {
+            return Err(format!("unknown attribute in constraint: {}", key));
+        }
+        if value.is_empty() {
+            return Err(format!("missing value in constraint: {}", source));
+        }
+
+        Ok(Constraint::Attribute {
+            key: key.to_string(),
+            value: value.to_string(),
+            equal: equal,
+        })
+    }
+
+    pub fn is_satisfied(&self, node: &NodeFacts) -> bool {
+        match *self {
+            Constraint::Attribute { ref key, ref value, equal } => {
+                let matches = match key.as_ref() {
+                    "node_name" => node.name == value,
+                    "node_type" => node.node_type == value,
+                    "node_function" => node.node_function == value,
+                    "group" => node.groups.contains(value),
+                    "gpu_health" => node.gpu_health == value,
+                    "gpu_driver" => driver_matches(node.gpu_driver, value),
+                    _ => false,
+                };
+                matches == equal
+            }
+            Constraint::Affinity(ref task) => node.tasks.contains(task),
+            Constraint::AntiAffinity(ref task) => !node.tasks.contains(task),
+        }
+    }
+}
+
+// 535 and 535.104 take any 535.104.05, 53 doesn't
+fn driver_matches(driver: &str, version: &str) -> bool {
+    driver == version || driver.starts_with(&format!("{}.", version))
+}
+
+// constraints that don't parse were already reported when the task was read and are ignored here
+pub fn satisfies_constraints(constraints: &Vec<String>, node: &NodeFacts) -> bool {
+    constraints.iter()
+        .filter_map(|source| Constraint::parse(source).ok())
+        .all(|constraint| constraint.is_satisfied(node))
+}
+
+fn task_reference(name: &str) -> Result<String, String> {
+    match name.trim().is_empty() {
+        true => Err("missing task in constraint".to_string()),
+        false => Ok(name.trim().to_string()),
+    }
+}
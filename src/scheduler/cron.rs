@@ -0,0 +1,115 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use chrono::{DateTime, Datelike, Timelike, UTC};
+
+// minute hour day-of-month month day-of-week, each field * or a list of values and ranges with an
+// optional /step, e.g. */5, 1-5 or 0,30. sundays are 0 or 7. evaluated in utc.
+#[derive(Clone, Debug)]
+pub struct CronSchedule {
+    minutes: Vec<bool>,
+    hours: Vec<bool>,
+    days_of_month: Vec<bool>,
+    months: Vec<bool>,
+    days_of_week: Vec<bool>,
+    // cron runs a job on either day when both day fields are restricted
+    restricted_days: (bool, bool),
+}
+
+impl CronSchedule {
+    pub fn parse(source: &str) -> Result<CronSchedule, String> {
+        let fields: Vec<&str> = source.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!("schedule needs 5 fields: {}", source));
+        }
+
+        let mut days_of_week = try!(parse_field(fields[4], 0, 7));
+        if days_of_week[7] {
+            days_of_week[0] = true;
+        }
+
+        Ok(CronSchedule {
+            minutes: try!(parse_field(fields[0], 0, 59)),
+            hours: try!(parse_field(fields[1], 0, 23)),
+            days_of_month: try!(parse_field(fields[2], 1, 31)),
+            months: try!(parse_field(fields[3], 1, 12)),
+            days_of_week: days_of_week,
+            // like cron a day field starting with *, e.g. */2, doesn't count as restricted
+            restricted_days: (!fields[2].starts_with('*'), !fields[4].starts_with('*')),
+        })
+    }
+
+    pub fn matches(&self, time: &DateTime<UTC>) -> bool {
+        let day_of_month = self.days_of_month[time.day() as usize];
+        let day_of_week = self.days_of_week[time.weekday().num_days_from_sunday() as usize];
+        let day = match self.restricted_days {
+            (true, true) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        };
+
+        self.minutes[time.minute() as usize] && self.hours[time.hour() as usize] && self.months[time.month() as usize] && day
+    }
+}
+
+// indexed by value, so a field from 1 to 12 gets 13 entries with the first one unused
+fn parse_field(field: &str, min: usize, max: usize) -> Result<Vec<bool>, String> {
+    let mut values = vec![false; max + 1];
+
+    for part in field.split(',') {
+        let (range, step) = match part.find('/') {
+            Some(position) => {
+                let step = try!(part[position + 1..].parse::<usize>().map_err(|_| format!("invalid step: {}", part)));
+                (&part[..position], step)
+            }
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err(format!("invalid step: {}", part));
+        }
+
+        let (first, last) = match range {
+            "*" => (min, max),
+            _ => {
+                let bounds: Vec<&str> = range.splitn(2, '-').collect();
+                let first = try!(bounds[0].parse::<usize>().map_err(|_| format!("invalid value: {}", part)));
+                let last = match bounds.get(1) {
+                    Some(last) => try!(last.parse::<usize>().map_err(|_| format!("invalid value: {}", part))),
+                    // 5/10 starts at 5 and goes on to the end
+                    None if step > 1 => max,
+                    None => first,
+                };
+                (first, last)
+            }
+        };
+        if first < min || last > max || first > last {
+            return Err(format!("{} is out of {}-{}", part, min, max));
+        }
+
+        let mut value = first;
+        while value <= last {
+            values[value] = true;
+            value += step;
+        }
+    }
+
+    Ok(values)
+}
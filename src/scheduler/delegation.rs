@@ -0,0 +1,177 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use chrono::UTC;
+use collaborator::{delegate_task, delegated_task_state, delete_delegated_task, kill_delegated_task};
+use state::{SLA, StateManager, Task, TaskState};
+use std::thread;
+use std::time::Duration;
+use utils::{read_int, read_string};
+use yaml_rust::Yaml;
+
+// what a delegated task shows while the peer doesn't answer for it
+const UNKNOWN: &'static str = "Unknown";
+
+struct Peer {
+    name: String,
+    url: String,
+    token: String,
+}
+
+// a task that found no place here for delegate_after_in_seconds is handed to the first peer controller
+// taking it, afterwards the peer is asked for its state until the task ends there. a peer that doesn't
+// know the task might just have restarted, it's only taken back once unknown for lost_after_in_seconds.
+pub fn run_delegation(state_manager: &StateManager) {
+    let config = state_manager.get_yaml();
+    let federation = &config["federation"];
+    let delegate_after = read_int(federation, "delegate_after_in_seconds".to_string(), 300);
+    let poll_interval = read_int(federation, "poll_interval_in_seconds".to_string(), 30) as u64;
+    let lost_after = read_int(federation, "lost_after_in_seconds".to_string(), 600);
+
+    let peers = read_peers(&config);
+    if peers.is_empty() {
+        return;
+    }
+
+    let my_name = state_manager.get_my_name();
+    loop {
+        thread::sleep(Duration::from_secs(poll_interval));
+
+        let now = UTC::now().timestamp();
+        for task in state_manager.request_list_tasks() {
+            if task.controller != my_name {
+                continue;
+            }
+            match task.state {
+                TaskState::Requested if is_delegable(&task) && task.last_update + delegate_after < now => {
+                    delegate(state_manager, &peers, &task)
+                }
+                TaskState::Delegated => follow(state_manager, &peers, &task, lost_after, now),
+                _ => {}
+            }
+        }
+    }
+}
+
+// tasks bound to one of our nodes or to every node can't run elsewhere,
+// and a task a peer delegated to us doesn't get passed on
+fn is_delegable(task: &Task) -> bool {
+    let pinned = !task.node_name.is_empty() && task.node_type.is_empty() && task.node_function.is_empty();
+    !pinned && !task.is_system_service && task.sla == SLA::None && task.delegated_from.is_empty()
+}
+
+fn delegate(state_manager: &StateManager, peers: &Vec<Peer>, task: &Task) {
+    for peer in peers {
+        match delegate_task(&peer.url, &peer.token, task) {
+            Ok(_) => {
+                let requested = format!("{:?}", TaskState::Requested);
+                // deleted or placed here while the peer took it, the peer's copy would run on its own
+                if !state_manager.send_update_delegation(task.name.clone(), "".to_string(), peer.name.clone(), requested) {
+                    info!("task changed while delegating, withdrawing it task={} peer={}", task.name, peer.name);
+                    if let Err(error) = delete_delegated_task(&peer.url, &peer.token, &task.name) {
+                        warn!("can't delete delegated task task={} peer={} error={}", task.name, peer.name, error);
+                    }
+                }
+                return;
+            }
+            Err(error) => warn!("can't delegate task={} peer={} error={}", task.name, peer.name, error),
+        }
+    }
+}
+
+// deleting or killing a delegated task here has to reach the peer running it
+pub fn forward_delete(state_manager: &StateManager, task: &Task) {
+    if let Some(peer) = read_peers(&state_manager.get_yaml()).into_iter().find(|peer| peer.name == task.delegated_to) {
+        if let Err(error) = delete_delegated_task(&peer.url, &peer.token, &task.name) {
+            warn!("can't delete delegated task task={} peer={} error={}", task.name, peer.name, error);
+        }
+    }
+}
+
+pub fn forward_kill(state_manager: &StateManager, task: &Task) {
+    if let Some(peer) = read_peers(&state_manager.get_yaml()).into_iter().find(|peer| peer.name == task.delegated_to) {
+        if let Err(error) = kill_delegated_task(&peer.url, &peer.token, &task.name) {
+            warn!("can't kill delegated task task={} peer={} error={}", task.name, peer.name, error);
+        }
+    }
+}
+
+fn read_peers(config: &Yaml) -> Vec<Peer> {
+    match config["federation"]["peers"].as_vec() {
+        Some(entries) => {
+            entries.iter()
+                .map(|entry| {
+                    Peer {
+                        name: read_string(entry, "name".to_string()),
+                        url: read_string(entry, "url".to_string()),
+                        token: read_string(entry, "token".to_string()),
+                    }
+                })
+                .filter(|peer| !peer.name.is_empty() && !peer.url.is_empty())
+                .collect()
+        }
+        None => vec![],
+    }
+}
+
+// the peer keeps the record of a delegated job that ended, it's cleaned up there once taken over here
+fn follow(state_manager: &StateManager, peers: &Vec<Peer>, task: &Task, lost_after: i64, now: i64) {
+    let peer = match peers.iter().find(|peer| peer.name == task.delegated_to) {
+        Some(peer) => peer,
+        None => {
+            info!("peer is gone, taking delegated task back task={} peer={}", task.name, task.delegated_to);
+            state_manager.send_update_delegation(task.name.clone(), task.delegated_to.clone(), "".to_string(), "".to_string());
+            return;
+        }
+    };
+
+    match delegated_task_state(&peer.url, &peer.token, &task.name) {
+        Ok(Some(remote_state)) => {
+            match remote_state.as_ref() {
+                "Finished" => {
+                    info!("delegated task finished task={} peer={}", task.name, peer.name);
+                    state_manager.send_update_task_state(task.name.clone(), TaskState::Finished);
+                    forward_delete(state_manager, task);
+                }
+                "Failed" | "NetworkFailed" | "Deleted" => {
+                    state_manager.send_fail_task(task.name.clone(), format!("{} on {}", remote_state, peer.name));
+                    forward_delete(state_manager, task);
+                }
+                _ if remote_state != task.remote_state => {
+                    state_manager.send_update_delegation(task.name.clone(), peer.name.clone(), peer.name.clone(), remote_state);
+                }
+                _ => {}
+            }
+        }
+        // last_update got set when the task turned unknown, that's where the deadline starts
+        Ok(None) if task.remote_state != UNKNOWN => {
+            warn!("peer doesn't know delegated task task={} peer={}", task.name, peer.name);
+            state_manager.send_update_delegation(task.name.clone(), peer.name.clone(), peer.name.clone(), UNKNOWN.to_string());
+        }
+        Ok(None) if task.last_update + lost_after < now => {
+            warn!("delegated task lost, taking it back task={} peer={}", task.name, peer.name);
+            state_manager.send_update_delegation(task.name.clone(), peer.name.clone(), "".to_string(), "".to_string());
+        }
+        Ok(None) => {}
+        Err(error) => warn!("can't follow delegated task task={} peer={} error={}", task.name, peer.name, error),
+    }
+}
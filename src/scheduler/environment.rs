@@ -0,0 +1,126 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use chrono::UTC;
+use collaborator::read_keys;
+use mesos::proto::{Environment, Environment_Variable};
+use protobuf;
+use state::{Task, is_variable_name};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+// a failed read is tried again after this, not on every offer
+const RETRY_IN_SECONDS: i64 = 10;
+
+enum Secrets {
+    Reading,
+    Read(BTreeMap<String, String>),
+    Failed(String, i64),
+}
+
+// secrets are read from consul off the offer path, a task that needs them is held back until they are there.
+// they are handed out once for the launch they were read for and never stored with the task
+pub struct EnvironmentResolver {
+    master_ip: String,
+    secrets: Arc<Mutex<HashMap<String, Secrets>>>,
+}
+
+impl EnvironmentResolver {
+    pub fn new(master_ip: &String) -> EnvironmentResolver {
+        EnvironmentResolver {
+            master_ip: master_ip.clone(),
+            secrets: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // the plain variables of the task with its secrets on top, a secret with the same name as a plain variable wins
+    pub fn task_environment(&self, task: &Task) -> Result<Option<Environment>, String> {
+        let mut env = task.env.clone();
+        if !task.env_from_consul_kv.is_empty() {
+            for (name, value) in try!(self.take_secrets(task)) {
+                env.insert(name, value);
+            }
+        }
+
+        if env.is_empty() {
+            return Ok(None);
+        }
+
+        let variables = env.into_iter()
+            .map(|(name, value)| {
+                let mut variable = Environment_Variable::new();
+                variable.set_name(name);
+                variable.set_value(value);
+                variable
+            })
+            .collect();
+        let mut environment = Environment::new();
+        environment.set_variables(protobuf::RepeatedField::from_vec(variables));
+        Ok(Some(environment))
+    }
+
+    fn take_secrets(&self, task: &Task) -> Result<BTreeMap<String, String>, String> {
+        let mut secrets = self.secrets.lock().unwrap();
+        match secrets.remove(&task.name) {
+            Some(Secrets::Read(read)) => return Ok(read),
+            Some(Secrets::Reading) => {
+                secrets.insert(task.name.clone(), Secrets::Reading);
+                return Err(format!("still reading {} from consul", task.env_from_consul_kv));
+            }
+            Some(Secrets::Failed(error, failed_at)) => {
+                if UTC::now().timestamp() - failed_at < RETRY_IN_SECONDS {
+                    secrets.insert(task.name.clone(), Secrets::Failed(error.clone(), failed_at));
+                    return Err(error);
+                }
+            }
+            None => {}
+        }
+
+        secrets.insert(task.name.clone(), Secrets::Reading);
+        let shared = self.secrets.clone();
+        let master_ip = self.master_ip.clone();
+        let task_name = task.name.clone();
+        let prefix = task.env_from_consul_kv.clone();
+        thread::spawn(move || {
+            let read = match read_secrets(&master_ip, &prefix) {
+                Ok(read) => Secrets::Read(read),
+                Err(error) => Secrets::Failed(error, UTC::now().timestamp()),
+            };
+            shared.lock().unwrap().insert(task_name, read);
+        });
+        Err(format!("reading {} from consul", task.env_from_consul_kv))
+    }
+}
+
+fn read_secrets(master_ip: &String, prefix: &String) -> Result<BTreeMap<String, String>, String> {
+    let keys = try!(read_keys(master_ip, prefix).map_err(|error| format!("can't read {} from consul: {}", prefix, error)));
+
+    let mut secrets = BTreeMap::new();
+    for (name, value) in keys {
+        if !is_variable_name(&name) {
+            return Err(format!("{}/{} is not a valid environment variable name", prefix.trim_matches('/'), name));
+        }
+        secrets.insert(name, value);
+    }
+    Ok(secrets)
+}
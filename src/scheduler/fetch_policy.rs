@@ -0,0 +1,209 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use chrono::UTC;
+use collaborator::verifying_client;
+use hyper::Client;
+use mesos::proto::CommandInfo_URI;
+use openssl::crypto::hash::{Type, hash};
+use state::Task;
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use utils::{read_bool, read_string, read_string_list};
+use yaml_rust::Yaml;
+
+// a file that didn't match or couldn't be downloaded is checked again after this, not on every offer
+const RETRY_IN_SECONDS: i64 = 60;
+const DOWNLOAD_TIMEOUT_IN_SECONDS: u64 = 60;
+
+enum Verification {
+    Verifying,
+    Verified,
+    Failed(String, i64),
+}
+
+// a file the mesos fetcher puts into the sandbox of the tasks it's meant for, services and nodes
+// narrow that down by service name and node name, empty means all of them
+#[derive(Clone, Debug)]
+pub struct FetchUri {
+    pub value: String,
+    pub checksum: String,
+    pub extract: bool,
+    pub executable: bool,
+    pub cache: bool,
+    pub services: Vec<String>,
+    pub nodes: Vec<String>,
+}
+
+// the fetcher has no notion of checksums, the controller downloads a file with one itself and
+// holds back the tasks that need it until it matched
+#[derive(Clone)]
+pub struct FetchPolicy {
+    uris: Vec<FetchUri>,
+    verifications: Arc<Mutex<HashMap<String, Verification>>>,
+}
+
+impl FetchPolicy {
+    pub fn new(config: &Yaml) -> FetchPolicy {
+        let fetcher = &config["fetcher"];
+        // the agents keep cached files across launches, a mass launch fetches each of them once per node
+        let cache_by_default = fetcher["cache"].as_bool().unwrap_or(true);
+
+        let mut uris = vec![];
+        for entry in fetcher["uris"].as_vec().unwrap_or(&vec![]) {
+            let value = read_string(entry, "value".to_string());
+            if value.is_empty() {
+                warn!("fetcher uri without value skipped entry={:?}", entry);
+                continue;
+            }
+            let checksum = read_string(entry, "checksum".to_string());
+            if !checksum.is_empty() && !is_valid_checksum(&checksum) {
+                warn!("fetcher uri with invalid checksum skipped uri={} checksum={}", value, checksum);
+                continue;
+            }
+            if !checksum.is_empty() && !value.starts_with("http://") && !value.starts_with("https://") {
+                warn!("fetcher uri with checksum skipped, only http and https can be verified uri={}", value);
+                continue;
+            }
+
+            uris.push(FetchUri {
+                value: value,
+                checksum: checksum,
+                extract: read_bool(entry, "extract".to_string()),
+                executable: read_bool(entry, "executable".to_string()),
+                cache: entry["cache"].as_bool().unwrap_or(cache_by_default),
+                services: read_string_list(entry, "services".to_string()),
+                nodes: read_string_list(entry, "nodes".to_string()),
+            });
+        }
+
+        FetchPolicy {
+            uris: uris,
+            verifications: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // an error while a file with a checksum isn't verified yet or didn't match, the task waits for the next offer
+    pub fn uris_for(&self, task: &Task, node_name: &str) -> Result<Vec<CommandInfo_URI>, String> {
+        let mut result = vec![];
+        let wanted = self.uris
+            .iter()
+            .filter(|uri| uri.services.is_empty() || uri.services.iter().any(|service| is_part_of(task, service)))
+            .filter(|uri| uri.nodes.is_empty() || uri.nodes.iter().any(|node| node == node_name));
+        for uri in wanted {
+            if !uri.checksum.is_empty() {
+                try!(self.verify(uri));
+            }
+
+            let mut fetched = CommandInfo_URI::new();
+            fetched.set_value(uri.value.clone());
+            fetched.set_extract(uri.extract);
+            fetched.set_executable(uri.executable);
+            fetched.set_cache(uri.cache);
+            result.push(fetched);
+        }
+        Ok(result)
+    }
+
+    // a file that matched once stays verified, the fetcher cache of the agents keeps serving that one anyway.
+    // publishing a new version under the same uri needs a controller restart, a new uri is the better way
+    fn verify(&self, uri: &FetchUri) -> Result<(), String> {
+        let mut verifications = self.verifications.lock().unwrap();
+        match verifications.get(&uri.value) {
+            Some(&Verification::Verified) => return Ok(()),
+            Some(&Verification::Verifying) => return Err(format!("still verifying {}", uri.value)),
+            Some(&Verification::Failed(ref error, failed_at)) if UTC::now().timestamp() - failed_at < RETRY_IN_SECONDS => {
+                return Err(error.clone())
+            }
+            _ => {}
+        }
+
+        verifications.insert(uri.value.clone(), Verification::Verifying);
+        let shared = self.verifications.clone();
+        let uri = uri.clone();
+        thread::spawn(move || {
+            let verification = match download_checksum(&uri) {
+                Ok(ref checksum) if *checksum == uri.checksum.to_lowercase() => {
+                    info!("fetcher uri verified uri={} checksum={}", uri.value, checksum);
+                    Verification::Verified
+                }
+                Ok(checksum) => {
+                    error!("fetcher uri checksum mismatch uri={} expected={} actual={}", uri.value, uri.checksum, checksum);
+                    Verification::Failed(format!("{} has checksum {}, expected {}", uri.value, checksum, uri.checksum),
+                                         UTC::now().timestamp())
+                }
+                Err(error) => {
+                    warn!("can't verify fetcher uri uri={} error={}", uri.value, error);
+                    Verification::Failed(error, UTC::now().timestamp())
+                }
+            };
+            shared.lock().unwrap().insert(uri.value.clone(), verification);
+        });
+        Err(format!("verifying {}", uri.value))
+    }
+}
+
+// <algorithm>:<hex digest> of what the uri serves right now
+fn download_checksum(uri: &FetchUri) -> Result<String, String> {
+    let algorithm = &uri.checksum[..uri.checksum.find(':').unwrap_or(0)];
+    let hash_type = match algorithm.to_lowercase().as_ref() {
+        "md5" => Type::MD5,
+        "sha1" => Type::SHA1,
+        "sha256" => Type::SHA256,
+        "sha512" => Type::SHA512,
+        _ => return Err(format!("unsupported checksum algorithm {}", algorithm)),
+    };
+
+    let mut client = match uri.value.starts_with("https://") {
+        true => try!(verifying_client(&"".to_string())),
+        false => Client::new(),
+    };
+    client.set_read_timeout(Some(Duration::from_secs(DOWNLOAD_TIMEOUT_IN_SECONDS)));
+    let mut response = try!(client.get(&uri.value).send().map_err(|error| format!("can't download {}: {}", uri.value, error)));
+    if !response.status.is_success() {
+        return Err(format!("can't download {}: status {}", uri.value, response.status));
+    }
+    let mut data = vec![];
+    try!(response.read_to_end(&mut data).map_err(|error| format!("can't download {}: {}", uri.value, error)));
+
+    let digest: String = hash(hash_type, &data).iter().map(|byte| format!("{:02x}", byte)).collect();
+    Ok(format!("{}:{}", algorithm.to_lowercase(), digest))
+}
+
+fn is_part_of(task: &Task, service: &String) -> bool {
+    task.name == *service || task.replica_group == *service || task.array_name == *service
+}
+
+// <algorithm>:<hex digest>, e.g. sha256:9f86d0..., md5, sha1, sha256 and sha512 can be verified
+fn is_valid_checksum(checksum: &str) -> bool {
+    match checksum.find(':') {
+        Some(position) => {
+            let (algorithm, digest) = (&checksum[..position], &checksum[position + 1..]);
+            ["md5", "sha1", "sha256", "sha512"].contains(&&*algorithm.to_lowercase()) && !digest.is_empty() &&
+            digest.chars().all(|c| c.is_digit(16))
+        }
+        None => false,
+    }
+}
@@ -20,8 +20,28 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 // THE SOFTWARE.
 
+pub use self::capacity_planner::{CapacityReport, NodeCapacity, NodeLoss, ServiceHeadroom, plan_capacity};
+pub use self::constraint::{Constraint, NodeFacts, satisfies_constraints};
+pub use self::delegation::{forward_delete, forward_kill, run_delegation};
+pub use self::fetch_policy::FetchPolicy;
+pub use self::offer_policy::OfferPolicy;
+pub use self::placement_policy::PlacementPolicy;
+pub use self::preemption::run_preemption;
 pub use self::run_scheduler::run_scheduler;
+pub use self::scaling::{ScaleReport, scale_service};
+pub use self::scheduled_jobs::{JobRun, ScheduledJob, ScheduledJobs, run_job_scheduler};
 pub use self::scheduler_impl::TorcScheduler;
 
+mod capacity_planner;
+mod constraint;
+mod cron;
+mod delegation;
+mod environment;
+mod fetch_policy;
+mod offer_policy;
 mod scheduler_impl;
 mod run_scheduler;
+mod scaling;
+mod scheduled_jobs;
+mod placement_policy;
+mod preemption;
@@ -0,0 +1,86 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use mesos::proto::Filters;
+use state::Task;
+use utils::{read_float, read_int};
+use yaml_rust::Yaml;
+
+// how offers get matched and handed back. the requested tasks are served by priority, a task gains a
+// priority step for every aging_interval_in_seconds it waits so lower ones get their turn, and within
+// a priority the one waiting longest goes first. an offer takes up to max_tasks_per_offer tasks, a task
+// that doesn't fit what's left of it leaves it to the smaller ones behind.
+#[derive(Clone, Debug)]
+pub struct OfferPolicy {
+    pub max_tasks_per_offer: usize,
+    aging_interval_in_seconds: i64,
+    decline_refuse_seconds: f64,
+    launch_refuse_seconds: f64,
+}
+
+impl OfferPolicy {
+    pub fn new(config: &Yaml) -> OfferPolicy {
+        let offers = &config["offers"];
+
+        OfferPolicy {
+            max_tasks_per_offer: read_int(offers, "max_tasks_per_offer".to_string(), 5).max(1) as usize,
+            aging_interval_in_seconds: read_int(offers, "aging_interval_in_seconds".to_string(), 60),
+            decline_refuse_seconds: read_float(offers, "decline_refuse_seconds".to_string(), 5.0),
+            launch_refuse_seconds: read_float(offers, "launch_refuse_seconds".to_string(), 1.0),
+        }
+    }
+
+    pub fn queue(&self, mut tasks: Vec<Task>, now: i64) -> Vec<Task> {
+        tasks.sort_by(|a, b| {
+            self.effective_priority(b, now)
+                .cmp(&self.effective_priority(a, now))
+                .then(a.last_update.cmp(&b.last_update))
+        });
+        tasks
+    }
+
+    pub fn effective_priority(&self, task: &Task, now: i64) -> i64 {
+        match self.aging_interval_in_seconds {
+            interval if interval > 0 => task.priority as i64 + (now - task.last_update).max(0) / interval,
+            _ => task.priority as i64,
+        }
+    }
+
+    // how long mesos holds back the resources of a declined offer from us
+    pub fn decline_filters(&self) -> Option<Filters> {
+        filters(self.decline_refuse_seconds)
+    }
+
+    // same for what's left over of an offer something got launched on
+    pub fn launch_filters(&self) -> Option<Filters> {
+        filters(self.launch_refuse_seconds)
+    }
+}
+
+fn filters(refuse_seconds: f64) -> Option<Filters> {
+    if refuse_seconds <= 0.0 {
+        return None;
+    }
+    let mut filters = Filters::new();
+    filters.set_refuse_seconds(refuse_seconds);
+    Some(filters)
+}
@@ -0,0 +1,80 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use state::Task;
+use std::collections::HashMap;
+use utils::{Expression, read_float};
+use yaml_rust::Yaml;
+
+static VARIABLES: &'static [&'static str] = &["node.free_cpu",
+                                              "node.free_mem",
+                                              "node.cost",
+                                              "node.running_tasks",
+                                              "task.cpu",
+                                              "task.memory"];
+
+#[derive(Clone, Debug)]
+pub struct PlacementPolicy {
+    score: Option<Expression>,
+    min_score: f64,
+}
+
+impl PlacementPolicy {
+    pub fn new(config: &Yaml) -> PlacementPolicy {
+        let placement = &config["placement"];
+
+        let score = match placement["score"].as_str() {
+            Some(source) => {
+                match Expression::compile(source, VARIABLES) {
+                    Ok(expression) => Some(expression),
+                    Err(error) => panic!("invalid placement score '{}': {}", source, error),
+                }
+            }
+            None => None,
+        };
+
+        PlacementPolicy {
+            score: score,
+            min_score: read_float(placement, "min_score".to_string(), 0.0),
+        }
+    }
+
+    // offers are handled one agent at a time, so the score acts as a threshold
+    pub fn accepts(&self, free_cpu: f64, free_mem: f64, node_cost: f64, running_tasks: usize, task: &Task) -> bool {
+        let score = match self.score {
+            Some(ref expression) => {
+                let mut variables = HashMap::new();
+                variables.insert("node.free_cpu".to_string(), free_cpu);
+                variables.insert("node.free_mem".to_string(), free_mem);
+                variables.insert("node.cost".to_string(), node_cost);
+                variables.insert("node.running_tasks".to_string(), running_tasks as f64);
+                variables.insert("task.cpu".to_string(), task.cpu);
+                variables.insert("task.memory".to_string(), task.memory);
+                expression.evaluate(&variables)
+            }
+            None => return true,
+        };
+
+        debug!("placement score task={} score={}", task.name, score);
+        score >= self.min_score
+    }
+}
@@ -0,0 +1,157 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use chrono::UTC;
+use collaborator::kill_task;
+use state::{EventType, Node, SLA, StateManager, Task, TaskState};
+use std::collections::BTreeMap;
+use std::thread;
+use std::time::Duration;
+use super::constraint::{NodeFacts, satisfies_constraints};
+use utils::{read_bool, read_int};
+
+// a requested task that found no place for wait_in_seconds gets room made on a node by killing
+// running tasks of a lower priority, those go back to being requested. system services and tasks
+// with an sla are never taken down. the offers are handed out by priority, so the room goes to the
+// task it was made for.
+pub fn run_preemption(state_manager: &StateManager) {
+    let config = state_manager.get_yaml();
+    let preemption = &config["preemption"];
+    if !read_bool(preemption, "enabled".to_string()) {
+        return;
+    }
+    let wait = read_int(preemption, "wait_in_seconds".to_string(), 60);
+    let poll_interval = read_int(preemption, "poll_interval_in_seconds".to_string(), 15) as u64;
+
+    let my_name = state_manager.get_my_name();
+    // the killed tasks take a moment to give their resources back, no second round for the same task until then
+    let mut preempted_for: BTreeMap<String, i64> = BTreeMap::new();
+    loop {
+        thread::sleep(Duration::from_secs(poll_interval));
+
+        let now = UTC::now().timestamp();
+        preempted_for = preempted_for.into_iter().filter(|&(_, at)| at + wait > now).collect();
+
+        let mut waiting: Vec<Task> = state_manager.request_list_requested_tasks()
+            .into_iter()
+            .filter(|task| task.priority > 0 && task.controller == my_name && task.last_update + wait < now)
+            .filter(|task| !preempted_for.contains_key(&task.name))
+            .collect();
+        waiting.sort_by(|a, b| b.priority.cmp(&a.priority));
+
+        for task in waiting {
+            let running_tasks = state_manager.request_list_running_tasks();
+            let nodes = state_manager.request_list_nodes();
+            let (node, victims) = match nodes.iter().filter_map(|node| plan_room(node, &task, &running_tasks)).next() {
+                Some(plan) => plan,
+                None => {
+                    debug!("nothing to preempt task={} priority={}", task.name, task.priority);
+                    continue;
+                }
+            };
+
+            for victim in &victims {
+                info!("preempting task={} priority={} for={} priority={} node={}",
+                      victim.name,
+                      victim.priority,
+                      task.name,
+                      task.priority,
+                      node);
+                state_manager.send_restart_task(victim.name.clone(), false);
+                kill_task(&victim.name);
+                state_manager.send_add_event(EventType::TaskPreempted,
+                                             victim.name.clone(),
+                                             format!("priority {} made room on {} for {} priority {}",
+                                                     victim.priority,
+                                                     node,
+                                                     task.name,
+                                                     task.priority));
+            }
+            preempted_for.insert(task.name.clone(), now);
+        }
+    }
+}
+
+// the node and the fewest lowest priority tasks to take down there for the task to fit
+fn plan_room(node: &Node, task: &Task, running_tasks: &Vec<Task>) -> Option<(String, Vec<Task>)> {
+    if !node.active || node.draining || !fits_on(node, task, running_tasks) {
+        return None;
+    }
+
+    let mut free_cpu = node.total_cpu - node.allocated_cpu;
+    let mut free_memory = node.total_memory - node.allocated_memory;
+    if free_cpu >= task.cpu && free_memory >= task.memory {
+        // it fits already, the offer just didn't come around yet
+        return None;
+    }
+
+    let mut candidates: Vec<&Task> = running_tasks.iter()
+        .filter(|running| running.state == TaskState::Running && running.slave_id == node.slave_id)
+        .filter(|running| running.priority < task.priority && !running.is_system_service && running.sla == SLA::None)
+        .collect();
+    candidates.sort_by(|a, b| a.priority.cmp(&b.priority).then(b.running_since.cmp(&a.running_since)));
+
+    let mut victims = vec![];
+    for candidate in candidates {
+        if free_cpu >= task.cpu && free_memory >= task.memory {
+            break;
+        }
+        free_cpu += candidate.cpu;
+        free_memory += candidate.memory;
+        victims.push(candidate.clone());
+    }
+
+    match free_cpu >= task.cpu && free_memory >= task.memory {
+        true => Some((node.name.clone(), victims)),
+        false => None,
+    }
+}
+
+fn fits_on(node: &Node, task: &Task, running_tasks: &Vec<Task>) -> bool {
+    if !task.node_name.is_empty() && task.node_name != node.name {
+        return false;
+    }
+    if !task.node_type.is_empty() && task.node_type != node.node_type {
+        return false;
+    }
+    if !task.node_function.is_empty() && task.node_function != node.node_function {
+        return false;
+    }
+
+    let mut node_tasks = vec![];
+    for running in running_tasks.iter().filter(|running| running.slave_id == node.slave_id) {
+        node_tasks.push(running.name.clone());
+        if !running.array_name.is_empty() {
+            node_tasks.push(running.array_name.clone());
+        }
+    }
+    let node_facts = NodeFacts {
+        name: &node.name,
+        node_type: &node.node_type,
+        node_function: &node.node_function,
+        groups: &node.groups,
+        tasks: &node_tasks,
+        gpu_health: node.gpu_health(),
+        gpu_driver: node.gpu_driver(),
+    };
+    satisfies_constraints(&task.constraints, &node_facts)
+}
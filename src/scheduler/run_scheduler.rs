@@ -24,19 +24,32 @@ use mesos::{ProtobufCallbackRouter, SchedulerConf, run_protobuf_scheduler};
 use mesos::proto::FrameworkID;
 use scheduler::TorcScheduler;
 use state::StateManager;
+use super::environment::EnvironmentResolver;
+use utils::{read_bool, read_int};
 
 pub fn run_scheduler(state_manager: &StateManager) {
     state_manager.send_ping();
 
-    let mut scheduler = TorcScheduler { state_manager: state_manager };
+    let mut scheduler = TorcScheduler {
+        state_manager: state_manager,
+        environments: EnvironmentResolver::new(&state_manager.get_master_ip()),
+    };
     let mut framework_id = FrameworkID::new();
     framework_id.set_value(state_manager.get_my_framework_id());
 
+    // a standby re-registers with the same framework id, mesos has to keep the tasks around until then
+    let config = state_manager.get_yaml();
+    let election = &config["leader-election"];
+    let framework_timeout = match read_bool(election, "enabled".to_string()) {
+        true => read_int(election, "failover_timeout_in_seconds".to_string(), 120) as f64,
+        false => 0f64,
+    };
+
     let conf = SchedulerConf {
         master_url: format!("http://{}:5050", state_manager.get_master_ip()),
         user: "root".to_string(),
         name: state_manager.get_my_name(),
-        framework_timeout: 0f64,
+        framework_timeout: framework_timeout,
         implicit_acknowledgements: true,
         framework_id: Some(framework_id),
     };
@@ -0,0 +1,77 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use state::StateManager;
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct ScaleReport {
+    pub name: String,
+    pub previous_count: usize,
+    pub count: usize,
+    pub started: Vec<String>,
+    pub stopped: Vec<String>,
+    pub rejected: String,
+}
+
+// new replicas copy the one with the lowest index and take the lowest free indexes,
+// scaling down stops the highest indexes first so the names stay <name>-1 to <name>-<count>
+pub fn scale_service(state_manager: &StateManager, name: &String, count: usize) -> Result<ScaleReport, String> {
+    let replicas = state_manager.request_list_replicas(name.clone());
+    if replicas.is_empty() {
+        return Err(format!("{} has no replicas", name));
+    }
+
+    let mut report = ScaleReport {
+        name: name.clone(),
+        previous_count: replicas.len(),
+        count: count,
+        started: vec![],
+        stopped: vec![],
+        rejected: "".to_string(),
+    };
+
+    if count > replicas.len() {
+        let template = &replicas[0];
+        let mut index = 0;
+        while replicas.len() + report.started.len() < count {
+            index += 1;
+            if replicas.iter().any(|replica| replica.replica_index == index) {
+                continue;
+            }
+            match state_manager.send_start_replica(template, index) {
+                Ok(_) => report.started.push(format!("{}-{}", name, index)),
+                Err(reason) => {
+                    report.rejected = reason;
+                    break;
+                }
+            }
+        }
+    }
+
+    for replica in replicas.iter().rev().take(replicas.len().saturating_sub(count)) {
+        state_manager.send_remove_task_by_name(replica.name.clone());
+        state_manager.send_kill_task_by_name(replica.name.clone());
+        report.stopped.push(replica.name.clone());
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,249 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use api::read_scheduled_service;
+use chrono::{TimeZone, UTC};
+use state::{StateManager, TaskState};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use super::cron::CronSchedule;
+use utils::{Task, read_int, read_string, read_task, start_array_member};
+use yaml_rust::Yaml;
+
+const POLL_INTERVAL_IN_SECONDS: u64 = 5;
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct ScheduledJob {
+    pub name: String,
+    pub schedule: String,
+    pub source: String,
+    pub runs: Vec<JobRun>,
+}
+
+// state is started, finished, failed, gone, skipped or rejected
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct JobRun {
+    pub name: String,
+    pub scheduled_at: i64,
+    pub state: String,
+    pub finished_at: i64,
+    pub reason: String,
+}
+
+struct Entry {
+    job: ScheduledJob,
+    schedule: CronSchedule,
+    template: Task,
+}
+
+pub struct ScheduledJobs {
+    history_limit: usize,
+    entries: Mutex<BTreeMap<String, Entry>>,
+}
+
+impl ScheduledJobs {
+    pub fn new(config: &Yaml) -> ScheduledJobs {
+        ScheduledJobs {
+            history_limit: read_int(&config["jobs"], "history_limit".to_string(), 20) as usize,
+            entries: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn add(&self, template: Task, schedule: &str, source: &str) -> Result<(), String> {
+        if !template.is_job {
+            return Err(format!("{} isn't a job", template.name));
+        }
+        if template.array_count > 0 {
+            return Err(format!("{} is a job array, a scheduled job launches a single run", template.name));
+        }
+        let parsed = try!(CronSchedule::parse(schedule));
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.contains_key(&template.name) {
+            return Err(format!("{} is already scheduled", template.name));
+        }
+        entries.insert(template.name.clone(),
+                       Entry {
+                           job: ScheduledJob {
+                               name: template.name.clone(),
+                               schedule: schedule.to_string(),
+                               source: source.to_string(),
+                               runs: vec![],
+                           },
+                           schedule: parsed,
+                           template: template,
+                       });
+        Ok(())
+    }
+
+    // a job from the config would come back with the next restart, only the ones added through the api can go
+    pub fn remove(&self, name: &str) -> Result<(), String> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(name).map(|entry| entry.job.source.clone()) {
+            Some(ref source) if source == "config" => Err(format!("{} is scheduled in the config", name)),
+            Some(_) => {
+                entries.remove(name);
+                Ok(())
+            }
+            None => Err(format!("{} isn't scheduled", name)),
+        }
+    }
+
+    pub fn list(&self) -> Vec<ScheduledJob> {
+        self.entries.lock().unwrap().values().map(|entry| entry.job.clone()).collect()
+    }
+
+    // the jobs to launch for the minute, a job whose previous run is still going gets the run recorded as skipped
+    fn due(&self, at: i64) -> Vec<Task> {
+        let time = UTC.timestamp(at, 0);
+        let mut result = vec![];
+
+        for entry in self.entries.lock().unwrap().values_mut() {
+            if !entry.schedule.matches(&time) {
+                continue;
+            }
+            let still_running = entry.job.runs.last().map(|run| run.state == "started").unwrap_or(false);
+            if still_running {
+                info!("skipping scheduled job, previous run still going job={} at={}", entry.job.name, at);
+                let run = new_run(&format!("{}-{}", entry.job.name, at), at, "skipped", "previous run still going");
+                entry.job.runs.push(run);
+                continue;
+            }
+            result.push(entry.template.clone());
+        }
+
+        result
+    }
+
+    fn record_run(&self, job_name: &String, run: JobRun) {
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(job_name) {
+            entry.job.runs.push(run);
+        }
+    }
+
+    // brings started runs up to date and returns the runs dropped from the history
+    fn update_runs(&self, state_manager: &StateManager) -> Vec<String> {
+        let mut dropped = vec![];
+
+        for entry in self.entries.lock().unwrap().values_mut() {
+            for run in entry.job.runs.iter_mut().filter(|run| run.state == "started") {
+                let (state, reason) = match state_manager.request_task(run.name.clone()) {
+                    None => ("gone", "".to_string()),
+                    Some(task) => {
+                        match task.state {
+                            TaskState::Finished => ("finished", "".to_string()),
+                            TaskState::Failed | TaskState::NetworkFailed => ("failed", task.failure_reason.clone()),
+                            _ => continue,
+                        }
+                    }
+                };
+                info!("scheduled job run done run={} state={}", run.name, state);
+                run.state = state.to_string();
+                run.reason = reason;
+                run.finished_at = UTC::now().timestamp();
+            }
+
+            while entry.job.runs.len() > self.history_limit && entry.job.runs[0].state != "started" {
+                let run = entry.job.runs.remove(0);
+                if run.state == "finished" || run.state == "failed" {
+                    dropped.push(run.name);
+                }
+            }
+        }
+
+        dropped
+    }
+}
+
+// launches the jobs with a schedule from the service groups and the ones added through the api, each run is a member
+// of a job array named after the job with the scheduled unix time as index, so its record stays after it finished
+pub fn run_job_scheduler(state_manager: &StateManager) {
+    let scheduled_jobs = state_manager.get_scheduled_jobs();
+    let config = state_manager.get_yaml();
+    for service_group in config["api"]["service-groups"].as_vec().unwrap_or(&vec![]) {
+        for service in service_group["services"].as_vec().unwrap_or(&vec![]) {
+            let schedule = read_string(service, "schedule".to_string());
+            if schedule.is_empty() {
+                continue;
+            }
+            let task = read_task(service, state_manager);
+            let name = task.name.clone();
+            match scheduled_jobs.add(task, &schedule, "config") {
+                Ok(_) => info!("job scheduled job={} schedule={}", name, schedule),
+                Err(reason) => warn!("can't schedule job={} reason={}", name, reason),
+            }
+        }
+    }
+    for stored in state_manager.request_schedules() {
+        let added = read_scheduled_service(&stored.service).and_then(|task| scheduled_jobs.add(task, &stored.schedule, "api"));
+        match added {
+            Ok(_) => info!("job schedule restored job={} schedule={}", stored.name, stored.schedule),
+            Err(reason) => warn!("can't restore job schedule job={} reason={}", stored.name, reason),
+        }
+    }
+
+    let now = UTC::now().timestamp();
+    let mut next_minute = now - now % 60 + 60;
+    loop {
+        thread::sleep(Duration::from_secs(POLL_INTERVAL_IN_SECONDS));
+
+        for run_name in scheduled_jobs.update_runs(state_manager) {
+            state_manager.send_remove_task_by_name(run_name);
+        }
+
+        let now = UTC::now().timestamp();
+        while next_minute <= now {
+            let at = next_minute;
+            next_minute += 60;
+            if state_manager.request_is_shutting_down() {
+                continue;
+            }
+
+            for template in scheduled_jobs.due(at) {
+                let run_name = format!("{}-{}", template.name, at);
+                let run = match start_array_member(state_manager, &template, at, false) {
+                    Ok(_) => {
+                        info!("scheduled job started run={}", run_name);
+                        new_run(&run_name, at, "started", "")
+                    }
+                    Err(reason) => {
+                        warn!("scheduled job rejected run={} reason={}", run_name, reason);
+                        new_run(&run_name, at, "rejected", &reason)
+                    }
+                };
+                scheduled_jobs.record_run(&template.name, run);
+            }
+        }
+    }
+}
+
+fn new_run(name: &str, scheduled_at: i64, state: &str, reason: &str) -> JobRun {
+    JobRun {
+        name: name.to_string(),
+        scheduled_at: scheduled_at,
+        state: state.to_string(),
+        finished_at: 0,
+        reason: reason.to_string(),
+    }
+}
@@ -22,7 +22,9 @@
 
 #![allow(unused_variables)]
 
-use collaborator::set_mesos_client;
+use chrono::UTC;
+use collaborator::{pinned_image, set_mesos_client};
+use health::{adopt_reconciled_task, forget_framework_id};
 use mesos::{Scheduler, SchedulerClient};
 use mesos::proto::{CommandInfo, ContainerInfo, ContainerInfo_DockerInfo, ContainerInfo_DockerInfo_Network, ContainerInfo_Type,
                    ExecutorID, FrameworkID, InverseOffer, Offer, OfferID, Parameter, SlaveID, TaskInfo, TaskStatus, Volume,
@@ -30,39 +32,71 @@ use mesos::proto::{CommandInfo, ContainerInfo, ContainerInfo_DockerInfo, Contain
 use mesos::proto::TaskState as MesosTaskState;
 use mesos::util;
 use protobuf;
-use state::{StateManager, TaskState};
+use state::{RestartDecision, StateManager, TaskState};
+use std::collections::BTreeMap;
+use std::process;
+use super::constraint::{NodeFacts, satisfies_constraints};
+use super::environment::EnvironmentResolver;
 use utils;
+use utils::CallTimer;
 
+const CPU_PERIOD: i64 = 100000;
 
 pub struct TorcScheduler<'lifetime> {
     pub state_manager: &'lifetime StateManager,
+    pub environments: EnvironmentResolver,
 }
 
 impl<'lifetime> Scheduler for TorcScheduler<'lifetime> {
     fn subscribed(&mut self, client: &SchedulerClient, framework_id: &FrameworkID, heartbeat_interval_seconds: Option<f64>) {
-        println!("received subscribed with id: {}", framework_id.get_value());
+        info!("received subscribed framework_id={}", framework_id.get_value());
 
         set_mesos_client(Some(client.clone()));
 
-        client.reconcile(vec![]).unwrap();
+        let timer = CallTimer::start("mesos", &self.state_manager.get_master_ip());
+        let result = client.reconcile(vec![]);
+        timer.finish(result.is_ok());
+        result.unwrap();
     }
 
     fn inverse_offers(&mut self, client: &SchedulerClient, inverse_offers: Vec<&InverseOffer>) {
-        println!("received inverse offers");
+        debug!("received inverse offers");
 
         // this never lets go willingly
         let offer_ids = inverse_offers.iter()
             .map(|o| o.get_id().clone())
             .collect();
-        client.decline(offer_ids, None).unwrap();
+        let timer = CallTimer::start("mesos", &self.state_manager.get_master_ip());
+        let result = client.decline(offer_ids, None);
+        timer.finish(result.is_ok());
+        result.unwrap();
     }
 
     fn offers(&mut self, client: &SchedulerClient, offers: Vec<&Offer>) {
-        // Offers are guaranteed to be for the same agent, and
-        // there will be at least one.
-        let slave_id = offers[0].get_slave_id();
+        if !self.state_manager.request_is_ready() {
+            warn!("required collaborators not reachable yet, declining offers");
+            let offer_ids = offers.iter()
+                .map(|o| o.get_id().clone())
+                .collect();
+            let timer = CallTimer::start("mesos", &self.state_manager.get_master_ip());
+            let result = client.decline(offer_ids, None);
+            timer.finish(result.is_ok());
+            result.unwrap();
+            return;
+        }
 
-        let requested_tasks = self.state_manager.request_list_requested_tasks();
+        let offer_policy = self.state_manager.get_offer_policy();
+        // higher priorities get the first pick, preemption makes room for them
+        let requested_tasks = offer_policy.queue(self.state_manager.request_list_requested_tasks(), UTC::now().timestamp());
+        let running_tasks = self.state_manager.request_list_running_tasks();
+        let accepted_tasks = self.state_manager.request_list_accepted_tasks();
+        let placement_policy = self.state_manager.get_placement_policy();
+        let fetch_policy = self.state_manager.get_fetch_policy();
+        let cluster_open = self.state_manager.request_is_cluster_open();
+        let volume_holders = self.state_manager.request_volume_holders();
+        let resolving_digests = self.state_manager.request_resolving_image_digests();
+        // tasks launched in this round by agent, they aren't running yet and affinity rules still have to see them
+        let mut started_here: BTreeMap<String, Vec<String>> = BTreeMap::new();
 
         let mut tasks_to_start: Vec<TaskInfo> = vec![];
         let mut offers_to_decline: Vec<OfferID> = vec![];
@@ -78,6 +112,7 @@ impl<'lifetime> Scheduler for TorcScheduler<'lifetime> {
 
         for offer in &offers {
             let mut found_match = false;
+            let slave_id = offer.get_slave_id();
 
             for attribute in &mut offer.get_attributes().into_iter() {
                 match attribute.get_name() {
@@ -97,25 +132,68 @@ impl<'lifetime> Scheduler for TorcScheduler<'lifetime> {
                 }
             }
 
-            println!("received offer from host: {}, name: {}, type: {}, function: {}",
-                     attribute_host,
-                     attribute_node_name,
-                     attribute_node_type,
-                     attribute_node_function);
-
+            debug!("received offer host={} node={} type={} function={}",
+                   attribute_host,
+                   attribute_node_name,
+                   attribute_node_type,
+                   attribute_node_function);
+
+            // job_slots of 0 means jobs are only limited by cpu and memory
+            let known_node = self.state_manager.request_node(attribute_node_name.to_string());
+            let (node_cost, job_slots, node_groups, draining, gpu_health, gpu_driver) = match known_node {
+                Some(node) => {
+                    let gpu_health = node.gpu_health();
+                    let gpu_driver = node.gpu_driver().to_string();
+                    (node.cost, node.job_slots, node.groups, node.draining, gpu_health, gpu_driver)
+                }
+                None => (1.0, 0, vec![], false, "none", "".to_string()),
+            };
+            // jobs launched on an earlier offer that aren't running yet hold their slot as well
+            let mut running_jobs = running_tasks.iter()
+                .chain(accepted_tasks.iter())
+                .filter(|placed_task| placed_task.is_job && placed_task.slave_id == offer.get_slave_id().get_value())
+                .count() as i64;
+
+            let mut node_tasks = started_here.get(slave_id.get_value()).cloned().unwrap_or(vec![]);
+            let on_this_node = running_tasks.iter()
+                .filter(|running_task| running_task.slave_id == offer.get_slave_id().get_value());
+            for running_task in on_this_node {
+                node_tasks.push(running_task.name.clone());
+                if !running_task.array_name.is_empty() {
+                    node_tasks.push(running_task.array_name.clone());
+                }
+            }
             if !self.state_manager.request_is_node_active(attribute_node_name.to_string()) {
                 self.state_manager.send_update_node(attribute_node_name.to_string(),
+                                                    attribute_host.to_string(),
                                                     attribute_node_type.to_string(),
                                                     attribute_node_function.to_string(),
                                                     offer.get_slave_id().get_value().to_string())
             }
 
-            for task_immutable in &requested_tasks {
-                let mut task = task_immutable.clone();
+            if draining {
+                offers_to_decline.push(offer.get_id().clone());
+                continue;
+            }
+
+            let mut launched_on_offer = 0;
+            for task in &requested_tasks {
+                if started_here.values().any(|names| names.contains(&task.name)) || resolving_digests.contains(&task.name) {
+                    continue;
+                }
+
                 if task.node_name.len() > 0 && task.node_name != attribute_node_name {
                     continue;
                 }
 
+                // back to the node holding its data
+                match volume_holders.get(&task.name) {
+                    Some(holder) if *holder != attribute_node_name && task.volumes.iter().any(|volume| !volume.read_only_mode) => {
+                        continue
+                    }
+                    _ => {}
+                }
+
                 if task.node_type.len() > 0 && task.node_type != attribute_node_type {
                     continue;
                 }
@@ -124,19 +202,79 @@ impl<'lifetime> Scheduler for TorcScheduler<'lifetime> {
                     continue;
                 }
 
-                if task.dependent_service.len() > 0 {
-                    match self.state_manager.request_task_state(task.dependent_service.to_string()) {
-                        TaskState::Running => {}
-                        _ => continue,
-                    }
+                let satisfied = {
+                    let node_facts = NodeFacts {
+                        name: attribute_node_name,
+                        node_type: attribute_node_type,
+                        node_function: attribute_node_function,
+                        groups: &node_groups,
+                        tasks: &node_tasks,
+                        gpu_health: gpu_health,
+                        gpu_driver: &gpu_driver,
+                    };
+                    satisfies_constraints(&task.constraints, &node_facts)
+                };
+                if !satisfied {
+                    continue;
+                }
+
+                // one running member of a group is enough, a single failed replica shouldn't keep its dependents down
+                if task.dependent_service.len() > 0 &&
+                   !self.state_manager
+                    .request_dependency_tasks(&task.dependent_service)
+                    .iter()
+                    .any(|dependency| dependency.state == TaskState::Running) {
+                    continue;
+                }
+
+                if !cluster_open && !task.is_system_service {
+                    continue;
+                }
+
+                match task.run_window {
+                    Some(ref run_window) if !run_window.is_open() => continue,
+                    _ => {}
+                }
+
+                if task.is_job && job_slots > 0 && running_jobs >= job_slots {
+                    continue;
                 }
 
                 if offer_cpus < task.cpu || offer_mem < task.memory {
                     continue;
                 }
 
-                println!("Starting {}, arguments: {:?}", task.name, task);
+                let node_running_tasks = running_tasks.iter()
+                    .filter(|running_task| running_task.slave_id == offer.get_slave_id().get_value())
+                    .count() + launched_on_offer;
+                if !placement_policy.accepts(offer_cpus, offer_mem, node_cost, node_running_tasks, &task) {
+                    continue;
+                }
+
+                let uris = match fetch_policy.uris_for(&task, attribute_node_name) {
+                    Ok(uris) => uris,
+                    Err(error) => {
+                        warn!("holding back task={} error={}", task.name, error);
+                        continue;
+                    }
+                };
+                // without its secrets the task would come up misconfigured, it waits for the next offer instead.
+                // they are handed out once, so this comes last
+                let environment = match self.environments.task_environment(&task) {
+                    Ok(environment) => environment,
+                    Err(error) => {
+                        warn!("holding back task={} error={}", task.name, error);
+                        continue;
+                    }
+                };
+
+                info!("starting task={} definition={:?}", task.name, task);
                 self.state_manager.send_update_task_state(task.name.clone(), TaskState::Accepted);
+                // until the task reports back it would still count on the node it ran on before
+                self.state_manager.send_update_task_info(task.name.clone(),
+                                                         "".to_string(),
+                                                         "".to_string(),
+                                                         offer.get_slave_id().get_value().to_string());
 
                 if task.node_type.len() > 0 || task.node_function.len() > 0 {
                     self.state_manager.send_update_task_node_name(task.name.clone(), attribute_node_name.to_string())
@@ -148,60 +286,47 @@ impl<'lifetime> Scheduler for TorcScheduler<'lifetime> {
                 let mut command = CommandInfo::new();
                 command.set_shell(false);
 
-                if task.arguments.len() > 0 {
-                    let elmts: Vec<&str> = task.arguments
-                        .split(|c: char| c == ' ')
-                        .filter(|s| !s.is_empty())
-                        .collect();
-
-                    let mut arguments: Vec<String> = vec![];
+                let arguments = utils::decode_arguments(&task.arguments);
+                if arguments.len() > 0 {
+                    command.set_arguments(protobuf::RepeatedField::from_vec(arguments));
+                }
 
-                    for elmt in elmts {
-                        arguments.push(elmt.to_string());
-                    }
+                if let Some(environment) = environment {
+                    command.set_environment(environment);
+                }
 
-                    command.set_arguments(protobuf::RepeatedField::from_vec(arguments));
+                if !uris.is_empty() {
+                    command.set_uris(protobuf::RepeatedField::from_vec(uris));
                 }
 
                 let mut container = ContainerInfo::new();
                 container.set_field_type(ContainerInfo_Type::DOCKER);
 
                 let mut docker = ContainerInfo_DockerInfo::new();
-                docker.set_image(task.image.clone());
+                docker.set_image(pinned_image(&task.image, &task.image_digest));
                 docker.set_privileged(task.privileged);
 
+                let mut parameters: Vec<Parameter> = utils::decode_parameters(&task.parameters)
+                    .into_iter()
+                    .map(|(key, value)| docker_parameter(&key, value))
+                    .collect();
+
                 match &*task.network_type {
                     "host" => docker.set_network(ContainerInfo_DockerInfo_Network::HOST),
                     "none" => docker.set_network(ContainerInfo_DockerInfo_Network::NONE),
-                    _ => {
-                        let new_parameters = format!("{} --net={}", task.parameters, task.network_type.clone());
-                        task.parameters = new_parameters.clone();
-                    }
+                    _ => parameters.push(docker_parameter("net", task.network_type.clone())),
                 }
 
-                if task.parameters.len() > 0 {
-                    let elmts: Vec<&str> = task.parameters
-                        .split(|c: char| c == '-' || c == '=' || c == ' ')
-                        .filter(|s| !s.is_empty())
-                        .collect();
-
-                    let mut count = 0;
-                    let mut parameters: Vec<Parameter> = vec![];
-
-                    loop {
-                        let mut parameter = Parameter::new();
-                        parameter.set_key(elmts[count].to_string());
-                        count += 1;
-                        parameter.set_value(elmts[count].to_string());
-                        count += 1;
-
-                        parameters.push(parameter);
-
-                        if count >= elmts.len() {
-                            break;
-                        }
+                // shares are work conserving, a quota caps the task at its cpu value even on an idle node
+                match task.cpu_policy.as_ref() {
+                    "quota" => {
+                        parameters.push(docker_parameter("cpu-period", CPU_PERIOD.to_string()));
+                        parameters.push(docker_parameter("cpu-quota", ((task.cpu * CPU_PERIOD as f64) as i64).to_string()));
                     }
+                    _ => parameters.push(docker_parameter("cpu-shares", ((task.cpu * 1024.0) as i64).to_string())),
+                }
 
+                if parameters.len() > 0 {
                     docker.set_parameters(protobuf::RepeatedField::from_vec(parameters));
                 }
 
@@ -230,12 +355,28 @@ impl<'lifetime> Scheduler for TorcScheduler<'lifetime> {
 
                 let task_info = util::task_info_for_container(name, &task_id, slave_id, &command, &container, resources);
                 tasks_to_start.push(task_info);
-                offers_to_accept.push(offer.get_id().clone());
+                if !found_match {
+                    offers_to_accept.push(offer.get_id().clone());
+                }
+                let started_on_agent = started_here.entry(slave_id.get_value().to_string()).or_insert(vec![]);
+                started_on_agent.push(task.name.clone());
+                node_tasks.push(task.name.clone());
+                if !task.array_name.is_empty() {
+                    started_on_agent.push(task.array_name.clone());
+                    node_tasks.push(task.array_name.clone());
+                }
 
-                // we don't do any mem, cpu matching with offer yet,
-                // assuming for now that we always have cpu and mem available ..
+                // what is left of the offer goes to the next tasks in the queue
+                offer_cpus -= task.cpu;
+                offer_mem -= task.memory;
+                if task.is_job {
+                    running_jobs += 1;
+                }
+                launched_on_offer += 1;
                 found_match = true;
-                break;
+                if launched_on_offer >= offer_policy.max_tasks_per_offer {
+                    break;
+                }
             }
 
             if !found_match {
@@ -245,45 +386,88 @@ impl<'lifetime> Scheduler for TorcScheduler<'lifetime> {
         }
 
         if tasks_to_start.len() > 0 {
-            client.launch(offers_to_accept, tasks_to_start, None).unwrap();
+            let timer = CallTimer::start("mesos", &self.state_manager.get_master_ip());
+            let result = client.launch(offers_to_accept, tasks_to_start, offer_policy.launch_filters());
+            timer.finish(result.is_ok());
+            result.unwrap();
         }
 
         if offers_to_decline.len() > 0 {
             let no_tasks_to_start: Vec<TaskInfo> = vec![];
-            client.launch(offers_to_decline, no_tasks_to_start, None).unwrap();
+            let timer = CallTimer::start("mesos", &self.state_manager.get_master_ip());
+            let result = client.launch(offers_to_decline, no_tasks_to_start, offer_policy.decline_filters());
+            timer.finish(result.is_ok());
+            result.unwrap();
             // client.decline(offers_to_decline, None).unwrap();
         }
     }
 
     fn rescind(&mut self, client: &SchedulerClient, offer_id: &OfferID) {
-        println!("received rescind");
+        debug!("received rescind");
     }
 
     fn update(&mut self, client: &SchedulerClient, status: &TaskStatus) {
-        println!("received update {:?} from {}",
-                 status.get_state(),
-                 status.get_task_id().get_value());
+        info!("received update state={:?} task_id={}",
+              status.get_state(),
+              status.get_task_id().get_value());
 
         let task_name = status.get_task_id().get_value().to_string();
 
         match status.get_state() {
             MesosTaskState::TASK_RUNNING => {
+                let slave_id = status.get_slave_id().get_value().to_string();
+                if self.state_manager.request_task(task_name.clone()).is_none() {
+                    adopt_reconciled_task(&self.state_manager, &task_name, &slave_id);
+                }
+
+                // reconciled statuses come without the inspect data, the task keeps what it has
                 let raw_data: Vec<u8> = Vec::from(status.get_data());
-                let docker_inspect = String::from_utf8(raw_data).unwrap();
-                utils::handle_inspect_data(&self.state_manager,
-                                           &task_name,
-                                           &docker_inspect,
-                                           &status.get_slave_id().get_value().to_string());
-                self.state_manager.send_update_task_state(task_name, TaskState::Running);
+                let docker_inspect = String::from_utf8(raw_data).unwrap_or("".to_string());
+                let has_ip = match docker_inspect.is_empty() {
+                    true => true,
+                    false => utils::handle_inspect_data(&self.state_manager, &task_name, &docker_inspect, &slave_id),
+                };
+                if has_ip {
+                    self.state_manager.send_update_task_state(task_name.clone(), TaskState::Running);
+                    self.state_manager.restart_stale_dependents(task_name);
+                } else {
+                    let task_state = self.state_manager.send_network_failure(task_name.clone());
+                    warn!("task without ip task={} state={:?}", task_name, task_state);
+                }
             }
             MesosTaskState::TASK_FINISHED |
             MesosTaskState::TASK_KILLED |
             MesosTaskState::TASK_FAILED |
             MesosTaskState::TASK_LOST => {
-                if self.state_manager.request_is_restartable_task(task_name.clone()) {
-                    self.state_manager.send_restart_task(task_name.clone());
-                } else {
-                    self.state_manager.send_remove_task_by_name(task_name);
+                match self.state_manager.request_task_state(task_name.clone()) {
+                    // killed by us after a network failure, deadline or delete, already taken care of
+                    TaskState::Restart | TaskState::NetworkFailed | TaskState::Failed | TaskState::Finished |
+                    TaskState::Deleted => {}
+                    _ => {
+                        match self.state_manager.request_task(task_name.clone()) {
+                            // members of a job array, jobs with artifacts and jobs delegated to us are kept around
+                            // as a record, the controller a job came from picks up how it ended
+                            Some(ref task) if !task.array_name.is_empty() || !task.artifacts.is_empty() ||
+                                              (task.is_job && !task.delegated_from.is_empty()) => {
+                                match status.get_state() {
+                                    MesosTaskState::TASK_FINISHED => {
+                                        self.state_manager.send_update_task_state(task_name, TaskState::Finished);
+                                        if !task.artifacts.is_empty() {
+                                            self.state_manager.send_collect_artifacts(task);
+                                        }
+                                    }
+                                    mesos_state => self.state_manager.send_fail_task(task_name, format!("{:?}", mesos_state)),
+                                }
+                            }
+                            _ => {
+                                let failed = status.get_state() != MesosTaskState::TASK_FINISHED;
+                                match self.state_manager.send_task_exited(task_name.clone(), failed) {
+                                    RestartDecision::Remove => self.state_manager.send_remove_task_by_name(task_name),
+                                    RestartDecision::Restart | RestartDecision::Quarantine(_) => {}
+                                }
+                            }
+                        }
+                    }
                 }
             }
             _ => {}
@@ -291,7 +475,7 @@ impl<'lifetime> Scheduler for TorcScheduler<'lifetime> {
     }
 
     fn message(&mut self, client: &SchedulerClient, slave_id: &SlaveID, executor_id: &ExecutorID, data: Vec<u8>) {
-        println!("received message");
+        debug!("received message");
     }
 
     fn failure(&mut self,
@@ -299,18 +483,37 @@ impl<'lifetime> Scheduler for TorcScheduler<'lifetime> {
                slave_id: Option<&SlaveID>,
                executor_id: Option<&ExecutorID>,
                status: Option<i32>) {
-        println!("received failure, status {:?}", status);
+        error!("received failure status={:?}", status);
     }
 
     fn error(&mut self, client: &SchedulerClient, message: String) {
-        println!("received error");
+        error!("received error message={}", message);
+
+        // the master never takes a removed framework back, re-registering with its id fails forever
+        if message.contains("has been removed") {
+            let config = self.state_manager.get_yaml();
+            if utils::read_bool(&config["leader-election"], "enabled".to_string()) {
+                forget_framework_id(&config,
+                                    &self.state_manager.get_master_ip(),
+                                    &self.state_manager.get_my_name());
+            }
+            error!("framework removed, exiting framework_id={}", self.state_manager.get_my_framework_id());
+            process::exit(1);
+        }
     }
 
     fn heartbeat(&mut self, client: &SchedulerClient) {
-        println!("received heartbeat");
+        debug!("received heartbeat");
     }
 
     fn disconnected(&mut self) {
-        println!("disconnected from scheduler");
+        warn!("disconnected from scheduler");
     }
 }
+
+fn docker_parameter(key: &str, value: String) -> Parameter {
+    let mut parameter = Parameter::new();
+    parameter.set_key(key.to_string());
+    parameter.set_value(value);
+    parameter
+}
@@ -0,0 +1,86 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use chrono::UTC;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use super::memory_usage::MemoryUsage;
+
+pub struct ConflictList {
+    conflict_list: Mutex<BTreeMap<(String, String), TaskConflict>>,
+}
+
+// one per task name and announcing controller, count goes up with every announcement that ran into it
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct TaskConflict {
+    pub name: String,
+    pub controller: String,
+    pub existing_controller: String,
+    pub outcome: String,
+    pub count: i64,
+    pub first_seen: i64,
+    pub last_seen: i64,
+}
+
+impl ConflictList {
+    pub fn new() -> ConflictList {
+        ConflictList { conflict_list: Mutex::new(BTreeMap::new()) }
+    }
+
+    // true the first time a controller runs into the name
+    pub fn add_conflict(&self, name: String, controller: String, existing_controller: String, outcome: String) -> bool {
+        let now = UTC::now().timestamp();
+        let mut conflict_list = self.conflict_list.lock().unwrap();
+
+        let key = (name.clone(), controller.clone());
+        match conflict_list.get_mut(&key) {
+            Some(conflict) => {
+                conflict.existing_controller = existing_controller;
+                conflict.outcome = outcome;
+                conflict.count += 1;
+                conflict.last_seen = now;
+                return false;
+            }
+            None => {}
+        }
+
+        conflict_list.insert(key,
+                             TaskConflict {
+                                 name: name,
+                                 controller: controller,
+                                 existing_controller: existing_controller,
+                                 outcome: outcome,
+                                 count: 1,
+                                 first_seen: now,
+                                 last_seen: now,
+                             });
+        true
+    }
+
+    pub fn get_conflicts(&self) -> Vec<TaskConflict> {
+        self.conflict_list.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage::measure("task_conflicts", self.conflict_list.lock().unwrap().values())
+    }
+}
@@ -0,0 +1,83 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use chrono::UTC;
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct CleanReport {
+    pub timestamp: i64,
+    pub triggered_by: String,
+    pub removed_tasks: Vec<String>,
+    pub requeued_tasks: Vec<String>,
+    pub stuck_launches: Vec<String>,
+    pub stopped_tasks: Vec<String>,
+    pub expired_jobs: Vec<String>,
+    pub expired_deletes: Vec<String>,
+    pub deferred_kills: Vec<String>,
+    pub inactive_nodes: Vec<String>,
+    pub overridden_tasks: Vec<String>,
+}
+
+impl CleanReport {
+    pub fn new(triggered_by: &str) -> CleanReport {
+        CleanReport {
+            timestamp: UTC::now().timestamp(),
+            triggered_by: triggered_by.to_string(),
+            removed_tasks: vec![],
+            requeued_tasks: vec![],
+            stuck_launches: vec![],
+            stopped_tasks: vec![],
+            expired_jobs: vec![],
+            expired_deletes: vec![],
+            deferred_kills: vec![],
+            inactive_nodes: vec![],
+            overridden_tasks: vec![],
+        }
+    }
+}
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct SyncTaskOutcome {
+    pub name: String,
+    pub registered: bool,
+    pub ttl_refreshed: bool,
+    pub announced: bool,
+}
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct SyncReport {
+    pub timestamp: i64,
+    pub triggered_by: String,
+    pub duration_in_ms: u64,
+    pub tasks: Vec<SyncTaskOutcome>,
+}
+
+impl SyncReport {
+    pub fn new(triggered_by: &str) -> SyncReport {
+        SyncReport {
+            timestamp: UTC::now().timestamp(),
+            triggered_by: triggered_by.to_string(),
+            duration_in_ms: 0,
+            tasks: vec![],
+        }
+    }
+}
@@ -0,0 +1,69 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use std::sync::Mutex;
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::thread;
+use std::time::Duration;
+
+// lets the state thread wake the sync and clean loops when tasks or nodes changed instead of
+// leaving them to their next poll, each loop subscribes once and gets its own channel
+pub struct CycleTrigger {
+    subscribers: Mutex<Vec<Sender<()>>>,
+}
+
+impl CycleTrigger {
+    pub fn new() -> CycleTrigger {
+        CycleTrigger { subscribers: Mutex::new(vec![]) }
+    }
+
+    pub fn subscribe(&self) -> Receiver<()> {
+        let (sender, receiver) = channel();
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    pub fn notify(&self) {
+        for subscriber in self.subscribers.lock().unwrap().iter() {
+            let _ = subscriber.send(());
+        }
+    }
+}
+
+// at least min_interval between two cycles so churn doesn't run them back to back, at most
+// max_interval so an idle cluster still gets looked at. returns what the cycle was triggered by.
+pub fn wait_for_cycle(wakeups: &Receiver<()>, min_interval: Duration, max_interval: Duration) -> &'static str {
+    thread::sleep(min_interval);
+
+    let remaining = match max_interval > min_interval {
+        true => max_interval - min_interval,
+        false => Duration::from_secs(0),
+    };
+    // changes that came in while sleeping count as well
+    let woken = wakeups.try_recv().is_ok() || wakeups.recv_timeout(remaining).is_ok();
+    while wakeups.try_recv().is_ok() {}
+
+    match woken {
+        true => "change",
+        false => "schedule",
+    }
+}
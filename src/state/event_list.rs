@@ -0,0 +1,200 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use chrono::UTC;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::Sender;
+use super::memory_usage::MemoryUsage;
+
+const MAX_EVENTS: usize = 1000;
+
+// numbers every change to the state, task changes as well as events. the numbers start over with
+// every controller start, the epoch (its start in milliseconds) tells the runs apart
+pub struct Sequence {
+    epoch: i64,
+    last: Mutex<u64>,
+}
+
+impl Sequence {
+    pub fn new() -> Sequence {
+        let now = UTC::now();
+        Sequence {
+            epoch: now.timestamp() * 1000 + now.timestamp_subsec_millis() as i64,
+            last: Mutex::new(0),
+        }
+    }
+
+    pub fn next(&self) -> u64 {
+        let mut last = self.last.lock().unwrap();
+        *last += 1;
+        *last
+    }
+
+    pub fn last(&self) -> u64 {
+        *self.last.lock().unwrap()
+    }
+
+    pub fn epoch(&self) -> i64 {
+        self.epoch
+    }
+}
+
+pub struct EventList {
+    event_list: Mutex<VecDeque<Event>>,
+    sink: Mutex<Option<Sender<Event>>>,
+    sequence: Arc<Sequence>,
+    // the sequence of the newest event dropped from the list, nothing after it is missing
+    dropped: Mutex<u64>,
+}
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct Event {
+    pub epoch: i64,
+    pub sequence: u64,
+    pub timestamp: i64,
+    pub event_type: EventType,
+    pub subject: String,
+    pub message: String,
+}
+
+#[derive(Clone, Hash, Eq, PartialEq, Debug, RustcEncodable, RustcDecodable)]
+pub enum EventType {
+    PowerStateChanged,
+    PowerStateMismatch,
+    TaskStateChanged,
+    NodeActive,
+    NodeInactive,
+    RouteQueueAlarm,
+    StuckLaunch,
+    DependentsRestarted,
+    NodeMetadataChanged,
+    NodeConflict,
+    HealthCheckFailed,
+    NodeDraining,
+    TaskDelegated,
+    TaskStateOverridden,
+    TaskConflict,
+    RouteRejected,
+    ServiceUpdated,
+    VolumeMigration,
+    TaskPreempted,
+    TaskRestored,
+    TaskQuarantined,
+    StandbyPromoted,
+    TaskAnnotationsChanged,
+    NodeRegistered,
+    NodeUnregistered,
+    DependencyTimeout,
+}
+
+impl EventList {
+    pub fn new(sequence: Arc<Sequence>) -> EventList {
+        EventList {
+            event_list: Mutex::new(VecDeque::new()),
+            sink: Mutex::new(None),
+            sequence: sequence,
+            dropped: Mutex::new(0),
+        }
+    }
+
+    // every event added from now on gets forwarded to the sink as well
+    pub fn set_sink(&self, sink: Option<Sender<Event>>) {
+        *self.sink.lock().unwrap() = sink;
+    }
+
+    // numbered and exported while holding the list, so the sequence is the order consumers see them in
+    pub fn add_event(&self, event_type: EventType, subject: String, message: String) {
+        let mut event_list = self.event_list.lock().unwrap();
+        let sequence = self.sequence.next();
+        info!("event sequence={} type={:?} subject={} message={}", sequence, event_type, subject, message);
+
+        let event = Event {
+            epoch: self.sequence.epoch(),
+            sequence: sequence,
+            timestamp: UTC::now().timestamp(),
+            event_type: event_type,
+            subject: subject,
+            message: message,
+        };
+
+        match *self.sink.lock().unwrap() {
+            Some(ref sink) => {
+                let _ = sink.send(event.clone());
+            }
+            None => {}
+        }
+
+        event_list.push_back(event);
+
+        while event_list.len() > MAX_EVENTS {
+            if let Some(event) = event_list.pop_front() {
+                *self.dropped.lock().unwrap() = event.sequence;
+            }
+        }
+    }
+
+    pub fn get_events(&self) -> Vec<Event> {
+        let mut result: Vec<Event> = vec![];
+
+        let event_list = self.event_list.lock().unwrap();
+        for event in event_list.iter() {
+            result.push(event.clone());
+        }
+
+        result
+    }
+
+    // a consumer that missed events or asks with a sequence from another epoch has to start over from a snapshot.
+    // task changes take numbers as well, a jump in the sequence alone doesn't mean an event got lost
+    pub fn get_events_since(&self, since: u64, epoch: Option<i64>) -> Result<Vec<Event>, String> {
+        let event_list = self.event_list.lock().unwrap();
+        let last = self.sequence.last();
+        let dropped = *self.dropped.lock().unwrap();
+
+        match epoch {
+            Some(epoch) if epoch != self.sequence.epoch() => {
+                return Err(format!("epoch {} is over, the controller restarted with epoch {}", epoch, self.sequence.epoch()));
+            }
+            _ => {}
+        }
+        if since > last {
+            return Err(format!("sequence {} is ahead of the last change {}", since, last));
+        }
+        if since < dropped {
+            return Err(format!("events after {} are gone, the oldest one kept is after {}", since, dropped));
+        }
+        Ok(event_list.iter().filter(|event| event.sequence > since).cloned().collect())
+    }
+
+    pub fn last_sequence(&self) -> u64 {
+        self.sequence.last()
+    }
+
+    pub fn epoch(&self) -> i64 {
+        self.sequence.epoch()
+    }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage::measure("events", self.event_list.lock().unwrap().iter())
+    }
+}
@@ -0,0 +1,85 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use chrono::UTC;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use super::node_list::{Node, PowerState};
+use utils::{read_float, read_int};
+use yaml_rust::Yaml;
+
+// limits on how much automation and operators may change at once, a misconfigured rolling plan
+// or a flapping health check shouldn't be able to take the whole cluster down in one go
+pub struct Guardrails {
+    max_draining_fraction: f64,
+    max_kills_per_minute: i64,
+    recent_kills: Mutex<VecDeque<i64>>,
+}
+
+impl Guardrails {
+    pub fn new(config: &Yaml) -> Guardrails {
+        let guardrails = &config["guardrails"];
+
+        Guardrails {
+            max_draining_fraction: read_float(guardrails, "max_draining_fraction".to_string(), 1.0),
+            max_kills_per_minute: read_int(guardrails, "max_automated_kills_per_minute".to_string(), 0),
+            recent_kills: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    // a node that was drained and shut down is out of service on purpose and no longer counts
+    pub fn check_draining(&self, nodes: &Vec<Node>, to_drain: &Vec<String>) -> Result<(), String> {
+        if nodes.is_empty() {
+            return Ok(());
+        }
+
+        let draining = nodes.iter()
+            .filter(|node| (node.draining && node.expected_power_state != PowerState::Off) || to_drain.contains(&node.name))
+            .count();
+        let fraction = draining as f64 / nodes.len() as f64;
+        if fraction > self.max_draining_fraction {
+            return Err(format!("draining {} of {} nodes exceeds the guardrail of {}",
+                               draining,
+                               nodes.len(),
+                               self.max_draining_fraction));
+        }
+        Ok(())
+    }
+
+    // 0 means no limit, a kill that doesn't fit into the last minute has to wait for the next attempt
+    pub fn take_kill(&self) -> bool {
+        if self.max_kills_per_minute <= 0 {
+            return true;
+        }
+
+        let now = UTC::now().timestamp();
+        let mut recent_kills = self.recent_kills.lock().unwrap();
+        while recent_kills.front().map_or(false, |killed| *killed + 60 <= now) {
+            recent_kills.pop_front();
+        }
+        if recent_kills.len() as i64 >= self.max_kills_per_minute {
+            return false;
+        }
+        recent_kills.push_back(now);
+        true
+    }
+}
@@ -0,0 +1,68 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use rustc_serialize::Encodable;
+use rustc_serialize::json;
+use std::mem;
+
+// what one of the in-memory collections holds. the bytes are an approximation, the size of the entries
+// themselves plus their encoded size standing in for the strings and lists they point to.
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct MemoryUsage {
+    pub collection: String,
+    pub entries: usize,
+    pub approximate_bytes: usize,
+}
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct MemoryReport {
+    pub timestamp: i64,
+    pub collections: Vec<MemoryUsage>,
+    pub approximate_bytes: usize,
+}
+
+impl MemoryUsage {
+    pub fn measure<'a, T, I>(collection: &str, entries: I) -> MemoryUsage
+        where T: 'a + Encodable,
+              I: Iterator<Item = &'a T>
+    {
+        let mut usage = MemoryUsage {
+            collection: collection.to_string(),
+            entries: 0,
+            approximate_bytes: 0,
+        };
+        for entry in entries {
+            usage.entries += 1;
+            usage.approximate_bytes += mem::size_of::<T>() + json::encode(entry).map(|encoded| encoded.len()).unwrap_or(0);
+        }
+        usage
+    }
+
+    // for entries that don't encode, only their own size counts
+    pub fn count<T>(collection: &str, entries: usize) -> MemoryUsage {
+        MemoryUsage {
+            collection: collection.to_string(),
+            entries: entries,
+            approximate_bytes: entries * mem::size_of::<T>(),
+        }
+    }
+}
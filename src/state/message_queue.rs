@@ -0,0 +1,77 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use std::collections::VecDeque;
+use std::sync::mpsc::{Receiver, RecvError};
+use std::time::Instant;
+
+// what the state thread works through next: messages carrying status updates and health transitions go ahead of
+// queries so a flood of them after a mass failure doesn't stall behind list calls, yet after max_urgent_in_a_row
+// of them a waiting query gets its turn. at most batch_size messages are taken off the channel at a time
+pub struct MessageQueue<T> {
+    urgent: VecDeque<(T, Instant)>,
+    normal: VecDeque<(T, Instant)>,
+    batch_size: usize,
+    max_urgent_in_a_row: usize,
+    urgent_in_a_row: usize,
+}
+
+impl<T> MessageQueue<T> {
+    pub fn new(batch_size: usize, max_urgent_in_a_row: usize) -> MessageQueue<T> {
+        MessageQueue {
+            urgent: VecDeque::new(),
+            normal: VecDeque::new(),
+            batch_size: batch_size.max(1),
+            max_urgent_in_a_row: max_urgent_in_a_row.max(1),
+            urgent_in_a_row: 0,
+        }
+    }
+
+    // blocks until there is a message, returns it with when it was taken off the channel
+    pub fn next(&mut self, receiver: &Receiver<T>, is_urgent: fn(&T) -> bool) -> Result<(T, Instant), RecvError> {
+        if self.urgent.is_empty() && self.normal.is_empty() {
+            let msg = try!(receiver.recv());
+            self.push(msg, is_urgent);
+        }
+        while self.urgent.len() + self.normal.len() < self.batch_size {
+            match receiver.try_recv() {
+                Ok(msg) => self.push(msg, is_urgent),
+                Err(_) => break,
+            }
+        }
+
+        if !self.urgent.is_empty() && (self.normal.is_empty() || self.urgent_in_a_row < self.max_urgent_in_a_row) {
+            self.urgent_in_a_row += 1;
+            Ok(self.urgent.pop_front().unwrap())
+        } else {
+            self.urgent_in_a_row = 0;
+            Ok(self.normal.pop_front().unwrap())
+        }
+    }
+
+    fn push(&mut self, msg: T, is_urgent: fn(&T) -> bool) {
+        match is_urgent(&msg) {
+            true => self.urgent.push_back((msg, Instant::now())),
+            false => self.normal.push_back((msg, Instant::now())),
+        }
+    }
+}
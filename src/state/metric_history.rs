@@ -0,0 +1,163 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use rustc_serialize::json;
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use super::node_list::Node;
+use super::state::TaskState;
+use super::task_list::Task;
+use utils::{read_int, read_string};
+use yaml_rust::Yaml;
+
+// aggregate view of the cluster at one point in time, reserved is what accepted and running tasks ask for
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct MetricSample {
+    pub timestamp: i64,
+    pub tasks_by_state: BTreeMap<String, i64>,
+    pub active_nodes: i64,
+    pub inactive_nodes: i64,
+    pub reserved_cpu: f64,
+    pub reserved_memory: f64,
+    pub total_cpu: f64,
+    pub total_memory: f64,
+}
+
+impl MetricSample {
+    pub fn take(timestamp: i64, tasks: &Vec<Task>, nodes: &Vec<Node>) -> MetricSample {
+        let mut sample = MetricSample {
+            timestamp: timestamp,
+            tasks_by_state: BTreeMap::new(),
+            active_nodes: 0,
+            inactive_nodes: 0,
+            reserved_cpu: 0.0,
+            reserved_memory: 0.0,
+            total_cpu: 0.0,
+            total_memory: 0.0,
+        };
+
+        for task in tasks {
+            *sample.tasks_by_state.entry(format!("{:?}", task.state)).or_insert(0) += 1;
+            if task.state == TaskState::Accepted || task.state == TaskState::Running {
+                sample.reserved_cpu += task.cpu;
+                sample.reserved_memory += task.memory;
+            }
+        }
+        for node in nodes {
+            match node.active {
+                true => sample.active_nodes += 1,
+                false => sample.inactive_nodes += 1,
+            }
+            sample.total_cpu += node.total_cpu;
+            sample.total_memory += node.total_memory;
+        }
+
+        sample
+    }
+}
+
+// samples go into one file per segment, named after the segment start, only the newest segments are kept
+pub struct MetricHistory {
+    directory: PathBuf,
+    segment_in_seconds: i64,
+    segments: i64,
+}
+
+impl MetricHistory {
+    // metric-history: path: <directory>, segment_in_seconds, segments
+    pub fn open(config: &Yaml) -> Option<MetricHistory> {
+        let history_config = &config["metric-history"];
+        if history_config.is_badvalue() {
+            return None;
+        }
+
+        let segment_in_seconds = read_int(history_config, "segment_in_seconds".to_string(), 3600);
+        Some(MetricHistory {
+            directory: PathBuf::from(read_string(history_config, "path".to_string())),
+            segment_in_seconds: if segment_in_seconds > 0 { segment_in_seconds } else { 3600 },
+            segments: read_int(history_config, "segments".to_string(), 168),
+        })
+    }
+
+    pub fn record(&self, sample: &MetricSample) -> Result<(), String> {
+        try!(fs::create_dir_all(&self.directory).map_err(|error| format!("can't create {:?}: {}", self.directory, error)));
+
+        let segment = sample.timestamp - sample.timestamp % self.segment_in_seconds;
+        let path = self.directory.join(segment.to_string());
+        let mut file = try!(OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|error| format!("can't open {:?}: {}", path, error)));
+        try!(writeln!(file, "{}", json::encode(sample).unwrap()).map_err(|error| error.to_string()));
+
+        let segments = list_segments(&self.directory);
+        if segments.len() as i64 > self.segments {
+            for old in &segments[..segments.len() - self.segments as usize] {
+                let _ = fs::remove_file(self.directory.join(old.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    // oldest first
+    pub fn read_since(&self, since: i64) -> Vec<MetricSample> {
+        let mut samples = vec![];
+        for segment in list_segments(&self.directory) {
+            if segment + self.segment_in_seconds <= since {
+                continue;
+            }
+            let file = match File::open(self.directory.join(segment.to_string())) {
+                Ok(file) => file,
+                Err(_) => continue,
+            };
+            // a line cut short by a crash while writing it is skipped
+            for line in BufReader::new(file).lines().filter_map(|line| line.ok()) {
+                match json::decode::<MetricSample>(&line) {
+                    Ok(sample) => {
+                        if sample.timestamp >= since {
+                            samples.push(sample);
+                        }
+                    }
+                    Err(_) => continue,
+                }
+            }
+        }
+        samples
+    }
+}
+
+// oldest first
+fn list_segments(directory: &PathBuf) -> Vec<i64> {
+    let mut segments: Vec<i64> = match fs::read_dir(directory) {
+        Ok(entries) => {
+            entries.filter_map(|entry| entry.ok())
+                .filter_map(|entry| entry.file_name().to_str().and_then(|name| name.parse::<i64>().ok()))
+                .collect()
+        }
+        Err(_) => vec![],
+    };
+    segments.sort();
+    segments
+}
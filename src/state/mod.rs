@@ -20,10 +20,46 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 // THE SOFTWARE.
 
-pub use self::node_list::Node;
+pub use self::conflict_list::TaskConflict;
+pub use self::cycle_report::{CleanReport, SyncReport, SyncTaskOutcome};
+pub use self::event_list::{Event, EventType, Sequence};
+pub use self::memory_usage::{MemoryReport, MemoryUsage};
+pub use self::metric_history::MetricSample;
+pub use self::node_list::{Gpu, Node, NodeUpdate, PowerState};
+pub use self::readiness_list::CollaboratorStatus;
+pub use self::restart_policy::{RestartDecision, RestartPolicy};
+pub use self::revision_list::{Revision, RevisionDiff, diff_revisions};
+pub use self::route_list::Route;
+pub use self::route_queue::{RouteOperation, RouteOperationKind};
+pub use self::run_window::RunWindow;
+pub use self::snapshot::StateSnapshot;
 pub use self::state::{StateManager, TaskState};
-pub use self::task_list::{SLA, Task, Volume};
+pub use self::store::{LoggedEntry, Replay, StoreEntry, StoredSchedule, StoredState, replay_store};
+pub use self::task_list::{HealthCheck, NetworkPolicy, PortRule, SLA, Task, TaskOverride, Volume};
+pub use self::task_spec::{TaskSpec, is_variable_name};
+pub use self::upgrade_report::{AgentUpgradeReport, AgentUpgradeStep};
+pub use self::volume_list::VolumeBinding;
 
 mod state;
 mod task_list;
+mod task_spec;
 mod node_list;
+mod event_list;
+mod guardrails;
+mod memory_usage;
+mod message_queue;
+mod metric_history;
+mod revision_list;
+mod readiness_list;
+mod restart_policy;
+mod cycle_report;
+mod cycle_trigger;
+mod conflict_list;
+mod run_window;
+mod route_list;
+mod route_queue;
+mod snapshot;
+mod store;
+mod update_coalescer;
+mod upgrade_report;
+mod volume_list;
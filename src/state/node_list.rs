@@ -21,39 +21,150 @@
 // THE SOFTWARE.
 
 use chrono::UTC;
-use std::collections::HashMap;
-use std::sync::Mutex;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::RwLock;
+use super::memory_usage::MemoryUsage;
 
 pub struct NodeList {
-    node_list: Mutex<HashMap<String, Node>>,
+    node_list: RwLock<HashMap<String, Node>>,
 }
 
-#[derive(Clone, Debug, RustcEncodable)]
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
 pub struct Node {
     pub name: String,
     pub ip: String,
     pub external_ip: String,
     pub management_ip: String,
+    pub power_backend: String,
+    pub power_credentials: String,
     pub node_type: String,
     pub node_function: String,
     pub active: bool,
     pub slave_id: String,
     pub port_id: i64,
+    pub cost: f64,
+    pub job_slots: i64,
+    pub total_cpu: f64,
+    pub total_memory: f64,
+    pub allocated_cpu: f64,
+    pub allocated_memory: f64,
+    pub groups: Vec<String>,
+    pub zone: String,
+    pub labels: BTreeMap<String, String>,
+    // operational context like a ticket or an owner, nothing acts on it
+    pub annotations: BTreeMap<String, String>,
+    pub metadata_version: i64,
+    pub draining: bool,
     pub last_seen: i64,
+    pub power_state: PowerState,
+    pub expected_power_state: PowerState,
+    pub expected_power_state_since: i64,
+    pub power_mismatch: bool,
+    pub power_in_watts: f64,
+    pub agent_version: String,
+    pub last_heartbeat: i64,
+    pub gpus: Vec<Gpu>,
+    // joined through POST /node/register asking to be kept, comes back after a restart without a nodes: entry
+    pub registered: bool,
+}
+
+// what the node agent reads off nvidia-smi, index -1 stands for a driver that didn't answer at all
+#[derive(Clone, Debug, PartialEq, RustcEncodable, RustcDecodable)]
+pub struct Gpu {
+    pub index: i64,
+    pub model: String,
+    pub driver_version: String,
+    pub healthy: bool,
+    pub health_reason: String,
+    pub memory_total_in_mb: i64,
+    pub memory_used_in_mb: i64,
+}
+
+// a partial change of the inventory fields, labels and annotations set to None get removed
+#[derive(Clone, Debug)]
+pub struct NodeUpdate {
+    pub external_ip: Option<String>,
+    pub management_ip: Option<String>,
+    pub port_id: Option<i64>,
+    pub zone: Option<String>,
+    pub labels: BTreeMap<String, Option<String>>,
+    pub annotations: BTreeMap<String, Option<String>>,
+}
+
+#[derive(Clone, Hash, Eq, PartialEq, Debug, RustcEncodable, RustcDecodable)]
+pub enum PowerState {
+    Unknown,
+    On,
+    Off,
+}
+
+impl Node {
+    // what isn't known about a node yet gets learned once its agent registers with mesos
+    pub fn new(name: String, ip: String, external_ip: String, management_ip: String, node_type: String) -> Node {
+        Node {
+            name: name,
+            ip: ip,
+            external_ip: external_ip,
+            management_ip: management_ip,
+            power_backend: "".to_string(),
+            power_credentials: "".to_string(),
+            node_type: node_type,
+            node_function: "none".to_string(),
+            active: false,
+            slave_id: "".to_string(),
+            port_id: 0,
+            cost: 1.0,
+            job_slots: 0,
+            total_cpu: 0.0,
+            total_memory: 0.0,
+            allocated_cpu: 0.0,
+            allocated_memory: 0.0,
+            groups: vec![],
+            zone: "".to_string(),
+            labels: BTreeMap::new(),
+            annotations: BTreeMap::new(),
+            metadata_version: 0,
+            draining: false,
+            last_seen: UTC::now().timestamp(),
+            power_state: PowerState::Unknown,
+            expected_power_state: PowerState::Unknown,
+            expected_power_state_since: 0,
+            power_mismatch: false,
+            power_in_watts: 0.0,
+            agent_version: "".to_string(),
+            last_heartbeat: 0,
+            gpus: vec![],
+            registered: false,
+        }
+    }
+
+    // none for a node without accelerators, failed as soon as one of them is
+    pub fn gpu_health(&self) -> &'static str {
+        match (self.gpus.is_empty(), self.gpus.iter().all(|gpu| gpu.healthy)) {
+            (true, _) => "none",
+            (false, true) => "healthy",
+            (false, false) => "failed",
+        }
+    }
+
+    // all gpus of a node run off the same driver
+    pub fn gpu_driver(&self) -> &str {
+        self.gpus.iter().map(|gpu| gpu.driver_version.as_str()).find(|version| !version.is_empty()).unwrap_or("")
+    }
 }
 
 impl NodeList {
     pub fn new() -> NodeList {
-        NodeList { node_list: Mutex::new(HashMap::new()) }
+        NodeList { node_list: RwLock::new(HashMap::new()) }
     }
 
     pub fn add_new_node(&self, node: &Node) {
-        println!("insert new node {}", node.name);
-        self.node_list.lock().unwrap().insert(node.name.to_string(), node.clone());
+        info!("insert new node node={}", node.name);
+        self.node_list.write().unwrap().insert(node.name.to_string(), node.clone());
     }
 
     pub fn is_node_active(&self, node_name: String) -> bool {
-        let mut node_list = self.node_list.lock().unwrap();
+        let mut node_list = self.node_list.write().unwrap();
 
         match node_list.get_mut(&node_name) {
             Some(node) => {
@@ -67,7 +178,7 @@ impl NodeList {
     }
 
     pub fn set_node_inactive(&self, node_name: String) {
-        let mut node_list = self.node_list.lock().unwrap();
+        let mut node_list = self.node_list.write().unwrap();
 
         match node_list.get_mut(&node_name) {
             Some(node) => {
@@ -81,39 +192,224 @@ impl NodeList {
     pub fn update_node(&self, node_name: String, node_type: String, node_function: String, slave_id: String) {
         let exists;
 
-        println!("upate node {}", node_name);
+        info!("update node node={}", node_name);
 
-        match self.node_list.lock().unwrap().get_mut(&node_name) {
+        match self.node_list.write().unwrap().get_mut(&node_name) {
             Some(node) => {
                 node.node_type = node_type.clone();
                 node.node_function = node_function.clone();
                 node.slave_id = slave_id.clone();
-                node.active = true;
+                // a drained node keeps offering resources until it's shut down, it stays out of scheduling
+                node.active = !node.draining;
                 exists = true
             }
             None => exists = false,
         }
 
         if exists == false {
-            println!("no node entry found for {}", node_name);
+            warn!("no node entry found node={}", node_name);
+        }
+    }
+
+    pub fn set_node_draining(&self, node_name: String, draining: bool) {
+        match self.node_list.write().unwrap().get_mut(&node_name) {
+            Some(node) => node.draining = draining,
+            None => {}
+        }
+    }
+
+    pub fn set_node_expected_power_state(&self, node_name: String, power_state: PowerState) {
+        match self.node_list.write().unwrap().get_mut(&node_name) {
+            Some(node) => {
+                node.expected_power_state = power_state;
+                node.expected_power_state_since = UTC::now().timestamp();
+                node.power_mismatch = false
+            }
+            None => {}
+        }
+    }
+
+    // returns the previously observed power state and whether a new mismatch got detected. a node
+    // that was just told to power on or off gets transition_timeout seconds to get there.
+    pub fn set_node_power_state(&self,
+                                node_name: String,
+                                power_state: PowerState,
+                                transition_timeout: i64)
+                                -> Result<(PowerState, bool), &'static str> {
+        match self.node_list.write().unwrap().get_mut(&node_name) {
+            Some(node) => {
+                let previous = node.power_state.clone();
+                node.power_state = power_state.clone();
+
+                let in_transition = node.expected_power_state_since + transition_timeout > UTC::now().timestamp();
+                let mismatch = node.expected_power_state != PowerState::Unknown && power_state != PowerState::Unknown &&
+                               node.expected_power_state != power_state && !in_transition;
+                let new_mismatch = mismatch && !node.power_mismatch;
+                node.power_mismatch = mismatch;
+
+                Ok((previous, new_mismatch))
+            }
+            None => Err("Can't find node"),
+        }
+    }
+
+    pub fn set_node_power_reading(&self, node_name: String, power_in_watts: f64) {
+        match self.node_list.write().unwrap().get_mut(&node_name) {
+            Some(node) => {
+                node.power_in_watts = power_in_watts;
+            }
+            None => {}
+        }
+    }
+
+    // agents from before gpu reporting send none, that leaves what's known alone
+    pub fn set_node_heartbeat(&self, node_name: String, agent_version: String, gpus: Option<Vec<Gpu>>) {
+        match self.node_list.write().unwrap().get_mut(&node_name) {
+            Some(node) => {
+                node.agent_version = agent_version;
+                node.last_heartbeat = UTC::now().timestamp();
+                if let Some(gpus) = gpus {
+                    // memory in use changes with every heartbeat, a gpu is the same one by its index
+                    for gpu in gpus.iter().filter(|gpu| !gpu.healthy) {
+                        if !node.gpus.iter().any(|known| known.index == gpu.index && !known.healthy) {
+                            warn!("gpu failed node={} gpu={} reason={}", node.name, gpu.index, gpu.health_reason);
+                        }
+                    }
+                    node.gpus = gpus;
+                }
+            }
+            None => {}
+        }
+    }
+
+    // returns what changed, the metadata version only moves when something did
+    pub fn update_node_metadata(&self, node_name: String, update: &NodeUpdate) -> Result<Vec<String>, &'static str> {
+        let mut node_list = self.node_list.write().unwrap();
+        let node = match node_list.get_mut(&node_name) {
+            Some(node) => node,
+            None => return Err("unknown node"),
+        };
+
+        let mut changes = vec![];
+        update_field(&mut node.external_ip, &update.external_ip, "external_ip", &mut changes);
+        update_field(&mut node.management_ip, &update.management_ip, "management_ip", &mut changes);
+        update_field(&mut node.port_id, &update.port_id, "port_id", &mut changes);
+        update_field(&mut node.zone, &update.zone, "zone", &mut changes);
+        update_map(&mut node.labels, &update.labels, "labels", &mut changes);
+        update_map(&mut node.annotations, &update.annotations, "annotations", &mut changes);
+
+        if !changes.is_empty() {
+            node.metadata_version += 1;
+        }
+        Ok(changes)
+    }
+
+    // another node claiming the same ip or slave, empty values never match
+    pub fn find_duplicate(&self, node_name: &String, ip: &String, slave_id: &String) -> Option<Node> {
+        self.node_list
+            .read()
+            .unwrap()
+            .values()
+            .find(|node| {
+                node.name != *node_name &&
+                ((!ip.is_empty() && node.ip == *ip) || (!slave_id.is_empty() && node.slave_id == *slave_id))
+            })
+            .cloned()
+    }
+
+    // an address of node that another node already has as one of its own, with the address
+    pub fn find_address_conflict(&self, node: &Node) -> Option<(Node, String)> {
+        let addresses: Vec<&String> = vec![&node.ip, &node.external_ip, &node.management_ip]
+            .into_iter()
+            .filter(|address| !address.is_empty())
+            .collect();
+        for existing in self.node_list.read().unwrap().values().filter(|existing| existing.name != node.name) {
+            for address in &addresses {
+                if existing.ip == **address || existing.external_ip == **address || existing.management_ip == **address {
+                    return Some((existing.clone(), address.to_string()));
+                }
+            }
+        }
+        None
+    }
+
+    pub fn remove_node(&self, node_name: &String) -> Option<Node> {
+        info!("remove node node={}", node_name);
+        self.node_list.write().unwrap().remove(node_name)
+    }
+
+    pub fn rename_node(&self, node_name: String, new_name: String) {
+        let mut node_list = self.node_list.write().unwrap();
+        match node_list.remove(&node_name) {
+            Some(mut node) => {
+                node.name = new_name.clone();
+                node_list.insert(new_name, node);
+            }
+            None => {}
         }
     }
 
     pub fn get_node(&self, node_name: String) -> Result<Node, &'static str> {
-        match self.node_list.lock().unwrap().get(&node_name) {
+        match self.node_list.read().unwrap().get(&node_name) {
             Some(node) => Ok(node.clone()),
             None => Err("Can't find node: {}"),
         }
     }
 
+    pub fn get_node_name_by_slave_id(&self, slave_id: String) -> String {
+        let mut result: String = "".to_string();
+
+        let map = self.node_list.read().unwrap();
+        for value in map.values().into_iter().filter(|value| !value.slave_id.is_empty()) {
+            if value.slave_id == slave_id {
+                result = value.name.clone();
+                break;
+            }
+        }
+
+        result
+    }
+
     pub fn get_nodes(&self) -> Vec<Node> {
         let mut result: Vec<Node> = vec![];
 
-        let map = self.node_list.lock().unwrap();
+        let map = self.node_list.read().unwrap();
         for value in map.values().into_iter() {
             result.push(value.clone());
         }
 
         result
     }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage::measure("nodes", self.node_list.read().unwrap().values())
+    }
+}
+
+// entries set to None get removed, returns nothing but the changes it made
+pub fn update_map(map: &mut BTreeMap<String, String>,
+                  update: &BTreeMap<String, Option<String>>,
+                  name: &str,
+                  changes: &mut Vec<String>) {
+    for (key, value) in update {
+        let previous = map.get(key).cloned();
+        if previous == *value {
+            continue;
+        }
+        match *value {
+            Some(ref value) => map.insert(key.clone(), value.clone()),
+            None => map.remove(key),
+        };
+        changes.push(format!("{}.{}: {:?} -> {:?}", name, key, previous, value));
+    }
+}
+
+fn update_field<T: Clone + PartialEq + ::std::fmt::Debug>(field: &mut T, value: &Option<T>, name: &str, changes: &mut Vec<String>) {
+    match *value {
+        Some(ref value) if value != field => {
+            changes.push(format!("{}: {:?} -> {:?}", name, field, value));
+            *field = value.clone();
+        }
+        _ => {}
+    }
 }
@@ -0,0 +1,87 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+pub struct ReadinessList {
+    readiness_list: Mutex<HashMap<String, CollaboratorStatus>>,
+    initialized: Mutex<bool>,
+}
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct CollaboratorStatus {
+    pub name: String,
+    pub address: String,
+    pub required: bool,
+    pub reachable: bool,
+    pub attempts: i64,
+    pub last_error: String,
+}
+
+impl ReadinessList {
+    pub fn new() -> ReadinessList {
+        ReadinessList {
+            readiness_list: Mutex::new(HashMap::new()),
+            initialized: Mutex::new(false),
+        }
+    }
+
+    pub fn set_collaborators(&self, collaborators: &Vec<CollaboratorStatus>) {
+        let mut readiness_list = self.readiness_list.lock().unwrap();
+        for collaborator in collaborators {
+            readiness_list.insert(collaborator.name.clone(), collaborator.clone());
+        }
+        *self.initialized.lock().unwrap() = true;
+    }
+
+    pub fn update_collaborator(&self, name: String, reachable: bool, error: String) {
+        match self.readiness_list.lock().unwrap().get_mut(&name) {
+            Some(collaborator) => {
+                collaborator.attempts += 1;
+                collaborator.reachable = reachable;
+                collaborator.last_error = error.clone();
+            }
+            None => {}
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        if !*self.initialized.lock().unwrap() {
+            return false;
+        }
+
+        let readiness_list = self.readiness_list.lock().unwrap();
+        readiness_list.values().all(|collaborator| !collaborator.required || collaborator.reachable)
+    }
+
+    pub fn get_collaborators(&self) -> Vec<CollaboratorStatus> {
+        let mut result: Vec<CollaboratorStatus> = vec![];
+
+        let map = self.readiness_list.lock().unwrap();
+        for value in map.values().into_iter() {
+            result.push(value.clone());
+        }
+
+        result
+    }
+}
@@ -0,0 +1,104 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use super::task_list::Task;
+use utils::read_int;
+use yaml_rust::Yaml;
+
+pub const RESTART_POLICIES: &'static [&'static str] = &["", "never", "on-failure", "always"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RestartDecision {
+    Restart,
+    Quarantine(String),
+    Remove,
+}
+
+// the delay before a restarted task gets requested again doubles with every restart in a row,
+// a task that stayed up for reset_after_in_seconds starts over at the base delay
+#[derive(Clone, Debug)]
+pub struct RestartPolicy {
+    pub base_delay_in_seconds: i64,
+    pub max_delay_in_seconds: i64,
+    pub reset_after_in_seconds: i64,
+    // 0 restarts forever, a task can set its own with max_restarts
+    pub max_restarts: i64,
+}
+
+impl RestartPolicy {
+    pub fn new(config: &Yaml) -> RestartPolicy {
+        let base_delay_in_seconds = read_int(&config["stateclean"], "restart_delay_in_seconds".to_string(), 30);
+        RestartPolicy {
+            base_delay_in_seconds: base_delay_in_seconds,
+            max_delay_in_seconds: read_int(&config["stateclean"],
+                                           "max_restart_delay_in_seconds".to_string(),
+                                           base_delay_in_seconds),
+            reset_after_in_seconds: read_int(&config["stateclean"], "restart_reset_after_in_seconds".to_string(), 600),
+            max_restarts: read_int(&config["stateclean"], "max_restarts".to_string(), 0),
+        }
+    }
+
+    pub fn delay_in_seconds(&self, restart_count: i64) -> i64 {
+        let mut delay = self.base_delay_in_seconds;
+        for _ in 1..restart_count {
+            if delay >= self.max_delay_in_seconds {
+                break;
+            }
+            delay *= 2;
+        }
+        delay.min(self.max_delay_in_seconds).max(self.base_delay_in_seconds)
+    }
+
+    // the restarts counted so far once the task exits at now, a long enough run clears them
+    pub fn restarts_in_a_row(&self, task: &Task, now: i64) -> i64 {
+        match task.running_since > 0 && task.running_since + self.reset_after_in_seconds < now {
+            true => 0,
+            false => task.restart_count,
+        }
+    }
+
+    // an empty restart_policy keeps the behaviour from before policies, only system services come back
+    pub fn decide(&self, task: &Task, my_name: &str, failed: bool, restarts: i64) -> RestartDecision {
+        if task.controller != my_name || task.is_job {
+            return RestartDecision::Remove;
+        }
+
+        let restart = match task.restart_policy.as_ref() {
+            "always" => true,
+            "on-failure" => failed,
+            "never" => false,
+            _ => task.is_system_service,
+        };
+        if !restart {
+            return RestartDecision::Remove;
+        }
+
+        let max_restarts = match task.max_restarts {
+            0 => self.max_restarts,
+            max_restarts => max_restarts,
+        };
+        match max_restarts > 0 && restarts >= max_restarts {
+            true => RestartDecision::Quarantine(format!("Quarantined after {} restarts in a row", restarts)),
+            false => RestartDecision::Restart,
+        }
+    }
+}
@@ -0,0 +1,186 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use chrono::UTC;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::sync::Mutex;
+use super::memory_usage::MemoryUsage;
+use super::task_list::Task;
+
+pub struct RevisionList {
+    revision_list: Mutex<HashMap<String, VecDeque<Revision>>>,
+    max_revisions: usize,
+}
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct Revision {
+    pub revision: u64,
+    pub timestamp: i64,
+    pub task_id: String,
+    pub image: String,
+    pub arguments: String,
+    pub parameters: String,
+    pub env: BTreeMap<String, String>,
+    pub env_from_consul_kv: String,
+    pub node_name: String,
+    pub memory: f64,
+    pub cpu: f64,
+    pub privileged: bool,
+    pub network_type: String,
+    pub ip: String,
+}
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct RevisionChange {
+    pub field: String,
+    pub old_value: String,
+    pub new_value: String,
+}
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct RevisionDiff {
+    pub from_revision: u64,
+    pub to_revision: u64,
+    pub changes: Vec<RevisionChange>,
+}
+
+impl Revision {
+    // the fields diff_revisions compares
+    fn describes(&self, task: &Task, node_name: &String) -> bool {
+        self.image == task.image && self.arguments == task.arguments && self.parameters == task.parameters &&
+        self.env == task.env && self.env_from_consul_kv == task.env_from_consul_kv &&
+        self.node_name == *node_name && self.memory == task.memory && self.cpu == task.cpu &&
+        self.privileged == task.privileged && self.network_type == task.network_type && self.ip == task.ip
+    }
+}
+
+impl RevisionList {
+    pub fn new(max_revisions: usize) -> RevisionList {
+        RevisionList {
+            revision_list: Mutex::new(HashMap::new()),
+            max_revisions: max_revisions,
+        }
+    }
+
+    pub fn add_revision(&self, task: &Task, node_name: String) {
+        let mut revision_list = self.revision_list.lock().unwrap();
+        let revisions = revision_list.entry(task.name.clone()).or_insert(VecDeque::new());
+
+        // a repeated running update of the same launch, or a restart that changed nothing, isn't a new revision
+        let revision = match revisions.back() {
+            Some(last) if last.describes(task, &node_name) => return,
+            Some(last) => last.revision + 1,
+            None => 1,
+        };
+
+        revisions.push_back(Revision {
+            revision: revision,
+            timestamp: UTC::now().timestamp(),
+            task_id: task.id.clone(),
+            image: task.image.clone(),
+            arguments: task.arguments.clone(),
+            parameters: task.parameters.clone(),
+            env: task.env.clone(),
+            env_from_consul_kv: task.env_from_consul_kv.clone(),
+            node_name: node_name,
+            memory: task.memory,
+            cpu: task.cpu,
+            privileged: task.privileged,
+            network_type: task.network_type.clone(),
+            ip: task.ip.clone(),
+        });
+
+        while revisions.len() > self.max_revisions {
+            revisions.pop_front();
+        }
+    }
+
+    pub fn get_revisions(&self, task_name: String) -> Vec<Revision> {
+        let mut result: Vec<Revision> = vec![];
+
+        match self.revision_list.lock().unwrap().get(&task_name) {
+            Some(revisions) => {
+                for revision in revisions.iter() {
+                    result.push(revision.clone());
+                }
+            }
+            None => {}
+        }
+
+        result
+    }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage::measure("revisions", self.revision_list.lock().unwrap().values().flat_map(|revisions| revisions.iter()))
+    }
+}
+
+pub fn diff_revisions(revisions: &Vec<Revision>) -> Vec<RevisionDiff> {
+    let mut result: Vec<RevisionDiff> = vec![];
+
+    for pair in revisions.windows(2) {
+        let (old, new) = (&pair[0], &pair[1]);
+        let mut changes: Vec<RevisionChange> = vec![];
+
+        add_change(&mut changes, "image", &old.image, &new.image);
+        add_change(&mut changes, "arguments", &old.arguments, &new.arguments);
+        add_change(&mut changes, "parameters", &old.parameters, &new.parameters);
+        add_env_changes(&mut changes, &old.env, &new.env);
+        add_change(&mut changes, "env_from_consul_kv", &old.env_from_consul_kv, &new.env_from_consul_kv);
+        add_change(&mut changes, "node_name", &old.node_name, &new.node_name);
+        add_change(&mut changes, "memory", &old.memory.to_string(), &new.memory.to_string());
+        add_change(&mut changes, "cpu", &old.cpu.to_string(), &new.cpu.to_string());
+        add_change(&mut changes, "privileged", &old.privileged.to_string(), &new.privileged.to_string());
+        add_change(&mut changes, "network_type", &old.network_type, &new.network_type);
+        add_change(&mut changes, "ip", &old.ip, &new.ip);
+
+        result.push(RevisionDiff {
+            from_revision: old.revision,
+            to_revision: new.revision,
+            changes: changes,
+        });
+    }
+
+    result
+}
+
+fn add_change(changes: &mut Vec<RevisionChange>, field: &str, old_value: &String, new_value: &String) {
+    if old_value != new_value {
+        changes.push(RevisionChange {
+            field: field.to_string(),
+            old_value: old_value.clone(),
+            new_value: new_value.clone(),
+        });
+    }
+}
+
+// one change per variable, a missing one shows as an empty value
+fn add_env_changes(changes: &mut Vec<RevisionChange>, old_env: &BTreeMap<String, String>, new_env: &BTreeMap<String, String>) {
+    let empty = String::new();
+    let names: BTreeSet<&String> = old_env.keys().chain(new_env.keys()).collect();
+    for name in names {
+        add_change(changes,
+                   &format!("env.{}", name),
+                   old_env.get(name).unwrap_or(&empty),
+                   new_env.get(name).unwrap_or(&empty));
+    }
+}
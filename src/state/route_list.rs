@@ -0,0 +1,60 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use super::memory_usage::MemoryUsage;
+
+// routes announced to the network agent, keyed by task name
+pub struct RouteList {
+    route_list: Mutex<HashMap<String, Route>>,
+}
+
+#[derive(Clone, Debug, PartialEq, RustcEncodable)]
+pub struct Route {
+    pub task_name: String,
+    pub ip: String,
+    pub next_hop: String,
+}
+
+impl RouteList {
+    pub fn new() -> RouteList {
+        RouteList { route_list: Mutex::new(HashMap::new()) }
+    }
+
+    // returns the route previously announced for the task, if any
+    pub fn set_route(&self, route: &Route) -> Option<Route> {
+        self.route_list.lock().unwrap().insert(route.task_name.clone(), route.clone())
+    }
+
+    pub fn remove_route(&self, task_name: String) -> Option<Route> {
+        self.route_list.lock().unwrap().remove(&task_name)
+    }
+
+    pub fn get_routes(&self) -> Vec<Route> {
+        self.route_list.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage::measure("routes", self.route_list.lock().unwrap().values())
+    }
+}
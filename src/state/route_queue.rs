@@ -0,0 +1,143 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use rustc_serialize::json;
+use std::cmp;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::sync::Mutex;
+use super::memory_usage::MemoryUsage;
+
+#[derive(Clone, Debug, PartialEq, RustcEncodable, RustcDecodable)]
+pub enum RouteOperationKind {
+    Add,
+    Delete,
+}
+
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct RouteOperation {
+    pub task_name: String,
+    pub kind: RouteOperationKind,
+    pub ip: String,
+    pub next_hop: String,
+    pub attempts: i64,
+    pub first_failed: i64,
+    pub next_attempt: i64,
+}
+
+// route operations the network agent didn't take, written to path on every change so they survive a restart
+pub struct RouteQueue {
+    operations: Mutex<Vec<RouteOperation>>,
+    path: String,
+}
+
+impl RouteOperation {
+    // a newer operation for the same task makes an older one obsolete, the route for an ip
+    // ends up however it was asked for last and a task only ever has its latest ip routed
+    pub fn supersedes(&self, older: &RouteOperation) -> bool {
+        self.task_name == older.task_name &&
+        (older.ip == self.ip || (older.kind == RouteOperationKind::Add && self.kind == RouteOperationKind::Add))
+    }
+}
+
+impl RouteQueue {
+    pub fn new(path: String) -> RouteQueue {
+        let operations = match path.is_empty() {
+            true => vec![],
+            false => load(&path),
+        };
+        if !operations.is_empty() {
+            info!("route operations left to retry count={} path={}", operations.len(), path);
+        }
+
+        RouteQueue {
+            operations: Mutex::new(operations),
+            path: path,
+        }
+    }
+
+    pub fn enqueue(&self, operation: &RouteOperation) {
+        let mut operations = self.operations.lock().unwrap();
+        operations.retain(|queued| !operation.supersedes(queued));
+        operations.push(operation.clone());
+        self.save(&operations);
+    }
+
+    // done operations take whatever they made obsolete with them, failed ones back off exponentially
+    pub fn finish(&self, operation: &RouteOperation, success: bool, now: i64, initial_backoff: i64, max_backoff: i64) {
+        let mut operations = self.operations.lock().unwrap();
+
+        if success {
+            operations.retain(|queued| !operation.supersedes(queued));
+        } else {
+            for queued in operations.iter_mut() {
+                if queued.task_name == operation.task_name && queued.kind == operation.kind && queued.ip == operation.ip {
+                    queued.attempts += 1;
+                    let backoff = cmp::min(max_backoff, initial_backoff << cmp::min(queued.attempts, 16));
+                    queued.next_attempt = now + backoff;
+                }
+            }
+        }
+
+        self.save(&operations);
+    }
+
+    pub fn get_due_operations(&self, now: i64) -> Vec<RouteOperation> {
+        self.operations.lock().unwrap().iter().filter(|operation| operation.next_attempt <= now).cloned().collect()
+    }
+
+    pub fn get_operations(&self) -> Vec<RouteOperation> {
+        self.operations.lock().unwrap().clone()
+    }
+
+    fn save(&self, operations: &Vec<RouteOperation>) {
+        if self.path.is_empty() {
+            return;
+        }
+
+        let result = File::create(&self.path).and_then(|mut file| file.write_all(json::encode(operations).unwrap().as_bytes()));
+        match result {
+            Ok(_) => {}
+            Err(error) => error!("can't write route queue path={} error={}", self.path, error),
+        }
+    }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage::measure("route_retries", self.operations.lock().unwrap().iter())
+    }
+}
+
+fn load(path: &String) -> Vec<RouteOperation> {
+    let mut content = String::new();
+    match File::open(path).and_then(|mut file| file.read_to_string(&mut content)) {
+        Ok(_) => {}
+        Err(_) => return vec![],
+    }
+
+    match json::decode(&content) {
+        Ok(operations) => operations,
+        Err(error) => {
+            warn!("ignoring unreadable route queue path={} error={}", path, error);
+            vec![]
+        }
+    }
+}
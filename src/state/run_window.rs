@@ -0,0 +1,99 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use chrono::{DateTime, Datelike, FixedOffset, Timelike, UTC, Weekday};
+
+// timezone is either "UTC" or a fixed offset like "+02:00" / "-05:00",
+// a window with end before start spans midnight and belongs to the day it started
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct RunWindow {
+    pub days: Vec<String>,
+    pub start: String,
+    pub end: String,
+    pub timezone: String,
+}
+
+impl RunWindow {
+    pub fn is_open(&self) -> bool {
+        self.is_open_at(UTC::now())
+    }
+
+    pub fn is_open_at(&self, now: DateTime<UTC>) -> bool {
+        let local = now.with_timezone(&parse_offset(&self.timezone));
+        let minute = (local.hour() * 60 + local.minute()) as i64;
+        let start = parse_minute_of_day(&self.start).unwrap_or(0);
+        let end = parse_minute_of_day(&self.end).unwrap_or(24 * 60);
+
+        if start <= end {
+            self.has_day(local.weekday()) && minute >= start && minute < end
+        } else {
+            (self.has_day(local.weekday()) && minute >= start) || (self.has_day(local.weekday().pred()) && minute < end)
+        }
+    }
+
+    fn has_day(&self, weekday: Weekday) -> bool {
+        if self.days.is_empty() {
+            return true;
+        }
+
+        let name = match weekday {
+            Weekday::Mon => "mon",
+            Weekday::Tue => "tue",
+            Weekday::Wed => "wed",
+            Weekday::Thu => "thu",
+            Weekday::Fri => "fri",
+            Weekday::Sat => "sat",
+            Weekday::Sun => "sun",
+        };
+
+        self.days.iter().any(|day| day.to_lowercase().starts_with(name))
+    }
+}
+
+fn parse_minute_of_day(time: &str) -> Option<i64> {
+    let elements: Vec<&str> = time.trim().split(':').collect();
+    if elements.len() != 2 {
+        return None;
+    }
+
+    match (elements[0].parse::<i64>(), elements[1].parse::<i64>()) {
+        (Ok(hour), Ok(minute)) if hour >= 0 && hour <= 24 && minute >= 0 && minute < 60 => Some(hour * 60 + minute),
+        _ => None,
+    }
+}
+
+fn parse_offset(timezone: &str) -> FixedOffset {
+    let timezone = timezone.trim();
+    if timezone.is_empty() || timezone.eq_ignore_ascii_case("utc") {
+        return FixedOffset::east(0);
+    }
+
+    let (sign, rest) = timezone.split_at(1);
+    match (sign, parse_minute_of_day(rest)) {
+        ("+", Some(minutes)) => FixedOffset::east(minutes as i32 * 60),
+        ("-", Some(minutes)) => FixedOffset::west(minutes as i32 * 60),
+        _ => {
+            warn!("unsupported run_window timezone, using UTC timezone={}", timezone);
+            FixedOffset::east(0)
+        }
+    }
+}
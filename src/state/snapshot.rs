@@ -0,0 +1,45 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use std::collections::BTreeMap;
+use super::node_list::Node;
+use super::readiness_list::CollaboratorStatus;
+use super::route_list::Route;
+use super::task_list::Task;
+
+// everything the state thread knows, captured in one go for support bundles
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct StateSnapshot {
+    pub timestamp: i64,
+    pub my_name: String,
+    pub cluster_open: bool,
+    pub teardown: bool,
+    pub tasks: Vec<Task>,
+    pub pending_tasks: Vec<String>,
+    pub nodes: Vec<Node>,
+    pub routes: Vec<Route>,
+    pub collaborators: Vec<CollaboratorStatus>,
+    pub counters: BTreeMap<String, usize>,
+    // the last change already reflected here, GET /events?since=&epoch= picks up right after it
+    pub event_epoch: i64,
+    pub event_sequence: u64,
+}
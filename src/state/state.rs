@@ -21,28 +21,67 @@
 // THE SOFTWARE.
 
 use chrono::UTC;
-use collaborator::{add_route, delete_route, kill_task, register_running_task, reset_fib};
-use std::fs::File;
-use std::io::Read;
+use collaborator::{HttpPolicy, IpmiConfig, NetworkAgent, NetworkAgentError, TtlCheck, add_route, agent_token, call_restart_hook,
+                   collect_artifacts, delete_route, deregister_task, kill_task, new_network_agent, power_reading, power_status,
+                   refresh_task_ttl, register_running_task, resolve_image_digest, set_agent_token, set_http_policy,
+                   start_event_export};
+use scheduler::{FetchPolicy, OfferPolicy, PlacementPolicy, ScheduledJobs, forward_delete, forward_kill};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
 use std::sync::mpsc::{Receiver, Sender, channel};
 use std::thread;
-use std::time::Duration;
-use super::node_list::{Node, NodeList};
-use super::task_list::{SLA, Task, TaskList, Volume};
-use utils::{read_int, read_string, read_string_replace_variable};
+use std::time::{Duration, Instant};
+use super::conflict_list::{ConflictList, TaskConflict};
+use super::cycle_report::{CleanReport, SyncReport, SyncTaskOutcome};
+use super::cycle_trigger::{CycleTrigger, wait_for_cycle};
+use super::event_list::{Event, EventList, EventType};
+use super::guardrails::Guardrails;
+use super::memory_usage::MemoryReport;
+use super::message_queue::MessageQueue;
+use super::metric_history::{MetricHistory, MetricSample};
+use super::node_list::{Gpu, Node, NodeList, NodeUpdate, PowerState};
+use super::readiness_list::{CollaboratorStatus, ReadinessList};
+use super::restart_policy::{RestartDecision, RestartPolicy};
+use super::revision_list::{Revision, RevisionList};
+use super::route_list::{Route, RouteList};
+use super::route_queue::{RouteOperation, RouteOperationKind, RouteQueue};
+use super::run_window::RunWindow;
+use super::snapshot::StateSnapshot;
+use super::store::{StoreEntry, StoredSchedule, StoredState, open_store, start_store_writer};
+use super::task_list::{HealthCheck, NetworkPolicy, SLA, Task, TaskList, TaskOverride, Volume};
+use super::task_spec::TaskSpec;
+use super::update_coalescer::{PendingUpdate, UpdateCoalescer};
+use super::upgrade_report::AgentUpgradeReport;
+use super::volume_list::{VolumeBinding, VolumeList};
+use utils::{load_config, observe_state_message, read_bool, read_float, read_int, read_string, read_string_list,
+            read_string_map, read_string_replace_variable};
 use uuid::Uuid;
-use yaml_rust::{Yaml, YamlLoader};
+use yaml_rust::Yaml;
 
+// changes go through the state-serve thread one at a time, reads of tasks and nodes
+// go straight to the shared lists so they don't queue up behind the changes
 #[derive (Clone)]
 pub struct StateManager {
     sender: Sender<StateRequestMsg>,
+    task_list: Arc<TaskList>,
+    node_list: Arc<NodeList>,
     master_ip: String,
     my_name: String,
     my_ip: String,
     my_framework_id: String,
-    ipmi_proxy: String,
+    ipmi_config: IpmiConfig,
     network_agent_type: String,
     network_agent_connection: String,
+    network_agent: Arc<NetworkAgent>,
+    placement_policy: PlacementPolicy,
+    fetch_policy: FetchPolicy,
+    offer_policy: OfferPolicy,
+    guardrails: Arc<Guardrails>,
+    cycle_trigger: Arc<CycleTrigger>,
+    update_coalescer: Arc<UpdateCoalescer>,
+    scheduled_jobs: Arc<ScheduledJobs>,
+    // tasks held back from offers until their image is pinned
+    resolving_digests: Arc<Mutex<BTreeSet<String>>>,
     config: Yaml,
 }
 
@@ -53,28 +92,61 @@ pub enum TaskState {
     Requested,
     Accepted,
     Running,
+    NetworkFailed,
+    Failed,
+    Finished,
+    Delegated,
+    Deleted,
 }
 
 
 impl StateManager {
-    pub fn new(master_ip: String, my_ip: String, config_file: String) -> StateManager {
+    // framework_id is the one shared by the leader-election group, otherwise every start registers a new framework
+    pub fn new(master_ip: String, my_ip: String, config_file: String, framework_id: Option<String>) -> StateManager {
         let (tx, rx) = channel();
-        let config = StateManager::read_config_file(config_file);
+        // already read once by main, only a file changed in between can fail here
+        let config = match StateManager::read_config_file(config_file) {
+            Ok(config) => config,
+            Err(error) => panic!("invalid config error={}", error),
+        };
         let my_name = config["name"].as_str().unwrap_or("torc-controller").to_string();
-        let ipmi_proxy = config["ipmiproxy"].as_str().unwrap_or("undefined").to_string();
+        let ipmi_config = IpmiConfig::new(&config);
+        let http_policy = HttpPolicy::from_config(&config);
         let network_agent_type = config["network-agent"]["type"].as_str().unwrap_or("undefined").to_string();
+        if let Err(error) = set_http_policy(http_policy) {
+            panic!("can't set up the collaborator http client error={}", error);
+        }
+        set_agent_token(agent_token(&config));
         let mut network_agent_connection = config["network-agent"]["connection"].as_str().unwrap_or("undefined").to_string();
         network_agent_connection = str::replace(&network_agent_connection, "$MASTER_IP", &master_ip);
+        let network_agent = new_network_agent(&network_agent_type, &network_agent_connection);
+        let placement_policy = PlacementPolicy::new(&config);
+        let fetch_policy = FetchPolicy::new(&config);
+        let offer_policy = OfferPolicy::new(&config);
+        let guardrails = Guardrails::new(&config);
+        let scheduled_jobs = ScheduledJobs::new(&config);
+        let coalescing_window = Duration::from_millis(read_int(&config["coalescing"], "window_in_ms".to_string(), 250) as u64);
 
         let statemanager = StateManager {
             sender: tx,
+            task_list: Arc::new(TaskList::new()),
+            node_list: Arc::new(NodeList::new()),
             master_ip: master_ip.clone(),
             my_name: my_name.clone(),
             my_ip: my_ip,
-            my_framework_id: format!("{}-{}", my_name.clone(), Uuid::new_v4().to_simple_string()),
-            ipmi_proxy: ipmi_proxy.clone(),
+            my_framework_id: framework_id.unwrap_or(format!("{}-{}", my_name.clone(), Uuid::new_v4().to_simple_string())),
+            ipmi_config: ipmi_config,
             network_agent_type: network_agent_type.clone(),
             network_agent_connection: network_agent_connection.clone(),
+            network_agent: network_agent,
+            placement_policy: placement_policy,
+            fetch_policy: fetch_policy,
+            offer_policy: offer_policy,
+            guardrails: Arc::new(guardrails),
+            cycle_trigger: Arc::new(CycleTrigger::new()),
+            update_coalescer: Arc::new(UpdateCoalescer::new(coalescing_window)),
+            scheduled_jobs: Arc::new(scheduled_jobs),
+            resolving_digests: Arc::new(Mutex::new(BTreeSet::new())),
             config: config,
         };
 
@@ -82,8 +154,11 @@ impl StateManager {
         statemanager.load_node_list();
         statemanager.start_syncing();
         statemanager.start_cleaning();
-
-        reset_fib(&network_agent_type, &network_agent_connection);
+        statemanager.start_power_polling();
+        statemanager.start_route_retries();
+        statemanager.start_update_flushing();
+        statemanager.start_store_snapshots();
+        statemanager.start_metric_sampling();
 
         statemanager
     }
@@ -96,8 +171,8 @@ impl StateManager {
         self.my_name.clone()
     }
 
-    pub fn get_ipmi_proxy(&self) -> String {
-        self.ipmi_proxy.clone()
+    pub fn get_ipmi_config(&self) -> IpmiConfig {
+        self.ipmi_config.clone()
     }
 
     pub fn get_my_framework_id(&self) -> String {
@@ -116,67 +191,97 @@ impl StateManager {
         self.network_agent_connection.clone()
     }
 
-    pub fn get_yaml(&self) -> Yaml {
-        self.config.clone()
+    pub fn get_network_agent(&self) -> Arc<NetworkAgent> {
+        self.network_agent.clone()
     }
 
-    pub fn send_ping(&self) {
-        let (sender, receiver) = channel();
+    pub fn get_placement_policy(&self) -> PlacementPolicy {
+        self.placement_policy.clone()
+    }
 
-        let msg = StateRequestMsg::Ping { sender: sender };
-        self.sender.send(msg).unwrap();
-        receiver.recv().unwrap();
+    pub fn get_fetch_policy(&self) -> FetchPolicy {
+        self.fetch_policy.clone()
     }
 
-    pub fn request_task_state(&self, task_name: String) -> TaskState {
+    pub fn get_offer_policy(&self) -> OfferPolicy {
+        self.offer_policy.clone()
+    }
+
+    pub fn get_scheduled_jobs(&self) -> Arc<ScheduledJobs> {
+        self.scheduled_jobs.clone()
+    }
+
+    // checks the guardrail and marks the nodes draining in one go, so two drains at once can't both pass it
+    pub fn send_begin_drain(&self, to_drain: &Vec<Node>) -> Result<(), String> {
         let (sender, receiver) = channel();
 
-        let msg = StateRequestMsg::GetTaskState {
+        let msg = StateRequestMsg::BeginDrain {
             sender: sender,
-            task_name: task_name,
+            node_names: to_drain.iter().map(|node| node.name.clone()).collect(),
         };
         self.sender.send(msg).unwrap();
 
-        let state = match receiver.recv().unwrap() {
-            StateResponseMsg::TaskState { task_state } => task_state,
-            _ => TaskState::NotRunning,
-        };
+        match receiver.recv().unwrap() {
+            StateResponseMsg::BeginDrain { result } => result,
+            _ => Err("unexpected response".to_string()),
+        }
+    }
 
-        state
+    // automation asks before killing a task, a kill over the per minute limit is left for its next pass
+    pub fn take_automated_kill(&self, task_name: &String) -> bool {
+        let granted = self.guardrails.take_kill();
+        if !granted {
+            warn!("guardrail deferred kill, too many automated kills in the last minute task={}", task_name);
+        }
+        granted
     }
 
-    pub fn request_task_ip(&self, task_name: String) -> String {
+    pub fn get_yaml(&self) -> Yaml {
+        self.config.clone()
+    }
+
+    pub fn send_ping(&self) {
         let (sender, receiver) = channel();
 
-        let msg = StateRequestMsg::GetTaskIP {
-            sender: sender,
-            task_name: task_name,
-        };
+        let msg = StateRequestMsg::Ping { sender: sender };
         self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+    }
 
-        let ip = match receiver.recv().unwrap() {
-            StateResponseMsg::TaskIP { task_ip } => task_ip,
-            _ => "".to_string(),
-        };
+    pub fn request_task_state(&self, task_name: String) -> TaskState {
+        self.task_list.get_task_state(task_name)
+    }
 
-        ip
+    pub fn request_task_ip(&self, task_name: String) -> String {
+        match self.task_list.get_task(task_name) {
+            Ok(task) => task.ip.clone(),
+            Err(_) => "".to_string(),
+        }
     }
 
-    pub fn request_task_name_by_id(&self, id_prefix: String) -> String {
-        let (sender, receiver) = channel();
+    pub fn request_task(&self, task_name: String) -> Option<Task> {
+        self.task_list.get_task(task_name).ok()
+    }
 
-        let msg = StateRequestMsg::GetTaskNameById {
-            sender: sender,
-            id_prefix: id_prefix,
-        };
-        self.sender.send(msg).unwrap();
+    // a dependency names a task or a group of them, the replicas of a service or the members of an array
+    pub fn request_dependency_tasks(&self, name: &String) -> Vec<Task> {
+        match self.request_task(name.clone()) {
+            Some(task) => vec![task],
+            None => {
+                self.request_list_tasks()
+                    .into_iter()
+                    .filter(|task| !task.standby && (task.replica_group == *name || task.array_name == *name))
+                    .collect()
+            }
+        }
+    }
 
-        let task_name: String = match receiver.recv().unwrap() {
-            StateResponseMsg::TaskName { task_name } => task_name,
-            _ => "".to_string(),
-        };
+    pub fn request_task_by_id(&self, id_prefix: String) -> Option<Task> {
+        self.task_list.get_task_by_id(id_prefix)
+    }
 
-        task_name.clone()
+    pub fn request_task_name_by_id(&self, id_prefix: String) -> String {
+        self.task_list.get_task_name_by_id(id_prefix)
     }
 
     pub fn send_update_task_state(&self, task_name: String, task_state: TaskState) {
@@ -217,52 +322,16 @@ impl StateManager {
         receiver.recv().unwrap();
     }
 
-    pub fn send_start_task(&self,
-                           name: &String,
-                           image: &String,
-                           node_name: &String,
-                           node_type: &String,
-                           node_function: &String,
-                           dependent_service: &String,
-                           arguments: &String,
-                           parameters: &String,
-                           memory: &f64,
-                           cpu: &f64,
-                           volumes: &Vec<Volume>,
-                           privileged: &bool,
-                           sla: &SLA,
-                           is_metered: &bool,
-                           is_system_service: &bool,
-                           is_job: &bool,
-                           network_type: &String) {
+    pub fn send_start_task(&self, spec: &TaskSpec) -> Result<(), String> {
+        try!(spec.validate());
 
         let (sender, receiver) = channel();
 
-        let new_task = Task {
-            name: name.clone(),
-            controller: self.get_my_name(),
-            id: "".to_string(),
-            image: image.clone(),
-            node_name: node_name.clone(),
-            node_type: node_type.clone(),
-            node_function: node_function.clone(),
-            dependent_service: dependent_service.clone(),
-            arguments: arguments.clone(),
-            parameters: parameters.clone(),
-            memory: memory.clone(),
-            cpu: cpu.clone(),
-            privileged: privileged.clone(),
-            sla: sla.clone(),
-            is_metered: is_metered.clone(),
-            is_system_service: is_system_service.clone(),
-            is_job: is_job.clone(),
-            volumes: volumes.clone(),
-            network_type: network_type.clone(),
-            ip: "".to_string(),
-            slave_id: "".to_string(),
-            state: TaskState::Requested,
-            last_update: UTC::now().timestamp(),
-        };
+        let new_task = self.new_task(spec, "".to_string());
+        let pin_image = self.wants_image_digest(&spec.image);
+        if pin_image {
+            self.resolving_digests.lock().unwrap().insert(spec.name.clone());
+        }
 
         let msg = StateRequestMsg::StartTask {
             sender: sender,
@@ -270,701 +339,3981 @@ impl StateManager {
         };
 
         self.sender.send(msg).unwrap();
-        receiver.recv().unwrap();
+        let result = match receiver.recv().unwrap() {
+            StateResponseMsg::StartTask { result } => result,
+            _ => Err("unexpected response".to_string()),
+        };
+
+        match (pin_image, result.is_ok()) {
+            (true, true) => self.start_resolving_image_digest(&spec.name, &spec.image),
+            (true, false) => {
+                self.resolving_digests.lock().unwrap().remove(&spec.name);
+            }
+            _ => {}
+        }
+        result
     }
 
-    pub fn send_restart_task(&self, task_name: String) {
+    // a task reconcile reports running that this controller never requested, e.g. one the leader started
+    // before a takeover. it gets recorded as accepted so the running update takes it over instead of
+    // the health checker starting it a second time
+    pub fn send_adopt_task(&self, spec: &TaskSpec, slave_id: &String) {
         let (sender, receiver) = channel();
 
-        let msg = StateRequestMsg::RestartTask {
+        let mut task = self.new_task(spec, "".to_string());
+        task.slave_id = slave_id.clone();
+        task.state = TaskState::Accepted;
+        task.accepted_since = UTC::now().timestamp();
+
+        let msg = StateRequestMsg::AdoptTask {
             sender: sender,
-            task_name: task_name,
+            task: task,
         };
         self.sender.send(msg).unwrap();
         receiver.recv().unwrap();
     }
 
-    pub fn request_is_restartable_task(&self, task_name: String) -> bool {
+    fn new_task(&self, spec: &TaskSpec, image_digest: String) -> Task {
+        Task {
+            name: spec.name.clone(),
+            controller: self.get_my_name(),
+            id: "".to_string(),
+            image: spec.image.clone(),
+            image_digest: image_digest,
+            node_name: spec.node_name.clone(),
+            node_type: spec.node_type.clone(),
+            node_function: spec.node_function.clone(),
+            constraints: spec.constraints.clone(),
+            dependent_service: spec.dependent_service.clone(),
+            restart_dependents: spec.restart_dependents,
+            arguments: spec.arguments.clone(),
+            parameters: spec.parameters.clone(),
+            env: spec.env.clone(),
+            env_from_consul_kv: spec.env_from_consul_kv.clone(),
+            memory: spec.memory,
+            cpu: spec.cpu,
+            cpu_policy: spec.cpu_policy.clone(),
+            privileged: spec.privileged,
+            sla: spec.sla.clone(),
+            priority: spec.priority,
+            is_metered: spec.is_metered,
+            is_system_service: spec.is_system_service,
+            is_job: spec.is_job,
+            volumes: spec.volumes.clone(),
+            network_type: spec.network_type.clone(),
+            run_window: spec.run_window.clone(),
+            health_check: spec.health_check.clone(),
+            network_policy: spec.network_policy.clone(),
+            aliases: spec.aliases.clone(),
+            slow_start_in_seconds: spec.slow_start_in_seconds,
+            accepted_since: 0,
+            running_since: 0,
+            max_runtime_seconds: spec.max_runtime_seconds,
+            restart_policy: spec.restart_policy.clone(),
+            max_restarts: spec.max_restarts,
+            failure_reason: "".to_string(),
+            array_name: spec.array_name.clone(),
+            array_index: spec.array_index,
+            replica_group: spec.replica_group.clone(),
+            replica_index: spec.replica_index,
+            standby_group: spec.standby_group.clone(),
+            standby: spec.standby,
+            delegated_to: "".to_string(),
+            delegated_from: "".to_string(),
+            remote_state: "".to_string(),
+            artifacts: spec.artifacts.clone(),
+            annotations: spec.annotations.clone(),
+            artifact_urls: vec![],
+            ip: "".to_string(),
+            slave_id: "".to_string(),
+            state: TaskState::Requested,
+            last_update: UTC::now().timestamp(),
+            deleted_at: 0,
+            network_retries: 0,
+            restart_count: 0,
+            power_in_watts: 0.0,
+            energy_in_wh: 0.0,
+        }
+    }
+
+    #[deprecated(note = "build a TaskSpec and use send_start_task")]
+    pub fn send_start_task_positional(&self,
+                                      name: &String,
+                                      image: &String,
+                                      node_name: &String,
+                                      node_type: &String,
+                                      node_function: &String,
+                                      constraints: &Vec<String>,
+                                      dependent_service: &String,
+                                      restart_dependents: &bool,
+                                      arguments: &String,
+                                      parameters: &String,
+                                      memory: &f64,
+                                      cpu: &f64,
+                                      cpu_policy: &String,
+                                      volumes: &Vec<Volume>,
+                                      privileged: &bool,
+                                      sla: &SLA,
+                                      priority: &u8,
+                                      is_metered: &bool,
+                                      is_system_service: &bool,
+                                      is_job: &bool,
+                                      network_type: &String,
+                                      run_window: &Option<RunWindow>,
+                                      health_check: &Option<HealthCheck>,
+                                      network_policy: &Option<NetworkPolicy>,
+                                      aliases: &Vec<String>,
+                                      slow_start_in_seconds: &i64,
+                                      max_runtime_seconds: &i64,
+                                      array_name: &String,
+                                      array_index: &i64,
+                                      replica_group: &String,
+                                      replica_index: &i64,
+                                      artifacts: &Vec<String>)
+                                      -> Result<(), String> {
+        let spec = TaskSpec::new(name, image, network_type)
+            .node(node_name, node_type, node_function)
+            .constraints(constraints.clone())
+            .dependent_service(dependent_service, *restart_dependents)
+            .arguments(arguments)
+            .parameters(parameters)
+            .resources(*memory, *cpu, cpu_policy)
+            .volumes(volumes.clone())
+            .privileged(*privileged)
+            .sla(sla.clone())
+            .priority(*priority)
+            .metered(*is_metered)
+            .system_service(*is_system_service)
+            .job(*is_job)
+            .run_window(run_window.clone())
+            .health_check(health_check.clone())
+            .network_policy(network_policy.clone())
+            .aliases(aliases.clone())
+            .slow_start(*slow_start_in_seconds)
+            .max_runtime(*max_runtime_seconds)
+            .array_member(array_name, *array_index)
+            .replica(replica_group, *replica_index)
+            .artifacts(artifacts.clone());
+        self.send_start_task(&spec)
+    }
+
+    fn wants_image_digest(&self, image: &String) -> bool {
+        read_bool(&self.get_yaml()["registry"], "resolve_digests".to_string()) || image.contains('@')
+    }
+
+    // pins the image to the digest it had when first requested, restarts reuse the same task and therefore the same
+    // image. the registry can take a while, the task waits for it in the background and offers pass it by until then,
+    // a registry that can't be asked leaves it unpinned
+    fn start_resolving_image_digest(&self, task_name: &String, image: &String) {
+        let scheme = self.get_yaml()["registry"]["scheme"].as_str().unwrap_or("http").to_string();
+        let state_manager = self.clone();
+        let task_name = task_name.clone();
+        let image = image.clone();
+        thread::Builder::new()
+            .name("image-digest".to_string())
+            .spawn(move || {
+                if let Some(digest) = resolve_image_digest(&image, &scheme) {
+                    state_manager.send_set_task_image_digest(task_name.clone(), image, digest);
+                }
+                state_manager.resolving_digests.lock().unwrap().remove(&task_name);
+            })
+            .unwrap();
+    }
+
+    pub fn send_set_task_image_digest(&self, task_name: String, image: String, digest: String) {
         let (sender, receiver) = channel();
 
-        let msg = StateRequestMsg::GetIsRestartableTask {
+        let msg = StateRequestMsg::SetTaskImageDigest {
             sender: sender,
             task_name: task_name,
+            image: image,
+            digest: digest,
         };
         self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+    }
 
-        let is_system_task = match receiver.recv().unwrap() {
-            StateResponseMsg::GetIsRestartableTask { is_restartable_task } => is_restartable_task,
-            _ => false,
+    pub fn request_resolving_image_digests(&self) -> BTreeSet<String> {
+        self.resolving_digests.lock().unwrap().clone()
+    }
+
+    pub fn send_network_failure(&self, task_name: String) -> TaskState {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::NetworkFailure {
+            sender: sender,
+            task_name: task_name.clone(),
         };
+        self.sender.send(msg).unwrap();
 
-        is_system_task
-    }
+        let task_state = match receiver.recv().unwrap() {
+            StateResponseMsg::NetworkFailure { task_state } => task_state,
+            _ => TaskState::NetworkFailed,
+        };
 
-    pub fn send_kill_task_by_name(&self, task_name: String) {
         kill_task(&task_name);
+
+        task_state
     }
 
-    pub fn send_remove_task_by_name(&self, task_name: String) {
+    pub fn send_fail_task(&self, task_name: String, reason: String) {
         let (sender, receiver) = channel();
 
-        let msg = StateRequestMsg::RemoveTask {
+        let msg = StateRequestMsg::FailTask {
             sender: sender,
             task_name: task_name,
+            reason: reason,
         };
         self.sender.send(msg).unwrap();
         receiver.recv().unwrap();
     }
 
-    pub fn send_announce_task(&self, task: &Task) {
-        let (sender, receiver) = channel();
-
-        if self.request_task_name_by_id(task.id.clone()).len() > 0 {
-            let msg = StateRequestMsg::UpdateTaskLastUpdate {
-                sender: sender,
-                task_name: task.name.clone(),
-            };
-
-            self.sender.send(msg).unwrap();
-        } else {
-            match self.request_node(task.node_name.clone()) {
-                Some(node) => {
-                    add_route(&self.get_network_agent_type(),
-                              &self.get_network_agent_connection(),
-                              &task.ip,
-                              &node.external_ip)
-                }
-                _ => {}
+    // runs in the background, the node agent might take a while to tar and upload
+    pub fn send_collect_artifacts(&self, task: &Task) {
+        let upload_url = read_string(&self.get_yaml()["artifacts"], "upload_url".to_string());
+        let upload_url = str::replace(&upload_url, "$MASTER_IP", &self.get_master_ip());
+        let node = self.request_list_nodes().into_iter().find(|node| !node.slave_id.is_empty() && node.slave_id == task.slave_id);
+        let node_ip = match node {
+            Some(node) => node.ip,
+            None => {
+                warn!("can't collect artifacts, node unknown task={}", task.name);
+                return;
             }
+        };
 
-            // just in case it hasn't get cleaned up yet.
-            let ip = self.request_task_ip(task.name.clone());
-            if ip.len() > 0 {
-                delete_route(&self.get_network_agent_type(),
-                             &self.get_network_agent_connection(),
-                             &ip);
-            }
+        let state_manager = self.clone();
+        let task = task.clone();
+        thread::Builder::new()
+            .name("artifacts".to_string())
+            .spawn(move || {
+                match collect_artifacts(&node_ip, &task.name, &task.id, &task.artifacts, &upload_url) {
+                    Some(artifact_url) => state_manager.send_set_task_artifact_urls(task.name.clone(), vec![artifact_url]),
+                    None => info!("no artifacts collected task={}", task.name),
+                }
+            })
+            .unwrap();
+    }
 
-            let msg = StateRequestMsg::StartTask {
-                sender: sender,
-                task: task.clone(),
-            };
+    pub fn send_set_task_artifact_urls(&self, task_name: String, artifact_urls: Vec<String>) {
+        let (sender, receiver) = channel();
 
-            self.sender.send(msg).unwrap();
-        }
+        let msg = StateRequestMsg::SetTaskArtifactUrls {
+            sender: sender,
+            task_name: task_name,
+            artifact_urls: artifact_urls,
+        };
+        self.sender.send(msg).unwrap();
         receiver.recv().unwrap();
     }
 
-    pub fn request_list_requested_tasks(&self) -> Vec<Task> {
+    // a running task that comes out of standby gets registered in consul, one that goes into it deregistered
+    pub fn send_set_task_standby(&self, task_name: String, standby: bool) {
         let (sender, receiver) = channel();
 
-        let msg = StateRequestMsg::GetRequestedTasks { sender: sender };
+        let msg = StateRequestMsg::SetTaskStandby {
+            sender: sender,
+            task_name: task_name,
+            standby: standby,
+        };
         self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+    }
 
-        let result: Vec<Task> = match receiver.recv().unwrap() {
-            StateResponseMsg::GetRequestedTasks { requested_tasks } => requested_tasks,
-            _ => vec![],
-        };
+    // reset_backoff for restarts somebody asked for, automated ones keep counting toward the restart policy's backoff
+    pub fn send_restart_task(&self, task_name: String, reset_backoff: bool) {
+        let (sender, receiver) = channel();
 
-        result
+        self.send_withdraw_route(task_name.clone());
+
+        let msg = StateRequestMsg::RestartTask {
+            sender: sender,
+            task_name: task_name,
+            reset_backoff: reset_backoff,
+        };
+        self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
     }
 
-    pub fn request_list_running_tasks(&self) -> Vec<Task> {
+    // a soft-deleted task keeps its definition as deleted for deletes.restore_window_in_seconds, the task itself gets killed
+    pub fn send_delete_task(&self, task_name: String) -> Result<(), String> {
         let (sender, receiver) = channel();
+        let delegated = match self.request_task(task_name.clone()) {
+            Some(task) if task.state == TaskState::Delegated => Some(task),
+            _ => None,
+        };
 
-        let msg = StateRequestMsg::GetRunningTasks { sender: sender };
+        let msg = StateRequestMsg::DeleteTask {
+            sender: sender,
+            task_name: task_name.clone(),
+        };
         self.sender.send(msg).unwrap();
 
-        let result: Vec<Task> = match receiver.recv().unwrap() {
-            StateResponseMsg::GetRunningTasks { running_tasks } => running_tasks,
-            _ => vec![],
+        let result = match receiver.recv().unwrap() {
+            StateResponseMsg::DeleteTask { result } => result,
+            _ => Err("unexpected response".to_string()),
         };
-
-        result
+        result.map(|deleted| {
+            for task_name in &deleted {
+                self.send_withdraw_route(task_name.clone());
+                kill_task(task_name);
+            }
+            if let Some(ref task) = delegated {
+                forward_delete(self, task);
+            }
+        })
     }
 
-    pub fn request_list_restart_tasks(&self) -> Vec<Task> {
+    // relaunches a soft-deleted task with the definition it had, through restart like any other relaunch
+    pub fn send_restore_task(&self, task_name: String) -> Result<(), String> {
         let (sender, receiver) = channel();
 
-        let msg = StateRequestMsg::GetRestartTasks { sender: sender };
-        self.sender.send(msg).unwrap();
+        // a route set after the delete would point at the old container until the task runs again
+        if self.request_task(task_name.clone()).map(|task| task.state == TaskState::Deleted).unwrap_or(false) {
+            self.send_withdraw_route(task_name.clone());
+        }
 
-        let result: Vec<Task> = match receiver.recv().unwrap() {
-            StateResponseMsg::GetRestartTasks { restart_tasks } => restart_tasks,
-            _ => vec![],
+        let msg = StateRequestMsg::RestoreTask {
+            sender: sender,
+            task_name: task_name,
         };
+        self.sender.send(msg).unwrap();
 
-        result
+        match receiver.recv().unwrap() {
+            StateResponseMsg::RestoreTask { result } => result,
+            _ => Err("unexpected response".to_string()),
+        }
     }
 
-    pub fn send_add_node(&self,
-                         name: String,
-                         ip: String,
-                         external_ip: String,
-                         management_ip: String,
-                         port_id: i64,
-                         node_type: String) {
+    // all of them in one go, nothing else changes the state in between
+    pub fn send_restart_tasks(&self, task_names: Vec<String>) {
         let (sender, receiver) = channel();
 
-        let new_node = Node {
-            name: name.clone(),
-            ip: ip.clone(),
-            external_ip: external_ip.clone(),
-            management_ip: management_ip.clone(),
-            node_type: node_type.clone(),
-            node_function: "none".to_string(),
-            active: false,
-            slave_id: "".to_string(),
-            port_id: port_id,
-            last_seen: UTC::now().timestamp(),
-        };
+        for task_name in &task_names {
+            self.send_withdraw_route(task_name.clone());
+        }
 
-        let msg = StateRequestMsg::AddNode {
+        let msg = StateRequestMsg::RestartTasks {
             sender: sender,
-            node: new_node,
+            task_names: task_names,
         };
-
         self.sender.send(msg).unwrap();
         receiver.recv().unwrap();
     }
 
-    pub fn request_is_node_active(&self, node_name: String) -> bool {
+    // a task whose container went away on its own, its restart policy decides whether it comes back
+    pub fn send_task_exited(&self, task_name: String, failed: bool) -> RestartDecision {
         let (sender, receiver) = channel();
 
-        let msg = StateRequestMsg::GetIsNodeActive {
+        let msg = StateRequestMsg::TaskExited {
             sender: sender,
-            node_name: node_name,
+            task_name: task_name.clone(),
+            failed: failed,
         };
         self.sender.send(msg).unwrap();
 
-        let is_active = match receiver.recv().unwrap() {
-            StateResponseMsg::GetIsNodeActive { is_active } => is_active,
-            _ => false,
+        let decision = match receiver.recv().unwrap() {
+            StateResponseMsg::TaskExited { decision } => decision,
+            _ => RestartDecision::Remove,
         };
-
-        is_active
+        if decision != RestartDecision::Remove {
+            self.send_withdraw_route(task_name);
+        }
+        decision
     }
-    pub fn send_set_node_inactive(&self, node_name: String) {
+
+    // another replica just like the template, only the runtime state starts over
+    pub fn send_start_replica(&self, template: &Task, replica_index: i64) -> Result<(), String> {
         let (sender, receiver) = channel();
 
-        let msg = StateRequestMsg::SetNodeInactive {
+        let mut task = template.clone();
+        task.name = format!("{}-{}", template.replica_group, replica_index);
+        task.replica_index = replica_index;
+        task.id = "".to_string();
+        if !template.node_type.is_empty() || !template.node_function.is_empty() {
+            // picked when the template got accepted, not part of the definition
+            task.node_name = "".to_string();
+        }
+        reset_runtime_state(&mut task);
+
+        let msg = StateRequestMsg::StartTask {
             sender: sender,
-            node_name: node_name,
+            task: task,
         };
-
         self.sender.send(msg).unwrap();
-        receiver.recv().unwrap();
-
+        match receiver.recv().unwrap() {
+            StateResponseMsg::StartTask { result } => result,
+            _ => Err("unexpected response".to_string()),
+        }
     }
 
-    pub fn send_update_node(&self, node_name: String, node_type: String, node_function: String, slave_id: String) {
+    // a rolling update swaps the definition of a task under the same name, mesos knows the task by it.
+    // the task comes back like a restarted one once the running container is killed
+    pub fn send_replace_task(&self, template: &Task) -> Result<(), String> {
         let (sender, receiver) = channel();
 
-        let msg = StateRequestMsg::UpdateNode {
+        let mut task = template.clone();
+        task.id = "".to_string();
+        if !template.node_type.is_empty() || !template.node_function.is_empty() {
+            task.node_name = "".to_string();
+        }
+        reset_runtime_state(&mut task);
+        task.state = TaskState::Restart;
+
+        let msg = StateRequestMsg::StartTask {
             sender: sender,
-            node_name: node_name,
-            node_type: node_type,
-            node_function: node_function,
-            slave_id: slave_id,
+            task: task,
         };
         self.sender.send(msg).unwrap();
-        receiver.recv().unwrap();
+        match receiver.recv().unwrap() {
+            StateResponseMsg::StartTask { result } => result,
+            _ => Err("unexpected response".to_string()),
+        }
     }
 
-    pub fn request_node(&self, node_name: String) -> Option<Node> {
+    // a task handed over by a peer controller without the capacity for it, it's ours from now on
+    // but never delegated any further
+    pub fn send_start_delegated_task(&self, delegated: &Task) -> Result<(), String> {
         let (sender, receiver) = channel();
 
-        let msg = StateRequestMsg::GetNode {
+        let mut task = delegated.clone();
+        task.delegated_from = delegated.controller.clone();
+        task.controller = self.get_my_name();
+        task.id = "".to_string();
+        if !delegated.node_type.is_empty() || !delegated.node_function.is_empty() {
+            // picked on the peer's nodes, if at all
+            task.node_name = "".to_string();
+        }
+        reset_runtime_state(&mut task);
+
+        let msg = StateRequestMsg::StartTask {
             sender: sender,
-            node_name: node_name,
+            task: task,
         };
         self.sender.send(msg).unwrap();
+        match receiver.recv().unwrap() {
+            StateResponseMsg::StartTask { result } => result,
+            _ => Err("unexpected response".to_string()),
+        }
+    }
 
-        let result = match receiver.recv().unwrap() {
-            StateResponseMsg::GetNode { node } => Some(node),
-            _ => None,
+    // cleanup leaves an overridden task alone for stateclean.override_grace_in_seconds
+    pub fn send_override_task(&self, task_name: String, task_override: TaskOverride) -> Result<Task, String> {
+        let grace = read_int(&self.get_yaml()["stateclean"], "override_grace_in_seconds".to_string(), 600);
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::OverrideTask {
+            sender: sender,
+            task_name: task_name,
+            task_override: task_override,
+            until: UTC::now().timestamp() + grace,
         };
+        self.sender.send(msg).unwrap();
 
-        result
+        match receiver.recv().unwrap() {
+            StateResponseMsg::OverrideTask { result } => result,
+            _ => Err("unexpected response".to_string()),
+        }
     }
 
-    pub fn request_list_nodes(&self) -> Vec<Node> {
+    pub fn request_overridden_tasks(&self) -> Vec<String> {
         let (sender, receiver) = channel();
 
-        let msg = StateRequestMsg::GetNodes { sender: sender };
+        let msg = StateRequestMsg::GetOverriddenTasks { sender: sender };
         self.sender.send(msg).unwrap();
 
-        let result: Vec<Node> = match receiver.recv().unwrap() {
-            StateResponseMsg::GetNodes { nodes } => nodes,
+        let result = match receiver.recv().unwrap() {
+            StateResponseMsg::GetOverriddenTasks { task_names } => task_names,
             _ => vec![],
         };
 
         result
     }
-}
 
-struct State {
-    initialized: bool,
-    master_ip: String,
-    my_name: String,
-    task_list: TaskList,
-    node_list: NodeList,
-}
+    // an empty peer takes the task back from wherever it was delegated to. expected is the peer the caller
+    // saw the task delegated to, empty if it was requested here, false if that's no longer the case
+    pub fn send_update_delegation(&self,
+                                  task_name: String,
+                                  expected: String,
+                                  delegated_to: String,
+                                  remote_state: String)
+                                  -> bool {
+        let (sender, receiver) = channel();
 
-enum StateRequestMsg {
-    Ping { sender: Sender<StateResponseMsg> },
-    GetTaskState {
-        sender: Sender<StateResponseMsg>,
-        task_name: String,
-    },
-    GetTaskIP {
-        sender: Sender<StateResponseMsg>,
-        task_name: String,
-    },
-    GetTaskNameById {
+        let msg = StateRequestMsg::UpdateDelegation {
+            sender: sender,
+            task_name: task_name,
+            expected: expected,
+            delegated_to: delegated_to,
+            remote_state: remote_state,
+        };
+        self.sender.send(msg).unwrap();
+        match receiver.recv().unwrap() {
+            StateResponseMsg::UpdateDelegation { applied } => applied,
+            _ => panic!("unexpected response"),
+        }
+    }
+
+    pub fn request_list_replicas(&self, replica_group: String) -> Vec<Task> {
+        self.task_list.get_replicas(replica_group)
+    }
+
+    pub fn request_list_tasks_on_node(&self, node: &Node) -> Vec<Task> {
+        self.task_list.get_tasks_on_node(node.name.clone(), node.slave_id.clone())
+    }
+
+    // a service's standbys go with it
+    pub fn send_kill_task_by_name(&self, task_name: String) {
+        match self.request_task(task_name.clone()) {
+            Some(ref task) if task.state == TaskState::Delegated => forward_kill(self, task),
+            _ => {}
+        }
+        kill_task(&task_name);
+        for standby in self.task_list.get_standby_group(task_name) {
+            kill_task(&standby.name);
+        }
+    }
+
+    pub fn send_remove_task_by_name(&self, task_name: String) {
+        let (sender, receiver) = channel();
+
+        self.send_withdraw_route(task_name.clone());
+        match self.request_task(task_name.clone()) {
+            Some(task) => {
+                if let Err(error) = deregister_task(&self.get_master_ip(), &task) {
+                    warn!("can't deregister task from consul task={} error={}", task.name, error);
+                }
+            }
+            None => {}
+        }
+
+        let msg = StateRequestMsg::RemoveTask {
+            sender: sender,
+            task_name: task_name,
+        };
+        self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+    }
+
+    // like send_remove_task_by_name but leaves consul alone, the names got taken over by a replacement
+    pub fn send_retire_task(&self, task_name: String) {
+        let (sender, receiver) = channel();
+
+        self.send_withdraw_route(task_name.clone());
+
+        let msg = StateRequestMsg::RemoveTask {
+            sender: sender,
+            task_name: task_name,
+        };
+        self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+    }
+
+    pub fn send_report_task_conflict(&self,
+                                     task_name: String,
+                                     controller: String,
+                                     existing_controller: String,
+                                     outcome: String) {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::ReportTaskConflict {
+            sender: sender,
+            task_name: task_name,
+            controller: controller,
+            existing_controller: existing_controller,
+            outcome: outcome,
+        };
+        self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+    }
+
+    // task name to the node holding its data, for tasks that have to go back there
+    pub fn request_volume_holders(&self) -> BTreeMap<String, String> {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::GetVolumeHolders { sender: sender };
+        self.sender.send(msg).unwrap();
+
+        match receiver.recv().unwrap() {
+            StateResponseMsg::GetVolumeHolders { holders } => holders,
+            _ => BTreeMap::new(),
+        }
+    }
+
+    // run from the health loop, nodes unavailable for longer than volumes.migrate_after_in_seconds let go
+    // of their data. a task still on such a node gets moved, it won't come back there
+    pub fn release_unavailable_volumes(&self) {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::ReleaseVolumes {
+            sender: sender,
+            migrate_after: read_int(&self.get_yaml()["volumes"], "migrate_after_in_seconds".to_string(), 600),
+        };
+        self.sender.send(msg).unwrap();
+
+        let released = match receiver.recv().unwrap() {
+            StateResponseMsg::ReleaseVolumes { released } => released,
+            _ => vec![],
+        };
+
+        for binding in released {
+            let task = match self.request_task(binding.task_name.clone()) {
+                Some(task) => task,
+                None => continue,
+            };
+            match task.state {
+                TaskState::Running | TaskState::Accepted => {}
+                _ => continue,
+            }
+            if task.node_name != binding.node_name || (task.node_type.is_empty() && task.node_function.is_empty()) {
+                continue;
+            }
+            info!("move task task={} from={}", task.name, binding.node_name);
+            self.send_update_task_node_name(task.name.clone(), "".to_string());
+            self.send_restart_task(task.name.clone(), false);
+            kill_task(&task.name);
+        }
+    }
+
+    pub fn request_list_volumes(&self) -> Vec<VolumeBinding> {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::GetVolumes { sender: sender };
+        self.sender.send(msg).unwrap();
+
+        let result = match receiver.recv().unwrap() {
+            StateResponseMsg::GetVolumes { volumes } => volumes,
+            _ => vec![],
+        };
+
+        result
+    }
+
+    pub fn request_task_conflicts(&self) -> Vec<TaskConflict> {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::GetTaskConflicts { sender: sender };
+        self.sender.send(msg).unwrap();
+
+        let result = match receiver.recv().unwrap() {
+            StateResponseMsg::GetTaskConflicts { conflicts } => conflicts,
+            _ => vec![],
+        };
+
+        result
+    }
+
+    pub fn send_announce_task(&self, task: &Task) {
+        let (sender, receiver) = channel();
+
+        if self.request_task_name_by_id(task.id.clone()).len() > 0 {
+            let msg = StateRequestMsg::UpdateTaskLastUpdate {
+                sender: sender,
+                task_name: task.name.clone(),
+            };
+
+            self.sender.send(msg).unwrap();
+        } else {
+            match self.request_node(task.node_name.clone()) {
+                Some(node) => self.send_announce_route(task.name.clone(), task.ip.clone(), node.external_ip.clone()),
+                _ => {}
+            }
+
+            let msg = StateRequestMsg::StartTask {
+                sender: sender,
+                task: task.clone(),
+            };
+
+            self.sender.send(msg).unwrap();
+        }
+        receiver.recv().unwrap();
+    }
+
+    // the only place routes get added, a previously announced route for the task gets withdrawn first
+    pub fn send_announce_route(&self, task_name: String, ip: String, next_hop: String) {
+        let (sender, receiver) = channel();
+
+        let route = Route {
+            task_name: task_name,
+            ip: ip,
+            next_hop: next_hop,
+        };
+
+        let msg = StateRequestMsg::SetRoute {
+            sender: sender,
+            route: route.clone(),
+        };
+        self.sender.send(msg).unwrap();
+
+        let previous = match receiver.recv().unwrap() {
+            StateResponseMsg::SetRoute { previous } => previous,
+            _ => None,
+        };
+
+        match previous {
+            Some(ref previous) if *previous != route && !previous.ip.is_empty() => {
+                self.submit_route_operation(RouteOperationKind::Delete, &previous.task_name, &previous.ip, &"".to_string())
+            }
+            _ => {}
+        }
+
+        self.submit_route_operation(RouteOperationKind::Add, &route.task_name, &route.ip, &route.next_hop);
+    }
+
+    fn submit_route_operation(&self, kind: RouteOperationKind, task_name: &String, ip: &String, next_hop: &String) {
+        self.update_coalescer.submit(PendingUpdate::Route {
+            kind: kind,
+            task_name: task_name.clone(),
+            ip: ip.clone(),
+            next_hop: next_hop.clone(),
+            replace: false,
+        });
+    }
+
+    // what's still waiting in the coalescing window goes out right away, for shutdown
+    pub fn flush_pending_updates(&self) {
+        let check = TtlCheck::from_config(&self.get_yaml());
+        for update in self.update_coalescer.take_all() {
+            self.apply_pending_update(update, &check);
+        }
+    }
+
+    fn apply_pending_update(&self, update: PendingUpdate, check: &TtlCheck) {
+        match update {
+            PendingUpdate::Register(task) => {
+                if let Err(error) = register_running_task(&self.get_master_ip(), &task, check) {
+                    warn!("can't register task with consul task={} error={}", task.name, error);
+                }
+            }
+            PendingUpdate::Deregister(task) => {
+                if let Err(error) = deregister_task(&self.get_master_ip(), &task) {
+                    warn!("can't deregister task from consul task={} error={}", task.name, error);
+                }
+            }
+            PendingUpdate::Route { kind, task_name, ip, next_hop, replace } => {
+                if replace {
+                    self.perform_route_operation(RouteOperationKind::Delete, &task_name, &ip, &"".to_string());
+                }
+                self.perform_route_operation(kind, &task_name, &ip, &next_hop)
+            }
+        }
+    }
+
+    // tries the operation right away, the retry queue takes over if the network agent doesn't take it
+    fn perform_route_operation(&self, kind: RouteOperationKind, task_name: &String, ip: &String, next_hop: &String) {
+        let mut operation = RouteOperation {
+            task_name: task_name.clone(),
+            kind: kind,
+            ip: ip.clone(),
+            next_hop: next_hop.clone(),
+            attempts: 0,
+            first_failed: 0,
+            next_attempt: 0,
+        };
+
+        if self.settle_route_operation(&operation, self.apply_route_operation(&operation)) {
+            self.send_finish_route_operation(operation, true);
+            return;
+        }
+
+        let (initial_backoff, _) = self.route_retry_backoff();
+        operation.attempts = 1;
+        operation.first_failed = UTC::now().timestamp();
+        operation.next_attempt = operation.first_failed + initial_backoff;
+        info!("queueing route operation={:?} task={} ip={}", operation.kind, operation.task_name, operation.ip);
+        self.send_queue_route_operation(operation);
+    }
+
+    // an add that finds the route in place or a delete that finds it gone did what it was asked for
+    fn apply_route_operation(&self, operation: &RouteOperation) -> Result<(), NetworkAgentError> {
+        let result = match operation.kind {
+            RouteOperationKind::Add => add_route(&*self.network_agent, &operation.ip, &operation.next_hop),
+            RouteOperationKind::Delete => delete_route(&*self.network_agent, &operation.ip),
+        };
+        match (&operation.kind, result) {
+            (&RouteOperationKind::Add, Err(NetworkAgentError::AlreadyExists)) |
+            (&RouteOperationKind::Delete, Err(NetworkAgentError::NotFound)) => Ok(()),
+            (_, result) => result,
+        }
+    }
+
+    // true when the operation is done with, a rejected one gets reported instead of retried forever
+    fn settle_route_operation(&self, operation: &RouteOperation, result: Result<(), NetworkAgentError>) -> bool {
+        match result {
+            Ok(_) => true,
+            Err(ref error) if error.is_retryable() => false,
+            Err(error) => {
+                self.send_add_event(EventType::RouteRejected,
+                                    operation.task_name.clone(),
+                                    format!("{:?} route to {} via {}: {}",
+                                            operation.kind,
+                                            operation.ip,
+                                            operation.next_hop,
+                                            error));
+                true
+            }
+        }
+    }
+
+    fn route_retry_backoff(&self) -> (i64, i64) {
+        let retry = &self.get_yaml()["network-agent"]["retry"];
+        (read_int(retry, "initial_backoff_in_seconds".to_string(), 2),
+         read_int(retry, "max_backoff_in_seconds".to_string(), 60))
+    }
+
+    pub fn send_queue_route_operation(&self, operation: RouteOperation) {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::QueueRouteOperation {
+            sender: sender,
+            operation: operation,
+        };
+        self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+    }
+
+    pub fn send_finish_route_operation(&self, operation: RouteOperation, success: bool) {
+        let (sender, receiver) = channel();
+        let (initial_backoff, max_backoff) = self.route_retry_backoff();
+
+        let msg = StateRequestMsg::FinishRouteOperation {
+            sender: sender,
+            operation: operation,
+            success: success,
+            initial_backoff: initial_backoff,
+            max_backoff: max_backoff,
+        };
+        self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+    }
+
+    pub fn request_due_route_operations(&self) -> Vec<RouteOperation> {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::GetDueRouteOperations { sender: sender };
+        self.sender.send(msg).unwrap();
+
+        let result = match receiver.recv().unwrap() {
+            StateResponseMsg::GetRouteOperations { operations } => operations,
+            _ => vec![],
+        };
+
+        result
+    }
+
+    pub fn request_list_route_operations(&self) -> Vec<RouteOperation> {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::GetRouteOperations { sender: sender };
+        self.sender.send(msg).unwrap();
+
+        let result = match receiver.recv().unwrap() {
+            StateResponseMsg::GetRouteOperations { operations } => operations,
+            _ => vec![],
+        };
+
+        result
+    }
+
+    pub fn send_add_event(&self, event_type: EventType, subject: String, message: String) {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::AddEvent {
+            sender: sender,
+            event_type: event_type,
+            subject: subject,
+            message: message,
+        };
+        self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+    }
+
+    pub fn send_write_store_snapshot(&self) {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::WriteStoreSnapshot { sender: sender };
+        self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+    }
+
+    // a final snapshot that is on disk once this returns, nothing gets persisted afterwards
+    pub fn send_flush_store(&self) {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::FlushStore { sender: sender };
+        self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+    }
+
+    // a task's route goes through the route queue from here, a replaced one from send_set_route and
+    // shutdown takes them straight to the network agent
+    pub fn send_withdraw_route(&self, task_name: String) {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::RemoveRoute {
+            sender: sender,
+            task_name: task_name,
+        };
+        self.sender.send(msg).unwrap();
+
+        match receiver.recv().unwrap() {
+            StateResponseMsg::RemoveRoute { previous: Some(previous) } => {
+                self.submit_route_operation(RouteOperationKind::Delete, &previous.task_name, &previous.ip, &"".to_string())
+            }
+            _ => {}
+        }
+    }
+
+    pub fn request_list_routes(&self) -> Vec<Route> {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::GetRoutes { sender: sender };
+        self.sender.send(msg).unwrap();
+
+        let result: Vec<Route> = match receiver.recv().unwrap() {
+            StateResponseMsg::GetRoutes { routes } => routes,
+            _ => vec![],
+        };
+
+        result
+    }
+
+    pub fn request_snapshot(&self) -> Result<StateSnapshot, String> {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::GetSnapshot { sender: sender };
+        if self.sender.send(msg).is_err() {
+            return Err("state manager not running".to_string());
+        }
+
+        match receiver.recv() {
+            Ok(StateResponseMsg::GetSnapshot { snapshot }) => Ok(snapshot),
+            Ok(_) => Err("unexpected response to snapshot request".to_string()),
+            Err(_) => Err("state manager not running".to_string()),
+        }
+    }
+
+    pub fn request_memory_usage(&self) -> MemoryReport {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::GetMemoryUsage { sender: sender };
+        self.sender.send(msg).unwrap();
+
+        match receiver.recv().unwrap() {
+            StateResponseMsg::GetMemoryUsage { report } => report,
+            _ => panic!("unexpected response to memory usage request"),
+        }
+    }
+
+    pub fn request_list_tasks(&self) -> Vec<Task> {
+        self.task_list.get_tasks()
+    }
+
+    pub fn request_list_requested_tasks(&self) -> Vec<Task> {
+        self.task_list.get_tasks_with_state(TaskState::Requested)
+    }
+
+    pub fn request_list_running_tasks(&self) -> Vec<Task> {
+        self.task_list.get_tasks_with_state(TaskState::Running)
+    }
+
+    pub fn request_list_restart_tasks(&self) -> Vec<Task> {
+        self.task_list.get_tasks_with_state(TaskState::Restart)
+    }
+
+    pub fn request_list_accepted_tasks(&self) -> Vec<Task> {
+        self.task_list.get_tasks_with_state(TaskState::Accepted)
+    }
+
+    pub fn send_add_node(&self,
+                         name: String,
+                         ip: String,
+                         external_ip: String,
+                         management_ip: String,
+                         power_backend: String,
+                         power_credentials: String,
+                         port_id: i64,
+                         node_type: String,
+                         cost: f64,
+                         job_slots: i64,
+                         total_cpu: f64,
+                         total_memory: f64,
+                         groups: Vec<String>,
+                         zone: String,
+                         labels: BTreeMap<String, String>) {
+        let (sender, receiver) = channel();
+
+        let new_node = Node {
+            power_backend: power_backend,
+            power_credentials: power_credentials,
+            port_id: port_id,
+            cost: cost,
+            job_slots: job_slots,
+            total_cpu: total_cpu,
+            total_memory: total_memory,
+            groups: groups,
+            zone: zone,
+            labels: labels,
+            ..Node::new(name, ip, external_ip, management_ip, node_type)
+        };
+
+        let msg = StateRequestMsg::AddNode {
+            sender: sender,
+            node: new_node,
+        };
+
+        self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+    }
+
+    // a node joining at runtime, refused if its name or ip is taken already
+    pub fn send_register_node(&self, node: Node) -> Result<Node, String> {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::RegisterNode {
+            sender: sender,
+            node: node,
+        };
+        self.sender.send(msg).unwrap();
+
+        match receiver.recv().unwrap() {
+            StateResponseMsg::RegisterNode { result } => result,
+            _ => Err("unexpected response".to_string()),
+        }
+    }
+
+    // only nodes that came in through a registration, the configured ones would be back with the next start
+    pub fn send_unregister_node(&self, node_name: String) -> Result<Node, String> {
+        let configured = self.get_yaml()["nodes"]
+            .as_vec()
+            .map(|nodes| nodes.iter().any(|node| node["name"].as_str() == Some(node_name.as_str())))
+            .unwrap_or(false);
+        if configured {
+            return Err(format!("{} is configured, not registered", node_name));
+        }
+
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::UnregisterNode {
+            sender: sender,
+            node_name: node_name,
+        };
+        self.sender.send(msg).unwrap();
+
+        match receiver.recv().unwrap() {
+            StateResponseMsg::UnregisterNode { result } => result,
+            _ => Err("unexpected response".to_string()),
+        }
+    }
+
+    pub fn request_is_node_active(&self, node_name: String) -> bool {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::GetIsNodeActive {
+            sender: sender,
+            node_name: node_name,
+        };
+        self.sender.send(msg).unwrap();
+
+        let is_active = match receiver.recv().unwrap() {
+            StateResponseMsg::GetIsNodeActive { is_active } => is_active,
+            _ => false,
+        };
+
+        is_active
+    }
+    pub fn send_set_node_draining(&self, node_name: String, draining: bool) {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::SetNodeDraining {
+            sender: sender,
+            node_name: node_name,
+            draining: draining,
+        };
+        self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+    }
+
+    pub fn send_set_node_inactive(&self, node_name: String) {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::SetNodeInactive {
+            sender: sender,
+            node_name: node_name,
+        };
+
+        self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+
+    }
+
+    pub fn send_update_node(&self, node_name: String, host: String, node_type: String, node_function: String, slave_id: String) {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::UpdateNode {
+            sender: sender,
+            node_name: node_name,
+            host: host,
+            node_type: node_type,
+            node_function: node_function,
+            slave_id: slave_id,
+        };
+        self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+    }
+
+    pub fn request_node(&self, node_name: String) -> Option<Node> {
+        self.node_list.get_node(node_name).ok().map(|node| self.with_allocation(node))
+    }
+
+    fn with_allocation(&self, node: Node) -> Node {
+        let tasks = self.task_list.get_tasks_on_node(node.name.clone(), node.slave_id.clone());
+        let (cpu, memory) = allocated_resources(&node, &tasks);
+        Node {
+            allocated_cpu: cpu,
+            allocated_memory: memory,
+            ..node
+        }
+    }
+
+    pub fn request_list_nodes_in_group(&self, group: String) -> Vec<Node> {
+        self.request_list_nodes()
+            .into_iter()
+            .filter(|node| node.groups.contains(&group))
+            .collect()
+    }
+
+    pub fn send_update_node_power_state(&self, node_name: String, power_state: PowerState) {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::UpdateNodePowerState {
+            sender: sender,
+            node_name: node_name,
+            power_state: power_state,
+            transition_timeout: read_int(&self.get_yaml()["powercheck"], "transition_timeout_in_seconds".to_string(), 300),
+        };
+        self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+    }
+
+    pub fn send_update_node_power_reading(&self, node_name: String, power_in_watts: f64) {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::UpdateNodePowerReading {
+            sender: sender,
+            node_name: node_name,
+            power_in_watts: power_in_watts,
+        };
+        self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+    }
+
+    pub fn send_update_node_metadata(&self, node_name: String, update: NodeUpdate) -> Result<Node, String> {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::UpdateNodeMetadata {
+            sender: sender,
+            node_name: node_name,
+            update: update,
+        };
+        self.sender.send(msg).unwrap();
+
+        match receiver.recv().unwrap() {
+            StateResponseMsg::UpdateNodeMetadata { result } => result,
+            _ => Err("unexpected response".to_string()),
+        }
+    }
+
+    pub fn send_update_task_annotations(&self,
+                                        task_name: String,
+                                        update: BTreeMap<String, Option<String>>)
+                                        -> Result<Task, String> {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::UpdateTaskAnnotations {
+            sender: sender,
+            task_name: task_name,
+            update: update,
+        };
+        self.sender.send(msg).unwrap();
+
+        match receiver.recv().unwrap() {
+            StateResponseMsg::UpdateTaskAnnotations { result } => result,
+            _ => Err("unexpected response".to_string()),
+        }
+    }
+
+    pub fn send_node_heartbeat(&self, node_name: String, agent_version: String, gpus: Option<Vec<Gpu>>) {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::NodeHeartbeat {
+            sender: sender,
+            node_name: node_name,
+            agent_version: agent_version,
+            gpus: gpus,
+        };
+        self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+    }
+
+    pub fn send_add_task_energy(&self, task_name: String, power_in_watts: f64, energy_in_wh: f64) {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::AddTaskEnergy {
+            sender: sender,
+            task_name: task_name,
+            power_in_watts: power_in_watts,
+            energy_in_wh: energy_in_wh,
+        };
+        self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+    }
+
+    pub fn send_set_node_expected_power_state(&self, node_name: String, power_state: PowerState) {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::SetNodeExpectedPowerState {
+            sender: sender,
+            node_name: node_name,
+            power_state: power_state,
+        };
+        self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+    }
+
+    pub fn request_task_revisions(&self, task_name: String) -> Vec<Revision> {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::GetTaskRevisions {
+            sender: sender,
+            task_name: task_name,
+        };
+        self.sender.send(msg).unwrap();
+
+        let result: Vec<Revision> = match receiver.recv().unwrap() {
+            StateResponseMsg::GetTaskRevisions { revisions } => revisions,
+            _ => vec![],
+        };
+
+        result
+    }
+
+    pub fn send_set_collaborators(&self, collaborators: Vec<CollaboratorStatus>) {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::SetCollaborators {
+            sender: sender,
+            collaborators: collaborators,
+        };
+        self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+    }
+
+    pub fn send_update_collaborator(&self, name: String, reachable: bool, error: String) {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::UpdateCollaborator {
+            sender: sender,
+            name: name,
+            reachable: reachable,
+            error: error,
+        };
+        self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+    }
+
+    pub fn request_is_ready(&self) -> bool {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::GetIsReady { sender: sender };
+        self.sender.send(msg).unwrap();
+
+        let is_ready = match receiver.recv().unwrap() {
+            StateResponseMsg::GetIsReady { is_ready } => is_ready,
+            _ => false,
+        };
+
+        is_ready
+    }
+
+    pub fn request_list_collaborators(&self) -> Vec<CollaboratorStatus> {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::GetCollaborators { sender: sender };
+        self.sender.send(msg).unwrap();
+
+        let result: Vec<CollaboratorStatus> = match receiver.recv().unwrap() {
+            StateResponseMsg::GetCollaborators { collaborators } => collaborators,
+            _ => vec![],
+        };
+
+        result
+    }
+
+    pub fn request_list_events(&self) -> Vec<Event> {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::GetEvents { sender: sender };
+        self.sender.send(msg).unwrap();
+
+        let result: Vec<Event> = match receiver.recv().unwrap() {
+            StateResponseMsg::GetEvents { events } => events,
+            _ => vec![],
+        };
+
+        result
+    }
+
+    pub fn request_list_events_since(&self, since: u64, epoch: Option<i64>) -> Result<Vec<Event>, String> {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::GetEventsSince {
+            sender: sender,
+            since: since,
+            epoch: epoch,
+        };
+        self.sender.send(msg).unwrap();
+
+        match receiver.recv().unwrap() {
+            StateResponseMsg::GetEventsSince { result } => result,
+            _ => Err("unexpected response".to_string()),
+        }
+    }
+
+    // false if another agent upgrade is still in progress
+    pub fn send_begin_agent_upgrade(&self, report: AgentUpgradeReport) -> bool {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::BeginAgentUpgrade {
+            sender: sender,
+            report: report,
+        };
+        self.sender.send(msg).unwrap();
+
+        let result = match receiver.recv().unwrap() {
+            StateResponseMsg::BeginAgentUpgrade { started } => started,
+            _ => false,
+        };
+
+        result
+    }
+
+    pub fn send_set_agent_upgrade_report(&self, report: AgentUpgradeReport) {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::SetAgentUpgradeReport {
+            sender: sender,
+            report: report,
+        };
+        self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+    }
+
+    pub fn request_agent_upgrade_report(&self) -> Option<AgentUpgradeReport> {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::GetAgentUpgradeReport { sender: sender };
+        self.sender.send(msg).unwrap();
+
+        let result = match receiver.recv().unwrap() {
+            StateResponseMsg::GetAgentUpgradeReport { report } => report,
+            _ => None,
+        };
+
+        result
+    }
+
+    pub fn send_set_last_clean_report(&self, report: CleanReport) {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::SetLastCleanReport {
+            sender: sender,
+            report: report,
+        };
+        self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+    }
+
+    pub fn request_last_clean_report(&self) -> Option<CleanReport> {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::GetLastCleanReport { sender: sender };
+        self.sender.send(msg).unwrap();
+
+        let result = match receiver.recv().unwrap() {
+            StateResponseMsg::GetLastCleanReport { report } => report,
+            _ => None,
+        };
+
+        result
+    }
+
+    pub fn send_set_last_sync_report(&self, report: SyncReport) {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::SetLastSyncReport {
+            sender: sender,
+            report: report,
+        };
+        self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+    }
+
+    pub fn request_last_sync_report(&self) -> Option<SyncReport> {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::GetLastSyncReport { sender: sender };
+        self.sender.send(msg).unwrap();
+
+        let result = match receiver.recv().unwrap() {
+            StateResponseMsg::GetLastSyncReport { report } => report,
+            _ => None,
+        };
+
+        result
+    }
+
+    pub fn send_set_cluster_open(&self, open: bool) {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::SetClusterOpen {
+            sender: sender,
+            open: open,
+        };
+        self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+    }
+
+    pub fn request_is_cluster_open(&self) -> bool {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::GetIsClusterOpen { sender: sender };
+        self.sender.send(msg).unwrap();
+
+        let result = match receiver.recv().unwrap() {
+            StateResponseMsg::GetIsClusterOpen { is_open } => is_open,
+            _ => false,
+        };
+
+        result
+    }
+
+    pub fn send_set_shutting_down(&self, active: bool) {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::SetShuttingDown {
+            sender: sender,
+            active: active,
+        };
+        self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+    }
+
+    pub fn request_is_shutting_down(&self) -> bool {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::GetIsShuttingDown { sender: sender };
+        self.sender.send(msg).unwrap();
+
+        let result = match receiver.recv().unwrap() {
+            StateResponseMsg::GetIsShuttingDown { is_shutting_down } => is_shutting_down,
+            _ => false,
+        };
+
+        result
+    }
+
+    // the jobs scheduled through the api, written to the store so they are scheduled again after a restart
+    pub fn send_put_schedule(&self, schedule: StoredSchedule) {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::PutSchedule {
+            sender: sender,
+            schedule: schedule,
+        };
+        self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+    }
+
+    pub fn send_remove_schedule(&self, job_name: String) {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::RemoveSchedule {
+            sender: sender,
+            job_name: job_name,
+        };
+        self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+    }
+
+    pub fn request_schedules(&self) -> Vec<StoredSchedule> {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::GetSchedules { sender: sender };
+        self.sender.send(msg).unwrap();
+
+        match receiver.recv().unwrap() {
+            StateResponseMsg::GetSchedules { schedules } => schedules,
+            _ => vec![],
+        }
+    }
+
+    pub fn send_set_teardown(&self, active: bool) {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::SetTeardown {
+            sender: sender,
+            active: active,
+        };
+        self.sender.send(msg).unwrap();
+        receiver.recv().unwrap();
+    }
+
+    pub fn request_is_teardown(&self) -> bool {
+        let (sender, receiver) = channel();
+
+        let msg = StateRequestMsg::GetIsTeardown { sender: sender };
+        self.sender.send(msg).unwrap();
+
+        let result = match receiver.recv().unwrap() {
+            StateResponseMsg::GetIsTeardown { is_teardown } => is_teardown,
+            _ => false,
+        };
+
+        result
+    }
+
+    pub fn run_sync_cycle(&self, triggered_by: &str) -> SyncReport {
+        let started = Instant::now();
+        let master_ip = self.get_master_ip();
+        let my_name = self.get_my_name();
+        let check = TtlCheck::from_config(&self.get_yaml());
+        let mut report = SyncReport::new(triggered_by);
+
+        debug!("syncing");
+        // tasks that stopped running don't get their ttl refreshed and fall out of consul on their own
+        // standbys stay out of consul and the routes until one of them gets promoted
+        let running_tasks = self.request_list_running_tasks();
+        for task in running_tasks.iter().filter(|task| !task.standby) {
+            let registered = match register_running_task(&master_ip, &task, &check) {
+                Ok(registered) => registered,
+                Err(error) => {
+                    warn!("can't register task with consul task={} error={}", task.name, error);
+                    false
+                }
+            };
+            let ttl_refreshed = match registered {
+                true => {
+                    match refresh_task_ttl(&master_ip, &task) {
+                        Ok(_) => true,
+                        Err(error) => {
+                            warn!("can't refresh consul ttl task={} error={}", task.name, error);
+                            false
+                        }
+                    }
+                }
+                false => false,
+            };
+            let announced = task.controller == my_name;
+            if announced {
+                self.send_announce_task(&task);
+            }
+            report.tasks.push(SyncTaskOutcome {
+                name: task.name.clone(),
+                registered: registered,
+                ttl_refreshed: ttl_refreshed,
+                announced: announced,
+            });
+        }
+
+        let elapsed = started.elapsed();
+        report.duration_in_ms = elapsed.as_secs() * 1000 + (elapsed.subsec_nanos() / 1_000_000) as u64;
+
+        self.send_set_last_sync_report(report.clone());
+        report
+    }
+
+    pub fn run_clean_cycle(&self, triggered_by: &str) -> CleanReport {
+        let config = self.get_yaml();
+        let timeout = config["stateclean"]["timeout_in_seconds"].as_i64().unwrap() as i64;
+        let restart_policy = RestartPolicy::new(&config);
+        let launch_timeout = read_int(&config["stateclean"], "launch_timeout_in_seconds".to_string(), 120);
+        let restore_window = read_int(&config["deletes"], "restore_window_in_seconds".to_string(), 3600);
+        let my_name = self.get_my_name();
+        let mut report = CleanReport::new(triggered_by);
+
+        debug!("cleaning");
+        // tasks an operator just corrected stay as they are until the override's grace period ends
+        report.overridden_tasks = self.request_overridden_tasks();
+        let running_tasks: Vec<Task> = self.request_list_running_tasks()
+            .into_iter()
+            .filter(|task| !report.overridden_tasks.contains(&task.name))
+            .collect();
+        for task in &running_tasks {
+            if task.controller == my_name {
+                continue;
+            };
+            let now = UTC::now().timestamp();
+            if (task.last_update + timeout) < now {
+                self.send_remove_task_by_name(task.name.clone());
+                report.removed_tasks.push(task.name.clone());
+            }
+        }
+
+        let restart_tasks = self.request_list_restart_tasks();
+        for task in &restart_tasks {
+            if task.controller != my_name || report.overridden_tasks.contains(&task.name) {
+                continue;
+            };
+            let now = UTC::now().timestamp();
+            if (task.last_update + restart_policy.delay_in_seconds(task.restart_count)) < now {
+                self.send_update_task_state(task.name.clone(), TaskState::Requested);
+                report.requeued_tasks.push(task.name.clone());
+            }
+        }
+
+        // an executor that never reports back leaves the task in accepted, it gets killed and goes through restart again
+        for task in &self.request_list_accepted_tasks() {
+            if task.controller != my_name || report.overridden_tasks.contains(&task.name) {
+                continue;
+            };
+            let now = UTC::now().timestamp();
+            if (task.accepted_since + launch_timeout) < now {
+                if !self.take_automated_kill(&task.name) {
+                    report.deferred_kills.push(task.name.clone());
+                    continue;
+                }
+                let node = match task.node_name.is_empty() {
+                    true => task.slave_id.clone(),
+                    false => task.node_name.clone(),
+                };
+                self.send_update_task_state(task.name.clone(), TaskState::Restart);
+                kill_task(&task.name);
+                self.send_add_event(EventType::StuckLaunch,
+                                    task.name.clone(),
+                                    format!("not running after {}s, image {} on node {}", launch_timeout, task.image, node));
+                report.stuck_launches.push(task.name.clone());
+            }
+        }
+
+        // jobs running past their deadline get killed and stay around as failed
+        for task in &running_tasks {
+            if task.controller != my_name || !task.is_job || task.max_runtime_seconds <= 0 || task.running_since == 0 {
+                continue;
+            };
+            let now = UTC::now().timestamp();
+            if (task.running_since + task.max_runtime_seconds) < now {
+                if !self.take_automated_kill(&task.name) {
+                    report.deferred_kills.push(task.name.clone());
+                    continue;
+                }
+                self.send_fail_task(task.name.clone(), "DeadlineExceeded".to_string());
+                self.send_withdraw_route(task.name.clone());
+                kill_task(&task.name);
+                report.expired_jobs.push(task.name.clone());
+            }
+        }
+
+        // outside of its run window a task gets stopped and waits in restart until the window opens again
+        for task in &running_tasks {
+            if task.controller != my_name {
+                continue;
+            };
+            match task.run_window {
+                Some(ref run_window) if !run_window.is_open() => {
+                    if !self.take_automated_kill(&task.name) {
+                        report.deferred_kills.push(task.name.clone());
+                        continue;
+                    }
+                    self.send_update_task_state(task.name.clone(), TaskState::Restart);
+                    self.send_withdraw_route(task.name.clone());
+                    kill_task(&task.name);
+                    report.stopped_tasks.push(task.name.clone());
+                }
+                _ => {}
+            }
+        }
+
+        // picks up dependents whose restart got deferred by the kill guardrail, a hook takes care of its own
+        if read_string(&config["dependents"], "restart_hook_url".to_string()).is_empty() {
+            for task in &running_tasks {
+                if task.controller == my_name && task.restart_dependents {
+                    self.restart_stale_dependents(task.name.clone());
+                }
+            }
+        }
+
+        // soft-deleted tasks past their restore window are gone for good
+        for task in self.request_list_tasks().iter().filter(|task| task.state == TaskState::Deleted) {
+            if task.controller != my_name {
+                continue;
+            };
+            let now = UTC::now().timestamp();
+            // tasks deleted before deleted_at was recorded fall back to their last update
+            let deleted_at = if task.deleted_at > 0 { task.deleted_at } else { task.last_update };
+            if (deleted_at + restore_window) < now {
+                self.send_remove_task_by_name(task.name.clone());
+                report.expired_deletes.push(task.name.clone());
+            }
+        }
+
+        let nodes = self.request_list_nodes();
+        for node in &nodes {
+            if node.active == false {
+                continue;
+            }
+            let now = UTC::now().timestamp();
+            if (node.last_seen + timeout) < now {
+                self.send_set_node_inactive(node.name.clone());
+                report.inactive_nodes.push(node.name.clone());
+            }
+        }
+
+        self.send_set_last_clean_report(report.clone());
+        report
+    }
+
+    // dependents started before the current run of their dependency may still hold its old ip,
+    // they get restarted or handed to the configured hook once the dependency is running again
+    pub fn restart_stale_dependents(&self, task_name: String) {
+        let dependency = match self.request_task(task_name.clone()) {
+            Some(task) => task,
+            None => return,
+        };
+        if !dependency.restart_dependents {
+            return;
+        }
+
+        let my_name = self.get_my_name();
+        let stale: Vec<String> = self.request_list_running_tasks()
+            .into_iter()
+            .filter(|task| task.dependent_service == task_name && task.controller == my_name)
+            .filter(|task| task.running_since < dependency.running_since)
+            .map(|task| task.name)
+            .collect();
+        if stale.is_empty() {
+            return;
+        }
+
+        let hook_url = read_string(&self.get_yaml()["dependents"], "restart_hook_url".to_string());
+        if hook_url.is_empty() {
+            self.restart_dependents(task_name, &stale);
+            return;
+        }
+
+        // the hook is an outside system, status updates and clean cycles don't wait for it to answer
+        let state_manager = self.clone();
+        thread::Builder::new()
+            .name("restart-hook".to_string())
+            .spawn(move || {
+                if call_restart_hook(&hook_url, &task_name, &dependency.ip, &stale) {
+                    state_manager.send_add_event(EventType::DependentsRestarted,
+                                                 task_name,
+                                                 format!("hook {} called for {}", hook_url, stale.join(", ")));
+                } else {
+                    state_manager.restart_dependents(task_name, &stale);
+                }
+            })
+            .unwrap();
+    }
+
+    fn restart_dependents(&self, task_name: String, stale: &Vec<String>) {
+        let mut restarted: Vec<String> = vec![];
+        for dependent in stale {
+            if !self.take_automated_kill(dependent) {
+                break;
+            }
+            self.send_restart_task(dependent.clone(), false);
+            kill_task(dependent);
+            restarted.push(dependent.clone());
+        }
+        if !restarted.is_empty() {
+            self.send_add_event(EventType::DependentsRestarted, task_name, format!("restarted {}", restarted.join(", ")));
+        }
+    }
+
+    // empty when there is no metric-history configured
+    pub fn request_metric_history(&self, since: i64) -> Vec<MetricSample> {
+        match MetricHistory::open(&self.get_yaml()) {
+            Some(history) => history.read_since(since),
+            None => vec![],
+        }
+    }
+
+    pub fn request_list_nodes(&self) -> Vec<Node> {
+        self.node_list.get_nodes().into_iter().map(|node| self.with_allocation(node)).collect()
+    }
+}
+
+struct State {
+    initialized: bool,
+    my_name: String,
+    network_retry_limit: i64,
+    restart_policy: RestartPolicy,
+    task_list: Arc<TaskList>,
+    node_list: Arc<NodeList>,
+    event_list: EventList,
+    revision_list: RevisionList,
+    readiness_list: ReadinessList,
+    route_list: RouteList,
+    route_queue: RouteQueue,
+    last_clean_report: Option<CleanReport>,
+    last_sync_report: Option<SyncReport>,
+    agent_upgrade_report: Option<AgentUpgradeReport>,
+    store_journal: Option<Sender<StoreEntry>>,
+    store_writer: Option<thread::JoinHandle<()>>,
+    restored_nodes: Vec<Node>,
+    node_merge_policy: String,
+    node_conflicts: BTreeSet<String>,
+    task_conflicts: ConflictList,
+    cluster_open: bool,
+    teardown: bool,
+    shutting_down: bool,
+    overrides: BTreeMap<String, i64>,
+    cycle_trigger: Arc<CycleTrigger>,
+    update_coalescer: Arc<UpdateCoalescer>,
+    volume_list: VolumeList,
+    guardrails: Arc<Guardrails>,
+    schedules: BTreeMap<String, StoredSchedule>,
+}
+
+enum StateRequestMsg {
+    Ping { sender: Sender<StateResponseMsg> },
+    UpdateTaskState {
+        sender: Sender<StateResponseMsg>,
+        task_name: String,
+        task_state: TaskState,
+    },
+    UpdateTaskNodeName {
+        sender: Sender<StateResponseMsg>,
+        task_name: String,
+        node_name: String,
+    },
+    UpdateTaskInfo {
+        sender: Sender<StateResponseMsg>,
+        task_name: String,
+        id: String,
+        ip: String,
+        slave_id: String,
+    },
+    UpdateTaskLastUpdate {
+        sender: Sender<StateResponseMsg>,
+        task_name: String,
+    },
+    StartTask {
+        sender: Sender<StateResponseMsg>,
+        task: Task,
+    },
+    AdoptTask {
+        sender: Sender<StateResponseMsg>,
+        task: Task,
+    },
+    RestartTask {
+        sender: Sender<StateResponseMsg>,
+        task_name: String,
+        reset_backoff: bool,
+    },
+    RestartTasks {
+        sender: Sender<StateResponseMsg>,
+        task_names: Vec<String>,
+    },
+    DeleteTask {
+        sender: Sender<StateResponseMsg>,
+        task_name: String,
+    },
+    RestoreTask {
+        sender: Sender<StateResponseMsg>,
+        task_name: String,
+    },
+    UpdateDelegation {
+        sender: Sender<StateResponseMsg>,
+        task_name: String,
+        expected: String,
+        delegated_to: String,
+        remote_state: String,
+    },
+    OverrideTask {
+        sender: Sender<StateResponseMsg>,
+        task_name: String,
+        task_override: TaskOverride,
+        until: i64,
+    },
+    GetOverriddenTasks { sender: Sender<StateResponseMsg> },
+    ReportTaskConflict {
+        sender: Sender<StateResponseMsg>,
+        task_name: String,
+        controller: String,
+        existing_controller: String,
+        outcome: String,
+    },
+    GetTaskConflicts { sender: Sender<StateResponseMsg> },
+    GetVolumeHolders { sender: Sender<StateResponseMsg> },
+    ReleaseVolumes {
+        sender: Sender<StateResponseMsg>,
+        migrate_after: i64,
+    },
+    GetVolumes { sender: Sender<StateResponseMsg> },
+    SetTaskArtifactUrls {
+        sender: Sender<StateResponseMsg>,
+        task_name: String,
+        artifact_urls: Vec<String>,
+    },
+    SetTaskStandby {
+        sender: Sender<StateResponseMsg>,
+        task_name: String,
+        standby: bool,
+    },
+    SetTaskImageDigest {
+        sender: Sender<StateResponseMsg>,
+        task_name: String,
+        image: String,
+        digest: String,
+    },
+    FailTask {
+        sender: Sender<StateResponseMsg>,
+        task_name: String,
+        reason: String,
+    },
+    NetworkFailure {
+        sender: Sender<StateResponseMsg>,
+        task_name: String,
+    },
+    RemoveTask {
+        sender: Sender<StateResponseMsg>,
+        task_name: String,
+    },
+    TaskExited {
+        sender: Sender<StateResponseMsg>,
+        task_name: String,
+        failed: bool,
+    },
+    GetSnapshot { sender: Sender<StateResponseMsg> },
+    GetMemoryUsage { sender: Sender<StateResponseMsg> },
+    AddNode {
+        sender: Sender<StateResponseMsg>,
+        node: Node,
+    },
+    RegisterNode {
+        sender: Sender<StateResponseMsg>,
+        node: Node,
+    },
+    UnregisterNode {
+        sender: Sender<StateResponseMsg>,
+        node_name: String,
+    },
+    GetIsNodeActive {
+        sender: Sender<StateResponseMsg>,
+        node_name: String,
+    },
+    UpdateNode {
+        sender: Sender<StateResponseMsg>,
+        node_name: String,
+        host: String,
+        node_type: String,
+        node_function: String,
+        slave_id: String,
+    },
+    SetNodeDraining {
+        sender: Sender<StateResponseMsg>,
+        node_name: String,
+        draining: bool,
+    },
+    BeginDrain {
+        sender: Sender<StateResponseMsg>,
+        node_names: Vec<String>,
+    },
+    SetNodeInactive {
+        sender: Sender<StateResponseMsg>,
+        node_name: String,
+    },
+    UpdateNodePowerState {
+        sender: Sender<StateResponseMsg>,
+        node_name: String,
+        power_state: PowerState,
+        transition_timeout: i64,
+    },
+    SetNodeExpectedPowerState {
+        sender: Sender<StateResponseMsg>,
+        node_name: String,
+        power_state: PowerState,
+    },
+    SetRoute {
+        sender: Sender<StateResponseMsg>,
+        route: Route,
+    },
+    RemoveRoute {
+        sender: Sender<StateResponseMsg>,
+        task_name: String,
+    },
+    GetRoutes { sender: Sender<StateResponseMsg> },
+    QueueRouteOperation {
         sender: Sender<StateResponseMsg>,
-        id_prefix: String,
+        operation: RouteOperation,
+    },
+    FinishRouteOperation {
+        sender: Sender<StateResponseMsg>,
+        operation: RouteOperation,
+        success: bool,
+        initial_backoff: i64,
+        max_backoff: i64,
+    },
+    GetDueRouteOperations { sender: Sender<StateResponseMsg> },
+    GetRouteOperations { sender: Sender<StateResponseMsg> },
+    WriteStoreSnapshot { sender: Sender<StateResponseMsg> },
+    FlushStore { sender: Sender<StateResponseMsg> },
+    AddEvent {
+        sender: Sender<StateResponseMsg>,
+        event_type: EventType,
+        subject: String,
+        message: String,
+    },
+    UpdateNodePowerReading {
+        sender: Sender<StateResponseMsg>,
+        node_name: String,
+        power_in_watts: f64,
+    },
+    UpdateNodeMetadata {
+        sender: Sender<StateResponseMsg>,
+        node_name: String,
+        update: NodeUpdate,
+    },
+    UpdateTaskAnnotations {
+        sender: Sender<StateResponseMsg>,
+        task_name: String,
+        update: BTreeMap<String, Option<String>>,
+    },
+    NodeHeartbeat {
+        sender: Sender<StateResponseMsg>,
+        node_name: String,
+        agent_version: String,
+        gpus: Option<Vec<Gpu>>,
     },
-    UpdateTaskState {
+    AddTaskEnergy {
         sender: Sender<StateResponseMsg>,
         task_name: String,
-        task_state: TaskState,
+        power_in_watts: f64,
+        energy_in_wh: f64,
     },
-    UpdateTaskNodeName {
+    GetEvents { sender: Sender<StateResponseMsg> },
+    GetEventsSince {
         sender: Sender<StateResponseMsg>,
-        task_name: String,
-        node_name: String,
+        since: u64,
+        epoch: Option<i64>,
     },
-    UpdateTaskInfo {
+    GetTaskRevisions {
         sender: Sender<StateResponseMsg>,
         task_name: String,
-        id: String,
-        ip: String,
-        slave_id: String,
     },
-    UpdateTaskLastUpdate {
+    SetCollaborators {
         sender: Sender<StateResponseMsg>,
-        task_name: String,
+        collaborators: Vec<CollaboratorStatus>,
     },
-    StartTask {
+    UpdateCollaborator {
         sender: Sender<StateResponseMsg>,
-        task: Task,
+        name: String,
+        reachable: bool,
+        error: String,
     },
-    RestartTask {
+    GetIsReady { sender: Sender<StateResponseMsg> },
+    GetCollaborators { sender: Sender<StateResponseMsg> },
+    SetLastCleanReport {
         sender: Sender<StateResponseMsg>,
-        task_name: String,
+        report: CleanReport,
     },
-    RemoveTask {
+    GetLastCleanReport { sender: Sender<StateResponseMsg> },
+    SetLastSyncReport {
         sender: Sender<StateResponseMsg>,
-        task_name: String,
+        report: SyncReport,
     },
-    GetIsRestartableTask {
+    GetLastSyncReport { sender: Sender<StateResponseMsg> },
+    BeginAgentUpgrade {
         sender: Sender<StateResponseMsg>,
-        task_name: String,
+        report: AgentUpgradeReport,
     },
-    GetRequestedTasks { sender: Sender<StateResponseMsg> },
-    GetRunningTasks { sender: Sender<StateResponseMsg> },
-    GetRestartTasks { sender: Sender<StateResponseMsg> },
-    AddNode {
+    SetAgentUpgradeReport {
         sender: Sender<StateResponseMsg>,
-        node: Node,
+        report: AgentUpgradeReport,
     },
-    GetIsNodeActive {
+    GetAgentUpgradeReport { sender: Sender<StateResponseMsg> },
+    SetClusterOpen {
         sender: Sender<StateResponseMsg>,
-        node_name: String,
+        open: bool,
     },
-    UpdateNode {
+    GetIsClusterOpen { sender: Sender<StateResponseMsg> },
+    SetTeardown {
         sender: Sender<StateResponseMsg>,
-        node_name: String,
-        node_type: String,
-        node_function: String,
-        slave_id: String,
+        active: bool,
     },
-    SetNodeInactive {
+    GetIsTeardown { sender: Sender<StateResponseMsg> },
+    SetShuttingDown {
         sender: Sender<StateResponseMsg>,
-        node_name: String,
+        active: bool,
     },
-    GetNode {
+    GetIsShuttingDown { sender: Sender<StateResponseMsg> },
+    PutSchedule {
         sender: Sender<StateResponseMsg>,
-        node_name: String,
+        schedule: StoredSchedule,
+    },
+    RemoveSchedule {
+        sender: Sender<StateResponseMsg>,
+        job_name: String,
     },
-    GetNodes { sender: Sender<StateResponseMsg> },
+    GetSchedules { sender: Sender<StateResponseMsg> },
 }
 
-enum StateResponseMsg {
-    Pong,
-    TaskState { task_state: TaskState },
-    TaskIP { task_ip: String },
-    TaskName { task_name: String },
-    UpdateTaskState,
-    UpdateTaskInfo,
-    UpdateTaskNodeName,
-    UpdateTaskLastUpdate,
-    StartTask,
-    RestartTask,
-    RemoveTask,
-    GetIsRestartableTask { is_restartable_task: bool },
-    GetRequestedTasks { requested_tasks: Vec<Task> },
-    GetRunningTasks { running_tasks: Vec<Task> },
-    GetRestartTasks { restart_tasks: Vec<Task> },
-    AddNode,
-    GetIsNodeActive { is_active: bool },
-    UpdateNode,
-    SetNodeInactive,
-    GetNodes { nodes: Vec<Node> },
-    GetNode { node: Node },
-}
+enum StateResponseMsg {
+    Pong,
+    UpdateTaskState,
+    UpdateTaskInfo,
+    UpdateTaskNodeName,
+    UpdateTaskLastUpdate,
+    StartTask { result: Result<(), String> },
+    AdoptTask,
+    RestartTask,
+    DeleteTask { result: Result<Vec<String>, String> },
+    RestoreTask { result: Result<(), String> },
+    RestartTasks,
+    UpdateDelegation { applied: bool },
+    OverrideTask { result: Result<Task, String> },
+    GetOverriddenTasks { task_names: Vec<String> },
+    ReportTaskConflict,
+    GetTaskConflicts { conflicts: Vec<TaskConflict> },
+    GetVolumeHolders { holders: BTreeMap<String, String> },
+    ReleaseVolumes { released: Vec<VolumeBinding> },
+    GetVolumes { volumes: Vec<VolumeBinding> },
+    SetTaskArtifactUrls,
+    SetTaskStandby,
+    SetTaskImageDigest,
+    FailTask,
+    NetworkFailure { task_state: TaskState },
+    RemoveTask,
+    TaskExited { decision: RestartDecision },
+    GetSnapshot { snapshot: StateSnapshot },
+    GetMemoryUsage { report: MemoryReport },
+    AddNode,
+    RegisterNode { result: Result<Node, String> },
+    UnregisterNode { result: Result<Node, String> },
+    GetIsNodeActive { is_active: bool },
+    UpdateNode,
+    SetNodeDraining,
+    BeginDrain { result: Result<(), String> },
+    SetNodeInactive,
+    UpdateNodePowerState,
+    SetNodeExpectedPowerState,
+    SetRoute { previous: Option<Route> },
+    RemoveRoute { previous: Option<Route> },
+    GetRoutes { routes: Vec<Route> },
+    QueueRouteOperation,
+    FinishRouteOperation,
+    GetRouteOperations { operations: Vec<RouteOperation> },
+    WriteStoreSnapshot,
+    FlushStore,
+    AddEvent,
+    UpdateNodePowerReading,
+    UpdateNodeMetadata { result: Result<Node, String> },
+    UpdateTaskAnnotations { result: Result<Task, String> },
+    NodeHeartbeat,
+    AddTaskEnergy,
+    GetEvents { events: Vec<Event> },
+    GetEventsSince { result: Result<Vec<Event>, String> },
+    GetTaskRevisions { revisions: Vec<Revision> },
+    SetCollaborators,
+    UpdateCollaborator,
+    GetIsReady { is_ready: bool },
+    GetCollaborators { collaborators: Vec<CollaboratorStatus> },
+    SetLastCleanReport,
+    GetLastCleanReport { report: Option<CleanReport> },
+    SetLastSyncReport,
+    GetLastSyncReport { report: Option<SyncReport> },
+    BeginAgentUpgrade { started: bool },
+    SetAgentUpgradeReport,
+    GetAgentUpgradeReport { report: Option<AgentUpgradeReport> },
+    SetClusterOpen,
+    GetIsClusterOpen { is_open: bool },
+    SetTeardown,
+    GetIsTeardown { is_teardown: bool },
+    SetShuttingDown,
+    GetIsShuttingDown { is_shutting_down: bool },
+    PutSchedule,
+    RemoveSchedule,
+    GetSchedules { schedules: Vec<StoredSchedule> },
+}
+
+
+impl StateManager {
+    pub fn read_config_file(config_file: String) -> Result<Yaml, String> {
+        load_config(&config_file)
+    }
+
+    fn start_serving(&self, rx: Receiver<StateRequestMsg>) {
+        let my_name = self.get_my_name();
+        let network_retry_limit = read_int(&self.get_yaml()["stateclean"], "network_retry_limit".to_string(), 3);
+        let restart_policy = RestartPolicy::new(&self.get_yaml());
+        // with gated workloads only system services get scheduled until a bootstrap went through
+        let cluster_open = !read_bool(&self.get_yaml()["bootstrap"], "gate_workloads".to_string());
+        let max_revisions = read_int(&self.get_yaml()["revisions"], "max_per_service".to_string(), 10) as usize;
+        let event_sink = start_event_export(&self.get_yaml(), &my_name);
+        let route_queue_file = read_string(&self.get_yaml()["network-agent"]["retry"], "queue_file".to_string());
+        let batch_size = read_int(&self.get_yaml()["state-loop"], "batch_size".to_string(), 64) as usize;
+        let max_urgent_in_a_row = read_int(&self.get_yaml()["state-loop"], "max_urgent_in_a_row".to_string(), 16) as usize;
+        let task_list = self.task_list.clone();
+        let sequence = task_list.sequence();
+        let node_list = self.node_list.clone();
+        let node_merge_policy = read_string(&self.get_yaml()["node-registration"], "merge_policy".to_string());
+        let cycle_trigger = self.cycle_trigger.clone();
+        let guardrails = self.guardrails.clone();
+        let update_coalescer = self.update_coalescer.clone();
+        // whatever got persisted before a restart is loaded before the first request gets served
+        let (stored_state, store_journal, store_writer) = match open_store(&self.get_yaml()) {
+            Some(mut store) => {
+                let stored_state = match store.load() {
+                    Ok(stored_state) => stored_state,
+                    Err(error) => {
+                        error!("can't restore state error={}", error);
+                        None
+                    }
+                };
+                let (journal, writer) = start_store_writer(store);
+                (stored_state, Some(journal), Some(writer))
+            }
+            None => (None, None, None),
+        };
+        thread::Builder::new()
+            .name("state-serve".to_string())
+            .spawn(move || {
+                let mut state = State {
+                    initialized: false,
+                    my_name: my_name,
+                    network_retry_limit: network_retry_limit,
+                    restart_policy: restart_policy,
+                    task_list: task_list,
+                    node_list: node_list,
+                    event_list: EventList::new(sequence),
+                    revision_list: RevisionList::new(max_revisions),
+                    readiness_list: ReadinessList::new(),
+                    route_list: RouteList::new(),
+                    route_queue: RouteQueue::new(route_queue_file),
+                    last_clean_report: None,
+                    last_sync_report: None,
+                    agent_upgrade_report: None,
+                    store_journal: store_journal.clone(),
+                    store_writer: store_writer,
+                    restored_nodes: vec![],
+                    node_merge_policy: node_merge_policy,
+                    node_conflicts: BTreeSet::new(),
+                    task_conflicts: ConflictList::new(),
+                    cluster_open: cluster_open,
+                    teardown: false,
+                    shutting_down: false,
+                    overrides: BTreeMap::new(),
+                    cycle_trigger: cycle_trigger,
+                    update_coalescer: update_coalescer,
+                    volume_list: VolumeList::new(),
+                    guardrails: guardrails,
+                    schedules: BTreeMap::new(),
+                };
+                match stored_state {
+                    Some(stored_state) => {
+                        info!("restoring state tasks={} nodes={}", stored_state.tasks.len(), stored_state.nodes.len());
+                        for binding in stored_state.volumes {
+                            state.volume_list.restore(binding);
+                        }
+                        // stores from before bindings were kept only know where the running tasks are
+                        for task in &stored_state.tasks {
+                            state.task_list.add_new_task(task);
+                            if task.state == TaskState::Running && state.volume_list.get_binding(&task.name).is_none() {
+                                state.volume_list.bind(task, task.node_name.clone());
+                            }
+                        }
+                        // registered nodes have no entry in the configuration to come back from
+                        for node in stored_state.nodes.iter().filter(|node| node.registered) {
+                            state.node_list.add_new_node(node);
+                        }
+                        for schedule in stored_state.schedules {
+                            state.schedules.insert(schedule.name.clone(), schedule);
+                        }
+                        state.restored_nodes = stored_state.nodes;
+                    }
+                    None => {}
+                }
+                state.task_list.set_journal(store_journal);
+                state.event_list.set_sink(event_sink);
+                state.initialized = true;
+
+                let mut queue = MessageQueue::new(batch_size, max_urgent_in_a_row);
+                loop {
+                    let (msg, queued_at) = queue.next(&rx, StateManager::is_urgent).unwrap();
+                    let started = Instant::now();
+                    let (message_type, urgent) = (StateManager::message_type(&msg), StateManager::is_urgent(&msg));
+                    let wakes_cycles = StateManager::wakes_cycles(&msg);
+                    match msg {
+                        StateRequestMsg::Ping { sender } => StateManager::ping(sender),
+                        StateRequestMsg::UpdateTaskState { sender, task_name, task_state } => {
+                            StateManager::update_task_state(sender, &state, task_name, task_state)
+                        }
+                        StateRequestMsg::UpdateTaskNodeName { sender, task_name, node_name } => {
+                            StateManager::update_task_node_name(sender, &state, task_name, node_name)
+                        }
+                        StateRequestMsg::UpdateTaskInfo { sender, task_name, id, ip, slave_id } => {
+                            StateManager::update_task_info(sender, &state, task_name, id, ip, slave_id)
+                        }
+                        StateRequestMsg::UpdateTaskLastUpdate { sender, task_name } => {
+                            StateManager::update_task_last_update(sender, &state, task_name)
+                        }
+                        StateRequestMsg::StartTask { sender, task } => StateManager::start_task(sender, &state, &task),
+                        StateRequestMsg::AdoptTask { sender, task } => StateManager::adopt_task(sender, &state, task),
+                        StateRequestMsg::RestartTask { sender, task_name, reset_backoff } => {
+                            StateManager::restart_task(sender, &state, task_name, reset_backoff)
+                        }
+                        StateRequestMsg::RestartTasks { sender, task_names } => {
+                            StateManager::restart_tasks(sender, &state, task_names)
+                        }
+                        StateRequestMsg::DeleteTask { sender, task_name } => StateManager::delete_task(sender, &state, task_name),
+                        StateRequestMsg::RestoreTask { sender, task_name } => StateManager::restore_task(sender, &state, task_name),
+                        StateRequestMsg::UpdateDelegation { sender, task_name, expected, delegated_to, remote_state } => {
+                            StateManager::update_delegation(sender, &state, task_name, expected, delegated_to, remote_state)
+                        }
+                        StateRequestMsg::OverrideTask { sender, task_name, task_override, until } => {
+                            StateManager::override_task(sender, &mut state, task_name, task_override, until)
+                        }
+                        StateRequestMsg::GetOverriddenTasks { sender } => {
+                            StateManager::get_overridden_tasks(sender, &mut state)
+                        }
+                        StateRequestMsg::ReportTaskConflict { sender, task_name, controller, existing_controller, outcome } => {
+                            StateManager::report_task_conflict(sender, &state, task_name, controller, existing_controller, outcome)
+                        }
+                        StateRequestMsg::GetTaskConflicts { sender } => StateManager::get_task_conflicts(sender, &state),
+                        StateRequestMsg::GetVolumeHolders { sender } => StateManager::get_volume_holders(sender, &state),
+                        StateRequestMsg::ReleaseVolumes { sender, migrate_after } => {
+                            StateManager::release_volumes(sender, &state, migrate_after)
+                        }
+                        StateRequestMsg::GetVolumes { sender } => StateManager::get_volumes(sender, &state),
+                        StateRequestMsg::SetTaskArtifactUrls { sender, task_name, artifact_urls } => {
+                            StateManager::set_task_artifact_urls(sender, &state, task_name, artifact_urls)
+                        }
+                        StateRequestMsg::SetTaskStandby { sender, task_name, standby } => {
+                            StateManager::set_task_standby(sender, &state, task_name, standby)
+                        }
+                        StateRequestMsg::SetTaskImageDigest { sender, task_name, image, digest } => {
+                            StateManager::set_task_image_digest(sender, &state, task_name, image, digest)
+                        }
+                        StateRequestMsg::FailTask { sender, task_name, reason } => {
+                            StateManager::fail_task(sender, &state, task_name, reason)
+                        }
+                        StateRequestMsg::NetworkFailure { sender, task_name } => {
+                            StateManager::network_failure(sender, &state, task_name)
+                        }
+                        StateRequestMsg::RemoveTask { sender, task_name } => {
+                            StateManager::remove_task_by_name(sender, &state, task_name)
+                        }
+                        StateRequestMsg::TaskExited { sender, task_name, failed } => {
+                            StateManager::task_exited(sender, &state, task_name, failed)
+                        }
+                        StateRequestMsg::GetSnapshot { sender } => StateManager::get_snapshot(sender, &state),
+                        StateRequestMsg::GetMemoryUsage { sender } => StateManager::get_memory_usage(sender, &state),
+                        StateRequestMsg::AddNode { sender, node } => StateManager::add_node(sender, &mut state, &node),
+                        StateRequestMsg::RegisterNode { sender, node } => StateManager::register_node(sender, &state, node),
+                        StateRequestMsg::UnregisterNode { sender, node_name } => {
+                            StateManager::unregister_node(sender, &state, node_name)
+                        }
+                        StateRequestMsg::GetIsNodeActive { sender, node_name } => {
+                            StateManager::get_is_node_active(sender, &state, node_name)
+                        }
+                        StateRequestMsg::UpdateNode { sender, node_name, host, node_type, node_function, slave_id } => {
+                            StateManager::update_node(sender,
+                                                      &mut state,
+                                                      node_name,
+                                                      host,
+                                                      node_type,
+                                                      node_function,
+                                                      slave_id)
+                        }
+                        StateRequestMsg::SetNodeDraining { sender, node_name, draining } => {
+                            StateManager::set_node_draining(sender, &state, node_name, draining)
+                        }
+                        StateRequestMsg::BeginDrain { sender, node_names } => StateManager::begin_drain(sender, &state, node_names),
+                        StateRequestMsg::SetNodeInactive { sender, node_name } => {
+                            StateManager::set_node_inactive(sender, &state, node_name)
+                        }
+                        StateRequestMsg::UpdateNodePowerState { sender, node_name, power_state, transition_timeout } => {
+                            StateManager::update_node_power_state(sender, &state, node_name, power_state, transition_timeout)
+                        }
+                        StateRequestMsg::SetNodeExpectedPowerState { sender, node_name, power_state } => {
+                            StateManager::set_node_expected_power_state(sender, &state, node_name, power_state)
+                        }
+                        StateRequestMsg::SetRoute { sender, route } => StateManager::set_route(sender, &state, route),
+                        StateRequestMsg::RemoveRoute { sender, task_name } => {
+                            StateManager::remove_route(sender, &state, task_name)
+                        }
+                        StateRequestMsg::GetRoutes { sender } => StateManager::get_routes(sender, &state),
+                        StateRequestMsg::QueueRouteOperation { sender, operation } => {
+                            StateManager::queue_route_operation(sender, &state, operation)
+                        }
+                        StateRequestMsg::FinishRouteOperation { sender, operation, success, initial_backoff, max_backoff } => {
+                            StateManager::finish_route_operation(sender, &state, operation, success, initial_backoff, max_backoff)
+                        }
+                        StateRequestMsg::GetDueRouteOperations { sender } => {
+                            StateManager::get_due_route_operations(sender, &state)
+                        }
+                        StateRequestMsg::GetRouteOperations { sender } => StateManager::get_route_operations(sender, &state),
+                        StateRequestMsg::WriteStoreSnapshot { sender } => StateManager::write_store_snapshot(sender, &state),
+                        StateRequestMsg::FlushStore { sender } => StateManager::flush_store(sender, &mut state),
+                        StateRequestMsg::AddEvent { sender, event_type, subject, message } => {
+                            StateManager::add_event(sender, &state, event_type, subject, message)
+                        }
+                        StateRequestMsg::UpdateNodePowerReading { sender, node_name, power_in_watts } => {
+                            StateManager::update_node_power_reading(sender, &state, node_name, power_in_watts)
+                        }
+                        StateRequestMsg::UpdateNodeMetadata { sender, node_name, update } => {
+                            StateManager::update_node_metadata(sender, &state, node_name, update)
+                        }
+                        StateRequestMsg::UpdateTaskAnnotations { sender, task_name, update } => {
+                            StateManager::update_task_annotations(sender, &state, task_name, update)
+                        }
+                        StateRequestMsg::NodeHeartbeat { sender, node_name, agent_version, gpus } => {
+                            StateManager::node_heartbeat(sender, &state, node_name, agent_version, gpus)
+                        }
+                        StateRequestMsg::AddTaskEnergy { sender, task_name, power_in_watts, energy_in_wh } => {
+                            StateManager::add_task_energy(sender, &state, task_name, power_in_watts, energy_in_wh)
+                        }
+                        StateRequestMsg::GetEvents { sender } => StateManager::get_events(sender, &state),
+                        StateRequestMsg::GetEventsSince { sender, since, epoch } => {
+                            StateManager::get_events_since(sender, &state, since, epoch)
+                        }
+                        StateRequestMsg::GetTaskRevisions { sender, task_name } => {
+                            StateManager::get_task_revisions(sender, &state, task_name)
+                        }
+                        StateRequestMsg::SetCollaborators { sender, collaborators } => {
+                            StateManager::set_collaborators(sender, &state, collaborators)
+                        }
+                        StateRequestMsg::UpdateCollaborator { sender, name, reachable, error } => {
+                            StateManager::update_collaborator(sender, &state, name, reachable, error)
+                        }
+                        StateRequestMsg::GetIsReady { sender } => StateManager::get_is_ready(sender, &state),
+                        StateRequestMsg::GetCollaborators { sender } => StateManager::get_collaborators(sender, &state),
+                        StateRequestMsg::SetLastCleanReport { sender, report } => {
+                            StateManager::set_last_clean_report(sender, &mut state, report)
+                        }
+                        StateRequestMsg::GetLastCleanReport { sender } => StateManager::get_last_clean_report(sender, &state),
+                        StateRequestMsg::SetLastSyncReport { sender, report } => {
+                            StateManager::set_last_sync_report(sender, &mut state, report)
+                        }
+                        StateRequestMsg::GetLastSyncReport { sender } => StateManager::get_last_sync_report(sender, &state),
+                        StateRequestMsg::BeginAgentUpgrade { sender, report } => {
+                            StateManager::begin_agent_upgrade(sender, &mut state, report)
+                        }
+                        StateRequestMsg::SetAgentUpgradeReport { sender, report } => {
+                            StateManager::set_agent_upgrade_report(sender, &mut state, report)
+                        }
+                        StateRequestMsg::GetAgentUpgradeReport { sender } => {
+                            StateManager::get_agent_upgrade_report(sender, &state)
+                        }
+                        StateRequestMsg::SetClusterOpen { sender, open } => {
+                            StateManager::set_cluster_open(sender, &mut state, open)
+                        }
+                        StateRequestMsg::GetIsClusterOpen { sender } => StateManager::get_is_cluster_open(sender, &state),
+                        StateRequestMsg::SetTeardown { sender, active } => {
+                            StateManager::set_teardown(sender, &mut state, active)
+                        }
+                        StateRequestMsg::GetIsTeardown { sender } => StateManager::get_is_teardown(sender, &state),
+                        StateRequestMsg::SetShuttingDown { sender, active } => {
+                            StateManager::set_shutting_down(sender, &mut state, active)
+                        }
+                        StateRequestMsg::GetIsShuttingDown { sender } => StateManager::get_is_shutting_down(sender, &state),
+                        StateRequestMsg::PutSchedule { sender, schedule } => {
+                            StateManager::put_schedule(sender, &mut state, schedule)
+                        }
+                        StateRequestMsg::RemoveSchedule { sender, job_name } => {
+                            StateManager::remove_schedule(sender, &mut state, job_name)
+                        }
+                        StateRequestMsg::GetSchedules { sender } => StateManager::get_schedules(sender, &state),
+                    }
+                    if wakes_cycles {
+                        state.cycle_trigger.notify();
+                    }
+                    observe_state_message(message_type, urgent, started - queued_at, started.elapsed());
+                }
+            })
+            .unwrap();
+    }
+
+    // status updates from mesos and health transitions, served ahead of queries and api changes
+    fn is_urgent(msg: &StateRequestMsg) -> bool {
+        match *msg {
+            StateRequestMsg::UpdateTaskState { .. } |
+            StateRequestMsg::UpdateTaskInfo { .. } |
+            StateRequestMsg::UpdateTaskNodeName { .. } |
+            StateRequestMsg::TaskExited { .. } |
+            StateRequestMsg::FailTask { .. } |
+            StateRequestMsg::NetworkFailure { .. } |
+            StateRequestMsg::NodeHeartbeat { .. } |
+            StateRequestMsg::SetNodeInactive { .. } |
+            StateRequestMsg::UpdateNodePowerState { .. } |
+            StateRequestMsg::UpdateCollaborator { .. } => true,
+            _ => false,
+        }
+    }
+
+    // the label the processing metrics carry
+    fn message_type(msg: &StateRequestMsg) -> &'static str {
+        match *msg {
+            StateRequestMsg::Ping { .. } => "Ping",
+            StateRequestMsg::UpdateTaskState { .. } => "UpdateTaskState",
+            StateRequestMsg::UpdateTaskNodeName { .. } => "UpdateTaskNodeName",
+            StateRequestMsg::UpdateTaskInfo { .. } => "UpdateTaskInfo",
+            StateRequestMsg::UpdateTaskLastUpdate { .. } => "UpdateTaskLastUpdate",
+            StateRequestMsg::StartTask { .. } => "StartTask",
+            StateRequestMsg::AdoptTask { .. } => "AdoptTask",
+            StateRequestMsg::RestartTask { .. } => "RestartTask",
+            StateRequestMsg::RestartTasks { .. } => "RestartTasks",
+            StateRequestMsg::DeleteTask { .. } => "DeleteTask",
+            StateRequestMsg::RestoreTask { .. } => "RestoreTask",
+            StateRequestMsg::UpdateDelegation { .. } => "UpdateDelegation",
+            StateRequestMsg::OverrideTask { .. } => "OverrideTask",
+            StateRequestMsg::GetOverriddenTasks { .. } => "GetOverriddenTasks",
+            StateRequestMsg::ReportTaskConflict { .. } => "ReportTaskConflict",
+            StateRequestMsg::GetTaskConflicts { .. } => "GetTaskConflicts",
+            StateRequestMsg::GetVolumeHolders { .. } => "GetVolumeHolders",
+            StateRequestMsg::ReleaseVolumes { .. } => "ReleaseVolumes",
+            StateRequestMsg::GetVolumes { .. } => "GetVolumes",
+            StateRequestMsg::SetTaskArtifactUrls { .. } => "SetTaskArtifactUrls",
+            StateRequestMsg::SetTaskStandby { .. } => "SetTaskStandby",
+            StateRequestMsg::SetTaskImageDigest { .. } => "SetTaskImageDigest",
+            StateRequestMsg::FailTask { .. } => "FailTask",
+            StateRequestMsg::NetworkFailure { .. } => "NetworkFailure",
+            StateRequestMsg::RemoveTask { .. } => "RemoveTask",
+            StateRequestMsg::TaskExited { .. } => "TaskExited",
+            StateRequestMsg::GetSnapshot { .. } => "GetSnapshot",
+            StateRequestMsg::GetMemoryUsage { .. } => "GetMemoryUsage",
+            StateRequestMsg::AddNode { .. } => "AddNode",
+            StateRequestMsg::RegisterNode { .. } => "RegisterNode",
+            StateRequestMsg::UnregisterNode { .. } => "UnregisterNode",
+            StateRequestMsg::GetIsNodeActive { .. } => "GetIsNodeActive",
+            StateRequestMsg::UpdateNode { .. } => "UpdateNode",
+            StateRequestMsg::SetNodeDraining { .. } => "SetNodeDraining",
+            StateRequestMsg::BeginDrain { .. } => "BeginDrain",
+            StateRequestMsg::SetNodeInactive { .. } => "SetNodeInactive",
+            StateRequestMsg::UpdateNodePowerState { .. } => "UpdateNodePowerState",
+            StateRequestMsg::SetNodeExpectedPowerState { .. } => "SetNodeExpectedPowerState",
+            StateRequestMsg::SetRoute { .. } => "SetRoute",
+            StateRequestMsg::RemoveRoute { .. } => "RemoveRoute",
+            StateRequestMsg::GetRoutes { .. } => "GetRoutes",
+            StateRequestMsg::QueueRouteOperation { .. } => "QueueRouteOperation",
+            StateRequestMsg::FinishRouteOperation { .. } => "FinishRouteOperation",
+            StateRequestMsg::GetDueRouteOperations { .. } => "GetDueRouteOperations",
+            StateRequestMsg::GetRouteOperations { .. } => "GetRouteOperations",
+            StateRequestMsg::WriteStoreSnapshot { .. } => "WriteStoreSnapshot",
+            StateRequestMsg::FlushStore { .. } => "FlushStore",
+            StateRequestMsg::AddEvent { .. } => "AddEvent",
+            StateRequestMsg::UpdateNodePowerReading { .. } => "UpdateNodePowerReading",
+            StateRequestMsg::UpdateNodeMetadata { .. } => "UpdateNodeMetadata",
+            StateRequestMsg::UpdateTaskAnnotations { .. } => "UpdateTaskAnnotations",
+            StateRequestMsg::NodeHeartbeat { .. } => "NodeHeartbeat",
+            StateRequestMsg::AddTaskEnergy { .. } => "AddTaskEnergy",
+            StateRequestMsg::GetEvents { .. } => "GetEvents",
+            StateRequestMsg::GetEventsSince { .. } => "GetEventsSince",
+            StateRequestMsg::GetTaskRevisions { .. } => "GetTaskRevisions",
+            StateRequestMsg::SetCollaborators { .. } => "SetCollaborators",
+            StateRequestMsg::UpdateCollaborator { .. } => "UpdateCollaborator",
+            StateRequestMsg::GetIsReady { .. } => "GetIsReady",
+            StateRequestMsg::GetCollaborators { .. } => "GetCollaborators",
+            StateRequestMsg::SetLastCleanReport { .. } => "SetLastCleanReport",
+            StateRequestMsg::GetLastCleanReport { .. } => "GetLastCleanReport",
+            StateRequestMsg::SetLastSyncReport { .. } => "SetLastSyncReport",
+            StateRequestMsg::GetLastSyncReport { .. } => "GetLastSyncReport",
+            StateRequestMsg::BeginAgentUpgrade { .. } => "BeginAgentUpgrade",
+            StateRequestMsg::SetAgentUpgradeReport { .. } => "SetAgentUpgradeReport",
+            StateRequestMsg::GetAgentUpgradeReport { .. } => "GetAgentUpgradeReport",
+            StateRequestMsg::SetClusterOpen { .. } => "SetClusterOpen",
+            StateRequestMsg::GetIsClusterOpen { .. } => "GetIsClusterOpen",
+            StateRequestMsg::SetTeardown { .. } => "SetTeardown",
+            StateRequestMsg::GetIsTeardown { .. } => "GetIsTeardown",
+            StateRequestMsg::SetShuttingDown { .. } => "SetShuttingDown",
+            StateRequestMsg::GetIsShuttingDown { .. } => "GetIsShuttingDown",
+            StateRequestMsg::PutSchedule { .. } => "PutSchedule",
+            StateRequestMsg::RemoveSchedule { .. } => "RemoveSchedule",
+            StateRequestMsg::GetSchedules { .. } => "GetSchedules",
+        }
+    }
+
+    // changes to tasks and nodes the sync and clean cycles act on
+    fn wakes_cycles(msg: &StateRequestMsg) -> bool {
+        match *msg {
+            StateRequestMsg::UpdateTaskState { .. } |
+            StateRequestMsg::UpdateTaskInfo { .. } |
+            StateRequestMsg::StartTask { .. } |
+            StateRequestMsg::AdoptTask { .. } |
+            StateRequestMsg::RestartTask { .. } |
+            StateRequestMsg::RestartTasks { .. } |
+            StateRequestMsg::TaskExited { .. } |
+            StateRequestMsg::DeleteTask { .. } |
+            StateRequestMsg::RestoreTask { .. } |
+            StateRequestMsg::FailTask { .. } |
+            StateRequestMsg::NetworkFailure { .. } |
+            StateRequestMsg::RemoveTask { .. } |
+            StateRequestMsg::UpdateNode { .. } |
+            StateRequestMsg::RegisterNode { .. } |
+            StateRequestMsg::UnregisterNode { .. } |
+            StateRequestMsg::SetNodeInactive { .. } |
+            StateRequestMsg::SetNodeDraining { .. } |
+            StateRequestMsg::BeginDrain { .. } => true,
+            _ => false,
+        }
+    }
+
+    // poll_interval_in_seconds is the longest a cycle waits, changes wake it after min_interval_in_seconds
+    fn cycle_intervals(config: &Yaml) -> (Duration, Duration) {
+        let max_interval = config["poll_interval_in_seconds"].as_i64().unwrap() as u64;
+        let min_interval = read_int(config, "min_interval_in_seconds".to_string(), 1) as u64;
+        (Duration::from_secs(min_interval.min(max_interval)), Duration::from_secs(max_interval))
+    }
+
+    fn start_syncing(&self) {
+        let (min_interval, max_interval) = StateManager::cycle_intervals(&self.get_yaml()["statesync"]);
+        let wakeups = self.cycle_trigger.subscribe();
+        let state_manager = self.clone();
+
+        thread::Builder::new()
+            .name("state-sync".to_string())
+            .spawn(move || {
+                loop {
+                    let triggered_by = wait_for_cycle(&wakeups, min_interval, max_interval);
+                    state_manager.run_sync_cycle(triggered_by);
+                }
+            })
+            .unwrap();
+    }
+
+    fn start_cleaning(&self) {
+        let (min_interval, max_interval) = StateManager::cycle_intervals(&self.get_yaml()["stateclean"]);
+        let wakeups = self.cycle_trigger.subscribe();
+        let state_manager = self.clone();
+
+        thread::Builder::new()
+            .name("state-clean".to_string())
+            .spawn(move || {
+                loop {
+                    let triggered_by = wait_for_cycle(&wakeups, min_interval, max_interval);
+                    state_manager.run_clean_cycle(triggered_by);
+                }
+            })
+            .unwrap();
+    }
+
+    fn start_route_retries(&self) {
+        let retry = self.get_yaml()["network-agent"]["retry"].clone();
+        let wait_time = read_int(&retry, "poll_interval_in_seconds".to_string(), 5) as u64;
+        let alarm_after = read_int(&retry, "alarm_after_in_seconds".to_string(), 300);
+        let state_manager = self.clone();
+
+        thread::Builder::new()
+            .name("route-retry".to_string())
+            .spawn(move || {
+                let mut alarmed = false;
+                loop {
+                    thread::sleep(Duration::from_secs(wait_time));
+                    for operation in state_manager.request_due_route_operations() {
+                        let result = state_manager.apply_route_operation(&operation);
+                        info!("retried route operation={:?} task={} ip={} attempt={} result={:?}",
+                              operation.kind,
+                              operation.task_name,
+                              operation.ip,
+                              operation.attempts + 1,
+                              result);
+                        let done = state_manager.settle_route_operation(&operation, result);
+                        state_manager.send_finish_route_operation(operation, done);
+                    }
+
+                    // alarm once per stretch of the queue being stuck
+                    let operations = state_manager.request_list_route_operations();
+                    let age = match operations.iter().map(|operation| operation.first_failed).min() {
+                        Some(first_failed) => UTC::now().timestamp() - first_failed,
+                        None => 0,
+                    };
+                    if age > alarm_after && !alarmed {
+                        state_manager.send_add_event(EventType::RouteQueueAlarm,
+                                                     state_manager.get_network_agent_connection(),
+                                                     format!("{} route operations pending, oldest for {}s",
+                                                             operations.len(),
+                                                             age));
+                    }
+                    alarmed = age > alarm_after;
+                }
+            })
+            .unwrap();
+    }
+
+    fn start_update_flushing(&self) {
+        let state_manager = self.clone();
+
+        thread::Builder::new()
+            .name("update-flush".to_string())
+            .spawn(move || {
+                loop {
+                    let batch = state_manager.update_coalescer.next_batch();
+                    let check = TtlCheck::from_config(&state_manager.get_yaml());
+                    debug!("flushing updates count={}", batch.len());
+                    for update in batch {
+                        state_manager.apply_pending_update(update, &check);
+                    }
+                }
+            })
+            .unwrap();
+    }
+
+    fn start_store_snapshots(&self) {
+        let store_config = &self.get_yaml()["state-store"];
+        if store_config.is_badvalue() {
+            return;
+        }
+
+        let wait_time = read_int(store_config, "snapshot_interval_in_seconds".to_string(), 60) as u64;
+        let state_manager = self.clone();
+
+        thread::Builder::new()
+            .name("state-snapshot".to_string())
+            .spawn(move || {
+                loop {
+                    thread::sleep(Duration::from_secs(wait_time));
+                    state_manager.send_write_store_snapshot();
+                }
+            })
+            .unwrap();
+    }
+
+    fn start_metric_sampling(&self) {
+        let config = self.get_yaml();
+        let history = match MetricHistory::open(&config) {
+            Some(history) => history,
+            None => return,
+        };
+
+        let wait_time = read_int(&config["metric-history"], "interval_in_seconds".to_string(), 60) as u64;
+        let state_manager = self.clone();
+
+        thread::Builder::new()
+            .name("metric-sampler".to_string())
+            .spawn(move || {
+                loop {
+                    thread::sleep(Duration::from_secs(wait_time));
+                    let sample = MetricSample::take(UTC::now().timestamp(),
+                                                    &state_manager.request_list_tasks(),
+                                                    &state_manager.request_list_nodes());
+                    match history.record(&sample) {
+                        Ok(_) => {}
+                        Err(reason) => warn!("metric sample not recorded error={}", reason),
+                    }
+                }
+            })
+            .unwrap();
+    }
+
+    fn start_power_polling(&self) {
+        let config = self.get_yaml();
+        if config["powercheck"].is_badvalue() {
+            return;
+        }
+
+        let wait_time = config["powercheck"]["poll_interval_in_seconds"].as_i64().unwrap() as u64;
+        let state_manager = self.clone();
+        let ipmi_config = self.get_ipmi_config();
+
+        thread::Builder::new()
+            .name("power-check".to_string())
+            .spawn(move || {
+                loop {
+                    thread::sleep(Duration::from_secs(wait_time));
+                    debug!("checking power");
+                    let nodes = state_manager.request_list_nodes();
+                    let running_tasks = state_manager.request_list_running_tasks();
+                    for node in &nodes {
+                        let node_ipmi_config = ipmi_config.for_node(node);
+                        if node.management_ip.is_empty() || !node_ipmi_config.is_defined() {
+                            continue;
+                        }
+                        let power_state = power_status(&node_ipmi_config, &node.management_ip);
+                        state_manager.send_update_node_power_state(node.name.clone(), power_state);
+
+                        let power_in_watts = match power_reading(&node_ipmi_config, &node.management_ip) {
+                            Some(power_in_watts) => power_in_watts,
+                            None => continue,
+                        };
+                        state_manager.send_update_node_power_reading(node.name.clone(), power_in_watts);
+
+                        // attribute the node's draw to its tasks proportional to their cpu shares
+                        let node_tasks: Vec<&Task> = running_tasks.iter()
+                            .filter(|task| !node.slave_id.is_empty() && task.slave_id == node.slave_id)
+                            .collect();
+                        let total_cpu = node_tasks.iter().fold(0.0, |total, task| total + task.cpu);
+                        if total_cpu <= 0.0 {
+                            continue;
+                        }
+                        for task in node_tasks {
+                            let task_power = power_in_watts * task.cpu / total_cpu;
+                            state_manager.send_add_task_energy(task.name.clone(),
+                                                               task_power,
+                                                               task_power * wait_time as f64 / 3600.0);
+                        }
+                    }
+                }
+            })
+            .unwrap();
+    }
+
+    fn load_node_list(&self) {
+        let config = self.get_yaml();
+        let nodes = config["nodes"].as_vec().unwrap();
+        for node in nodes {
+            let name = read_string(node, "name".to_string());
+            let mut groups = vec![];
+            match config["node-groups"].as_vec() {
+                Some(node_groups) => {
+                    for node_group in node_groups {
+                        if read_string_list(node_group, "nodes".to_string()).contains(&name) {
+                            groups.push(read_string(node_group, "name".to_string()));
+                        }
+                    }
+                }
+                None => {}
+            }
+
+            self.send_add_node(read_string(node, "name".to_string()),
+                               read_string_replace_variable(node, "ip".to_string(), &self),
+                               read_string_replace_variable(node, "external_ip".to_string(), &self),
+                               read_string(node, "management_ip".to_string()),
+                               read_string(node, "power_backend".to_string()),
+                               read_string(node, "power_credentials".to_string()),
+                               read_int(node, "port".to_string(), 0),
+                               read_string(node, "type".to_string()),
+                               read_float(node, "cost".to_string(), 1.0),
+                               read_int(node, "job_slots".to_string(), 0),
+                               read_float(node, "cpu".to_string(), 0.0),
+                               read_float(node, "memory".to_string(), 0.0),
+                               groups,
+                               read_string(node, "zone".to_string()),
+                               read_string_map(node, "labels".to_string()))
+        }
+    }
+
+    fn ping(sender: Sender<StateResponseMsg>) {
+        debug!("got ping");
+        let msg = StateResponseMsg::Pong;
+        sender.send(msg).unwrap();
+    }
 
+    fn update_task_state(sender: Sender<StateResponseMsg>, state: &State, task_name: String, task_state: TaskState) {
+        let previous = state.task_list.get_task(task_name.clone()).ok();
+        state.task_list.set_task_state(task_name.to_string(), task_state.clone());
+        // mesos repeats updates, e.g. on reconcile, the exported events only carry actual transitions
+        match previous {
+            Some(ref task) if task.state != task_state => {
+                state.event_list.add_event(EventType::TaskStateChanged, task_name.clone(), format!("{:?}", task_state))
+            }
+            _ => {}
+        }
 
-impl StateManager {
-    fn read_config_file(config_file: String) -> Yaml {
-        let mut file = match File::open(config_file) {
-            Ok(file) => file,
-            Err(err) => panic!(err.to_string()),
+        match task_state {
+            TaskState::Running => {
+                state.task_list.reset_network_retries(task_name.clone());
+                let result = state.task_list.get_task(task_name.clone());
+                match result {
+                    Ok(task) => {
+                        let node_name = state.node_list.get_node_name_by_slave_id(task.slave_id.clone());
+                        state.revision_list.add_revision(&task, node_name.clone());
+                        let previous = state.volume_list.bind(&task, node_name.clone());
+                        StateManager::journal_volume(state, &task.name);
+                        if let Some(previous) = previous {
+                            state.event_list.add_event(EventType::VolumeMigration,
+                                                       task.name.clone(),
+                                                       format!("running on {}, {} was on {} before",
+                                                               node_name,
+                                                               previous.host_paths.join(", "),
+                                                               previous.node_name));
+                        }
+                        if task.standby {
+                            info!("standby ready task={} group={}", task.name, task.standby_group);
+                        } else {
+                            state.update_coalescer.submit(PendingUpdate::Register(task));
+                        }
+                    }
+                    Err(error_msg) => {
+                        error!("can't retrieve task error={:?} task={}",
+                               error_msg,
+                               task_name.clone())
+                    }
+                }
+
+            }
+            _ => {
+                match previous {
+                    Some(ref task) if task.state == TaskState::Running && !task.standby => {
+                        state.update_coalescer.submit(PendingUpdate::Deregister(task.clone()))
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let msg = StateResponseMsg::UpdateTaskState;
+        sender.send(msg).unwrap();
+    }
+
+    fn update_task_node_name(sender: Sender<StateResponseMsg>, state: &State, task_name: String, node_name: String) {
+        state.task_list.set_task_node_name(task_name.to_string(), node_name);
+
+        let msg = StateResponseMsg::UpdateTaskNodeName;
+        sender.send(msg).unwrap();
+    }
+
+    fn update_task_info(sender: Sender<StateResponseMsg>,
+                        state: &State,
+                        task_name: String,
+                        id: String,
+                        ip: String,
+                        slave_id: String) {
+        state.task_list.set_task_info(task_name.to_string(), id, ip, slave_id);
+
+        let msg = StateResponseMsg::UpdateTaskInfo;
+        sender.send(msg).unwrap();
+    }
+
+    fn update_task_last_update(sender: Sender<StateResponseMsg>, state: &State, task_name: String) {
+        state.task_list.update_task_last_update(task_name.to_string());
+
+        let msg = StateResponseMsg::UpdateTaskLastUpdate;
+        sender.send(msg).unwrap();
+    }
+
+    fn start_task(sender: Sender<StateResponseMsg>, state: &State, task: &Task) {
+        let result = StateManager::check_capacity(state, task);
+        match result {
+            Ok(_) => {
+                info!("start task task={}", task.name);
+                state.task_list.add_new_task(&task);
+            }
+            Err(ref reason) => warn!("not starting task task={} reason={}", task.name, reason),
+        }
+        let msg = StateResponseMsg::StartTask { result: result };
+        sender.send(msg).unwrap();
+    }
+
+    // the node's ip stands in until a status with the inspect data comes along
+    fn adopt_task(sender: Sender<StateResponseMsg>, state: &State, task: Task) {
+        if state.task_list.get_task(task.name.clone()).is_err() {
+            let mut task = task;
+            let node_name = state.node_list.get_node_name_by_slave_id(task.slave_id.clone());
+            if let Ok(node) = state.node_list.get_node(node_name) {
+                task.ip = node.ip.clone();
+            }
+            info!("adopt task task={} slave_id={} ip={}", task.name, task.slave_id, task.ip);
+            state.task_list.add_new_task(&task);
+        }
+        let msg = StateResponseMsg::AdoptTask;
+        sender.send(msg).unwrap();
+    }
+
+    // a task pinned to a node has to fit next to what is already there, one placed by type or function
+    // only has to fit on one of the candidates once they're empty, otherwise it would wait forever
+    fn check_capacity(state: &State, task: &Task) -> Result<(), String> {
+        let pinned = !task.node_name.is_empty() && task.node_type.is_empty() && task.node_function.is_empty();
+        if pinned {
+            let node = match state.node_list.get_node(task.node_name.clone()) {
+                Ok(node) => node,
+                Err(_) => return Ok(()),
+            };
+            let on_node: Vec<Task> = state.task_list
+                .get_tasks_on_node(node.name.clone(), node.slave_id.clone())
+                .into_iter()
+                .filter(|placed| placed.name != task.name)
+                .collect();
+            let (cpu, memory) = allocated_resources(&node, &on_node);
+            if node.total_cpu > 0.0 && cpu + task.cpu > node.total_cpu {
+                return Err(format!("would overcommit cpu on {}: {} allocated, {} asked for, {} total",
+                                   node.name,
+                                   cpu,
+                                   task.cpu,
+                                   node.total_cpu));
+            }
+            if node.total_memory > 0.0 && memory + task.memory > node.total_memory {
+                return Err(format!("would overcommit memory on {}: {} allocated, {} asked for, {} total",
+                                   node.name,
+                                   memory,
+                                   task.memory,
+                                   node.total_memory));
+            }
+            return Ok(());
+        }
+
+        let candidates: Vec<Node> = state.node_list
+            .get_nodes()
+            .into_iter()
+            .filter(|node| task.node_type.is_empty() || node.node_type == task.node_type)
+            .filter(|node| task.node_function.is_empty() || node.node_function == task.node_function)
+            .filter(|node| node.total_cpu > 0.0 && node.total_memory > 0.0)
+            .collect();
+        if !candidates.is_empty() &&
+           !candidates.iter().any(|node| task.cpu <= node.total_cpu && task.memory <= node.total_memory) {
+            return Err(format!("no node is big enough for cpu {} and memory {}", task.cpu, task.memory));
+        }
+        Ok(())
+    }
+
+    fn restart_task(sender: Sender<StateResponseMsg>, state: &State, task_name: String, reset_backoff: bool) {
+        info!("restart task task={} reset_backoff={}", task_name, reset_backoff);
+        StateManager::fail_over(state, &task_name);
+        state.task_list.update_task_last_update(task_name.clone());
+        if reset_backoff {
+            state.task_list.set_restart_count(task_name.clone(), 0);
+        }
+        state.task_list.set_task_state(task_name.clone(), TaskState::Restart);
+        let msg = StateResponseMsg::RestartTask;
+        sender.send(msg).unwrap();
+    }
+
+    // asked for, not a crash, the backoff starts over
+    fn restart_tasks(sender: Sender<StateResponseMsg>, state: &State, task_names: Vec<String>) {
+        info!("restart tasks tasks={}", task_names.join(", "));
+        for task_name in task_names {
+            StateManager::fail_over(state, &task_name);
+            state.task_list.update_task_last_update(task_name.clone());
+            state.task_list.set_restart_count(task_name.clone(), 0);
+            state.task_list.set_task_state(task_name, TaskState::Restart);
+        }
+        let msg = StateResponseMsg::RestartTasks;
+        sender.send(msg).unwrap();
+    }
+
+    // deleting a service by its name takes its standbys along, a single member of the group fails over
+    fn delete_task(sender: Sender<StateResponseMsg>, state: &State, task_name: String) {
+        let result = match state.task_list.get_task(task_name.clone()) {
+            Ok(ref task) if task.state == TaskState::Deleted => Err(format!("{} is already deleted", task_name)),
+            Ok(task) => {
+                info!("delete task task={}", task_name);
+                let group = state.task_list.get_standby_group(task_name.clone());
+                if group.is_empty() {
+                    StateManager::fail_over(state, &task_name);
+                }
+                let mut deleted = vec![];
+                for task in Some(task).into_iter().chain(group.into_iter().filter(|task| task.state != TaskState::Deleted)) {
+                    state.task_list.set_task_deleted(task.name.clone());
+                    if state.volume_list.remove(&task.name).is_some() {
+                        StateManager::journal_volume(state, &task.name);
+                    }
+                    state.event_list.add_event(EventType::TaskStateChanged, task.name.clone(), format!("{:?}", TaskState::Deleted));
+                    deleted.push(task.name.clone());
+                    if task.state == TaskState::Running && !task.standby {
+                        state.update_coalescer.submit(PendingUpdate::Deregister(task));
+                    }
+                }
+                Ok(deleted)
+            }
+            Err(_) => Err(format!("{} not found", task_name)),
         };
+        let msg = StateResponseMsg::DeleteTask { result: result };
+        sender.send(msg).unwrap();
+    }
 
-        let mut content = String::new();
-        file.read_to_string(&mut content).unwrap();
-        let config = YamlLoader::load_from_str(&content).unwrap();
-        // Multi document support, doc is a yaml::Yaml
-        config[0].clone()
+    fn restore_task(sender: Sender<StateResponseMsg>, state: &State, task_name: String) {
+        let result = match state.task_list.get_task(task_name.clone()) {
+            Ok(ref task) if task.state == TaskState::Deleted => {
+                info!("restore task task={}", task_name);
+                state.task_list.set_task_restored(task_name.clone());
+                state.event_list.add_event(EventType::TaskRestored, task_name.clone(), "restored after delete".to_string());
+                Ok(())
+            }
+            Ok(task) => Err(format!("{} isn't deleted, it is {:?}", task_name, task.state)),
+            Err(_) => Err(format!("{} not found, deleted longer than the restore window ago", task_name)),
+        };
+        let msg = StateResponseMsg::RestoreTask { result: result };
+        sender.send(msg).unwrap();
     }
 
-    fn start_serving(&self, rx: Receiver<StateRequestMsg>) {
-        let master_ip = self.get_master_ip();
-        let my_name = self.get_my_name();
-        thread::Builder::new()
-            .name("state-serve".to_string())
-            .spawn(move || {
-                let mut state = State {
-                    initialized: false,
-                    master_ip: master_ip,
-                    my_name: my_name,
-                    task_list: TaskList::new(),
-                    node_list: NodeList::new(),
+    fn update_delegation(sender: Sender<StateResponseMsg>,
+                         state: &State,
+                         task_name: String,
+                         expected: String,
+                         delegated_to: String,
+                         remote_state: String) {
+        let applied = state.task_list.set_task_delegation(task_name.clone(), &expected, delegated_to.clone(), remote_state);
+        if applied && expected != delegated_to {
+            let message = match delegated_to.is_empty() {
+                true => format!("taken back from {}", expected),
+                false => format!("delegated to {}", delegated_to),
+            };
+            state.event_list.add_event(EventType::TaskDelegated, task_name, message);
+        }
+        let msg = StateResponseMsg::UpdateDelegation { applied: applied };
+        sender.send(msg).unwrap();
+    }
+
+    fn override_task(sender: Sender<StateResponseMsg>,
+                     state: &mut State,
+                     task_name: String,
+                     task_override: TaskOverride,
+                     until: i64) {
+        let result = match state.task_list.get_task(task_name.clone()) {
+            Ok(task) => {
+                let node_name = task_override.node_name.clone().unwrap_or(task.node_name.clone());
+                let slave_id = match task_override.node_name {
+                    Some(ref node_name) => {
+                        match state.node_list.get_node(node_name.clone()) {
+                            Ok(node) => node.slave_id,
+                            Err(_) => "".to_string(),
+                        }
+                    }
+                    None => task.slave_id.clone(),
                 };
-                state.initialized = true;
+                let ip = task_override.ip.clone().unwrap_or(task.ip.clone());
+
+                state.task_list.set_task_state(task_name.clone(), task_override.state.clone());
+                state.task_list.set_task_node_name(task_name.clone(), node_name.clone());
+                state.task_list.set_task_info(task_name.clone(), task.id.clone(), ip.clone(), slave_id);
+                state.overrides.insert(task_name.clone(), until);
+                state.event_list.add_event(EventType::TaskStateOverridden,
+                                           task_name.clone(),
+                                           format!("{:?} on {} at {} -> {:?} on {} at {}: {}",
+                                                   task.state,
+                                                   task.node_name,
+                                                   task.ip,
+                                                   task_override.state,
+                                                   node_name,
+                                                   ip,
+                                                   task_override.reason));
+                Ok(state.task_list.get_task(task_name).unwrap())
+            }
+            Err(error_msg) => Err(error_msg.to_string()),
+        };
+        let msg = StateResponseMsg::OverrideTask { result: result };
+        sender.send(msg).unwrap();
+    }
+
+    // expired overrides are dropped on the way
+    fn get_overridden_tasks(sender: Sender<StateResponseMsg>, state: &mut State) {
+        let now = UTC::now().timestamp();
+        state.overrides = state.overrides.clone().into_iter().filter(|&(_, until)| until > now).collect();
+
+        let msg = StateResponseMsg::GetOverriddenTasks { task_names: state.overrides.keys().cloned().collect() };
+        sender.send(msg).unwrap();
+    }
+
+    // the event goes out once per name and controller, announcements keep coming every sync
+    fn report_task_conflict(sender: Sender<StateResponseMsg>,
+                            state: &State,
+                            task_name: String,
+                            controller: String,
+                            existing_controller: String,
+                            outcome: String) {
+        let message = format!("announced by {}, already held by {}, {}", controller, existing_controller, outcome);
+        if state.task_conflicts.add_conflict(task_name.clone(), controller, existing_controller, outcome) {
+            state.event_list.add_event(EventType::TaskConflict, task_name, message);
+        }
+        let msg = StateResponseMsg::ReportTaskConflict;
+        sender.send(msg).unwrap();
+    }
+
+    fn get_task_conflicts(sender: Sender<StateResponseMsg>, state: &State) {
+        let msg = StateResponseMsg::GetTaskConflicts { conflicts: state.task_conflicts.get_conflicts() };
+        sender.send(msg).unwrap();
+    }
+
+    fn get_volume_holders(sender: Sender<StateResponseMsg>, state: &State) {
+        let msg = StateResponseMsg::GetVolumeHolders { holders: state.volume_list.get_holders() };
+        sender.send(msg).unwrap();
+    }
+
+    fn release_volumes(sender: Sender<StateResponseMsg>, state: &State, migrate_after: i64) {
+        let is_available = |node_name: &String| {
+            match state.node_list.get_node(node_name.clone()) {
+                Ok(node) => node.active && !node.draining,
+                Err(_) => false,
+            }
+        };
+        let (released, changed) = state.volume_list.release_unavailable(is_available, migrate_after);
+        for binding in changed {
+            StateManager::journal_entry(state, StoreEntry::PutVolume(binding));
+        }
+        for binding in &released {
+            state.event_list.add_event(EventType::VolumeMigration,
+                                       binding.task_name.clone(),
+                                       format!("{} unavailable for more than {}s, {} has to follow the task",
+                                               binding.node_name,
+                                               migrate_after,
+                                               binding.host_paths.join(", ")));
+        }
+
+        let msg = StateResponseMsg::ReleaseVolumes { released: released };
+        sender.send(msg).unwrap();
+    }
+
+    fn get_volumes(sender: Sender<StateResponseMsg>, state: &State) {
+        let msg = StateResponseMsg::GetVolumes { volumes: state.volume_list.get_bindings() };
+        sender.send(msg).unwrap();
+    }
+
+    fn set_task_artifact_urls(sender: Sender<StateResponseMsg>, state: &State, task_name: String, artifact_urls: Vec<String>) {
+        state.task_list.set_task_artifact_urls(task_name, artifact_urls);
+        let msg = StateResponseMsg::SetTaskArtifactUrls;
+        sender.send(msg).unwrap();
+    }
+
+    fn set_task_standby(sender: Sender<StateResponseMsg>, state: &State, task_name: String, standby: bool) {
+        if let Ok(task) = state.task_list.get_task(task_name.clone()) {
+            state.task_list.set_standby(task_name, standby);
+            if task.state == TaskState::Running && task.standby != standby {
+                let mut task = task;
+                task.standby = standby;
+                match standby {
+                    true => state.update_coalescer.submit(PendingUpdate::Deregister(task)),
+                    false => state.update_coalescer.submit(PendingUpdate::Register(task)),
+                }
+            }
+        }
+        let msg = StateResponseMsg::SetTaskStandby;
+        sender.send(msg).unwrap();
+    }
+
+    fn set_task_image_digest(sender: Sender<StateResponseMsg>, state: &State, task_name: String, image: String, digest: String) {
+        state.task_list.set_task_image_digest(task_name, image, digest);
+        let msg = StateResponseMsg::SetTaskImageDigest;
+        sender.send(msg).unwrap();
+    }
+
+    fn fail_task(sender: Sender<StateResponseMsg>, state: &State, task_name: String, reason: String) {
+        warn!("task failed task={} reason={}", task_name, reason);
+        let changed = match state.task_list.get_task(task_name.clone()) {
+            Ok(task) => task.state != TaskState::Failed,
+            Err(_) => false,
+        };
+        StateManager::fail_over(state, &task_name);
+        state.task_list.set_task_failed(task_name.clone(), reason.clone());
+        if changed {
+            state.event_list.add_event(EventType::TaskStateChanged, task_name, format!("{:?} {}", TaskState::Failed, reason));
+        }
+        let msg = StateResponseMsg::FailTask;
+        sender.send(msg).unwrap();
+    }
+
+    fn network_failure(sender: Sender<StateResponseMsg>, state: &State, task_name: String) {
+        let retries = state.task_list.increment_network_retries(task_name.clone());
+
+        let task_state = if retries <= state.network_retry_limit {
+            warn!("network failure task={} retry={} limit={}",
+                  task_name,
+                  retries,
+                  state.network_retry_limit);
+            state.task_list.update_task_last_update(task_name.clone());
+            TaskState::Restart
+        } else {
+            error!("network failure, giving up task={} retries={}",
+                   task_name,
+                   state.network_retry_limit);
+            TaskState::NetworkFailed
+        };
+
+        state.task_list.set_task_state(task_name.clone(), task_state.clone());
+        let msg = StateResponseMsg::NetworkFailure { task_state: task_state };
+        sender.send(msg).unwrap();
+    }
+
+    fn task_exited(sender: Sender<StateResponseMsg>, state: &State, task_name: String, failed: bool) {
+        let decision = match state.task_list.get_task(task_name.clone()) {
+            // the status of the kill that goes with a restart, counting it again would double the backoff
+            Ok(ref task) if task.state == TaskState::Restart => RestartDecision::Restart,
+            Ok(task) => {
+                StateManager::fail_over(state, &task_name);
+                let restarts = state.restart_policy.restarts_in_a_row(&task, UTC::now().timestamp());
+                let decision = state.restart_policy.decide(&task, &state.my_name, failed, restarts);
+                match decision {
+                    RestartDecision::Restart => {
+                        info!("restart task task={} restarts={} failed={}", task_name, restarts + 1, failed);
+                        state.task_list.update_task_last_update(task_name.clone());
+                        state.task_list.set_restart_count(task_name.clone(), restarts + 1);
+                        state.task_list.set_task_state(task_name.clone(), TaskState::Restart);
+                    }
+                    RestartDecision::Quarantine(ref reason) => {
+                        warn!("task quarantined task={} restarts={}", task_name, restarts);
+                        state.task_list.set_task_failed(task_name.clone(), reason.clone());
+                        state.event_list.add_event(EventType::TaskQuarantined, task_name.clone(), reason.clone());
+                    }
+                    RestartDecision::Remove => {}
+                }
+                decision
+            }
+            Err(_) => RestartDecision::Remove,
+        };
+
+        let msg = StateResponseMsg::TaskExited { decision: decision };
+        sender.send(msg).unwrap();
+    }
+
+    // a running standby of the group takes over right away, the task it replaces comes back as a standby
+    fn fail_over(state: &State, task_name: &String) {
+        let task = match state.task_list.get_task(task_name.clone()) {
+            Ok(task) => task,
+            Err(_) => return,
+        };
+        if task.standby || task.standby_group.is_empty() || task.controller != state.my_name {
+            return;
+        }
+
+        let standby = state.task_list
+            .get_tasks()
+            .into_iter()
+            .filter(|candidate| candidate.standby && candidate.standby_group == task.standby_group)
+            .find(|candidate| candidate.state == TaskState::Running && candidate.controller == state.my_name);
+        match standby {
+            Some(mut standby) => {
+                info!("promote standby task={} replaces={}", standby.name, task.name);
+                state.task_list.set_standby(standby.name.clone(), false);
+                state.task_list.set_standby(task.name.clone(), true);
+                state.event_list.add_event(EventType::StandbyPromoted,
+                                           standby.name.clone(),
+                                           format!("took over from {}", task.name));
+                // as a standby the failed task won't be deregistered once its terminal status arrives
+                if task.state == TaskState::Running {
+                    state.update_coalescer.submit(PendingUpdate::Deregister(task.clone()));
+                }
+                standby.standby = false;
+                state.update_coalescer.submit(PendingUpdate::Register(standby));
+            }
+            None => warn!("no standby ready task={} group={}", task.name, task.standby_group),
+        }
+    }
+
+    fn remove_task_by_name(sender: Sender<StateResponseMsg>, state: &State, task_name: String) {
+        info!("remove task task={}", task_name);
+
+        state.task_list.remove_task_by_name(task_name.to_string());
+        if state.volume_list.remove(&task_name).is_some() {
+            StateManager::journal_volume(state, &task_name);
+        }
+        let msg = StateResponseMsg::RemoveTask;
+        sender.send(msg).unwrap();
+    }
+
+    fn get_snapshot(sender: Sender<StateResponseMsg>, state: &State) {
+        let tasks = state.task_list.get_tasks();
+
+        let mut counters: BTreeMap<String, usize> = BTreeMap::new();
+        for task in &tasks {
+            *counters.entry(format!("tasks.{:?}", task.state).to_lowercase()).or_insert(0) += 1;
+        }
+        counters.insert("tasks".to_string(), tasks.len());
+        counters.insert("events".to_string(), state.event_list.get_events().len());
+
+        let snapshot = StateSnapshot {
+            timestamp: UTC::now().timestamp(),
+            my_name: state.my_name.clone(),
+            cluster_open: state.cluster_open,
+            teardown: state.teardown,
+            pending_tasks: tasks.iter()
+                .filter(|task| task.state == TaskState::Requested)
+                .map(|task| task.name.clone())
+                .collect(),
+            tasks: tasks,
+            nodes: state.node_list.get_nodes(),
+            routes: state.route_list.get_routes(),
+            collaborators: state.readiness_list.get_collaborators(),
+            counters: counters,
+            event_epoch: state.event_list.epoch(),
+            event_sequence: state.event_list.last_sequence(),
+        };
+
+        let msg = StateResponseMsg::GetSnapshot { snapshot: snapshot };
+        sender.send(msg).unwrap();
+    }
+
+    fn get_memory_usage(sender: Sender<StateResponseMsg>, state: &State) {
+        let collections = vec![state.task_list.memory_usage(),
+                               state.node_list.memory_usage(),
+                               state.event_list.memory_usage(),
+                               state.revision_list.memory_usage(),
+                               state.route_list.memory_usage(),
+                               state.route_queue.memory_usage(),
+                               state.update_coalescer.memory_usage(),
+                               state.task_conflicts.memory_usage(),
+                               state.volume_list.memory_usage()];
+
+        let report = MemoryReport {
+            timestamp: UTC::now().timestamp(),
+            approximate_bytes: collections.iter().map(|usage| usage.approximate_bytes).sum(),
+            collections: collections,
+        };
+
+        let msg = StateResponseMsg::GetMemoryUsage { report: report };
+        sender.send(msg).unwrap();
+    }
+
+    fn add_node(sender: Sender<StateResponseMsg>, state: &mut State, node: &Node) {
+        // two entries in the configuration for the same ip, the first one stays
+        match state.node_list.find_duplicate(&node.name, &node.ip, &"".to_string()) {
+            Some(duplicate) => {
+                StateManager::report_node_conflict(state, &node.name, &duplicate.name, "configured twice, ignored".to_string());
+                let msg = StateResponseMsg::AddNode;
+                sender.send(msg).unwrap();
+                return;
+            }
+            None => {}
+        }
+
+        // the configuration wins, what was learned at runtime is carried over from before the restart,
+        // and so is what PATCH /node changed since
+        let mut node = node.clone();
+        match state.restored_nodes.iter().find(|restored| restored.name == node.name) {
+            Some(restored) => {
+                if restored.metadata_version > 0 {
+                    node.external_ip = restored.external_ip.clone();
+                    node.management_ip = restored.management_ip.clone();
+                    node.port_id = restored.port_id;
+                    node.zone = restored.zone.clone();
+                    node.labels = restored.labels.clone();
+                    node.metadata_version = restored.metadata_version;
+                }
+                node.node_type = restored.node_type.clone();
+                node.node_function = restored.node_function.clone();
+                node.active = restored.active;
+                node.draining = restored.draining;
+                node.slave_id = restored.slave_id.clone();
+                node.last_seen = restored.last_seen;
+                node.power_state = restored.power_state.clone();
+                node.expected_power_state = restored.expected_power_state.clone();
+                node.expected_power_state_since = restored.expected_power_state_since;
+                node.agent_version = restored.agent_version.clone();
+                node.last_heartbeat = restored.last_heartbeat;
+                node.gpus = restored.gpus.clone();
+                node.annotations = restored.annotations.clone();
+            }
+            None => {}
+        }
+        state.node_list.add_new_node(&node);
+        let msg = StateResponseMsg::AddNode;
+        sender.send(msg).unwrap();
+    }
+
+    fn register_node(sender: Sender<StateResponseMsg>, state: &State, node: Node) {
+        let result = match (state.node_list.get_node(node.name.clone()).is_ok(), state.node_list.find_address_conflict(&node)) {
+            (true, _) => Err(format!("{} is already known", node.name)),
+            (false, Some((duplicate, address))) => Err(format!("{} has {} of {}", node.name, address, duplicate.name)),
+            (false, None) => {
+                state.node_list.add_new_node(&node);
+                state.event_list.add_event(EventType::NodeRegistered,
+                                           node.name.clone(),
+                                           format!("ip {} type {} persisted {}", node.ip, node.node_type, node.registered));
+                if node.registered {
+                    StateManager::journal_entry(state, StoreEntry::PutNode(node.clone()));
+                }
+                Ok(node)
+            }
+        };
+        let msg = StateResponseMsg::RegisterNode { result: result };
+        sender.send(msg).unwrap();
+    }
 
-                loop {
-                    match rx.recv().unwrap() {
-                        StateRequestMsg::Ping { sender } => StateManager::ping(sender),
-                        StateRequestMsg::GetTaskState { sender, task_name } => {
-                            StateManager::get_task_state(sender, &state, task_name)
-                        }
-                        StateRequestMsg::GetTaskIP { sender, task_name } => StateManager::get_task_ip(sender, &state, task_name),
-                        StateRequestMsg::GetTaskNameById { sender, id_prefix } => {
-                            StateManager::get_task_name_by_id(sender, &state, id_prefix)
-                        }
-                        StateRequestMsg::UpdateTaskState { sender, task_name, task_state } => {
-                            StateManager::update_task_state(sender, &state, task_name, task_state)
-                        }
-                        StateRequestMsg::UpdateTaskNodeName { sender, task_name, node_name } => {
-                            StateManager::update_task_node_name(sender, &state, task_name, node_name)
-                        }
-                        StateRequestMsg::UpdateTaskInfo { sender, task_name, id, ip, slave_id } => {
-                            StateManager::update_task_info(sender, &state, task_name, id, ip, slave_id)
-                        }
-                        StateRequestMsg::UpdateTaskLastUpdate { sender, task_name } => {
-                            StateManager::update_task_last_update(sender, &state, task_name)
-                        }
-                        StateRequestMsg::StartTask { sender, task } => StateManager::start_task(sender, &state, &task),
-                        StateRequestMsg::RestartTask { sender, task_name } => StateManager::restart_task(sender, &state, task_name),
-                        StateRequestMsg::RemoveTask { sender, task_name } => {
-                            StateManager::remove_task_by_name(sender, &state, task_name)
-                        }
-                        StateRequestMsg::GetIsRestartableTask { sender, task_name } => {
-                            StateManager::get_is_restartable_task(sender, &state, task_name)
-                        }
-                        StateRequestMsg::GetRequestedTasks { sender } => StateManager::get_requested_tasks(sender, &state),
-                        StateRequestMsg::GetRunningTasks { sender } => StateManager::get_running_tasks(sender, &state),
-                        StateRequestMsg::GetRestartTasks { sender } => StateManager::get_restart_tasks(sender, &state),
-                        StateRequestMsg::AddNode { sender, node } => StateManager::add_node(sender, &state, &node),
-                        StateRequestMsg::GetIsNodeActive { sender, node_name } => {
-                            StateManager::get_is_node_active(sender, &state, node_name)
-                        }
-                        StateRequestMsg::UpdateNode { sender, node_name, node_type, node_function, slave_id } => {
-                            StateManager::update_node(sender,
-                                                      &state,
-                                                      node_name,
-                                                      node_type,
-                                                      node_function,
-                                                      slave_id)
-                        }
-                        StateRequestMsg::SetNodeInactive { sender, node_name } => {
-                            StateManager::set_node_inactive(sender, &state, node_name)
+    // a node with tasks still placed on it stays, they would be left without a node
+    fn unregister_node(sender: Sender<StateResponseMsg>, state: &State, node_name: String) {
+        let result = match state.node_list.get_node(node_name.clone()) {
+            Ok(node) => {
+                let placed = state.task_list
+                    .get_tasks_on_node(node.name.clone(), node.slave_id.clone())
+                    .into_iter()
+                    .filter(|task| match task.state {
+                        TaskState::Finished | TaskState::Failed | TaskState::Deleted | TaskState::NotRunning => false,
+                        _ => true,
+                    })
+                    .count();
+                match placed {
+                    0 => {
+                        state.node_list.remove_node(&node.name);
+                        state.event_list.add_event(EventType::NodeUnregistered,
+                                                   node.name.clone(),
+                                                   format!("ip {} persisted {}", node.ip, node.registered));
+                        if node.registered {
+                            StateManager::journal_entry(state, StoreEntry::RemoveNode(node.name.clone()));
                         }
-                        StateRequestMsg::GetNode { sender, node_name } => StateManager::get_node(sender, &state, node_name),
-                        StateRequestMsg::GetNodes { sender } => StateManager::get_nodes(sender, &state),
+                        Ok(node)
                     }
+                    _ => Err(format!("{} still has {} tasks", node.name, placed)),
                 }
-            })
-            .unwrap();
+            }
+            Err(_) => Err(format!("no node {}", node_name)),
+        };
+        let msg = StateResponseMsg::UnregisterNode { result: result };
+        sender.send(msg).unwrap();
     }
 
-    fn start_syncing(&self) {
-        let config = self.get_yaml();
-        let wait_time = config["statesync"]["poll_interval_in_seconds"].as_i64().unwrap() as u64;
-        let state_manager = self.clone();
-        let master_ip = self.master_ip.clone();
-        let my_name = self.get_my_name();
+    fn get_is_node_active(sender: Sender<StateResponseMsg>, state: &State, node_name: String) {
+        let is_active = state.node_list.is_node_active(node_name.clone());
+        let msg = StateResponseMsg::GetIsNodeActive { is_active: is_active };
+        sender.send(msg).unwrap();
+    }
 
-        thread::Builder::new()
-            .name("state-sync".to_string())
-            .spawn(move || {
-                loop {
-                    thread::sleep(Duration::from_secs(wait_time));
-                    println!("syncing ....");
-                    let running_tasks = state_manager.request_list_running_tasks();
-                    for task in &running_tasks {
-                        register_running_task(&master_ip, &task);
-                        if task.controller == my_name {
-                            state_manager.send_announce_task(&task);
-                        }
+    fn update_node(sender: Sender<StateResponseMsg>,
+                   state: &mut State,
+                   node_name: String,
+                   host: String,
+                   node_type: String,
+                   node_function: String,
+                   slave_id: String) {
+        // a node registering under another name than it is configured with, or two registrations
+        // for one slave, get merged according to the policy
+        let mut node_name = node_name;
+        match state.node_list.find_duplicate(&node_name, &host, &slave_id) {
+            Some(duplicate) => {
+                let policy = state.node_merge_policy.clone();
+                match policy.as_ref() {
+                    "registration_wins" => {
+                        state.node_list.rename_node(duplicate.name.clone(), node_name.clone());
+                        StateManager::report_node_conflict(state, &node_name, &duplicate.name, "registration kept".to_string());
+                    }
+                    "reject" => {
+                        StateManager::report_node_conflict(state, &node_name, &duplicate.name, "registration rejected".to_string());
+                        let msg = StateResponseMsg::UpdateNode;
+                        sender.send(msg).unwrap();
+                        return;
+                    }
+                    _ => {
+                        StateManager::report_node_conflict(state, &node_name, &duplicate.name, "configuration kept".to_string());
+                        node_name = duplicate.name.clone();
                     }
                 }
-            })
-            .unwrap();
+            }
+            None => {}
+        }
+
+        state.node_list.update_node(node_name.clone(),
+                                    node_type.clone(),
+                                    node_function.clone(),
+                                    slave_id.clone());
+        state.event_list.add_event(EventType::NodeActive, node_name.clone(), format!("slave {}", slave_id));
+        let msg = StateResponseMsg::UpdateNode;
+        sender.send(msg).unwrap();
     }
 
-    fn start_cleaning(&self) {
-        let config = self.get_yaml();
-        let wait_time = config["stateclean"]["poll_interval_in_seconds"].as_i64().unwrap() as u64;
-        let timeout = config["stateclean"]["timeout_in_seconds"].as_i64().unwrap() as i64;
-        let restart_delay = config["stateclean"]["restart_delay_in_seconds"].as_i64().unwrap() as i64;
-        let state_manager = self.clone();
-        let my_name = self.get_my_name();
+    // reported once per pair of nodes, registrations keep coming with every offer
+    fn report_node_conflict(state: &mut State, node_name: &String, duplicate_name: &String, outcome: String) {
+        if state.node_conflicts.insert(format!("{}/{}", node_name, duplicate_name)) {
+            state.event_list.add_event(EventType::NodeConflict,
+                                       node_name.clone(),
+                                       format!("duplicate of {}, {}", duplicate_name, outcome));
+        }
+    }
 
-        thread::Builder::new()
-            .name("state-clean".to_string())
-            .spawn(move || {
-                loop {
-                    thread::sleep(Duration::from_secs(wait_time));
-                    println!("cleaning ...");
-                    let running_tasks = state_manager.request_list_running_tasks();
-                    for task in &running_tasks {
-                        if task.controller == my_name {
-                            continue;
-                        };
-                        let now = UTC::now().timestamp();
-                        if (task.last_update + timeout) < now {
-                            state_manager.send_remove_task_by_name(task.name.clone());
-                            delete_route(&state_manager.get_network_agent_type(),
-                                         &state_manager.get_network_agent_connection(),
-                                         &task.ip);
-                        }
-                    }
+    fn set_node_draining(sender: Sender<StateResponseMsg>, state: &State, node_name: String, draining: bool) {
+        state.node_list.set_node_draining(node_name.clone(), draining);
+        state.event_list.add_event(EventType::NodeDraining, node_name, format!("{}", draining));
+        let msg = StateResponseMsg::SetNodeDraining;
+        sender.send(msg).unwrap();
+    }
 
-                    let restart_tasks = state_manager.request_list_restart_tasks();
-                    for task in &restart_tasks {
-                        if task.controller != my_name {
-                            continue;
-                        };
-                        let now = UTC::now().timestamp();
-                        if (task.last_update + restart_delay) < now {
-                            state_manager.send_update_task_state(task.name.clone(), TaskState::Requested);
-                        }
-                    }
+    fn begin_drain(sender: Sender<StateResponseMsg>, state: &State, node_names: Vec<String>) {
+        let nodes = state.node_list.get_nodes();
+        let result = state.guardrails.check_draining(&nodes, &node_names);
+        if result.is_ok() {
+            for node_name in node_names {
+                state.node_list.set_node_draining(node_name.clone(), true);
+                state.event_list.add_event(EventType::NodeDraining, node_name, "true".to_string());
+            }
+        }
 
-                    let nodes = state_manager.request_list_nodes();
-                    for node in &nodes {
-                        if node.active == false {
-                            continue;
-                        }
-                        let now = UTC::now().timestamp();
-                        if (node.last_seen + timeout) < now {
-                            state_manager.send_set_node_inactive(node.name.clone());
-                        }
+        let msg = StateResponseMsg::BeginDrain { result: result };
+        sender.send(msg).unwrap();
+    }
 
-                    }
-                }
-            })
-            .unwrap();
+    fn set_node_inactive(sender: Sender<StateResponseMsg>, state: &State, node_name: String) {
+        state.node_list.set_node_inactive(node_name.clone());
+        state.event_list.add_event(EventType::NodeInactive, node_name.clone(), "not seen".to_string());
+        let msg = StateResponseMsg::SetNodeInactive;
+        sender.send(msg).unwrap();
     }
 
-    fn load_node_list(&self) {
-        let config = self.get_yaml();
-        let nodes = config["nodes"].as_vec().unwrap();
-        for node in nodes {
-            self.send_add_node(read_string(node, "name".to_string()),
-                               read_string_replace_variable(node, "ip".to_string(), &self),
-                               read_string_replace_variable(node, "external_ip".to_string(), &self),
-                               read_string(node, "management_ip".to_string()),
-                               read_int(node, "port".to_string(), 0),
-                               read_string(node, "type".to_string()))
+    fn update_node_power_state(sender: Sender<StateResponseMsg>,
+                               state: &State,
+                               node_name: String,
+                               power_state: PowerState,
+                               transition_timeout: i64) {
+        match state.node_list.set_node_power_state(node_name.clone(), power_state.clone(), transition_timeout) {
+            Ok((previous, new_mismatch)) => {
+                if previous != power_state {
+                    state.event_list.add_event(EventType::PowerStateChanged,
+                                               node_name.clone(),
+                                               format!("{:?} -> {:?}", previous, power_state));
+                }
+                if new_mismatch {
+                    let node = state.node_list.get_node(node_name.clone()).unwrap();
+                    state.event_list.add_event(EventType::PowerStateMismatch,
+                                               node_name.clone(),
+                                               format!("expected {:?}, observed {:?}",
+                                                       node.expected_power_state,
+                                                       power_state));
+                }
+            }
+            Err(error_msg) => error!("can't update power state error={:?} node={}", error_msg, node_name),
         }
+
+        let msg = StateResponseMsg::UpdateNodePowerState;
+        sender.send(msg).unwrap();
     }
 
-    fn ping(sender: Sender<StateResponseMsg>) {
-        println!("got ping");
-        let msg = StateResponseMsg::Pong;
+    fn set_route(sender: Sender<StateResponseMsg>, state: &State, route: Route) {
+        let previous = state.route_list.set_route(&route);
+        let msg = StateResponseMsg::SetRoute { previous: previous };
         sender.send(msg).unwrap();
     }
 
-    fn get_task_state(sender: Sender<StateResponseMsg>, state: &State, task_name: String) {
-        let task_state = state.task_list.get_task_state(task_name);
-        let msg = StateResponseMsg::TaskState { task_state: task_state };
+    fn remove_route(sender: Sender<StateResponseMsg>, state: &State, task_name: String) {
+        let previous = state.route_list.remove_route(task_name);
+        let msg = StateResponseMsg::RemoveRoute { previous: previous };
         sender.send(msg).unwrap();
     }
 
-    fn get_task_ip(sender: Sender<StateResponseMsg>, state: &State, task_name: String) {
-        let result = state.task_list.get_task(task_name.clone());
-        let ip = match result {
-            Ok(task) => task.ip.clone(),
-            Err(_) => "".to_string(),
+    fn queue_route_operation(sender: Sender<StateResponseMsg>, state: &State, operation: RouteOperation) {
+        state.route_queue.enqueue(&operation);
+        let msg = StateResponseMsg::QueueRouteOperation;
+        sender.send(msg).unwrap();
+    }
+
+    fn finish_route_operation(sender: Sender<StateResponseMsg>,
+                              state: &State,
+                              operation: RouteOperation,
+                              success: bool,
+                              initial_backoff: i64,
+                              max_backoff: i64) {
+        state.route_queue.finish(&operation, success, UTC::now().timestamp(), initial_backoff, max_backoff);
+        let msg = StateResponseMsg::FinishRouteOperation;
+        sender.send(msg).unwrap();
+    }
+
+    fn get_due_route_operations(sender: Sender<StateResponseMsg>, state: &State) {
+        let operations = state.route_queue.get_due_operations(UTC::now().timestamp());
+        let msg = StateResponseMsg::GetRouteOperations { operations: operations };
+        sender.send(msg).unwrap();
+    }
+
+    fn get_route_operations(sender: Sender<StateResponseMsg>, state: &State) {
+        let msg = StateResponseMsg::GetRouteOperations { operations: state.route_queue.get_operations() };
+        sender.send(msg).unwrap();
+    }
+
+    // goes through the journal channel so it lands after every change it includes
+    fn write_store_snapshot(sender: Sender<StateResponseMsg>, state: &State) {
+        StateManager::snapshot_store(state);
+        let msg = StateResponseMsg::WriteStoreSnapshot;
+        sender.send(msg).unwrap();
+    }
+
+    fn snapshot_store(state: &State) {
+        match state.store_journal {
+            Some(ref journal) => {
+                let stored_state = StoredState {
+                    timestamp: UTC::now().timestamp(),
+                    tasks: state.task_list.get_tasks(),
+                    nodes: state.node_list.get_nodes(),
+                    schedules: state.schedules.values().cloned().collect(),
+                    volumes: state.volume_list.get_bindings(),
+                };
+                journal.send(StoreEntry::Snapshot(stored_state)).unwrap();
+            }
+            None => {}
+        }
+    }
+
+    fn journal_volume(state: &State, task_name: &String) {
+        let entry = match state.volume_list.get_binding(task_name) {
+            Some(binding) => StoreEntry::PutVolume(binding),
+            None => StoreEntry::RemoveVolume(task_name.clone()),
         };
-        let msg = StateResponseMsg::TaskIP { task_ip: ip };
+        StateManager::journal_entry(state, entry);
+    }
+
+    // numbered in the same sequence as the task changes, so the order in the log is the order they happened in
+    fn journal_entry(state: &State, entry: StoreEntry) {
+        state.task_list.sequence().next();
+        match state.store_journal {
+            Some(ref journal) => {
+                let _ = journal.send(entry);
+            }
+            None => {}
+        }
+    }
+
+    fn flush_store(sender: Sender<StateResponseMsg>, state: &mut State) {
+        match state.store_journal.take() {
+            Some(journal) => {
+                let stored_state = StoredState {
+                    timestamp: UTC::now().timestamp(),
+                    tasks: state.task_list.get_tasks(),
+                    nodes: state.node_list.get_nodes(),
+                    schedules: state.schedules.values().cloned().collect(),
+                    volumes: state.volume_list.get_bindings(),
+                };
+                journal.send(StoreEntry::Snapshot(stored_state)).unwrap();
+                state.task_list.set_journal(None);
+            }
+            None => {}
+        }
+        match state.store_writer.take() {
+            Some(writer) => {
+                let _ = writer.join();
+            }
+            None => {}
+        }
+        let msg = StateResponseMsg::FlushStore;
         sender.send(msg).unwrap();
     }
 
-    fn get_task_name_by_id(sender: Sender<StateResponseMsg>, state: &State, id_prefix: String) {
-        let task_name = state.task_list.get_task_name_by_id(id_prefix);
-        let msg = StateResponseMsg::TaskName { task_name: task_name };
+    fn add_event(sender: Sender<StateResponseMsg>, state: &State, event_type: EventType, subject: String, message: String) {
+        state.event_list.add_event(event_type, subject, message);
+        let msg = StateResponseMsg::AddEvent;
         sender.send(msg).unwrap();
     }
 
-    fn update_task_state(sender: Sender<StateResponseMsg>, state: &State, task_name: String, task_state: TaskState) {
-        state.task_list.set_task_state(task_name.to_string(), task_state.clone());
+    fn get_routes(sender: Sender<StateResponseMsg>, state: &State) {
+        let result = state.route_list.get_routes();
+        let msg = StateResponseMsg::GetRoutes { routes: result };
+        sender.send(msg).unwrap();
+    }
 
-        match task_state {
-            TaskState::Running => {
-                let result = state.task_list.get_task(task_name.clone());
-                match result {
-                    Ok(task) => register_running_task(&state.master_ip.clone(), &task),
-                    Err(error_msg) => {
-                        println!("error [{:?}] while retrieving {}",
-                                 error_msg,
-                                 task_name.clone())
-                    }
+    fn update_node_power_reading(sender: Sender<StateResponseMsg>, state: &State, node_name: String, power_in_watts: f64) {
+        state.node_list.set_node_power_reading(node_name, power_in_watts);
+        let msg = StateResponseMsg::UpdateNodePowerReading;
+        sender.send(msg).unwrap();
+    }
+
+    fn update_node_metadata(sender: Sender<StateResponseMsg>, state: &State, node_name: String, update: NodeUpdate) {
+        let result = match state.node_list.update_node_metadata(node_name.clone(), &update) {
+            Ok(changes) => {
+                let node = state.node_list.get_node(node_name.clone()).unwrap();
+                if !changes.is_empty() {
+                    StateManager::journal_entry(state, StoreEntry::PutNode(node.clone()));
+                    state.event_list.add_event(EventType::NodeMetadataChanged,
+                                               node_name,
+                                               format!("version {}: {}", node.metadata_version, changes.join(", ")));
                 }
+                Ok(node)
+            }
+            Err(error_msg) => Err(error_msg.to_string()),
+        };
+        let msg = StateResponseMsg::UpdateNodeMetadata { result: result };
+        sender.send(msg).unwrap();
+    }
 
+    fn update_task_annotations(sender: Sender<StateResponseMsg>,
+                               state: &State,
+                               task_name: String,
+                               update: BTreeMap<String, Option<String>>) {
+        let result = match state.task_list.update_annotations(task_name.clone(), &update) {
+            Ok((task, changes)) => {
+                if !changes.is_empty() {
+                    state.event_list.add_event(EventType::TaskAnnotationsChanged, task_name, changes.join(", "));
+                }
+                Ok(task)
             }
-            _ => {}
-        }
+            Err(error_msg) => Err(error_msg.to_string()),
+        };
+        let msg = StateResponseMsg::UpdateTaskAnnotations { result: result };
+        sender.send(msg).unwrap();
+    }
 
-        let msg = StateResponseMsg::UpdateTaskState;
+    fn node_heartbeat(sender: Sender<StateResponseMsg>,
+                      state: &State,
+                      node_name: String,
+                      agent_version: String,
+                      gpus: Option<Vec<Gpu>>) {
+        state.node_list.set_node_heartbeat(node_name, agent_version, gpus);
+        let msg = StateResponseMsg::NodeHeartbeat;
         sender.send(msg).unwrap();
     }
 
-    fn update_task_node_name(sender: Sender<StateResponseMsg>, state: &State, task_name: String, node_name: String) {
-        state.task_list.set_task_node_name(task_name.to_string(), node_name);
+    fn add_task_energy(sender: Sender<StateResponseMsg>,
+                       state: &State,
+                       task_name: String,
+                       power_in_watts: f64,
+                       energy_in_wh: f64) {
+        state.task_list.add_task_energy(task_name, power_in_watts, energy_in_wh);
+        let msg = StateResponseMsg::AddTaskEnergy;
+        sender.send(msg).unwrap();
+    }
 
-        let msg = StateResponseMsg::UpdateTaskNodeName;
+    fn set_node_expected_power_state(sender: Sender<StateResponseMsg>,
+                                     state: &State,
+                                     node_name: String,
+                                     power_state: PowerState) {
+        state.node_list.set_node_expected_power_state(node_name.clone(), power_state);
+        let msg = StateResponseMsg::SetNodeExpectedPowerState;
         sender.send(msg).unwrap();
     }
 
-    fn update_task_info(sender: Sender<StateResponseMsg>,
-                        state: &State,
-                        task_name: String,
-                        id: String,
-                        ip: String,
-                        slave_id: String) {
-        state.task_list.set_task_info(task_name.to_string(), id, ip, slave_id);
+    fn get_events(sender: Sender<StateResponseMsg>, state: &State) {
+        let result: Vec<Event> = state.event_list.get_events();
+        let msg = StateResponseMsg::GetEvents { events: result };
+        sender.send(msg).unwrap();
+    }
 
-        let msg = StateResponseMsg::UpdateTaskInfo;
+    fn get_events_since(sender: Sender<StateResponseMsg>, state: &State, since: u64, epoch: Option<i64>) {
+        let msg = StateResponseMsg::GetEventsSince { result: state.event_list.get_events_since(since, epoch) };
         sender.send(msg).unwrap();
     }
 
-    fn update_task_last_update(sender: Sender<StateResponseMsg>, state: &State, task_name: String) {
-        state.task_list.update_task_last_update(task_name.to_string());
+    fn get_task_revisions(sender: Sender<StateResponseMsg>, state: &State, task_name: String) {
+        let result: Vec<Revision> = state.revision_list.get_revisions(task_name);
+        let msg = StateResponseMsg::GetTaskRevisions { revisions: result };
+        sender.send(msg).unwrap();
+    }
 
-        let msg = StateResponseMsg::UpdateTaskLastUpdate;
+    fn set_collaborators(sender: Sender<StateResponseMsg>, state: &State, collaborators: Vec<CollaboratorStatus>) {
+        state.readiness_list.set_collaborators(&collaborators);
+        let msg = StateResponseMsg::SetCollaborators;
         sender.send(msg).unwrap();
     }
 
-    fn start_task(sender: Sender<StateResponseMsg>, state: &State, task: &Task) {
-        println!("start task {}", task.name);
+    fn update_collaborator(sender: Sender<StateResponseMsg>, state: &State, name: String, reachable: bool, error: String) {
+        state.readiness_list.update_collaborator(name, reachable, error);
+        let msg = StateResponseMsg::UpdateCollaborator;
+        sender.send(msg).unwrap();
+    }
 
-        state.task_list.add_new_task(&task);
-        let msg = StateResponseMsg::StartTask;
+    fn get_is_ready(sender: Sender<StateResponseMsg>, state: &State) {
+        let is_ready = state.readiness_list.is_ready();
+        let msg = StateResponseMsg::GetIsReady { is_ready: is_ready };
         sender.send(msg).unwrap();
     }
 
-    fn restart_task(sender: Sender<StateResponseMsg>, state: &State, task_name: String) {
-        println!("restart task {}", task_name);
-        state.task_list.update_task_last_update(task_name.clone());
-        state.task_list.set_task_state(task_name.clone(), TaskState::Restart);
-        let msg = StateResponseMsg::RestartTask;
+    fn get_collaborators(sender: Sender<StateResponseMsg>, state: &State) {
+        let result: Vec<CollaboratorStatus> = state.readiness_list.get_collaborators();
+        let msg = StateResponseMsg::GetCollaborators { collaborators: result };
         sender.send(msg).unwrap();
     }
 
-    fn get_is_restartable_task(sender: Sender<StateResponseMsg>, state: &State, task_name: String) {
-        let result = state.task_list.get_task(task_name.clone());
-        let is_restartable_task = match result {
-            Ok(task) => task.is_system_service && task.controller == state.my_name && task.is_job == false,
-            Err(_) => false,
+    fn begin_agent_upgrade(sender: Sender<StateResponseMsg>, state: &mut State, report: AgentUpgradeReport) {
+        let in_progress = match state.agent_upgrade_report {
+            Some(ref current) => !current.finished,
+            None => false,
         };
+        if !in_progress {
+            state.agent_upgrade_report = Some(report);
+        }
+        let msg = StateResponseMsg::BeginAgentUpgrade { started: !in_progress };
+        sender.send(msg).unwrap();
+    }
 
-        let msg = StateResponseMsg::GetIsRestartableTask { is_restartable_task: is_restartable_task };
+    fn set_agent_upgrade_report(sender: Sender<StateResponseMsg>, state: &mut State, report: AgentUpgradeReport) {
+        state.agent_upgrade_report = Some(report);
+        let msg = StateResponseMsg::SetAgentUpgradeReport;
         sender.send(msg).unwrap();
     }
 
-    fn remove_task_by_name(sender: Sender<StateResponseMsg>, state: &State, task_name: String) {
-        println!("remove task {}", task_name);
+    fn get_agent_upgrade_report(sender: Sender<StateResponseMsg>, state: &State) {
+        let msg = StateResponseMsg::GetAgentUpgradeReport { report: state.agent_upgrade_report.clone() };
+        sender.send(msg).unwrap();
+    }
 
-        state.task_list.remove_task_by_name(task_name.to_string());
-        let msg = StateResponseMsg::RemoveTask;
+    fn set_last_clean_report(sender: Sender<StateResponseMsg>, state: &mut State, report: CleanReport) {
+        state.last_clean_report = Some(report);
+        let msg = StateResponseMsg::SetLastCleanReport;
         sender.send(msg).unwrap();
     }
 
-    fn get_requested_tasks(sender: Sender<StateResponseMsg>, state: &State) {
-        let result: Vec<Task> = state.task_list.get_tasks_with_state(TaskState::Requested);
-        let msg = StateResponseMsg::GetRequestedTasks { requested_tasks: result };
+    fn get_last_clean_report(sender: Sender<StateResponseMsg>, state: &State) {
+        let msg = StateResponseMsg::GetLastCleanReport { report: state.last_clean_report.clone() };
         sender.send(msg).unwrap();
     }
 
-    fn get_running_tasks(sender: Sender<StateResponseMsg>, state: &State) {
-        let result: Vec<Task> = state.task_list.get_tasks_with_state(TaskState::Running);
-        let msg = StateResponseMsg::GetRunningTasks { running_tasks: result };
+    fn set_last_sync_report(sender: Sender<StateResponseMsg>, state: &mut State, report: SyncReport) {
+        state.last_sync_report = Some(report);
+        let msg = StateResponseMsg::SetLastSyncReport;
         sender.send(msg).unwrap();
     }
 
-    fn get_restart_tasks(sender: Sender<StateResponseMsg>, state: &State) {
-        let result: Vec<Task> = state.task_list.get_tasks_with_state(TaskState::Restart);
-        let msg = StateResponseMsg::GetRestartTasks { restart_tasks: result };
+    fn get_last_sync_report(sender: Sender<StateResponseMsg>, state: &State) {
+        let msg = StateResponseMsg::GetLastSyncReport { report: state.last_sync_report.clone() };
         sender.send(msg).unwrap();
     }
 
-    fn add_node(sender: Sender<StateResponseMsg>, state: &State, node: &Node) {
-        state.node_list.add_new_node(&node);
-        let msg = StateResponseMsg::AddNode;
+    fn set_cluster_open(sender: Sender<StateResponseMsg>, state: &mut State, open: bool) {
+        info!("cluster open for regular workloads open={}", open);
+        state.cluster_open = open;
+        let msg = StateResponseMsg::SetClusterOpen;
         sender.send(msg).unwrap();
     }
 
-    fn get_is_node_active(sender: Sender<StateResponseMsg>, state: &State, node_name: String) {
-        let is_active = state.node_list.is_node_active(node_name.clone());
-        let msg = StateResponseMsg::GetIsNodeActive { is_active: is_active };
+    fn get_is_cluster_open(sender: Sender<StateResponseMsg>, state: &State) {
+        let msg = StateResponseMsg::GetIsClusterOpen { is_open: state.cluster_open };
         sender.send(msg).unwrap();
     }
 
-    fn update_node(sender: Sender<StateResponseMsg>,
-                   state: &State,
-                   node_name: String,
-                   node_type: String,
-                   node_function: String,
-                   slave_id: String) {
-        state.node_list.update_node(node_name.clone(),
-                                    node_type.clone(),
-                                    node_function.clone(),
-                                    slave_id.clone());
-        let msg = StateResponseMsg::UpdateNode;
+    fn set_teardown(sender: Sender<StateResponseMsg>, state: &mut State, active: bool) {
+        info!("teardown active={}", active);
+        state.teardown = active;
+        let msg = StateResponseMsg::SetTeardown;
         sender.send(msg).unwrap();
     }
 
-    fn set_node_inactive(sender: Sender<StateResponseMsg>, state: &State, node_name: String) {
-        state.node_list.set_node_inactive(node_name.clone());
-        let msg = StateResponseMsg::SetNodeInactive;
+    fn get_is_teardown(sender: Sender<StateResponseMsg>, state: &State) {
+        let msg = StateResponseMsg::GetIsTeardown { is_teardown: state.teardown };
+        sender.send(msg).unwrap();
+    }
+
+    fn set_shutting_down(sender: Sender<StateResponseMsg>, state: &mut State, active: bool) {
+        info!("shutting down active={}", active);
+        state.shutting_down = active;
+        let msg = StateResponseMsg::SetShuttingDown;
+        sender.send(msg).unwrap();
+    }
+
+    fn get_is_shutting_down(sender: Sender<StateResponseMsg>, state: &State) {
+        let msg = StateResponseMsg::GetIsShuttingDown { is_shutting_down: state.shutting_down };
+        sender.send(msg).unwrap();
+    }
+
+    fn put_schedule(sender: Sender<StateResponseMsg>, state: &mut State, schedule: StoredSchedule) {
+        StateManager::journal_entry(state, StoreEntry::PutSchedule(schedule.clone()));
+        state.schedules.insert(schedule.name.clone(), schedule);
+        let msg = StateResponseMsg::PutSchedule;
         sender.send(msg).unwrap();
     }
 
-    fn get_node(sender: Sender<StateResponseMsg>, state: &State, node_name: String) {
-        let result: Node = state.node_list.get_node(node_name.clone()).unwrap();
-        let msg = StateResponseMsg::GetNode { node: result };
+    fn remove_schedule(sender: Sender<StateResponseMsg>, state: &mut State, job_name: String) {
+        if state.schedules.remove(&job_name).is_some() {
+            StateManager::journal_entry(state, StoreEntry::RemoveSchedule(job_name));
+        }
+        let msg = StateResponseMsg::RemoveSchedule;
         sender.send(msg).unwrap();
     }
 
-    fn get_nodes(sender: Sender<StateResponseMsg>, state: &State) {
-        let result: Vec<Node> = state.node_list.get_nodes();
-        let msg = StateResponseMsg::GetNodes { nodes: result };
+    fn get_schedules(sender: Sender<StateResponseMsg>, state: &State) {
+        let msg = StateResponseMsg::GetSchedules { schedules: state.schedules.values().cloned().collect() };
         sender.send(msg).unwrap();
     }
 }
+
+// everything a task picked up while being placed and run, what's left is its definition
+fn reset_runtime_state(task: &mut Task) {
+    task.accepted_since = 0;
+    task.running_since = 0;
+    task.failure_reason = "".to_string();
+    task.delegated_to = "".to_string();
+    task.remote_state = "".to_string();
+    task.artifact_urls = vec![];
+    task.ip = "".to_string();
+    task.slave_id = "".to_string();
+    task.state = TaskState::Requested;
+    task.last_update = UTC::now().timestamp();
+    task.network_retries = 0;
+    task.restart_count = 0;
+    task.power_in_watts = 0.0;
+    task.energy_in_wh = 0.0;
+}
+
+// what the tasks on a node hold on to, requested ones only count when pinned to it,
+// others still carry the node they ran on before
+fn allocated_resources(node: &Node, tasks: &Vec<Task>) -> (f64, f64) {
+    let holding: Vec<&Task> = tasks.iter()
+        .filter(|task| {
+            match task.state {
+                TaskState::Accepted | TaskState::Running => true,
+                TaskState::Requested => task.node_name == node.name && task.node_type.is_empty() && task.node_function.is_empty(),
+                _ => false,
+            }
+        })
+        .collect();
+    (holding.iter().map(|task| task.cpu).sum(), holding.iter().map(|task| task.memory).sum())
+}
@@ -0,0 +1,499 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use api::decode_task;
+use chrono::UTC;
+use rustc_serialize::json::{self, Json};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{Sender, channel};
+use std::thread::{self, JoinHandle};
+use super::node_list::Node;
+use super::task_list::Task;
+use super::volume_list::VolumeBinding;
+use utils::{read_int, read_string};
+use yaml_rust::Yaml;
+
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct StoredState {
+    pub timestamp: i64,
+    pub tasks: Vec<Task>,
+    pub nodes: Vec<Node>,
+    pub schedules: Vec<StoredSchedule>,
+    pub volumes: Vec<VolumeBinding>,
+}
+
+// a job scheduled through the api, kept as the service it was posted as. the ones from the config
+// are read from there again
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct StoredSchedule {
+    pub name: String,
+    pub schedule: String,
+    pub service: String,
+}
+
+// task changes go into the log as they happen, a snapshot replaces the log. nodes only get logged for what
+// an operator changed, registrations and metadata edits, the rest of a node is runtime state
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub enum StoreEntry {
+    PutTask(Task),
+    RemoveTask(String),
+    PutNode(Node),
+    RemoveNode(String),
+    PutSchedule(StoredSchedule),
+    RemoveSchedule(String),
+    PutVolume(VolumeBinding),
+    RemoveVolume(String),
+    Snapshot(StoredState),
+}
+
+// a log line, the timestamp lets a replay stop at any point in the past
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct LoggedEntry {
+    pub timestamp: i64,
+    pub entry: StoreEntry,
+}
+
+// the snapshot a replay starts from and the logged changes up to the point asked for
+pub struct Replay {
+    pub base: StoredState,
+    pub entries: Vec<LoggedEntry>,
+}
+
+pub trait StateStore: Send {
+    fn append(&mut self, entry: &StoreEntry) -> Result<(), String>;
+    fn write_snapshot(&mut self, state: &StoredState) -> Result<(), String>;
+    // the last snapshot with the log replayed on top
+    fn load(&mut self) -> Result<Option<StoredState>, String>;
+}
+
+// snapshot.json and wal.log in a local directory, with history set the last generations
+// are kept below history/<closed at> for torc-replay
+pub struct FileStore {
+    directory: PathBuf,
+    history: usize,
+}
+
+impl FileStore {
+    pub fn new(directory: &str, history: usize) -> FileStore {
+        FileStore {
+            directory: PathBuf::from(directory),
+            history: history,
+        }
+    }
+
+    fn snapshot_path(&self) -> PathBuf {
+        self.directory.join("snapshot.json")
+    }
+
+    fn log_path(&self) -> PathBuf {
+        self.directory.join("wal.log")
+    }
+
+    // a copy of the current snapshot starts a generation of its own, the log follows it once the new snapshot
+    // is in place. the current files stay where they are until then, a crash in between loses nothing
+    fn start_generation(&self) -> Result<PathBuf, String> {
+        let generation = self.directory.join("history").join(UTC::now().timestamp().to_string());
+        try!(fs::create_dir_all(&generation).map_err(|error| format!("can't create {:?}: {}", generation, error)));
+
+        let current = self.snapshot_path();
+        if current.exists() {
+            try!(fs::copy(&current, generation.join("snapshot.json"))
+                .map_err(|error| format!("can't copy {:?} into {:?}: {}", current, generation, error)));
+        }
+        Ok(generation)
+    }
+
+    // the oldest generations beyond history are dropped
+    fn prune_generations(&self) {
+        let history = self.directory.join("history");
+        let generations = list_generations(&history);
+        if generations.len() > self.history {
+            for closed_at in &generations[..generations.len() - self.history] {
+                let expired = history.join(closed_at.to_string());
+                if let Err(error) = fs::remove_dir_all(&expired) {
+                    warn!("can't remove state store generation path={:?} error={}", expired, error);
+                }
+            }
+        }
+    }
+}
+
+impl StateStore for FileStore {
+    fn append(&mut self, entry: &StoreEntry) -> Result<(), String> {
+        let logged = LoggedEntry {
+            timestamp: UTC::now().timestamp(),
+            entry: entry.clone(),
+        };
+        let line = format!("{}\n", json::encode(&logged).unwrap());
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.log_path())
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|error| format!("can't append to {:?}: {}", self.log_path(), error))
+    }
+
+    // written and synced next to the old one and renamed over it, so a crash never leaves half a snapshot.
+    // the log only goes once the rename is on disk, until then replaying it on either snapshot ends up the same
+    fn write_snapshot(&mut self, state: &StoredState) -> Result<(), String> {
+        let staged = self.directory.join("snapshot.tmp");
+        try!(File::create(&staged)
+            .and_then(|mut file| file.write_all(json::encode(state).unwrap().as_bytes()).and_then(|_| file.sync_all()))
+            .map_err(|error| format!("can't write snapshot to {:?}: {}", self.directory, error)));
+        let generation = match self.history > 0 {
+            true => Some(try!(self.start_generation())),
+            false => None,
+        };
+        try!(fs::rename(&staged, self.snapshot_path())
+            .and_then(|_| File::open(&self.directory).and_then(|directory| directory.sync_all()))
+            .map_err(|error| format!("can't write snapshot to {:?}: {}", self.directory, error)));
+
+        match generation {
+            Some(generation) => {
+                let log = self.log_path();
+                if log.exists() {
+                    try!(fs::rename(&log, generation.join("wal.log"))
+                        .map_err(|error| format!("can't move {:?} into {:?}: {}", log, generation, error)));
+                }
+                self.prune_generations();
+                Ok(())
+            }
+            None => {
+                File::create(self.log_path())
+                    .map(|_| ())
+                    .map_err(|error| format!("can't truncate {:?}: {}", self.log_path(), error))
+            }
+        }
+    }
+
+    fn load(&mut self) -> Result<Option<StoredState>, String> {
+        try!(fs::create_dir_all(&self.directory).map_err(|error| format!("can't create {:?}: {}", self.directory, error)));
+
+        let state = try!(read_snapshot(&self.snapshot_path()));
+        let entries = read_log(&self.log_path());
+        if entries.is_empty() {
+            return Ok(state);
+        }
+
+        let mut replayed = state.unwrap_or(StoredState {
+            timestamp: 0,
+            tasks: vec![],
+            nodes: vec![],
+            schedules: vec![],
+            volumes: vec![],
+        });
+        for logged in &entries {
+            apply(&mut replayed, &logged.entry);
+        }
+        replayed.timestamp = UTC::now().timestamp();
+        Ok(Some(replayed))
+    }
+}
+
+// the state as the controller saw it at a point in the past, from the newest generation
+// that started before then, needs a file store with history
+pub fn replay_store(directory: &str, at: i64) -> Result<Replay, String> {
+    let directory = PathBuf::from(directory);
+    let history = directory.join("history");
+
+    let mut generations: Vec<PathBuf> = list_generations(&history)
+        .iter()
+        .map(|closed_at| history.join(closed_at.to_string()))
+        .collect();
+    generations.push(directory.clone());
+
+    for generation in generations.iter().rev() {
+        let base = match try!(read_snapshot(&generation.join("snapshot.json"))) {
+            Some(base) => base,
+            None => {
+                StoredState {
+                    timestamp: 0,
+                    tasks: vec![],
+                    nodes: vec![],
+                    schedules: vec![],
+                    volumes: vec![],
+                }
+            }
+        };
+        if base.timestamp > at {
+            continue;
+        }
+
+        let entries = read_log(&generation.join("wal.log"))
+            .into_iter()
+            .filter(|logged| logged.timestamp <= at)
+            .collect();
+        return Ok(Replay {
+            base: base,
+            entries: entries,
+        });
+    }
+
+    Err(format!("no state kept in {:?} from before {}", directory, at))
+}
+
+impl Replay {
+    // the state after the first steps entries, all of them for the state at the point asked for
+    pub fn state_after(&self, steps: usize) -> StoredState {
+        let mut state = self.base.clone();
+        for logged in self.entries.iter().take(steps) {
+            apply(&mut state, &logged.entry);
+            state.timestamp = logged.timestamp;
+        }
+        state
+    }
+}
+
+fn apply(state: &mut StoredState, entry: &StoreEntry) {
+    match *entry {
+        StoreEntry::PutTask(ref task) => {
+            state.tasks.retain(|stored| stored.name != task.name);
+            state.tasks.push(task.clone());
+        }
+        StoreEntry::RemoveTask(ref task_name) => state.tasks.retain(|stored| stored.name != *task_name),
+        StoreEntry::PutNode(ref node) => {
+            state.nodes.retain(|stored| stored.name != node.name);
+            state.nodes.push(node.clone());
+        }
+        StoreEntry::RemoveNode(ref node_name) => state.nodes.retain(|stored| stored.name != *node_name),
+        StoreEntry::PutSchedule(ref schedule) => {
+            state.schedules.retain(|stored| stored.name != schedule.name);
+            state.schedules.push(schedule.clone());
+        }
+        StoreEntry::RemoveSchedule(ref job_name) => state.schedules.retain(|stored| stored.name != *job_name),
+        StoreEntry::PutVolume(ref binding) => {
+            state.volumes.retain(|stored| stored.task_name != binding.task_name);
+            state.volumes.push(binding.clone());
+        }
+        StoreEntry::RemoveVolume(ref task_name) => state.volumes.retain(|stored| stored.task_name != *task_name),
+        StoreEntry::Snapshot(_) => {}
+    }
+}
+
+fn read_snapshot(path: &PathBuf) -> Result<Option<StoredState>, String> {
+    match File::open(path) {
+        Ok(mut file) => {
+            let mut content = String::new();
+            try!(file.read_to_string(&mut content).map_err(|error| error.to_string()));
+            let stored = try!(Json::from_str(&content).map_err(|error| error.to_string()));
+            Ok(Some(try!(decode_state(&stored))))
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+// tasks and nodes written by an older controller lack the fields added since, they get their defaults
+// instead of failing the whole snapshot, one that can't be decoded at all is left out. snapshots from
+// before schedules and volume bindings were stored have none
+fn decode_state(stored: &Json) -> Result<StoredState, String> {
+    let timestamp = match stored.find("timestamp").and_then(|timestamp| timestamp.as_i64()) {
+        Some(timestamp) => timestamp,
+        None => return Err("no timestamp in stored state".to_string()),
+    };
+
+    let mut tasks = vec![];
+    for task in stored.find("tasks").and_then(|tasks| tasks.as_array()).unwrap_or(&vec![]) {
+        match decode_task(&task.to_string(), None) {
+            Ok(task) => tasks.push(task),
+            Err(error) => warn!("can't restore task error={}", error),
+        }
+    }
+    let mut nodes = vec![];
+    for node in stored.find("nodes").and_then(|nodes| nodes.as_array()).unwrap_or(&vec![]) {
+        match decode_node(node) {
+            Ok(node) => nodes.push(node),
+            Err(error) => warn!("can't restore node error={}", error),
+        }
+    }
+
+    let mut schedules = vec![];
+    for schedule in stored.find("schedules").and_then(|schedules| schedules.as_array()).unwrap_or(&vec![]) {
+        match json::decode(&schedule.to_string()) {
+            Ok(schedule) => schedules.push(schedule),
+            Err(error) => warn!("can't restore schedule error={}", error),
+        }
+    }
+    let mut volumes = vec![];
+    for binding in stored.find("volumes").and_then(|volumes| volumes.as_array()).unwrap_or(&vec![]) {
+        match json::decode(&binding.to_string()) {
+            Ok(binding) => volumes.push(binding),
+            Err(error) => warn!("can't restore volume binding error={}", error),
+        }
+    }
+
+    Ok(StoredState {
+        timestamp: timestamp,
+        tasks: tasks,
+        nodes: nodes,
+        schedules: schedules,
+        volumes: volumes,
+    })
+}
+
+fn decode_node(stored: &Json) -> Result<Node, String> {
+    let defaults = Node::new("".to_string(), "".to_string(), "".to_string(), "".to_string(), "".to_string());
+    let mut node = match Json::from_str(&json::encode(&defaults).unwrap()) {
+        Ok(Json::Object(node)) => node,
+        _ => return Err("can't encode node defaults".to_string()),
+    };
+    match *stored {
+        Json::Object(ref stored) => {
+            for (field, value) in stored {
+                node.insert(field.clone(), value.clone());
+            }
+        }
+        _ => return Err("node has to be an object".to_string()),
+    }
+    json::decode(&Json::Object(node).to_string()).map_err(|error| error.to_string())
+}
+
+// entries are encoded as {"variant": .., "fields": [..]}
+fn decode_entry(entry: &Json) -> Result<StoreEntry, String> {
+    let variant = entry.find("variant").and_then(|variant| variant.as_string()).unwrap_or("");
+    let field = match entry.find("fields").and_then(|fields| fields.as_array()).and_then(|fields| fields.first()) {
+        Some(field) => field,
+        None => return Err(format!("no fields in {}", entry)),
+    };
+    match variant {
+        "PutTask" => decode_task(&field.to_string(), None).map(StoreEntry::PutTask),
+        "RemoveTask" => {
+            field.as_string()
+                .map(|task_name| StoreEntry::RemoveTask(task_name.to_string()))
+                .ok_or(format!("no task name in {}", entry))
+        }
+        "PutNode" => decode_node(field).map(StoreEntry::PutNode),
+        "RemoveNode" => {
+            field.as_string()
+                .map(|node_name| StoreEntry::RemoveNode(node_name.to_string()))
+                .ok_or(format!("no node name in {}", entry))
+        }
+        "PutSchedule" => json::decode(&field.to_string()).map(StoreEntry::PutSchedule).map_err(|error| error.to_string()),
+        "RemoveSchedule" => {
+            field.as_string()
+                .map(|job_name| StoreEntry::RemoveSchedule(job_name.to_string()))
+                .ok_or(format!("no job name in {}", entry))
+        }
+        "Snapshot" => decode_state(field).map(StoreEntry::Snapshot),
+        other => Err(format!("unknown entry {}", other)),
+    }
+}
+
+fn read_log(path: &PathBuf) -> Vec<LoggedEntry> {
+    let log = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return vec![],
+    };
+
+    let mut entries = vec![];
+    for line in BufReader::new(log).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        // the last line may be cut short by a crash while writing it, a line that can't be read
+        // is skipped instead of dropping everything after it,
+        // lines from before entries got a timestamp count as written at 0
+        let logged = Json::from_str(&line).map_err(|error| error.to_string()).and_then(|logged| {
+            match logged.find("entry") {
+                Some(entry) => {
+                    decode_entry(entry).map(|entry| {
+                        LoggedEntry {
+                            timestamp: logged.find("timestamp").and_then(|timestamp| timestamp.as_i64()).unwrap_or(0),
+                            entry: entry,
+                        }
+                    })
+                }
+                None => {
+                    decode_entry(&logged).map(|entry| {
+                        LoggedEntry {
+                            timestamp: 0,
+                            entry: entry,
+                        }
+                    })
+                }
+            }
+        });
+        match logged {
+            Ok(logged) => entries.push(logged),
+            Err(error) => warn!("skipping unreadable state store entry path={:?} error={}", path, error),
+        }
+    }
+    entries
+}
+
+// oldest first
+fn list_generations(history: &PathBuf) -> Vec<i64> {
+    let mut generations: Vec<i64> = match fs::read_dir(history) {
+        Ok(entries) => {
+            entries.filter_map(|entry| entry.ok())
+                .filter_map(|entry| entry.file_name().to_str().and_then(|name| name.parse::<i64>().ok()))
+                .collect()
+        }
+        Err(_) => vec![],
+    };
+    generations.sort();
+    generations
+}
+
+// state-store: type: file, path: <directory>; anything else keeps the state in memory only
+pub fn open_store(config: &Yaml) -> Option<Box<StateStore>> {
+    let store_config = &config["state-store"];
+
+    match read_string(store_config, "type".to_string()).as_ref() {
+        "file" => {
+            let history = read_int(store_config, "history".to_string(), 0) as usize;
+            Some(Box::new(FileStore::new(&read_string(store_config, "path".to_string()), history)))
+        }
+        "" | "none" => None,
+        other => {
+            warn!("unknown state store, keeping state in memory only type={}", other);
+            None
+        }
+    }
+}
+
+// the store is written from its own thread, entries arrive in the order the state changed
+// the writer ends once every sender is gone, joining it waits for what was sent before
+pub fn start_store_writer(store: Box<StateStore>) -> (Sender<StoreEntry>, JoinHandle<()>) {
+    let (sender, receiver) = channel::<StoreEntry>();
+    let mut store = store;
+
+    let writer = thread::Builder::new()
+        .name("state-store".to_string())
+        .spawn(move || {
+            for entry in receiver {
+                let result = match entry {
+                    StoreEntry::Snapshot(ref state) => store.write_snapshot(state),
+                    _ => store.append(&entry),
+                };
+                match result {
+                    Ok(_) => {}
+                    Err(error) => error!("can't persist state error={}", error),
+                }
+            }
+        })
+        .unwrap();
+
+    (sender, writer)
+}
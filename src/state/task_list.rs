@@ -21,12 +21,28 @@
 // THE SOFTWARE.
 
 use chrono::UTC;
-use std::collections::HashMap;
-use std::sync::Mutex;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::mpsc::Sender;
+use super::event_list::Sequence;
+use super::memory_usage::MemoryUsage;
+use super::node_list::update_map;
+use super::run_window::RunWindow;
 use super::state::TaskState;
+use super::store::StoreEntry;
 
 pub struct TaskList {
-    task_list: Mutex<HashMap<String, Task>>,
+    task_list: RwLock<HashMap<String, Task>>,
+    node_index: RwLock<NodeIndex>,
+    journal: Mutex<Option<Sender<StoreEntry>>>,
+    sequence: Arc<Sequence>,
+}
+
+// task names by the node they are pinned or placed on and by the slave they run on,
+// a task only known by its slave so far is still found for the node
+struct NodeIndex {
+    by_node_name: HashMap<String, HashSet<String>>,
+    by_slave_id: HashMap<String, HashSet<String>>,
 }
 
 #[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
@@ -35,25 +51,62 @@ pub struct Task {
     pub controller: String,
     pub id: String,
     pub image: String,
+    pub image_digest: String,
     pub node_name: String,
     pub node_type: String,
     pub node_function: String,
+    pub constraints: Vec<String>,
     pub dependent_service: String,
+    pub restart_dependents: bool,
     pub arguments: String,
     pub parameters: String,
+    pub env: BTreeMap<String, String>,
+    // a consul kv prefix, every key below it becomes a variable when the task is launched
+    pub env_from_consul_kv: String,
     pub memory: f64,
     pub cpu: f64,
+    pub cpu_policy: String,
     pub volumes: Vec<Volume>,
     pub privileged: bool,
     pub sla: SLA,
+    pub priority: u8,
     pub is_metered: bool,
     pub is_system_service: bool,
     pub is_job: bool,
     pub network_type: String,
+    pub run_window: Option<RunWindow>,
+    pub health_check: Option<HealthCheck>,
+    pub network_policy: Option<NetworkPolicy>,
+    pub aliases: Vec<String>,
+    pub slow_start_in_seconds: i64,
+    pub max_runtime_seconds: i64,
+    pub restart_policy: String,
+    pub max_restarts: i64,
+    pub failure_reason: String,
+    pub array_name: String,
+    pub array_index: i64,
+    pub replica_group: String,
+    pub replica_index: i64,
+    // warm spares started next to the active tasks of a service, kept out of consul and routing until promoted
+    pub standby_group: String,
+    pub standby: bool,
+    pub delegated_to: String,
+    pub delegated_from: String,
+    pub remote_state: String,
+    pub artifacts: Vec<String>,
+    pub annotations: BTreeMap<String, String>,
+    pub artifact_urls: Vec<String>,
+    pub accepted_since: i64,
+    pub running_since: i64,
     pub ip: String,
     pub slave_id: String,
     pub state: TaskState,
     pub last_update: i64,
+    pub deleted_at: i64,
+    pub network_retries: i64,
+    pub restart_count: i64,
+    pub power_in_watts: f64,
+    pub energy_in_wh: f64,
 }
 
 #[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
@@ -63,6 +116,38 @@ pub struct Volume {
     pub read_only_mode: bool,
 }
 
+// an operator's correction of what the controller believes, None keeps the recorded value
+#[derive(Clone, Debug)]
+pub struct TaskOverride {
+    pub state: TaskState,
+    pub node_name: Option<String>,
+    pub ip: Option<String>,
+    pub reason: String,
+}
+
+// probed against the task ip, check_type is http (any 2xx on path) or tcp (connect)
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct HealthCheck {
+    pub check_type: String,
+    pub path: String,
+    pub port: i64,
+    pub interval_in_seconds: i64,
+    pub failures: i64,
+}
+
+// the ports a task takes connections on, enforced on the node by the agent's firewall
+#[derive(Clone, Debug, PartialEq, RustcEncodable, RustcDecodable)]
+pub struct NetworkPolicy {
+    pub ingress: Vec<PortRule>,
+}
+
+// protocol is tcp or udp
+#[derive(Clone, Debug, PartialEq, RustcEncodable, RustcDecodable)]
+pub struct PortRule {
+    pub port: i64,
+    pub protocol: String,
+}
+
 #[derive(Clone, Hash, Eq, PartialEq, Debug, RustcEncodable, RustcDecodable)]
 pub enum SLA {
     None,
@@ -70,51 +155,159 @@ pub enum SLA {
     SingletonEachSlave,
 }
 
+pub const FULL_TRAFFIC_WEIGHT: i64 = 10;
+
+impl Task {
+    // ramps linearly from 1 to full weight over slow_start_in_seconds after the task went running
+    pub fn traffic_weight(&self) -> i64 {
+        if self.slow_start_in_seconds <= 0 || self.running_since == 0 {
+            return FULL_TRAFFIC_WEIGHT;
+        }
+
+        let elapsed = UTC::now().timestamp() - self.running_since;
+        if elapsed >= self.slow_start_in_seconds {
+            return FULL_TRAFFIC_WEIGHT;
+        }
+
+        let weight = 1 + (FULL_TRAFFIC_WEIGHT - 1) * elapsed.max(0) / self.slow_start_in_seconds;
+        weight.min(FULL_TRAFFIC_WEIGHT)
+    }
+
+    // the name clients find the task by in consul, whichever member of a service with standbys
+    // is active answers for the service
+    pub fn service_name(&self) -> &String {
+        match self.standby_group.is_empty() {
+            true => &self.name,
+            false => &self.standby_group,
+        }
+    }
+}
 
 impl TaskList {
     pub fn new() -> TaskList {
-        TaskList { task_list: Mutex::new(HashMap::new()) }
+        TaskList {
+            task_list: RwLock::new(HashMap::new()),
+            node_index: RwLock::new(NodeIndex::new()),
+            journal: Mutex::new(None),
+            sequence: Arc::new(Sequence::new()),
+        }
+    }
+
+    // shared with the event list, events and task changes are numbered in one sequence
+    pub fn sequence(&self) -> Arc<Sequence> {
+        self.sequence.clone()
+    }
+
+    // every change from now on gets written to the journal as well, restored tasks go in without one
+    pub fn set_journal(&self, journal: Option<Sender<StoreEntry>>) {
+        *self.journal.lock().unwrap() = journal;
     }
 
     pub fn add_new_task(&self, task: &Task) {
-        self.task_list.lock().unwrap().insert(task.name.to_string(), task.clone());
+        let mut node_index = self.node_index.write().unwrap();
+        match self.task_list.write().unwrap().insert(task.name.to_string(), task.clone()) {
+            Some(previous) => node_index.remove(&previous),
+            None => {}
+        }
+        node_index.insert(task);
+        self.journal(StoreEntry::PutTask(task.clone()));
     }
 
     pub fn remove_task_by_name(&self, task_name: String) {
-        self.task_list.lock().unwrap().remove(&task_name);
+        match self.task_list.write().unwrap().remove(&task_name) {
+            Some(previous) => self.node_index.write().unwrap().remove(&previous),
+            None => {}
+        }
+        self.journal(StoreEntry::RemoveTask(task_name));
     }
 
     pub fn set_task_state(&self, task_name: String, task_state: TaskState) {
-        match self.task_list.lock().unwrap().get_mut(&task_name) {
+        match self.task_list.write().unwrap().get_mut(&task_name) {
             Some(task) => {
+                if task_state == TaskState::Accepted && task.state != TaskState::Accepted {
+                    task.accepted_since = UTC::now().timestamp();
+                }
+                if task_state == TaskState::Running && task.state != TaskState::Running {
+                    task.running_since = UTC::now().timestamp();
+                }
+                // the next run starts its own clock, a crash before it runs again doesn't count as a long run
+                if task_state == TaskState::Restart {
+                    task.running_since = 0;
+                }
                 task.state = task_state.clone();
+                self.journal(StoreEntry::PutTask(task.clone()));
             }
             None => {}
         }
     }
 
     pub fn set_task_node_name(&self, task_name: String, node_name: String) {
-        match self.task_list.lock().unwrap().get_mut(&task_name) {
+        match self.task_list.write().unwrap().get_mut(&task_name) {
             Some(task) => {
+                let mut node_index = self.node_index.write().unwrap();
+                node_index.remove(task);
                 task.node_name = node_name.clone();
+                node_index.insert(task);
+                self.journal(StoreEntry::PutTask(task.clone()));
             }
             None => {}
         }
     }
 
     pub fn update_task_last_update(&self, task_name: String) {
-        match self.task_list.lock().unwrap().get_mut(&task_name) {
+        match self.task_list.write().unwrap().get_mut(&task_name) {
+            Some(task) => {
+                task.last_update = UTC::now().timestamp();
+                debug!("task last update task={}", task_name);
+                self.journal(StoreEntry::PutTask(task.clone()));
+            }
+            None => {}
+        }
+    }
+
+    // the restore window runs from here, later bumps to last_update don't extend it
+    pub fn set_task_deleted(&self, task_name: String) {
+        match self.task_list.write().unwrap().get_mut(&task_name) {
+            Some(task) => {
+                let now = UTC::now().timestamp();
+                task.state = TaskState::Deleted;
+                task.last_update = now;
+                task.deleted_at = now;
+                self.journal(StoreEntry::PutTask(task.clone()));
+            }
+            None => {}
+        }
+    }
+
+    // a restored task starts over, wherever it ran before is gone, a task placed by type or function
+    // gets placed again while a pinned one keeps its node
+    pub fn set_task_restored(&self, task_name: String) {
+        match self.task_list.write().unwrap().get_mut(&task_name) {
             Some(task) => {
+                if !task.node_type.is_empty() || !task.node_function.is_empty() {
+                    task.node_name = "".to_string();
+                }
+                task.ip = "".to_string();
+                task.slave_id = "".to_string();
+                task.accepted_since = 0;
+                task.running_since = 0;
+                task.failure_reason = "".to_string();
+                task.network_retries = 0;
+                task.restart_count = 0;
+                task.deleted_at = 0;
+                task.state = TaskState::Restart;
                 task.last_update = UTC::now().timestamp();
-                println!("task last update: {}", task_name);
+                self.journal(StoreEntry::PutTask(task.clone()));
             }
             None => {}
         }
     }
 
     pub fn set_task_info(&self, task_name: String, task_id: String, task_ip: String, slave_id: String) {
-        match self.task_list.lock().unwrap().get_mut(&task_name) {
+        match self.task_list.write().unwrap().get_mut(&task_name) {
             Some(task) => {
+                let mut node_index = self.node_index.write().unwrap();
+                node_index.remove(task);
                 if task_id.len() > 0 {
                     task.id = task_id.clone();
                 }
@@ -124,23 +317,163 @@ impl TaskList {
                 if slave_id.len() > 0 {
                     task.slave_id = slave_id.clone();
                 }
-                println!("task changed {:?}", task);
+                node_index.insert(task);
+                debug!("task changed task={:?}", task);
+                self.journal(StoreEntry::PutTask(task.clone()));
             }
             None => {}
         }
     }
 
+    pub fn increment_network_retries(&self, task_name: String) -> i64 {
+        match self.task_list.write().unwrap().get_mut(&task_name) {
+            Some(task) => {
+                task.network_retries += 1;
+                self.journal(StoreEntry::PutTask(task.clone()));
+                task.network_retries
+            }
+            None => 0,
+        }
+    }
+
+    pub fn reset_network_retries(&self, task_name: String) {
+        match self.task_list.write().unwrap().get_mut(&task_name) {
+            Some(task) => {
+                task.network_retries = 0;
+                self.journal(StoreEntry::PutTask(task.clone()));
+            }
+            None => {}
+        }
+    }
+
+    pub fn add_task_energy(&self, task_name: String, power_in_watts: f64, energy_in_wh: f64) {
+        match self.task_list.write().unwrap().get_mut(&task_name) {
+            Some(task) => {
+                task.power_in_watts = power_in_watts;
+                task.energy_in_wh += energy_in_wh;
+                self.journal(StoreEntry::PutTask(task.clone()));
+            }
+            None => {}
+        }
+    }
+
+    pub fn set_restart_count(&self, task_name: String, restart_count: i64) {
+        match self.task_list.write().unwrap().get_mut(&task_name) {
+            Some(task) => {
+                task.restart_count = restart_count;
+                self.journal(StoreEntry::PutTask(task.clone()));
+            }
+            None => {}
+        }
+    }
+
+    // returns the task and what changed, only a change gets journaled
+    pub fn update_annotations(&self,
+                              task_name: String,
+                              update: &BTreeMap<String, Option<String>>)
+                              -> Result<(Task, Vec<String>), &'static str> {
+        match self.task_list.write().unwrap().get_mut(&task_name) {
+            Some(task) => {
+                let mut changes = vec![];
+                update_map(&mut task.annotations, update, "annotations", &mut changes);
+                if !changes.is_empty() {
+                    self.journal(StoreEntry::PutTask(task.clone()));
+                }
+                Ok((task.clone(), changes))
+            }
+            None => Err("unknown task"),
+        }
+    }
+
+    pub fn set_standby(&self, task_name: String, standby: bool) {
+        match self.task_list.write().unwrap().get_mut(&task_name) {
+            Some(task) => {
+                task.standby = standby;
+                self.journal(StoreEntry::PutTask(task.clone()));
+            }
+            None => {}
+        }
+    }
+
+    pub fn set_task_failed(&self, task_name: String, reason: String) {
+        match self.task_list.write().unwrap().get_mut(&task_name) {
+            Some(task) => {
+                task.state = TaskState::Failed;
+                task.failure_reason = reason;
+                task.last_update = UTC::now().timestamp();
+                self.journal(StoreEntry::PutTask(task.clone()));
+            }
+            None => {}
+        }
+    }
+
+    pub fn set_task_artifact_urls(&self, task_name: String, artifact_urls: Vec<String>) {
+        match self.task_list.write().unwrap().get_mut(&task_name) {
+            Some(task) => {
+                task.artifact_urls = artifact_urls;
+                self.journal(StoreEntry::PutTask(task.clone()));
+            }
+            None => {}
+        }
+    }
+
+    // only for the image the digest was resolved for, a replace in the meantime brought its own
+    pub fn set_task_image_digest(&self, task_name: String, image: String, digest: String) {
+        match self.task_list.write().unwrap().get_mut(&task_name) {
+            Some(task) if task.image == image && task.image_digest.is_empty() => {
+                task.image_digest = digest;
+                self.journal(StoreEntry::PutTask(task.clone()));
+            }
+            _ => {}
+        }
+    }
+
+    // the task goes back to requested once delegated_to is cleared, so it is placed here again.
+    // only applied while the task is still where the caller saw it, requested here for an empty expected peer,
+    // delegated to that peer otherwise. false if it moved on in between, e.g. got deleted
+    pub fn set_task_delegation(&self, task_name: String, expected: &String, delegated_to: String, remote_state: String) -> bool {
+        match self.task_list.write().unwrap().get_mut(&task_name) {
+            Some(task) => {
+                let unchanged = match expected.is_empty() {
+                    true => task.state == TaskState::Requested && task.delegated_to.is_empty(),
+                    false => task.state == TaskState::Delegated && task.delegated_to == *expected,
+                };
+                if !unchanged {
+                    return false;
+                }
+                task.state = match delegated_to.is_empty() {
+                    true => TaskState::Requested,
+                    false => TaskState::Delegated,
+                };
+                task.delegated_to = delegated_to;
+                task.remote_state = remote_state;
+                task.last_update = UTC::now().timestamp();
+                self.journal(StoreEntry::PutTask(task.clone()));
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn get_task_state(&self, task_name: String) -> TaskState {
-        match self.task_list.lock().unwrap().get(&task_name) {
+        match self.task_list.read().unwrap().get(&task_name) {
             Some(task) => task.state.clone(),
             None => TaskState::NotRunning,
         }
     }
 
+    pub fn get_task_by_id(&self, id_prefix: String) -> Option<Task> {
+        let map = self.task_list.read().unwrap();
+        map.values()
+            .filter(|value| !value.id.is_empty())
+            .find(|value| value.id.starts_with(&id_prefix))
+            .cloned()
+    }
+
     pub fn get_task_name_by_id(&self, id_prefix: String) -> String {
         let mut result: String = "".to_string();
 
-        let map = self.task_list.lock().unwrap();
+        let map = self.task_list.read().unwrap();
         for value in map.values().into_iter().filter(|value| !value.id.is_empty()) {
             if value.id.starts_with(&id_prefix) {
                 result = value.name.clone();
@@ -152,20 +485,117 @@ impl TaskList {
     }
 
     pub fn get_task(&self, task_name: String) -> Result<Task, &'static str> {
-        match self.task_list.lock().unwrap().get(&task_name) {
+        match self.task_list.read().unwrap().get(&task_name) {
             Some(task) => Ok(task.clone()),
             None => Err("Can't find task"),
         }
     }
 
+    pub fn get_tasks(&self) -> Vec<Task> {
+        self.task_list.read().unwrap().values().cloned().collect()
+    }
+
+    fn journal(&self, entry: StoreEntry) {
+        self.sequence.next();
+        match *self.journal.lock().unwrap() {
+            Some(ref journal) => {
+                let _ = journal.send(entry);
+            }
+            None => {}
+        }
+    }
+
+    pub fn get_tasks_on_node(&self, node_name: String, slave_id: String) -> Vec<Task> {
+        let task_names = self.node_index.read().unwrap().task_names(&node_name, &slave_id);
+        let task_list = self.task_list.read().unwrap();
+        task_names.iter().filter_map(|name| task_list.get(name)).cloned().collect()
+    }
+
+    // the other tasks started for the service, its standbys and, after a fail over, the task they replaced
+    pub fn get_standby_group(&self, standby_group: String) -> Vec<Task> {
+        self.task_list
+            .read()
+            .unwrap()
+            .values()
+            .filter(|value| value.standby_group == standby_group && value.name != standby_group)
+            .cloned()
+            .collect()
+    }
+
+    pub fn get_replicas(&self, replica_group: String) -> Vec<Task> {
+        let mut result: Vec<Task> = self.task_list
+            .read()
+            .unwrap()
+            .values()
+            .filter(|value| value.replica_group == replica_group)
+            .cloned()
+            .collect();
+        result.sort_by(|a, b| a.replica_index.cmp(&b.replica_index));
+
+        result
+    }
+
     pub fn get_tasks_with_state(&self, task_state: TaskState) -> Vec<Task> {
         let mut result: Vec<Task> = vec![];
 
-        let map = self.task_list.lock().unwrap();
+        let map = self.task_list.read().unwrap();
         for value in map.values().into_iter().filter(|value| value.state == task_state) {
             result.push(value.clone());
         }
 
         result
     }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage::measure("tasks", self.task_list.read().unwrap().values())
+    }
+}
+
+impl NodeIndex {
+    fn new() -> NodeIndex {
+        NodeIndex {
+            by_node_name: HashMap::new(),
+            by_slave_id: HashMap::new(),
+        }
+    }
+
+    fn insert(&mut self, task: &Task) {
+        if !task.node_name.is_empty() {
+            self.by_node_name.entry(task.node_name.clone()).or_insert(HashSet::new()).insert(task.name.clone());
+        }
+        if !task.slave_id.is_empty() {
+            self.by_slave_id.entry(task.slave_id.clone()).or_insert(HashSet::new()).insert(task.name.clone());
+        }
+    }
+
+    fn remove(&mut self, task: &Task) {
+        remove_from_index(&mut self.by_node_name, &task.node_name, &task.name);
+        remove_from_index(&mut self.by_slave_id, &task.slave_id, &task.name);
+    }
+
+    fn task_names(&self, node_name: &String, slave_id: &String) -> BTreeSet<String> {
+        let mut result: BTreeSet<String> = BTreeSet::new();
+        match self.by_node_name.get(node_name) {
+            Some(task_names) => result.extend(task_names.iter().cloned()),
+            None => {}
+        }
+        match self.by_slave_id.get(slave_id) {
+            Some(task_names) => result.extend(task_names.iter().cloned()),
+            None => {}
+        }
+        result
+    }
+}
+
+fn remove_from_index(index: &mut HashMap<String, HashSet<String>>, key: &String, task_name: &String) {
+    let now_empty = match index.get_mut(key) {
+        Some(task_names) => {
+            task_names.remove(task_name);
+            task_names.is_empty()
+        }
+        None => false,
+    };
+    if now_empty {
+        index.remove(key);
+    }
 }
@@ -0,0 +1,326 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use collaborator::CALICO_NETWORK;
+use std::collections::BTreeMap;
+use super::restart_policy::RESTART_POLICIES;
+use super::run_window::RunWindow;
+use super::task_list::{HealthCheck, NetworkPolicy, SLA, Volume};
+use utils::{DEFAULT_CPU, DEFAULT_MEMORY};
+
+// everything a caller decides about a task before it is requested, the rest (id, node, state, ..)
+// gets filled in by the state manager and the scheduler
+#[derive(Clone, Debug)]
+pub struct TaskSpec {
+    pub name: String,
+    pub image: String,
+    pub network_type: String,
+    pub node_name: String,
+    pub node_type: String,
+    pub node_function: String,
+    pub constraints: Vec<String>,
+    pub dependent_service: String,
+    pub restart_dependents: bool,
+    pub arguments: String,
+    pub parameters: String,
+    pub env: BTreeMap<String, String>,
+    pub env_from_consul_kv: String,
+    pub memory: f64,
+    pub cpu: f64,
+    pub cpu_policy: String,
+    pub volumes: Vec<Volume>,
+    pub privileged: bool,
+    pub sla: SLA,
+    pub priority: u8,
+    pub is_metered: bool,
+    pub is_system_service: bool,
+    pub is_job: bool,
+    pub run_window: Option<RunWindow>,
+    pub health_check: Option<HealthCheck>,
+    pub network_policy: Option<NetworkPolicy>,
+    pub aliases: Vec<String>,
+    pub slow_start_in_seconds: i64,
+    pub max_runtime_seconds: i64,
+    pub restart_policy: String,
+    pub max_restarts: i64,
+    pub array_name: String,
+    pub array_index: i64,
+    pub replica_group: String,
+    pub replica_index: i64,
+    pub standby_group: String,
+    pub standby: bool,
+    pub artifacts: Vec<String>,
+    pub annotations: BTreeMap<String, String>,
+}
+
+impl TaskSpec {
+    pub fn new(name: &str, image: &str, network_type: &str) -> TaskSpec {
+        TaskSpec {
+            name: name.to_string(),
+            image: image.to_string(),
+            network_type: network_type.to_string(),
+            node_name: "".to_string(),
+            node_type: "".to_string(),
+            node_function: "".to_string(),
+            constraints: vec![],
+            dependent_service: "".to_string(),
+            restart_dependents: false,
+            arguments: "".to_string(),
+            parameters: "".to_string(),
+            env: BTreeMap::new(),
+            env_from_consul_kv: "".to_string(),
+            memory: DEFAULT_MEMORY,
+            cpu: DEFAULT_CPU,
+            cpu_policy: "shares".to_string(),
+            volumes: vec![],
+            privileged: false,
+            sla: SLA::None,
+            priority: 0,
+            is_metered: false,
+            is_system_service: false,
+            is_job: false,
+            run_window: None,
+            health_check: None,
+            network_policy: None,
+            aliases: vec![],
+            slow_start_in_seconds: 0,
+            max_runtime_seconds: 0,
+            restart_policy: "".to_string(),
+            max_restarts: 0,
+            array_name: "".to_string(),
+            array_index: 0,
+            replica_group: "".to_string(),
+            replica_index: 0,
+            standby_group: "".to_string(),
+            standby: false,
+            artifacts: vec![],
+            annotations: BTreeMap::new(),
+        }
+    }
+
+    pub fn node(mut self, node_name: &str, node_type: &str, node_function: &str) -> TaskSpec {
+        self.node_name = node_name.to_string();
+        self.node_type = node_type.to_string();
+        self.node_function = node_function.to_string();
+        self
+    }
+
+    pub fn constraints(mut self, constraints: Vec<String>) -> TaskSpec {
+        self.constraints = constraints;
+        self
+    }
+
+    pub fn dependent_service(mut self, dependent_service: &str, restart_dependents: bool) -> TaskSpec {
+        self.dependent_service = dependent_service.to_string();
+        self.restart_dependents = restart_dependents;
+        self
+    }
+
+    pub fn arguments(mut self, arguments: &str) -> TaskSpec {
+        self.arguments = arguments.to_string();
+        self
+    }
+
+    pub fn parameters(mut self, parameters: &str) -> TaskSpec {
+        self.parameters = parameters.to_string();
+        self
+    }
+
+    pub fn env(mut self, env: BTreeMap<String, String>) -> TaskSpec {
+        self.env = env;
+        self
+    }
+
+    pub fn env_from_consul_kv(mut self, prefix: &str) -> TaskSpec {
+        self.env_from_consul_kv = prefix.to_string();
+        self
+    }
+
+    pub fn resources(mut self, memory: f64, cpu: f64, cpu_policy: &str) -> TaskSpec {
+        self.memory = memory;
+        self.cpu = cpu;
+        self.cpu_policy = cpu_policy.to_string();
+        self
+    }
+
+    pub fn volumes(mut self, volumes: Vec<Volume>) -> TaskSpec {
+        self.volumes = volumes;
+        self
+    }
+
+    pub fn privileged(mut self, privileged: bool) -> TaskSpec {
+        self.privileged = privileged;
+        self
+    }
+
+    pub fn sla(mut self, sla: SLA) -> TaskSpec {
+        self.sla = sla;
+        self
+    }
+
+    pub fn priority(mut self, priority: u8) -> TaskSpec {
+        self.priority = priority;
+        self
+    }
+
+    pub fn metered(mut self, is_metered: bool) -> TaskSpec {
+        self.is_metered = is_metered;
+        self
+    }
+
+    pub fn system_service(mut self, is_system_service: bool) -> TaskSpec {
+        self.is_system_service = is_system_service;
+        self
+    }
+
+    pub fn job(mut self, is_job: bool) -> TaskSpec {
+        self.is_job = is_job;
+        self
+    }
+
+    pub fn run_window(mut self, run_window: Option<RunWindow>) -> TaskSpec {
+        self.run_window = run_window;
+        self
+    }
+
+    pub fn health_check(mut self, health_check: Option<HealthCheck>) -> TaskSpec {
+        self.health_check = health_check;
+        self
+    }
+
+    pub fn network_policy(mut self, network_policy: Option<NetworkPolicy>) -> TaskSpec {
+        self.network_policy = network_policy;
+        self
+    }
+
+    pub fn aliases(mut self, aliases: Vec<String>) -> TaskSpec {
+        self.aliases = aliases;
+        self
+    }
+
+    pub fn slow_start(mut self, slow_start_in_seconds: i64) -> TaskSpec {
+        self.slow_start_in_seconds = slow_start_in_seconds;
+        self
+    }
+
+    pub fn max_runtime(mut self, max_runtime_seconds: i64) -> TaskSpec {
+        self.max_runtime_seconds = max_runtime_seconds;
+        self
+    }
+
+    pub fn restart_policy(mut self, restart_policy: &str, max_restarts: i64) -> TaskSpec {
+        self.restart_policy = restart_policy.to_string();
+        self.max_restarts = max_restarts;
+        self
+    }
+
+    pub fn array_member(mut self, array_name: &str, array_index: i64) -> TaskSpec {
+        self.array_name = array_name.to_string();
+        self.array_index = array_index;
+        self
+    }
+
+    pub fn replica(mut self, replica_group: &str, replica_index: i64) -> TaskSpec {
+        self.replica_group = replica_group.to_string();
+        self.replica_index = replica_index;
+        self
+    }
+
+    pub fn standby(mut self, standby_group: &str, standby: bool) -> TaskSpec {
+        self.standby_group = standby_group.to_string();
+        self.standby = standby;
+        self
+    }
+
+    pub fn artifacts(mut self, artifacts: Vec<String>) -> TaskSpec {
+        self.artifacts = artifacts;
+        self
+    }
+
+    pub fn annotations(mut self, annotations: BTreeMap<String, String>) -> TaskSpec {
+        self.annotations = annotations;
+        self
+    }
+
+    // same rules the REST api applies to a posted service, so programmatic users can't sneak past them
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() || self.image.is_empty() || self.network_type.is_empty() {
+            return Err("name, image and network_type are required".to_string());
+        }
+        if !(self.memory > 0.0) {
+            return Err(format!("memory has to be greater than 0: {}", self.memory));
+        }
+        if !(self.cpu > 0.0) {
+            return Err(format!("cpu has to be greater than 0: {}", self.cpu));
+        }
+        // felix owns forwarding to calico workloads, rules of the node agent would never see that traffic
+        if self.network_policy.is_some() && self.network_type == CALICO_NETWORK {
+            return Err(format!("network_policy isn't enforced on the calico network {}, use calico profiles",
+                               CALICO_NETWORK));
+        }
+        if self.cpu_policy != "shares" && self.cpu_policy != "quota" {
+            return Err(format!("cpu_policy has to be shares or quota: {}", self.cpu_policy));
+        }
+        if self.slow_start_in_seconds < 0 {
+            return Err(format!("slow_start_in_seconds can't be negative: {}", self.slow_start_in_seconds));
+        }
+        if self.max_runtime_seconds < 0 {
+            return Err(format!("max_runtime_seconds can't be negative: {}", self.max_runtime_seconds));
+        }
+        for name in self.env.keys() {
+            if !is_variable_name(name) {
+                return Err(format!("invalid environment variable name: {}", name));
+            }
+        }
+        if !RESTART_POLICIES.contains(&self.restart_policy.as_str()) {
+            return Err(format!("restart_policy has to be never, on-failure or always: {}", self.restart_policy));
+        }
+        if self.max_restarts < 0 {
+            return Err(format!("max_restarts can't be negative: {}", self.max_restarts));
+        }
+        if self.array_index < 0 || self.replica_index < 0 {
+            return Err("array_index and replica_index can't be negative".to_string());
+        }
+        if self.array_index > 0 && self.array_name.is_empty() {
+            return Err(format!("array member {} needs an array_name", self.array_index));
+        }
+        if self.replica_index > 0 && self.replica_group.is_empty() {
+            return Err(format!("replica {} needs a replica_group", self.replica_index));
+        }
+        if self.annotations.keys().any(|key| key.is_empty()) {
+            return Err("annotation keys can't be empty".to_string());
+        }
+        if self.standby && self.standby_group.is_empty() {
+            return Err(format!("standby {} needs a standby_group", self.name));
+        }
+        if !self.standby_group.is_empty() && self.is_job {
+            return Err(format!("job {} can't have standbys", self.name));
+        }
+        Ok(())
+    }
+}
+
+// what a shell would take, docker passes anything but tasks tend to be started through one
+pub fn is_variable_name(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with(|c: char| c.is_digit(10)) &&
+    name.chars().all(|c| c == '_' || (c.is_ascii() && c.is_alphanumeric()))
+}
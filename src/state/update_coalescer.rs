@@ -0,0 +1,134 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use std::collections::BTreeMap;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+use super::memory_usage::MemoryUsage;
+use super::route_queue::RouteOperationKind;
+use super::task_list::Task;
+use utils::{observe_flushed_updates, observe_submitted_update};
+
+#[derive(Clone, Debug)]
+pub enum PendingUpdate {
+    Register(Task),
+    Deregister(Task),
+    // replace takes the route out before adding it, for an add that superseded a delete of the same ip
+    Route {
+        kind: RouteOperationKind,
+        task_name: String,
+        ip: String,
+        next_hop: String,
+        replace: bool,
+    },
+}
+
+impl PendingUpdate {
+    // what a later update overwrites, consul knows services by task name and the network agent routes by ip
+    pub fn destination(&self) -> (&'static str, String) {
+        match *self {
+            PendingUpdate::Register(ref task) |
+            PendingUpdate::Deregister(ref task) => ("consul", task.name.clone()),
+            PendingUpdate::Route { ref ip, .. } => ("route", ip.clone()),
+        }
+    }
+}
+
+// collects consul and route updates for a short window before they go out, a burst of task transitions
+// ends up as one call per destination carrying the latest update instead of one call per transition
+pub struct UpdateCoalescer {
+    window: Duration,
+    pending: Mutex<BTreeMap<(&'static str, String), PendingUpdate>>,
+    submitted: Condvar,
+}
+
+impl UpdateCoalescer {
+    pub fn new(window: Duration) -> UpdateCoalescer {
+        UpdateCoalescer {
+            window: window,
+            pending: Mutex::new(BTreeMap::new()),
+            submitted: Condvar::new(),
+        }
+    }
+
+    pub fn submit(&self, update: PendingUpdate) {
+        let destination = update.destination();
+        let mut pending = self.pending.lock().unwrap();
+        // the route the delete was for may go via another next hop, the add alone would find it in place
+        let update = match update {
+            PendingUpdate::Route { kind: RouteOperationKind::Add, task_name, ip, next_hop, replace } => {
+                let pending_replace = match pending.get(&destination) {
+                    Some(&PendingUpdate::Route { kind: RouteOperationKind::Delete, .. }) => true,
+                    Some(&PendingUpdate::Route { replace, .. }) => replace,
+                    _ => false,
+                };
+                PendingUpdate::Route {
+                    kind: RouteOperationKind::Add,
+                    task_name: task_name,
+                    ip: ip,
+                    next_hop: next_hop,
+                    replace: replace || pending_replace,
+                }
+            }
+            update => update,
+        };
+        let superseded = pending.insert(destination.clone(), update);
+        if let Some(ref superseded) = superseded {
+            debug!("coalesced update destination={}:{} superseded={:?}", destination.0, destination.1, superseded);
+        }
+        observe_submitted_update(destination.0, superseded.is_some());
+        self.submitted.notify_one();
+    }
+
+    // blocks until something got submitted, then leaves the window for more to come in
+    pub fn next_batch(&self) -> Vec<PendingUpdate> {
+        {
+            let mut pending = self.pending.lock().unwrap();
+            while pending.is_empty() {
+                pending = self.submitted.wait(pending).unwrap();
+            }
+        }
+        thread::sleep(self.window);
+
+        self.take_all()
+    }
+
+    pub fn take_all(&self) -> Vec<PendingUpdate> {
+        let mut pending = self.pending.lock().unwrap();
+        let batch: Vec<PendingUpdate> = pending.values().cloned().collect();
+        pending.clear();
+
+        for kind in &["consul", "route"] {
+            let count = batch.iter().filter(|update| update.destination().0 == *kind).count();
+            if count > 0 {
+                observe_flushed_updates(kind, count);
+            }
+        }
+
+        batch
+    }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage::count::<PendingUpdate>("pending_updates", self.pending.lock().unwrap().len())
+    }
+}
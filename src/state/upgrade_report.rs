@@ -0,0 +1,58 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use chrono::UTC;
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct AgentUpgradeStep {
+    pub node: String,
+    pub batch: usize,
+    pub previous_version: String,
+    // pending, upgrading, upgraded, skipped, failed, rolled_back
+    pub status: String,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct AgentUpgradeReport {
+    pub timestamp: i64,
+    pub version: String,
+    pub url: String,
+    pub batch_size: usize,
+    pub finished: bool,
+    pub ok: bool,
+    pub steps: Vec<AgentUpgradeStep>,
+}
+
+impl AgentUpgradeReport {
+    pub fn new(version: &str, url: &str, batch_size: usize) -> AgentUpgradeReport {
+        AgentUpgradeReport {
+            timestamp: UTC::now().timestamp(),
+            version: version.to_string(),
+            url: url.to_string(),
+            batch_size: batch_size,
+            finished: false,
+            ok: true,
+            steps: vec![],
+        }
+    }
+}
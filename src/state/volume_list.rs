@@ -0,0 +1,162 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use chrono::UTC;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use super::memory_usage::MemoryUsage;
+use super::task_list::Task;
+
+pub struct VolumeList {
+    volume_list: Mutex<BTreeMap<String, VolumeBinding>>,
+}
+
+// where a task's writable host paths hold its data, kept by task name until the task is deleted so a
+// restarted task finds its data. state is bound, or migrating once the node holding the data was
+// unavailable for too long and the task got moved to another node, the data has to follow it there.
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct VolumeBinding {
+    pub task_name: String,
+    pub node_name: String,
+    pub host_paths: Vec<String>,
+    pub state: String,
+    pub bound_since: i64,
+    pub last_used: i64,
+    pub unavailable_since: i64,
+    pub migrated_from: String,
+}
+
+pub fn writable_host_paths(task: &Task) -> Vec<String> {
+    task.volumes.iter().filter(|volume| !volume.read_only_mode).map(|volume| volume.host_path.clone()).collect()
+}
+
+impl VolumeList {
+    pub fn new() -> VolumeList {
+        VolumeList { volume_list: Mutex::new(BTreeMap::new()) }
+    }
+
+    // the previous binding when the task came up on another node than the one holding its data
+    pub fn bind(&self, task: &Task, node_name: String) -> Option<VolumeBinding> {
+        let host_paths = writable_host_paths(task);
+        if host_paths.is_empty() || node_name.is_empty() {
+            return None;
+        }
+
+        let now = UTC::now().timestamp();
+        let mut volume_list = self.volume_list.lock().unwrap();
+        let previous = volume_list.get(&task.name).cloned();
+        let binding = match previous {
+            Some(ref previous) if previous.node_name == node_name => {
+                VolumeBinding {
+                    host_paths: host_paths,
+                    state: "bound".to_string(),
+                    last_used: now,
+                    unavailable_since: 0,
+                    ..previous.clone()
+                }
+            }
+            _ => {
+                VolumeBinding {
+                    task_name: task.name.clone(),
+                    node_name: node_name.clone(),
+                    host_paths: host_paths,
+                    state: "bound".to_string(),
+                    bound_since: now,
+                    last_used: now,
+                    unavailable_since: 0,
+                    migrated_from: previous.as_ref().map(|previous| previous.node_name.clone()).unwrap_or("".to_string()),
+                }
+            }
+        };
+        volume_list.insert(task.name.clone(), binding);
+
+        previous.and_then(|previous| {
+            match previous.node_name == node_name {
+                true => None,
+                false => Some(previous),
+            }
+        })
+    }
+
+    // a binding from the store, as it was when the controller went down
+    pub fn restore(&self, binding: VolumeBinding) {
+        self.volume_list.lock().unwrap().insert(binding.task_name.clone(), binding);
+    }
+
+    // the node each bound task has to go to
+    pub fn get_holders(&self) -> BTreeMap<String, String> {
+        self.volume_list
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|binding| binding.state == "bound")
+            .map(|binding| (binding.task_name.clone(), binding.node_name.clone()))
+            .collect()
+    }
+
+    // a node that stays unavailable for longer than migrate_after lets go of its bindings, those are returned
+    // first so the migration can be announced, every binding that changed comes second
+    pub fn release_unavailable<F>(&self, is_available: F, migrate_after: i64) -> (Vec<VolumeBinding>, Vec<VolumeBinding>)
+        where F: Fn(&String) -> bool
+    {
+        let now = UTC::now().timestamp();
+        let mut volume_list = self.volume_list.lock().unwrap();
+        let mut released = vec![];
+        let mut changed = vec![];
+
+        for binding in volume_list.values_mut().filter(|binding| binding.state == "bound") {
+            if is_available(&binding.node_name) {
+                if binding.unavailable_since == 0 {
+                    continue;
+                }
+                binding.unavailable_since = 0;
+            } else if binding.unavailable_since == 0 {
+                binding.unavailable_since = now;
+            } else if now - binding.unavailable_since > migrate_after {
+                binding.state = "migrating".to_string();
+                released.push(binding.clone());
+            } else {
+                continue;
+            }
+            changed.push(binding.clone());
+        }
+
+        (released, changed)
+    }
+
+    pub fn get_binding(&self, task_name: &String) -> Option<VolumeBinding> {
+        self.volume_list.lock().unwrap().get(task_name).cloned()
+    }
+
+    // the task is gone, one created under the same name later starts without a node to go back to
+    pub fn remove(&self, task_name: &String) -> Option<VolumeBinding> {
+        self.volume_list.lock().unwrap().remove(task_name)
+    }
+
+    pub fn get_bindings(&self) -> Vec<VolumeBinding> {
+        self.volume_list.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage::measure("volumes", self.volume_list.lock().unwrap().values())
+    }
+}
@@ -0,0 +1,81 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use rustc_serialize::json::Json;
+use std::collections::BTreeMap;
+
+// arguments and parameters stay strings on the task, so peers and revisions keep working with them.
+// a list of arguments gets stored as a json array and a map of parameters as a json object, where a value
+// can be a list for parameters given more than once like env. anything else is the plain string from before
+// and gets split on spaces, dashes and equal signs as it always was.
+pub fn encode_argument_list(arguments: &Vec<String>) -> String {
+    Json::Array(arguments.iter().map(|argument| Json::String(argument.clone())).collect()).to_string()
+}
+
+pub fn encode_parameter_map(parameters: &BTreeMap<String, Vec<String>>) -> String {
+    let mut encoded = BTreeMap::new();
+    for (key, values) in parameters {
+        let value = match values.len() {
+            1 => Json::String(values[0].clone()),
+            _ => Json::Array(values.iter().map(|value| Json::String(value.clone())).collect()),
+        };
+        encoded.insert(key.clone(), value);
+    }
+    Json::Object(encoded).to_string()
+}
+
+pub fn decode_arguments(arguments: &str) -> Vec<String> {
+    if let Ok(Json::Array(values)) = Json::from_str(arguments) {
+        if values.iter().all(|value| value.is_string()) {
+            return values.iter().map(|value| value.as_string().unwrap().to_string()).collect();
+        }
+    }
+
+    arguments.split(' ').filter(|argument| !argument.is_empty()).map(|argument| argument.to_string()).collect()
+}
+
+// key and value pairs in the order docker gets them
+pub fn decode_parameters(parameters: &str) -> Vec<(String, String)> {
+    if let Ok(Json::Object(encoded)) = Json::from_str(parameters) {
+        let mut result = vec![];
+        for (key, value) in encoded {
+            match value {
+                Json::String(value) => result.push((key, value)),
+                Json::Array(values) => {
+                    for value in values.iter().filter_map(|value| value.as_string()) {
+                        result.push((key.clone(), value.to_string()));
+                    }
+                }
+                other => result.push((key, other.to_string())),
+            }
+        }
+        return result;
+    }
+
+    let elements: Vec<&str> = parameters.split(|c: char| c == '-' || c == '=' || c == ' ')
+        .filter(|element| !element.is_empty())
+        .collect();
+    elements.chunks(2)
+        .filter(|pair| pair.len() == 2)
+        .map(|pair| (pair[0].to_string(), pair[1].to_string()))
+        .collect()
+}
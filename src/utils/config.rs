@@ -20,7 +20,10 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 // THE SOFTWARE.
 
-use state::{SLA, StateManager, Volume};
+use scheduler::Constraint;
+use std::collections::BTreeMap;
+use state::{HealthCheck, NetworkPolicy, PortRule, RunWindow, SLA, StateManager, TaskSpec, Volume};
+use super::arguments::{encode_argument_list, encode_parameter_map};
 use yaml_rust::yaml::Yaml;
 
 #[derive(Clone, Debug)]
@@ -30,17 +33,37 @@ pub struct Task {
     pub node_name: String,
     pub node_type: String,
     pub node_function: String,
+    pub constraints: Vec<String>,
     pub dependent_service: String,
+    pub restart_dependents: bool,
     pub arguments: String,
     pub parameters: String,
+    pub env: BTreeMap<String, String>,
+    pub env_from_consul_kv: String,
     pub memory: f64,
     pub cpu: f64,
+    pub cpu_policy: String,
     pub volumes: Vec<Volume>,
     pub privileged: bool,
     pub sla: SLA,
+    pub priority: u8,
     pub is_metered: bool,
     pub is_job: bool,
     pub network_type: String,
+    pub run_window: Option<RunWindow>,
+    pub health_check: Option<HealthCheck>,
+    pub network_policy: Option<NetworkPolicy>,
+    pub aliases: Vec<String>,
+    pub slow_start_in_seconds: i64,
+    pub max_runtime_seconds: i64,
+    pub restart_policy: String,
+    pub max_restarts: i64,
+    pub array_count: i64,
+    pub array_var: String,
+    pub instances: i64,
+    pub standby_replicas: i64,
+    pub artifacts: Vec<String>,
+    pub annotations: BTreeMap<String, String>,
 }
 
 pub fn read_task(service: &Yaml, state: &StateManager) -> Task {
@@ -50,21 +73,240 @@ pub fn read_task(service: &Yaml, state: &StateManager) -> Task {
         node_name: read_string(service, "node_name".to_string()),
         node_type: read_string(service, "node_type".to_string()),
         node_function: read_string(service, "node_function".to_string()),
+        constraints: read_constraints(service),
         dependent_service: read_string(service, "dependent_service".to_string()),
-        arguments: read_string_replace_variable(service, "arguments".to_string(), &state),
-        parameters: read_string_replace_variable(service, "parameters".to_string(), &state),
+        restart_dependents: read_bool(service, "restart_dependents".to_string()),
+        arguments: read_arguments(service, &state),
+        parameters: read_parameters(service, &state),
+        env: read_env(service, &state),
+        env_from_consul_kv: read_string(service, "env_from_consul_kv".to_string()),
         memory: read_float(service, "memory".to_string(), super::DEFAULT_MEMORY),
         cpu: read_float(service, "cpu".to_string(), super::DEFAULT_CPU),
+        cpu_policy: read_cpu_policy(service),
         volumes: read_volumes_for_service(service),
         privileged: read_bool(service, "privileged".to_string()),
         sla: read_sla(service),
+        priority: read_priority(service),
         is_metered: read_bool(service, "is_metered".to_string()),
         is_job: read_bool(service, "is_job".to_string()),
         network_type: service["network_type"].as_str().unwrap().to_string(),
+        run_window: read_run_window(service),
+        health_check: read_health_check(service),
+        network_policy: read_network_policy(service),
+        aliases: read_string_list(service, "aliases".to_string()),
+        slow_start_in_seconds: read_int(service, "slow_start_in_seconds".to_string(), 0),
+        max_runtime_seconds: read_int(service, "max_runtime_seconds".to_string(), 0),
+        restart_policy: read_string(service, "restart_policy".to_string()),
+        max_restarts: read_int(service, "max_restarts".to_string(), 0),
+        array_count: read_int(&service["array"], "count".to_string(), 0),
+        array_var: service["array"]["var"].as_str().unwrap_or("INDEX").to_string(),
+        instances: read_int(service, "instances".to_string(), 0),
+        standby_replicas: read_int(service, "standby_replicas".to_string(), 0),
+        artifacts: read_string_list(service, "artifacts".to_string()),
+        annotations: read_string_map(service, "annotations".to_string()),
     };
     new_task.clone()
 }
 
+// a service from any of the configured service groups
+pub fn read_service_definition(state: &StateManager, name: &str) -> Option<Task> {
+    let config = state.get_yaml();
+    let service_groups = match config["api"]["service-groups"].as_vec() {
+        Some(service_groups) => service_groups.clone(),
+        None => return None,
+    };
+
+    for service_group in &service_groups {
+        match service_group["services"].as_vec() {
+            Some(services) => {
+                for service in services {
+                    if read_string(service, "name".to_string()) == name {
+                        return Some(read_task(service, state));
+                    }
+                }
+            }
+            None => {}
+        }
+    }
+    None
+}
+
+pub fn read_service_group(state: &StateManager, name: &str) -> Vec<Task> {
+    let mut result = Vec::new();
+    let config = state.get_yaml();
+
+    match config["api"]["service-groups"].as_vec() {
+        Some(service_groups) => {
+            for service_group in service_groups {
+                if read_string(service_group, "name".to_string()) != name {
+                    continue;
+                }
+                match service_group["services"].as_vec() {
+                    Some(services) => {
+                        for service in services {
+                            result.push(read_task(service, state));
+                        }
+                    }
+                    None => {}
+                }
+            }
+        }
+        None => {}
+    }
+
+    result
+}
+
+// a job array fans out into count jobs named <name>-<index>, with $<var> in arguments and parameters set to the index,
+// a service with instances into replicas named <name>-1 to <name>-<instances>,
+// standby_replicas adds warm spares named <name>-standby-1 to <name>-standby-<standby_replicas> on top
+// stops at the first member or replica that doesn't fit
+pub fn start_task(state: &StateManager, task: &Task, is_system_service: bool) -> Result<(), String> {
+    let none = "".to_string();
+    let standby_group = match !task.is_job && task.standby_replicas > 0 {
+        true => task.name.clone(),
+        false => none.clone(),
+    };
+
+    if task.is_job && task.array_count > 0 {
+        for index in 0..task.array_count {
+            try!(start_array_member(state, task, index, is_system_service));
+        }
+        return Ok(());
+    } else if !task.is_job && task.instances > 0 {
+        for index in 1..task.instances + 1 {
+            let mut replica = task.clone();
+            replica.name = format!("{}-{}", task.name, index);
+            try!(send_start_task(state, &replica, is_system_service, &none, 0, &task.name, index, &standby_group, false));
+        }
+    } else {
+        try!(send_start_task(state, task, is_system_service, &none, 0, &none, 0, &standby_group, false));
+    }
+
+    if !standby_group.is_empty() {
+        for index in 1..task.standby_replicas + 1 {
+            let mut standby = task.clone();
+            standby.name = format!("{}-standby-{}", task.name, index);
+            try!(send_start_task(state, &standby, is_system_service, &none, 0, &none, 0, &standby_group, true));
+        }
+    }
+    Ok(())
+}
+
+// a single member of the job array, scheduled jobs start their runs this way with the scheduled time as index
+pub fn start_array_member(state: &StateManager, task: &Task, index: i64, is_system_service: bool) -> Result<(), String> {
+    let none = "".to_string();
+    let mut member = task.clone();
+    member.name = format!("{}-{}", task.name, index);
+    member.arguments = replace_array_variable(&task.arguments, &task.array_var, index);
+    member.parameters = replace_array_variable(&task.parameters, &task.array_var, index);
+    member.env.insert(task.array_var.clone(), index.to_string());
+    send_start_task(state, &member, is_system_service, &task.name, index, &none, 0, &none, false)
+}
+
+// the name the first task started for a definition gets, to tell whether it's already there
+pub fn first_task_name(task: &Task) -> String {
+    if task.is_job && task.array_count > 0 {
+        format!("{}-0", task.name)
+    } else if !task.is_job && task.instances > 0 {
+        format!("{}-1", task.name)
+    } else {
+        task.name.clone()
+    }
+}
+
+// $INDEX_FOO is a variable of its own, a bare $INDEX only gets replaced where the name ends
+fn replace_array_variable(value: &String, variable: &String, index: i64) -> String {
+    let braced = str::replace(value, &format!("${{{}}}", variable), &index.to_string());
+    let token = format!("${}", variable);
+    let mut replaced = String::new();
+    let mut rest = &braced[..];
+
+    while let Some(position) = rest.find(&token[..]) {
+        let after = &rest[position + token.len()..];
+        let whole = match after.chars().next() {
+            Some(next) => !(next.is_alphanumeric() || next == '_'),
+            None => true,
+        };
+        replaced.push_str(&rest[..position]);
+        match whole {
+            true => replaced.push_str(&index.to_string()),
+            false => replaced.push_str(&token),
+        }
+        rest = after;
+    }
+    replaced.push_str(rest);
+
+    replaced
+}
+
+fn send_start_task(state: &StateManager,
+                   task: &Task,
+                   is_system_service: bool,
+                   array_name: &String,
+                   array_index: i64,
+                   replica_group: &String,
+                   replica_index: i64,
+                   standby_group: &String,
+                   standby: bool)
+                   -> Result<(), String> {
+    let spec = task_spec(task,
+                         is_system_service,
+                         array_name,
+                         array_index,
+                         replica_group,
+                         replica_index,
+                         standby_group,
+                         standby);
+    state.send_start_task(&spec)
+}
+
+// records a task that is already running under its definition instead of requesting it
+pub fn adopt_task(state: &StateManager, slave_id: &String, task: &Task, is_system_service: bool) {
+    let none = "".to_string();
+    let spec = task_spec(task, is_system_service, &none, 0, &none, 0, &none, false);
+    state.send_adopt_task(&spec, slave_id)
+}
+
+fn task_spec(task: &Task,
+             is_system_service: bool,
+             array_name: &String,
+             array_index: i64,
+             replica_group: &String,
+             replica_index: i64,
+             standby_group: &String,
+             standby: bool)
+             -> TaskSpec {
+    TaskSpec::new(&task.name, &task.image, &task.network_type)
+        .node(&task.node_name, &task.node_type, &task.node_function)
+        .constraints(task.constraints.clone())
+        .dependent_service(&task.dependent_service, task.restart_dependents)
+        .arguments(&task.arguments)
+        .parameters(&task.parameters)
+        .env(task.env.clone())
+        .env_from_consul_kv(&task.env_from_consul_kv)
+        .resources(task.memory, task.cpu, &task.cpu_policy)
+        .volumes(task.volumes.clone())
+        .privileged(task.privileged)
+        .sla(task.sla.clone())
+        .priority(task.priority)
+        .metered(task.is_metered)
+        .system_service(is_system_service)
+        .job(task.is_job)
+        .run_window(task.run_window.clone())
+        .health_check(task.health_check.clone())
+        .network_policy(task.network_policy.clone())
+        .aliases(task.aliases.clone())
+        .slow_start(task.slow_start_in_seconds)
+        .max_runtime(task.max_runtime_seconds)
+        .restart_policy(&task.restart_policy, task.max_restarts)
+        .array_member(array_name, array_index)
+        .replica(replica_group, replica_index)
+        .standby(standby_group, standby)
+        .artifacts(task.artifacts.clone())
+        .annotations(task.annotations.clone())
+}
+
 pub fn read_string(element: &Yaml, key: String) -> String {
     match element[key.as_ref()].is_badvalue() {
         true => "".to_string(),
@@ -84,24 +326,66 @@ pub fn read_string_replace_variable(element: &Yaml, key: String, state: &StateMa
     resolved_arguments.clone()
 }
 
+pub fn read_string_list(element: &Yaml, key: String) -> Vec<String> {
+    let mut result = Vec::new();
+
+    match element[key.as_ref()].as_vec() {
+        Some(values) => {
+            for value in values {
+                match value.as_str() {
+                    Some(string) => result.push(string.to_string()),
+                    None => {}
+                }
+            }
+        }
+        None => {}
+    }
+
+    result
+}
+
+pub fn read_string_map(element: &Yaml, key: String) -> BTreeMap<String, String> {
+    let mut result = BTreeMap::new();
+
+    match element[key.as_ref()].as_hash() {
+        Some(values) => {
+            for (key, value) in values {
+                match (key.as_str(), value.as_str()) {
+                    (Some(key), Some(value)) => {
+                        result.insert(key.to_string(), value.to_string());
+                    }
+                    _ => {}
+                }
+            }
+        }
+        None => {}
+    }
+
+    result
+}
+
+// the typed readers also take a string, that's what a ${VAR} substituted by the config loader is
 pub fn read_bool(element: &Yaml, key: String) -> bool {
-    match element[key.as_ref()].is_badvalue() {
+    let value = &element[key.as_ref()];
+    match value.is_badvalue() {
         true => false,
-        false => element[key.as_ref()].as_bool().unwrap(),
+        false => value.as_bool().or_else(|| value.as_str().and_then(|text| text.trim().parse().ok())).unwrap(),
     }
 }
 
 pub fn read_float(element: &Yaml, key: String, default: f64) -> f64 {
-    match element[key.as_ref()].is_badvalue() {
+    let value = &element[key.as_ref()];
+    match value.is_badvalue() {
         true => default,
-        false => element[key.as_ref()].as_f64().unwrap(),
+        false => value.as_f64().or_else(|| value.as_str().and_then(|text| text.trim().parse().ok())).unwrap(),
     }
 }
 
 pub fn read_int(element: &Yaml, key: String, default: i64) -> i64 {
-    match element[key.as_ref()].is_badvalue() {
+    let value = &element[key.as_ref()];
+    match value.is_badvalue() {
         true => default,
-        false => element[key.as_ref()].as_i64().unwrap(),
+        false => value.as_i64().or_else(|| value.as_str().and_then(|text| text.trim().parse().ok())).unwrap(),
     }
 }
 
@@ -126,6 +410,174 @@ fn read_volumes_for_service(service: &Yaml) -> Vec<Volume> {
     result.clone()
 }
 
+fn read_run_window(service: &Yaml) -> Option<RunWindow> {
+    let run_window = &service["run_window"];
+    match run_window.is_badvalue() {
+        true => None,
+        false => {
+            Some(RunWindow {
+                days: read_string_list(run_window, "days".to_string()),
+                start: read_string(run_window, "start".to_string()),
+                end: read_string(run_window, "end".to_string()),
+                timezone: read_string(run_window, "timezone".to_string()),
+            })
+        }
+    }
+}
+
+// healthcheck: {type: http, path: /health, port: 8080, interval: 10, failures: 3}
+// held to what the api takes, a check that can never pass would restart the task forever
+fn read_health_check(service: &Yaml) -> Option<HealthCheck> {
+    let health_check = &service["healthcheck"];
+    if health_check.is_badvalue() {
+        return None;
+    }
+
+    let health_check = HealthCheck {
+        check_type: read_string(health_check, "type".to_string()),
+        path: read_string(health_check, "path".to_string()),
+        port: read_int(health_check, "port".to_string(), 0),
+        interval_in_seconds: read_int(health_check, "interval".to_string(), 10),
+        failures: read_int(health_check, "failures".to_string(), 3),
+    };
+    let valid = (health_check.check_type == "http" || health_check.check_type == "tcp") && health_check.port > 0 &&
+                health_check.port <= 65535 && health_check.interval_in_seconds > 0 &&
+                health_check.failures > 0;
+    match valid {
+        true => Some(health_check),
+        false => {
+            warn!("ignoring healthcheck service={:?} type={} port={}",
+                  service["name"].as_str(),
+                  health_check.check_type,
+                  health_check.port);
+            None
+        }
+    }
+}
+
+// network_policy: {ingress: [{port: 8080, protocol: tcp}]}, protocol defaults to tcp
+fn read_network_policy(service: &Yaml) -> Option<NetworkPolicy> {
+    let network_policy = &service["network_policy"];
+    if network_policy.is_badvalue() {
+        return None;
+    }
+
+    let mut ingress = vec![];
+    for rule in network_policy["ingress"].as_vec().cloned().unwrap_or(vec![]) {
+        let protocol = match read_string(&rule, "protocol".to_string()).as_ref() {
+            "" => "tcp".to_string(),
+            protocol => protocol.to_string(),
+        };
+        let port = read_int(&rule, "port".to_string(), 0);
+        match port > 0 && port <= 65535 && (protocol == "tcp" || protocol == "udp") {
+            true => {
+                ingress.push(PortRule {
+                    port: port,
+                    protocol: protocol,
+                })
+            }
+            false => warn!("ignoring ingress rule service={:?} port={} protocol={}", service["name"].as_str(), port, protocol),
+        }
+    }
+
+    Some(NetworkPolicy { ingress: ingress })
+}
+
+// invalid constraints are reported and left out instead of keeping the task from ever being placed
+fn read_constraints(service: &Yaml) -> Vec<String> {
+    let mut result = vec![];
+
+    for source in read_string_list(service, "constraints".to_string()) {
+        match Constraint::parse(&source) {
+            Ok(_) => result.push(source),
+            Err(error) => warn!("ignoring constraint service={:?} error={}", service["name"].as_str(), error),
+        }
+    }
+
+    result
+}
+
+// a list of arguments is passed on as is, so they can hold spaces, quotes and newlines
+fn read_arguments(service: &Yaml, state: &StateManager) -> String {
+    match service["arguments"].as_vec() {
+        Some(arguments) => {
+            let arguments = arguments.iter()
+                .filter_map(read_scalar)
+                .map(|argument| str::replace(&argument, "$MASTER_IP", &state.get_master_ip()))
+                .collect();
+            encode_argument_list(&arguments)
+        }
+        None => read_string_replace_variable(service, "arguments".to_string(), state),
+    }
+}
+
+// a map of docker parameters, a list as value gives the parameter once for every entry
+fn read_parameters(service: &Yaml, state: &StateManager) -> String {
+    match service["parameters"].as_hash() {
+        Some(parameters) => {
+            let mut result = BTreeMap::new();
+            for (key, value) in parameters {
+                let key = match key.as_str() {
+                    Some(key) => key.to_string(),
+                    None => continue,
+                };
+                let values: Vec<String> = match value.as_vec() {
+                    Some(values) => values.iter().filter_map(read_scalar).collect(),
+                    None => read_scalar(value).into_iter().collect(),
+                };
+                let values = values.iter().map(|value| str::replace(value, "$MASTER_IP", &state.get_master_ip())).collect();
+                result.insert(key, values);
+            }
+            encode_parameter_map(&result)
+        }
+        None => read_string_replace_variable(service, "parameters".to_string(), state),
+    }
+}
+
+// in the order given, values that aren't scalars are skipped
+fn read_env(service: &Yaml, state: &StateManager) -> BTreeMap<String, String> {
+    let mut result = BTreeMap::new();
+    if let Some(env) = service["env"].as_hash() {
+        for (name, value) in env {
+            match (name.as_str(), read_scalar(value)) {
+                (Some(name), Some(value)) => {
+                    result.insert(name.to_string(), str::replace(&value, "$MASTER_IP", &state.get_master_ip()));
+                }
+                _ => warn!("ignoring env entry service={:?} name={:?}", service["name"].as_str(), name),
+            }
+        }
+    }
+    result
+}
+
+fn read_scalar(value: &Yaml) -> Option<String> {
+    match *value {
+        Yaml::String(ref value) => Some(value.clone()),
+        Yaml::Integer(value) => Some(value.to_string()),
+        Yaml::Real(ref value) => Some(value.clone()),
+        Yaml::Boolean(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+fn read_cpu_policy(service: &Yaml) -> String {
+    match read_string(service, "cpu_policy".to_string()).as_ref() {
+        "quota" => "quota".to_string(),
+        _ => "shares".to_string(),
+    }
+}
+
+// 0 to 255, higher ones may take the place of lower ones when resources run out
+fn read_priority(service: &Yaml) -> u8 {
+    match read_int(service, "priority".to_string(), 0) {
+        priority if priority >= 0 && priority <= 255 => priority as u8,
+        priority => {
+            warn!("ignoring priority service={:?} priority={}", service["name"].as_str(), priority);
+            0
+        }
+    }
+}
+
 fn read_sla(service: &Yaml) -> SLA {
     let sla: SLA;
     sla = match service["sla"].is_badvalue() {
@@ -0,0 +1,261 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use yaml_rust::{Yaml, YamlLoader};
+use yaml_rust::yaml::Hash;
+
+const INCLUDE_TAG: &'static str = "!include ";
+// deep enough for any layout of fragments, shallow enough to stop an include cycle
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+// the keys the controller can't do without, checked up front instead of failing on an unwrap later
+const REQUIRED_INTEGERS: &'static [&'static str] = &["healthcheck.poll_interval_in_seconds",
+                                                     "stateclean.poll_interval_in_seconds",
+                                                     "stateclean.timeout_in_seconds",
+                                                     "statesync.poll_interval_in_seconds"];
+const REQUIRED_LISTS: &'static [&'static str] = &["nodes", "dns-addons", "healthcheck.system_services"];
+const REQUIRED_SERVICE_KEYS: &'static [&'static str] = &["name", "image_name", "network_type"];
+
+// the documents of a config file are merged in order, later ones win key by key so a base document can be followed
+// by per-environment overrides. a value of !include <file> is replaced by that file, relative to the one including it,
+// and ${VAR} or ${VAR:-default} by the environment variable, ${MASTER_IP} is left to the controller like $MASTER_IP
+pub fn load_config(path: &str) -> Result<Yaml, String> {
+    let config = try!(load_file(Path::new(path), 0));
+    match config {
+        Yaml::Hash(_) => {}
+        _ => return Err(format!("{}: the top level has to be a mapping", path)),
+    }
+    try!(validate(&config).map_err(|error| format!("{}: {}", path, error)));
+    Ok(config)
+}
+
+fn load_file(path: &Path, depth: usize) -> Result<Yaml, String> {
+    if depth > MAX_INCLUDE_DEPTH {
+        return Err(format!("{}: includes nested deeper than {}", path.display(), MAX_INCLUDE_DEPTH));
+    }
+
+    let mut content = String::new();
+    try!(File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut content))
+        .map_err(|error| format!("{}: {}", path.display(), error)));
+    let documents = try!(YamlLoader::load_from_str(&quote_includes(&content))
+        .map_err(|error| format!("{}: {}", path.display(), error)));
+
+    let directory = path.parent().unwrap_or(Path::new("."));
+    let mut merged = Yaml::Null;
+    for document in documents {
+        if let Yaml::Null = document {
+            continue;
+        }
+        let resolved = try!(resolve(document, "", directory, depth)
+            .map_err(|error| format!("{}: {}", path.display(), error)));
+        merged = merge(merged, resolved);
+    }
+    Ok(merged)
+}
+
+// yaml-rust drops tags it doesn't know, so !include becomes a quoted string the resolver can spot
+fn quote_includes(content: &str) -> String {
+    let lines: Vec<String> = content.lines()
+        .map(|line| {
+            match line.find(INCLUDE_TAG) {
+                Some(index) if is_value_position(&line[..index]) => {
+                    let file = line[index + INCLUDE_TAG.len()..].trim().trim_matches(|c: char| c == '"' || c == '\'');
+                    format!("{}\"{}{}\"", &line[..index], INCLUDE_TAG, file)
+                }
+                _ => line.to_string(),
+            }
+        })
+        .collect();
+    lines.join("\n")
+}
+
+fn is_value_position(before: &str) -> bool {
+    let before = before.trim_right();
+    before.is_empty() || before.ends_with(':') || before.ends_with('-')
+}
+
+fn resolve(value: Yaml, key: &str, directory: &Path, depth: usize) -> Result<Yaml, String> {
+    match value {
+        Yaml::Hash(hash) => {
+            let mut resolved = Hash::new();
+            for (name, value) in hash {
+                let path = match name.as_str() {
+                    Some(name) if key.is_empty() => name.to_string(),
+                    Some(name) => format!("{}.{}", key, name),
+                    None => format!("{}.{:?}", key, name),
+                };
+                let value = try!(resolve(value, &path, directory, depth));
+                resolved.insert(name, value);
+            }
+            Ok(Yaml::Hash(resolved))
+        }
+        Yaml::Array(values) => {
+            let mut resolved = vec![];
+            for (index, value) in values.into_iter().enumerate() {
+                resolved.push(try!(resolve(value, &format!("{}[{}]", key, index), directory, depth)));
+            }
+            Ok(Yaml::Array(resolved))
+        }
+        Yaml::String(ref text) if text.starts_with(INCLUDE_TAG) => {
+            let included = directory.join(text[INCLUDE_TAG.len()..].trim());
+            load_file(&included, depth + 1).map_err(|error| format!("{}: {}", key, error))
+        }
+        // substituted values stay strings, a secret like 0123 or true must not turn into a number or a bool.
+        // read_int, read_float and read_bool take numbers and bools given as strings
+        Yaml::String(ref text) if text.contains("${") => {
+            substitute(text).map(Yaml::String).map_err(|error| format!("{}: {}", key, error))
+        }
+        value => Ok(value),
+    }
+}
+
+fn substitute(text: &str) -> Result<String, String> {
+    let mut result = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find("${") {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => return Err(format!("unterminated variable in {}", text)),
+        };
+        result.push_str(&rest[..start]);
+
+        let expression = &rest[start + 2..end];
+        let (name, default) = match expression.find(":-") {
+            Some(index) => (&expression[..index], Some(&expression[index + 2..])),
+            None => (expression, None),
+        };
+        if name == "MASTER_IP" {
+            result.push_str("$MASTER_IP");
+        } else {
+            match (env::var(name), default) {
+                (Ok(value), _) => result.push_str(&value),
+                (Err(_), Some(default)) => result.push_str(default),
+                (Err(_), None) => return Err(format!("environment variable {} is not set", name)),
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+// hashes merge key by key, anything else is replaced by the later value
+fn merge(base: Yaml, overlay: Yaml) -> Yaml {
+    match (base, overlay) {
+        (Yaml::Hash(mut base), Yaml::Hash(overlay)) => {
+            for (key, value) in overlay {
+                let merged = match base.remove(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => value,
+                };
+                base.insert(key, merged);
+            }
+            Yaml::Hash(base)
+        }
+        (_, overlay) => overlay,
+    }
+}
+
+fn validate(config: &Yaml) -> Result<(), String> {
+    for key in REQUIRED_INTEGERS {
+        match *lookup(config, key) {
+            Yaml::Integer(_) => {}
+            Yaml::String(_) => return Err(format!("{} has to be an integer, ${{VAR}} gives a string", key)),
+            _ => return Err(format!("{} has to be an integer", key)),
+        }
+    }
+    for key in REQUIRED_LISTS {
+        if lookup(config, key).as_vec().is_none() {
+            return Err(format!("{} has to be a list", key));
+        }
+    }
+    // power polling only starts with a powercheck section
+    if !config["powercheck"].is_badvalue() && config["powercheck"]["poll_interval_in_seconds"].as_i64().is_none() {
+        return Err("powercheck.poll_interval_in_seconds has to be an integer".to_string());
+    }
+    // no segments to keep would remove the one just written
+    if let Some(segments) = config["metric-history"]["segments"].as_i64() {
+        if segments < 1 {
+            return Err("metric-history.segments has to be at least 1".to_string());
+        }
+    }
+    // the scheduler driver and the fboss and snaproute client libraries only speak cleartext
+    if let Some(collaborators) = config["http"]["tls"]["collaborators"].as_hash() {
+        for collaborator in collaborators.keys().filter_map(|collaborator| collaborator.as_str()) {
+            match collaborator {
+                "mesos" | "fboss" | "snaproute" => {
+                    return Err(format!("http.tls.collaborators lists {}, its client has no tls support", collaborator))
+                }
+                _ => {}
+            }
+        }
+    }
+    // without the consul client leader election waits forever and registrations go nowhere
+    if !cfg!(feature = "consul") {
+        if config["leader-election"]["enabled"].as_bool() == Some(true) {
+            return Err("leader-election needs consul, build with --features consul".to_string());
+        }
+        if !config["consul"].is_badvalue() {
+            return Err("the consul section needs consul, build with --features consul or remove it".to_string());
+        }
+    }
+
+    try!(validate_services(config["healthcheck"]["system_services"].as_vec(), "healthcheck.system_services"));
+    if let Some(service_groups) = config["api"]["service-groups"].as_vec() {
+        for (index, service_group) in service_groups.iter().enumerate() {
+            try!(validate_services(service_group["services"].as_vec(),
+                                   &format!("api.service-groups[{}].services", index)));
+        }
+    }
+    Ok(())
+}
+
+fn validate_services(services: Option<&Vec<Yaml>>, key: &str) -> Result<(), String> {
+    for (index, service) in services.into_iter().flat_map(|services| services.iter()).enumerate() {
+        for required in REQUIRED_SERVICE_KEYS {
+            if service[*required].as_str().is_none() {
+                return Err(format!("{}[{}].{} has to be a string", key, index, required));
+            }
+        }
+        for (volume_index, volume) in service["volumes"].as_vec().into_iter().flat_map(|volumes| volumes.iter()).enumerate() {
+            if volume["host_path"].as_str().is_none() || volume["container_path"].as_str().is_none() ||
+               volume["read_only_mode"].as_bool().is_none() {
+                return Err(format!("{}[{}].volumes[{}] needs host_path, container_path and read_only_mode",
+                                   key,
+                                   index,
+                                   volume_index));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn lookup<'a>(config: &'a Yaml, key: &str) -> &'a Yaml {
+    key.split('.').fold(config, |value, name| &value[name])
+}
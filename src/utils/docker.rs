@@ -22,19 +22,28 @@
 
 use rustc_serialize::json::Json;
 use state::StateManager;
+use std::net::Ipv4Addr;
+use std::str::FromStr;
 
-pub fn handle_inspect_data(state_manager: &StateManager, task_name: &String, inspect_data: &String, slave_id: &String) {
+pub fn handle_inspect_data(state_manager: &StateManager, task_name: &String, inspect_data: &String, slave_id: &String) -> bool {
     // println!("{}", inspect_data);
 
     let json = Json::from_str(&inspect_data).unwrap();
 
     let id = json.as_array().unwrap()[0].find_path(&["Id"]).unwrap().as_string().unwrap();
     let node_name = json.as_array().unwrap()[0].find_path(&["Config", "Hostname"]).unwrap().as_string().unwrap();
-    let mut new_ip = match json.as_array().unwrap()[0].find_path(&["NetworkSettings", "Networks", "torc", "IPAddress"]) {
+    let network_ip = json.as_array().unwrap()[0].find_path(&["NetworkSettings", "Networks", "torc", "IPAddress"]);
+    let mut new_ip = match network_ip {
         None => "".to_string(),
-        Some(ip) => ip.as_string().unwrap().to_string(),
+        Some(ip) => ip.as_string().unwrap_or("").to_string(),
     };
 
+    if network_ip.is_some() && !is_valid_ip(&new_ip) {
+        error!("network driver failed to assign ip task={} ip=[{}]", task_name, new_ip);
+        state_manager.send_update_task_info(task_name.to_string(), id.to_string(), "".to_string(), slave_id.clone());
+        return false;
+    }
+
     if new_ip.len() == 0 {
         match state_manager.request_node(node_name.to_string()) {
             Some(node) => new_ip = node.ip.clone(),
@@ -46,4 +55,12 @@ pub fn handle_inspect_data(state_manager: &StateManager, task_name: &String, ins
                                         id.to_string(),
                                         new_ip.clone(),
                                         slave_id.clone());
+    true
+}
+
+fn is_valid_ip(ip: &String) -> bool {
+    match Ipv4Addr::from_str(ip) {
+        Ok(address) => !address.is_unspecified(),
+        Err(_) => false,
+    }
 }
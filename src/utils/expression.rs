@@ -0,0 +1,273 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use std::collections::HashMap;
+use std::iter::Peekable;
+use std::str::Chars;
+
+#[derive(Clone, Debug)]
+pub enum Expression {
+    Number(f64),
+    Variable(String),
+    Negate(Box<Expression>),
+    Binary(Operator, Box<Expression>, Box<Expression>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Identifier(String),
+    Operator(Operator),
+    OpenParen,
+    CloseParen,
+}
+
+impl Expression {
+    // variables not listed in known_variables are rejected at compile time
+    pub fn compile(source: &str, known_variables: &[&str]) -> Result<Expression, String> {
+        let tokens = try!(tokenize(source));
+        let mut position = 0;
+        let expression = try!(parse_comparison(&tokens, &mut position));
+
+        if position < tokens.len() {
+            return Err(format!("unexpected token {:?} in '{}'", tokens[position], source));
+        }
+
+        try!(expression.check_variables(known_variables));
+        Ok(expression)
+    }
+
+    pub fn evaluate(&self, variables: &HashMap<String, f64>) -> f64 {
+        match *self {
+            Expression::Number(value) => value,
+            Expression::Variable(ref name) => *variables.get(name).unwrap_or(&0.0),
+            Expression::Negate(ref inner) => -inner.evaluate(variables),
+            Expression::Binary(ref operator, ref left, ref right) => {
+                let left = left.evaluate(variables);
+                let right = right.evaluate(variables);
+                match *operator {
+                    Operator::Add => left + right,
+                    Operator::Subtract => left - right,
+                    Operator::Multiply => left * right,
+                    Operator::Divide => if right == 0.0 { 0.0 } else { left / right },
+                    Operator::Less => as_number(left < right),
+                    Operator::LessEqual => as_number(left <= right),
+                    Operator::Greater => as_number(left > right),
+                    Operator::GreaterEqual => as_number(left >= right),
+                    Operator::Equal => as_number(left == right),
+                    Operator::NotEqual => as_number(left != right),
+                }
+            }
+        }
+    }
+
+    fn check_variables(&self, known_variables: &[&str]) -> Result<(), String> {
+        match *self {
+            Expression::Number(_) => Ok(()),
+            Expression::Variable(ref name) => {
+                if known_variables.contains(&name.as_ref()) {
+                    Ok(())
+                } else {
+                    Err(format!("unknown variable {}", name))
+                }
+            }
+            Expression::Negate(ref inner) => inner.check_variables(known_variables),
+            Expression::Binary(_, ref left, ref right) => {
+                try!(left.check_variables(known_variables));
+                right.check_variables(known_variables)
+            }
+        }
+    }
+}
+
+fn as_number(value: bool) -> f64 {
+    if value { 1.0 } else { 0.0 }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = source.chars().peekable();
+
+    loop {
+        let c = match chars.peek() {
+            Some(c) => *c,
+            None => break,
+        };
+
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_digit(10) || c == '.' {
+            let number = read_while(&mut chars, |c| c.is_digit(10) || c == '.');
+            match number.parse::<f64>() {
+                Ok(value) => tokens.push(Token::Number(value)),
+                Err(_) => return Err(format!("invalid number {}", number)),
+            }
+        } else if c.is_alphabetic() || c == '_' {
+            let identifier = read_while(&mut chars, |c| c.is_alphanumeric() || c == '_' || c == '.');
+            tokens.push(Token::Identifier(identifier));
+        } else {
+            chars.next();
+            let next_is_equal = chars.peek() == Some(&'=');
+            let token = match (c, next_is_equal) {
+                ('(', _) => Token::OpenParen,
+                (')', _) => Token::CloseParen,
+                ('+', _) => Token::Operator(Operator::Add),
+                ('-', _) => Token::Operator(Operator::Subtract),
+                ('*', _) => Token::Operator(Operator::Multiply),
+                ('/', _) => Token::Operator(Operator::Divide),
+                ('<', true) => Token::Operator(Operator::LessEqual),
+                ('<', false) => Token::Operator(Operator::Less),
+                ('>', true) => Token::Operator(Operator::GreaterEqual),
+                ('>', false) => Token::Operator(Operator::Greater),
+                ('=', true) => Token::Operator(Operator::Equal),
+                ('!', true) => Token::Operator(Operator::NotEqual),
+                _ => return Err(format!("unexpected character '{}'", c)),
+            };
+            match token {
+                Token::Operator(Operator::LessEqual) |
+                Token::Operator(Operator::GreaterEqual) |
+                Token::Operator(Operator::Equal) |
+                Token::Operator(Operator::NotEqual) => {
+                    chars.next();
+                }
+                _ => {}
+            }
+            tokens.push(token);
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn read_while<F>(chars: &mut Peekable<Chars>, predicate: F) -> String
+    where F: Fn(char) -> bool
+{
+    let mut result = String::new();
+    loop {
+        match chars.peek() {
+            Some(c) if predicate(*c) => result.push(*c),
+            _ => break,
+        }
+        chars.next();
+    }
+    result
+}
+
+fn parse_comparison(tokens: &Vec<Token>, position: &mut usize) -> Result<Expression, String> {
+    let left = try!(parse_additive(tokens, position));
+
+    match tokens.get(*position) {
+        Some(&Token::Operator(ref operator)) if is_comparison(operator) => {
+            *position += 1;
+            let right = try!(parse_additive(tokens, position));
+            Ok(Expression::Binary(operator.clone(), Box::new(left), Box::new(right)))
+        }
+        _ => Ok(left),
+    }
+}
+
+fn parse_additive(tokens: &Vec<Token>, position: &mut usize) -> Result<Expression, String> {
+    let mut left = try!(parse_term(tokens, position));
+
+    loop {
+        match tokens.get(*position) {
+            Some(&Token::Operator(ref operator)) if *operator == Operator::Add || *operator == Operator::Subtract => {
+                *position += 1;
+                let right = try!(parse_term(tokens, position));
+                left = Expression::Binary(operator.clone(), Box::new(left), Box::new(right));
+            }
+            _ => return Ok(left),
+        }
+    }
+}
+
+fn parse_term(tokens: &Vec<Token>, position: &mut usize) -> Result<Expression, String> {
+    let mut left = try!(parse_unary(tokens, position));
+
+    loop {
+        match tokens.get(*position) {
+            Some(&Token::Operator(ref operator)) if *operator == Operator::Multiply || *operator == Operator::Divide => {
+                *position += 1;
+                let right = try!(parse_unary(tokens, position));
+                left = Expression::Binary(operator.clone(), Box::new(left), Box::new(right));
+            }
+            _ => return Ok(left),
+        }
+    }
+}
+
+fn parse_unary(tokens: &Vec<Token>, position: &mut usize) -> Result<Expression, String> {
+    match tokens.get(*position) {
+        Some(&Token::Operator(Operator::Subtract)) => {
+            *position += 1;
+            let inner = try!(parse_unary(tokens, position));
+            Ok(Expression::Negate(Box::new(inner)))
+        }
+        _ => parse_primary(tokens, position),
+    }
+}
+
+fn parse_primary(tokens: &Vec<Token>, position: &mut usize) -> Result<Expression, String> {
+    let token = match tokens.get(*position) {
+        Some(token) => token.clone(),
+        None => return Err("unexpected end of expression".to_string()),
+    };
+    *position += 1;
+
+    match token {
+        Token::Number(value) => Ok(Expression::Number(value)),
+        Token::Identifier(name) => Ok(Expression::Variable(name)),
+        Token::OpenParen => {
+            let inner = try!(parse_comparison(tokens, position));
+            match tokens.get(*position) {
+                Some(&Token::CloseParen) => {
+                    *position += 1;
+                    Ok(inner)
+                }
+                _ => Err("missing closing parenthesis".to_string()),
+            }
+        }
+        _ => Err(format!("unexpected token {:?}", token)),
+    }
+}
+
+fn is_comparison(operator: &Operator) -> bool {
+    match *operator {
+        Operator::Less | Operator::LessEqual | Operator::Greater | Operator::GreaterEqual | Operator::Equal |
+        Operator::NotEqual => true,
+        _ => false,
+    }
+}
@@ -0,0 +1,53 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use chrono::UTC;
+use env_logger::LogBuilder;
+use log::{LogLevelFilter, LogRecord};
+use std::env;
+use std::str::FromStr;
+
+// records go to stderr as "<time> <level> <module>: <message> key=value ...",
+// TORC_LOG takes an env_logger filter like "info,torc_scheduler::state=debug" and wins over the configured level
+pub fn init_logging(level: &str) {
+    let mut builder = LogBuilder::new();
+    builder.format(|record: &LogRecord| {
+        format!("{} {} {}: {}",
+                UTC::now().format("%Y-%m-%dT%H:%M:%S%.3fZ"),
+                record.level(),
+                record.target(),
+                record.args())
+    });
+    builder.filter(None, LogLevelFilter::from_str(level).unwrap_or(LogLevelFilter::Info));
+
+    match env::var("TORC_LOG") {
+        Ok(filter) => {
+            builder.parse(&filter);
+        }
+        Err(_) => {}
+    }
+
+    match builder.init() {
+        Ok(_) => {}
+        Err(error) => println!("logging already initialized: {}", error),
+    }
+}
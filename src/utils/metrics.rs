@@ -0,0 +1,236 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 AT&T
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+static BUCKETS: &'static [f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+struct CallStats {
+    buckets: Vec<u64>,
+    count: u64,
+    sum: f64,
+    errors: u64,
+}
+
+struct DurationStats {
+    buckets: Vec<u64>,
+    count: u64,
+    sum: f64,
+}
+
+impl DurationStats {
+    fn new() -> DurationStats {
+        DurationStats {
+            buckets: vec![0; BUCKETS.len()],
+            count: 0,
+            sum: 0.0,
+        }
+    }
+
+    fn observe(&mut self, duration: Duration) {
+        let seconds = as_seconds(duration);
+        for (index, bound) in BUCKETS.iter().enumerate() {
+            if seconds <= *bound {
+                self.buckets[index] += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+
+    fn render(&self, name: &str, labels: &String, result: &mut String) {
+        for (index, bound) in BUCKETS.iter().enumerate() {
+            result.push_str(&format!("{}_bucket{{{},le=\"{}\"}} {}\n", name, labels, bound, self.buckets[index]));
+        }
+        result.push_str(&format!("{}_bucket{{{},le=\"+Inf\"}} {}\n", name, labels, self.count));
+        result.push_str(&format!("{}_sum{{{}}} {}\n", name, labels, self.sum));
+        result.push_str(&format!("{}_count{{{}}} {}\n", name, labels, self.count));
+    }
+}
+
+#[derive(Default)]
+struct CoalescingStats {
+    submitted: u64,
+    superseded: u64,
+    applied: u64,
+    batches: u64,
+    largest_batch: u64,
+}
+
+lazy_static! {
+    static ref CALLS: Mutex<BTreeMap<(String, String), CallStats>> = {
+        Mutex::new(BTreeMap::new())
+    };
+    static ref COALESCING: Mutex<BTreeMap<String, CoalescingStats>> = {
+        Mutex::new(BTreeMap::new())
+    };
+    static ref STATE_MESSAGES: Mutex<BTreeMap<(String, String), (DurationStats, DurationStats)>> = {
+        Mutex::new(BTreeMap::new())
+    };
+}
+
+pub struct CallTimer {
+    collaborator: String,
+    target: String,
+    started: Instant,
+}
+
+impl CallTimer {
+    pub fn start(collaborator: &str, target: &str) -> CallTimer {
+        CallTimer {
+            collaborator: collaborator.to_string(),
+            target: target.to_string(),
+            started: Instant::now(),
+        }
+    }
+
+    pub fn finish(self, success: bool) {
+        observe_call(&self.collaborator, &self.target, self.started.elapsed(), success);
+    }
+}
+
+pub fn observe_call(collaborator: &String, target: &String, duration: Duration, success: bool) {
+    let seconds = as_seconds(duration);
+
+    let mut calls = CALLS.lock().unwrap();
+    let stats = calls.entry((collaborator.clone(), target.clone())).or_insert(CallStats {
+        buckets: vec![0; BUCKETS.len()],
+        count: 0,
+        sum: 0.0,
+        errors: 0,
+    });
+
+    for (index, bound) in BUCKETS.iter().enumerate() {
+        if seconds <= *bound {
+            stats.buckets[index] += 1;
+        }
+    }
+    stats.count += 1;
+    stats.sum += seconds;
+    if !success {
+        stats.errors += 1;
+    }
+}
+
+// waited is the time between the state thread taking a message off its channel and getting to it
+pub fn observe_state_message(message_type: &str, urgent: bool, waited: Duration, processed: Duration) {
+    let priority = match urgent {
+        true => "urgent",
+        false => "normal",
+    };
+    let mut messages = STATE_MESSAGES.lock().unwrap();
+    let stats = messages.entry((message_type.to_string(), priority.to_string()))
+        .or_insert((DurationStats::new(), DurationStats::new()));
+    stats.0.observe(waited);
+    stats.1.observe(processed);
+}
+
+pub fn observe_submitted_update(destination: &str, superseded: bool) {
+    let mut coalescing = COALESCING.lock().unwrap();
+    let stats = coalescing.entry(destination.to_string()).or_insert(CoalescingStats::default());
+    stats.submitted += 1;
+    if superseded {
+        stats.superseded += 1;
+    }
+}
+
+pub fn observe_flushed_updates(destination: &str, count: usize) {
+    let mut coalescing = COALESCING.lock().unwrap();
+    let stats = coalescing.entry(destination.to_string()).or_insert(CoalescingStats::default());
+    stats.applied += count as u64;
+    stats.batches += 1;
+    if count as u64 > stats.largest_batch {
+        stats.largest_batch = count as u64;
+    }
+}
+
+// prometheus text exposition format
+pub fn render_metrics() -> String {
+    let calls = CALLS.lock().unwrap();
+    let mut result = String::new();
+
+    result.push_str("# TYPE torc_collaborator_call_duration_seconds histogram\n");
+    for (&(ref collaborator, ref target), stats) in calls.iter() {
+        let labels = format!("collaborator=\"{}\",target=\"{}\"", collaborator, target);
+        for (index, bound) in BUCKETS.iter().enumerate() {
+            result.push_str(&format!("torc_collaborator_call_duration_seconds_bucket{{{},le=\"{}\"}} {}\n",
+                                     labels,
+                                     bound,
+                                     stats.buckets[index]));
+        }
+        result.push_str(&format!("torc_collaborator_call_duration_seconds_bucket{{{},le=\"+Inf\"}} {}\n",
+                                 labels,
+                                 stats.count));
+        result.push_str(&format!("torc_collaborator_call_duration_seconds_sum{{{}}} {}\n", labels, stats.sum));
+        result.push_str(&format!("torc_collaborator_call_duration_seconds_count{{{}}} {}\n", labels, stats.count));
+    }
+
+    result.push_str("# TYPE torc_collaborator_call_errors_total counter\n");
+    for (&(ref collaborator, ref target), stats) in calls.iter() {
+        result.push_str(&format!("torc_collaborator_call_errors_total{{collaborator=\"{}\",target=\"{}\"}} {}\n",
+                                 collaborator,
+                                 target,
+                                 stats.errors));
+    }
+
+    // superseded over submitted is how much the coalescing window saved
+    let coalescing = COALESCING.lock().unwrap();
+    result.push_str("# TYPE torc_coalesced_updates_total counter\n");
+    for (destination, stats) in coalescing.iter() {
+        for &(outcome, value) in &[("submitted", stats.submitted), ("superseded", stats.superseded), ("applied", stats.applied)] {
+            result.push_str(&format!("torc_coalesced_updates_total{{destination=\"{}\",outcome=\"{}\"}} {}\n",
+                                     destination,
+                                     outcome,
+                                     value));
+        }
+    }
+    result.push_str("# TYPE torc_coalesced_batches_total counter\n");
+    for (destination, stats) in coalescing.iter() {
+        result.push_str(&format!("torc_coalesced_batches_total{{destination=\"{}\"}} {}\n", destination, stats.batches));
+    }
+    result.push_str("# TYPE torc_coalesced_largest_batch gauge\n");
+    for (destination, stats) in coalescing.iter() {
+        result.push_str(&format!("torc_coalesced_largest_batch{{destination=\"{}\"}} {}\n",
+                                 destination,
+                                 stats.largest_batch));
+    }
+
+    let messages = STATE_MESSAGES.lock().unwrap();
+    result.push_str("# TYPE torc_state_message_wait_seconds histogram\n");
+    for (&(ref message_type, ref priority), stats) in messages.iter() {
+        let labels = format!("type=\"{}\",priority=\"{}\"", message_type, priority);
+        stats.0.render("torc_state_message_wait_seconds", &labels, &mut result);
+    }
+    result.push_str("# TYPE torc_state_message_duration_seconds histogram\n");
+    for (&(ref message_type, ref priority), stats) in messages.iter() {
+        let labels = format!("type=\"{}\",priority=\"{}\"", message_type, priority);
+        stats.1.render("torc_state_message_duration_seconds", &labels, &mut result);
+    }
+
+    result
+}
+
+fn as_seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1_000_000_000.0
+}
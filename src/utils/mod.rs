@@ -20,12 +20,24 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 // THE SOFTWARE.
 
-pub use self::config::{read_bool, read_int, read_string, read_string_replace_variable, read_task};
+pub use self::arguments::{decode_arguments, decode_parameters, encode_argument_list, encode_parameter_map};
+pub use self::config::{adopt_task, first_task_name, read_bool, read_float, read_int, read_string, read_string_list,
+                       read_string_map, read_string_replace_variable, read_service_definition, read_service_group,
+                       read_task, start_array_member, start_task};
 pub use self::config::Task;
+pub use self::config_loader::load_config;
 pub use self::docker::handle_inspect_data;
+pub use self::expression::Expression;
+pub use self::logging::init_logging;
+pub use self::metrics::{CallTimer, observe_flushed_updates, observe_state_message, observe_submitted_update, render_metrics};
 
+mod arguments;
 mod config;
+mod config_loader;
 mod docker;
+mod expression;
+mod logging;
+mod metrics;
 
 pub const DEFAULT_MEMORY: f64 = 128.0;
 pub const DEFAULT_CPU: f64 = 0.2;